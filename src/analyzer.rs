@@ -0,0 +1,279 @@
+//! Analyzing many files at once, for tools embedding the analysis: files
+//! are found on disk, read and decoded, analyzed on a pool of threads and,
+//! optionally, their results cached between runs.
+
+use crate::cache::Cache;
+use crate::discovery::Discovery;
+use crate::encoding::{self, Encoding};
+use crate::notebook::{self, Notebook};
+use crate::timings::{Phase, Timings};
+use crate::{
+    analyze_project_with_timings, analyze_source_with_timings, Config, ProjectFile, Warning,
+};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+/// A file read for analysis. Notebooks are analyzed as the source of their
+/// code cells, so their warnings are then pointed back at the cells.
+pub struct SourceFile {
+    pub filename: String,
+    /// The decoded source, or that of the code cells of a notebook.
+    pub source_code: String,
+    /// The encoding the file was read in, to write it back in.
+    pub encoding: Encoding,
+    pub notebook: Option<Notebook>,
+}
+
+impl SourceFile {
+    /// Read and decode the file at `path`.
+    pub fn read(path: &Path) -> Result<Self> {
+        let filename = path.display().to_string();
+        let bytes = fs::read(path).with_context(|| format!("failed to read '{}'", filename))?;
+        Self::decode(filename.clone(), &bytes)
+            .with_context(|| format!("failed to read '{}'", filename))
+    }
+
+    /// Decode the contents of a file read some other way, such as from
+    /// stdin, reporting its warnings under `filename`.
+    pub fn decode(filename: String, bytes: &[u8]) -> Result<Self> {
+        let (contents, encoding) = encoding::decode(bytes)?;
+        let (source_code, notebook) = match notebook::is_notebook(&filename) {
+            true => {
+                let notebook = Notebook::parse(&contents)?;
+                (notebook.source.clone(), Some(notebook))
+            }
+            false => (contents, None),
+        };
+        Ok(SourceFile {
+            filename,
+            source_code,
+            encoding,
+            notebook,
+        })
+    }
+
+    /// Point warnings found in the source at the notebook cells they are
+    /// in, if the file is a notebook.
+    pub fn locate(&self, warnings: &mut [Warning]) {
+        if let Some(notebook) = &self.notebook {
+            notebook.locate(warnings);
+        }
+    }
+
+    /// The source a warning's lines are counted in: that of its cell in a
+    /// notebook, or the whole file's otherwise.
+    pub fn source_of(&self, warning: &Warning) -> &str {
+        notebook::source_of(warning, &self.source_code, self.notebook.as_ref())
+    }
+}
+
+/// Analyzes files one by one with a fixed configuration.
+pub struct Analyzer {
+    config: Config,
+    cache: Option<Cache>,
+    jobs: usize,
+    timings: Option<Timings>,
+}
+
+impl Analyzer {
+    /// An analyzer using as many threads as there are CPUs, without a cache.
+    pub fn new(config: Config) -> Self {
+        Analyzer {
+            config,
+            cache: None,
+            jobs: thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+            timings: None,
+        }
+    }
+
+    /// Keep results in `dir`, so unchanged files aren't analyzed again.
+    pub fn with_cache(mut self, dir: &Path) -> Self {
+        self.cache = Some(Cache::new(dir, &self.config));
+        self
+    }
+
+    /// Analyze up to `jobs` files at once.
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Time the phases of the analysis and each file, as
+    /// [`Analyzer::timings`] then returns.
+    pub fn with_timings(mut self) -> Self {
+        self.timings = Some(Timings::new());
+        self
+    }
+
+    /// Where the time went, if the analyzer times it.
+    pub fn timings(&self) -> Option<&Timings> {
+        self.timings.as_ref()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The most files analyzed at once.
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// The warnings found in one file's source.
+    pub fn analyze_source(&self, source_code: &str, filename: &str) -> Vec<Warning> {
+        let started = Instant::now();
+        let warnings = self.analyze_uncached(source_code, filename);
+        if let Some(timings) = &self.timings {
+            timings.add_file(filename, started.elapsed());
+        }
+        warnings
+    }
+
+    fn analyze_uncached(&self, source_code: &str, filename: &str) -> Vec<Warning> {
+        if let Some(warnings) = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get(filename, source_code))
+        {
+            return warnings;
+        }
+        let warnings =
+            analyze_source_with_timings(source_code, filename, &self.config, self.timings.as_ref());
+        if let Some(cache) = &self.cache {
+            cache.put(filename, source_code, &warnings);
+        }
+        warnings
+    }
+
+    /// The warnings found in a file, pointing at cells for notebooks.
+    pub fn analyze_file(&self, file: &SourceFile) -> Vec<Warning> {
+        let mut warnings = self.analyze_source(&file.source_code, &file.filename);
+        file.locate(&mut warnings);
+        warnings
+    }
+
+    /// The warnings of each `(filename, source)` pair, in the same order.
+    /// Threads take the next file in turn, so the order doesn't depend on
+    /// which finishes first.
+    pub fn analyze_sources(&self, sources: &[(&str, &str)]) -> Vec<Vec<Warning>> {
+        self.analyze_sources_with_progress(sources, &|_| {})
+    }
+
+    /// Like [`Analyzer::analyze_sources`], calling `progress` with the name
+    /// of each file once it is analyzed, from whichever thread analyzed it.
+    pub fn analyze_sources_with_progress(
+        &self,
+        sources: &[(&str, &str)],
+        progress: &(dyn Fn(&str) + Sync),
+    ) -> Vec<Vec<Warning>> {
+        let next = AtomicUsize::new(0);
+        let results: Vec<Mutex<Vec<Warning>>> = sources.iter().map(|_| Mutex::default()).collect();
+        thread::scope(|scope| {
+            for _ in 0..self.jobs.min(sources.len()) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((filename, source_code)) = sources.get(i) else {
+                        break;
+                    };
+                    *results[i].lock().unwrap() = self.analyze_source(source_code, filename);
+                    progress(filename);
+                });
+            }
+        });
+        if let Some(cache) = &self.cache {
+            cache.evict();
+        }
+        results
+            .into_iter()
+            .map(|warnings| warnings.into_inner().unwrap())
+            .collect()
+    }
+
+    /// The warnings of each file, in the same order, like
+    /// [`Analyzer::analyze_sources`] but pointing at cells for notebooks.
+    pub fn analyze_files(&self, files: &[SourceFile]) -> Vec<Vec<Warning>> {
+        self.analyze_files_with_progress(files, &|_| {})
+    }
+
+    /// Like [`Analyzer::analyze_files`], calling `progress` with the name
+    /// of each file once it is analyzed.
+    pub fn analyze_files_with_progress(
+        &self,
+        files: &[SourceFile],
+        progress: &(dyn Fn(&str) + Sync),
+    ) -> Vec<Vec<Warning>> {
+        let sources: Vec<(&str, &str)> = files
+            .iter()
+            .map(|file| (file.filename.as_str(), file.source_code.as_str()))
+            .collect();
+        self.analyze_sources_with_progress(&sources, progress)
+            .into_iter()
+            .zip(files)
+            .map(|(mut warnings, file)| {
+                file.locate(&mut warnings);
+                warnings
+            })
+            .collect()
+    }
+
+    /// The warnings of each file, in the same order, analyzing them
+    /// together as a project in which `modules` gives the dotted module
+    /// name of each file. Projects are analyzed as a whole, so their
+    /// results aren't cached.
+    pub fn analyze_project(&self, files: &[SourceFile], modules: &[String]) -> Vec<Vec<Warning>> {
+        let project_files: Vec<ProjectFile> = files
+            .iter()
+            .zip(modules)
+            .map(|(file, module)| ProjectFile {
+                filename: &file.filename,
+                module,
+                source: &file.source_code,
+            })
+            .collect();
+        let mut by_file: HashMap<String, Vec<Warning>> = HashMap::new();
+        for warning in
+            analyze_project_with_timings(&project_files, &self.config, self.timings.as_ref())
+        {
+            by_file
+                .entry(warning.filename.clone())
+                .or_default()
+                .push(warning);
+        }
+        files
+            .iter()
+            .map(|file| {
+                let mut warnings = by_file.remove(&file.filename).unwrap_or_default();
+                file.locate(&mut warnings);
+                warnings
+            })
+            .collect()
+    }
+
+    /// Read the file at `path`, counting the time it takes as reading.
+    pub fn read(&self, path: &Path) -> Result<SourceFile> {
+        let started = Instant::now();
+        let file = SourceFile::read(path);
+        if let Some(timings) = &self.timings {
+            timings.add(Phase::Read, started.elapsed());
+        }
+        file
+    }
+
+    /// The warnings found in the files at `paths`, searching directories for
+    /// the files `discovery` picks up, ordered by file and position. The
+    /// warnings of notebooks point at their cells.
+    pub fn analyze_paths(&self, paths: &[PathBuf], discovery: &Discovery) -> Result<Vec<Warning>> {
+        let files = discovery
+            .files(paths)?
+            .iter()
+            .map(|path| self.read(path))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(self.analyze_files(&files).concat())
+    }
+}
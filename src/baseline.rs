@@ -0,0 +1,143 @@
+//! Baseline files record previously reported warnings, so that only new
+//! warnings are shown when adopting the linter on an existing codebase.
+//!
+//! Warnings are matched on their file, rule and a fingerprint of the message
+//! and the code they point at, rather than on line numbers, so edits
+//! elsewhere in the file don't bring recorded warnings back.
+
+use crate::Warning;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    filename: String,
+    line: usize,
+    message: String,
+    // Missing from baselines written before fingerprints were recorded,
+    // which are matched on filename, line and message instead
+    #[serde(default)]
+    rule: Option<String>,
+    #[serde(default)]
+    fingerprint: Option<String>,
+}
+
+/// Warnings to suppress.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    fingerprints: HashSet<(String, String, String)>,
+    lines: HashSet<(String, usize, String)>,
+}
+
+impl Baseline {
+    /// Load a baseline from a JSON array of recorded warnings.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read baseline '{}'", path.display()))?;
+        let entries: Vec<Entry> = serde_json::from_str(&contents)
+            .with_context(|| format!("invalid baseline '{}'", path.display()))?;
+        let mut baseline = Baseline::default();
+        for entry in entries {
+            match (entry.rule, entry.fingerprint) {
+                (Some(rule), Some(fingerprint)) => {
+                    baseline
+                        .fingerprints
+                        .insert((entry.filename, rule, fingerprint));
+                }
+                _ => {
+                    baseline
+                        .lines
+                        .insert((entry.filename, entry.line, entry.message));
+                }
+            }
+        }
+        Ok(baseline)
+    }
+
+    /// Write `warnings`, each with the source of the file it was found in,
+    /// as the new baseline at `path`.
+    pub fn write(path: &Path, warnings: &[(Warning, &str)]) -> Result<()> {
+        let entries: Vec<Entry> = warnings
+            .iter()
+            .map(|(warning, source_code)| entry(warning, source_code))
+            .collect();
+        let contents = serde_json::to_string_pretty(&entries)?;
+        fs::write(path, contents + "\n")
+            .with_context(|| format!("failed to write baseline '{}'", path.display()))
+    }
+
+    /// Add `warnings` to the baseline at `path`, keeping the warnings it
+    /// already records, or create it.
+    pub fn append(path: &Path, warnings: &[(Warning, &str)]) -> Result<()> {
+        let mut entries: Vec<Entry> = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("invalid baseline '{}'", path.display()))?,
+            Err(_) => Vec::new(),
+        };
+        entries.extend(
+            warnings
+                .iter()
+                .map(|(warning, source_code)| entry(warning, source_code)),
+        );
+        let contents = serde_json::to_string_pretty(&entries)?;
+        fs::write(path, contents + "\n")
+            .with_context(|| format!("failed to write baseline '{}'", path.display()))
+    }
+
+    /// Whether the warning, found in `source_code`, was already present when
+    /// the baseline was taken.
+    pub fn contains(&self, warning: &Warning, source_code: &str) -> bool {
+        self.fingerprints.contains(&(
+            warning.filename.clone(),
+            warning.rule.code().to_string(),
+            fingerprint(warning, source_code),
+        )) || self.lines.contains(&(
+            warning.filename.clone(),
+            warning.line,
+            warning.message.clone(),
+        ))
+    }
+}
+
+fn entry(warning: &Warning, source_code: &str) -> Entry {
+    Entry {
+        filename: warning.filename.clone(),
+        line: warning.line,
+        message: warning.message.clone(),
+        rule: Some(warning.rule.code().to_string()),
+        fingerprint: Some(fingerprint(warning, source_code)),
+    }
+}
+
+// A stable hash of the warning's message and the lines it points at, with
+// whitespace collapsed so reindenting the code keeps the fingerprint
+fn fingerprint(warning: &Warning, source_code: &str) -> String {
+    let code: Vec<&str> = source_code
+        .lines()
+        .skip(warning.line - 1)
+        .take(warning.end_line - warning.line + 1)
+        .flat_map(str::split_whitespace)
+        .collect();
+
+    format!(
+        "{:016x}",
+        stable_hash(&[warning.message.as_str(), &code.join(" ")])
+    )
+}
+
+// FNV-1a over the parts, separated by NUL bytes, which unlike the standard
+// library's hasher is guaranteed not to change between releases
+pub(crate) fn stable_hash(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (i, part) in parts.iter().enumerate() {
+        let separator: &[u8] = if i == 0 { b"" } else { b"\0" };
+        for &byte in separator.iter().chain(part.as_bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
@@ -0,0 +1,197 @@
+//! A cache of analysis results on disk, so unchanged files aren't parsed
+//! or analyzed again. Entries are keyed by a hash of the file's name and
+//! contents, the version and build of the running executable and the
+//! options affecting the analysis; entries unused for a month are evicted.
+//!
+//! The cache is best-effort: entries that can't be read or written are
+//! treated as missing.
+
+use crate::baseline::stable_hash;
+use crate::{Config, Rule, Warning};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// Entries not read or written for this long are removed by `evict`
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Cached warnings by file.
+pub struct Cache {
+    dir: PathBuf,
+    // Everything besides the file that the warnings depend on
+    key: String,
+    // The rules of the configuration, those of checks from other crates
+    // included, which recorded warnings are read back with
+    rules: Vec<Rule>,
+}
+
+impl Cache {
+    /// A cache in `dir` for results of analyzing with `config`.
+    pub fn new(dir: &Path, config: &Config) -> Self {
+        Cache {
+            dir: dir.to_path_buf(),
+            key: config_key(config),
+            rules: config.rules(),
+        }
+    }
+
+    /// The platform's cache location: `$XDG_CACHE_HOME/pywrong`,
+    /// `~/.cache/pywrong`, `~/Library/Caches/pywrong` on macOS or
+    /// `%LOCALAPPDATA%\pywrong\cache` on Windows.
+    pub fn default_dir() -> Option<PathBuf> {
+        let var = |name| env::var_os(name).filter(|value| !value.is_empty());
+        if cfg!(windows) {
+            return var("LOCALAPPDATA").map(|dir| Path::new(&dir).join("pywrong").join("cache"));
+        }
+        if let Some(dir) = var("XDG_CACHE_HOME") {
+            return Some(Path::new(&dir).join("pywrong"));
+        }
+        let home = PathBuf::from(var("HOME")?);
+        if cfg!(target_os = "macos") {
+            Some(home.join("Library").join("Caches").join("pywrong"))
+        } else {
+            Some(home.join(".cache").join("pywrong"))
+        }
+    }
+
+    /// The warnings recorded for the file, if it was analyzed before.
+    pub fn get(&self, filename: &str, source_code: &str) -> Option<Vec<Warning>> {
+        let path = self.entry(filename, source_code);
+        let contents = fs::read_to_string(&path).ok()?;
+        let warnings = serde_json::from_str::<Vec<Value>>(&contents)
+            .ok()?
+            .into_iter()
+            .map(|warning| self.warning(warning))
+            .collect::<Option<Vec<Warning>>>()?;
+
+        // Mark the entry as used, so it isn't evicted
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(warnings)
+    }
+
+    /// Record the warnings found in the file.
+    pub fn put(&self, filename: &str, source_code: &str, warnings: &[Warning]) {
+        let Ok(contents) = serde_json::to_string(warnings) else {
+            return;
+        };
+        let path = self.entry(filename, source_code);
+
+        // Written whole and then renamed, so that concurrent runs never read
+        // a partial entry
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        let _ = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&partial, contents))
+            .and_then(|_| fs::rename(&partial, &path));
+    }
+
+    /// Remove entries that haven't been used for a month. Only the files
+    /// the cache writes are considered, since the directory may be one the
+    /// user keeps other files in.
+    pub fn evict(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        for entry in entries.flatten() {
+            if !entry.file_name().to_str().is_some_and(is_cache_file) {
+                continue;
+            }
+            let expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age > MAX_AGE));
+            if expired {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    // A recorded warning. Its rule is looked up among the configuration's,
+    // as parsing a rule only knows the built-in ones.
+    fn warning(&self, mut warning: Value) -> Option<Warning> {
+        let code = warning.get("rule")?.as_str()?;
+        let rule = *self.rules.iter().find(|rule| rule.code() == code)?;
+        // Stands in for the rule until the rest is parsed
+        warning["rule"] = Value::from(Rule::ALL[0].code());
+        let mut warning: Warning = serde_json::from_value(warning).ok()?;
+        warning.rule = rule;
+        Some(warning)
+    }
+
+    fn entry(&self, filename: &str, source_code: &str) -> PathBuf {
+        let hash = stable_hash(&[&self.key, filename, source_code]);
+        self.dir.join(format!("{:016x}.json", hash))
+    }
+}
+
+// Whether a file name is that of an entry, `<hash>.json`, or of one left
+// partially written by `put`, `<hash>.<pid>.tmp`
+fn is_cache_file(name: &str) -> bool {
+    let Some((hash, extension)) = name.split_once('.') else {
+        return false;
+    };
+    let is_hash = hash.len() == 16
+        && hash
+            .bytes()
+            .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'));
+    let is_partial = extension
+        .strip_suffix(".tmp")
+        .is_some_and(|pid| !pid.is_empty() && pid.bytes().all(|byte| byte.is_ascii_digit()));
+    is_hash && (extension == "json" || is_partial)
+}
+
+// The crate version, the executable's modification time, so that a rebuilt
+// executable doesn't reuse the results of the old one, and every option, with
+// sets sorted so that the key is the same from run to run
+fn config_key(config: &Config) -> String {
+    fn sorted<T: Ord + std::fmt::Debug>(items: impl IntoIterator<Item = T>) -> String {
+        let mut items: Vec<T> = items.into_iter().collect();
+        items.sort();
+        format!("{:?}", items)
+    }
+    [
+        env!("CARGO_PKG_VERSION").to_string(),
+        format!(
+            "{:?}",
+            env::current_exe()
+                .and_then(fs::metadata)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        ),
+        format!("{:?}", config.python_version),
+        format!("{:?}", config.newest_python_version),
+        config.suggest_fixes.to_string(),
+        sorted(&config.enabled_rules),
+        config.check_annotations.to_string(),
+        config.strict_asserts.to_string(),
+        config.skip_syntax_errors.to_string(),
+        config.public_only.to_string(),
+        format!(
+            "{:?}",
+            config.stubs.as_ref().map(|stubs| stubs.fingerprint())
+        ),
+        format!(
+            "{:?}",
+            config
+                .site_packages
+                .as_ref()
+                .map(|site_packages| site_packages.fingerprint())
+        ),
+        sorted(&config.swallowing_decorators),
+        sorted(&config.higher_order_functions),
+        sorted(&config.safe_functions),
+        format!("{:?}", config.docstring_style),
+        format!("{:?}", config.entry_points.as_ref().map(sorted)),
+        format!("{:?}", config.frameworks),
+        sorted(&config.allowed_swallowed),
+        sorted(&config.acceptable_exceptions),
+        sorted(&config.acceptable_by_function),
+        sorted(&config.required_env_vars),
+        sorted(&config.severities),
+    ]
+    .join("\0")
+}
@@ -0,0 +1,118 @@
+//! The lines touched by a change, so that only warnings on new or modified
+//! code are reported. The change is either the working tree's difference
+//! from a git revision or a unified diff read from stdin.
+
+use anyhow::{bail, Context, Result};
+use pysleuth::Warning;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Added and modified lines by file.
+pub struct ChangedLines {
+    // Keyed by canonical path; `None` when the whole file is new
+    files: HashMap<PathBuf, Option<HashSet<usize>>>,
+}
+
+impl ChangedLines {
+    /// The lines changed since `base`, a revision such as `main` or
+    /// `HEAD~3`, or those added by the patch on stdin when `base` is `-`.
+    pub fn load(base: &str) -> Result<Self> {
+        if base == "-" {
+            let patch =
+                io::read_to_string(io::stdin()).context("failed to read patch from stdin")?;
+            return Ok(Self::parse(&patch, Path::new(".")));
+        }
+
+        let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim_end());
+        let patch = git(&[
+            "diff",
+            "--unified=0",
+            "--no-color",
+            "--no-ext-diff",
+            base,
+            "--",
+        ])?;
+        let mut changes = Self::parse(&patch, &root);
+
+        // Untracked files are all new code
+        for path in git(&["ls-files", "--others", "--exclude-standard", "--full-name"])?.lines() {
+            changes.files.insert(canonical(&root.join(path)), None);
+        }
+        Ok(changes)
+    }
+
+    // Patch paths are relative to `root` and may have git's `b/` prefix
+    fn parse(patch: &str, root: &Path) -> Self {
+        let mut files = HashMap::new();
+        let mut current = None;
+        let mut line = 0;
+        for text in patch.lines() {
+            if let Some(path) = text.strip_prefix("+++ ") {
+                // The path ends at a tab, if the patch records timestamps
+                let path = path.split('\t').next().unwrap();
+                current = if path == "/dev/null" {
+                    None
+                } else {
+                    let path = canonical(&root.join(path.strip_prefix("b/").unwrap_or(path)));
+                    files
+                        .entry(path.clone())
+                        .or_insert_with(|| Some(HashSet::new()));
+                    Some(path)
+                };
+            } else if let Some(hunk) = text.strip_prefix("@@ ") {
+                // `@@ -12,3 +14,5 @@`, where a missing count means one line
+                line = hunk
+                    .split_whitespace()
+                    .find_map(|range| range.strip_prefix('+'))
+                    .and_then(|range| range.split(',').next())
+                    .and_then(|start| start.parse().ok())
+                    .unwrap_or(0);
+            } else if text.starts_with("--- ") {
+                continue;
+            } else if let Some(Some(lines)) = current.as_ref().and_then(|path| files.get_mut(path))
+            {
+                match text.chars().next() {
+                    Some('+') => {
+                        lines.insert(line);
+                        line += 1;
+                    }
+                    Some(' ') => line += 1,
+                    _ => {}
+                }
+            }
+        }
+        ChangedLines { files }
+    }
+
+    /// Whether any line the warning spans was added or modified.
+    pub fn contains(&self, warning: &Warning) -> bool {
+        match self.files.get(&canonical(Path::new(&warning.filename))) {
+            Some(None) => true,
+            Some(Some(lines)) => {
+                (warning.line..=warning.end_line).any(|line| lines.contains(&line))
+            }
+            None => false,
+        }
+    }
+}
+
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,321 @@
+//! Checks for exceptions raised by single nodes, such as `d[k]` raising
+//! KeyError. Each check names the kinds of node it looks at and is run on
+//! every such node in the scope of each function; what it finds is reported
+//! where no enclosing handler catches it, and propagates to the callers of
+//! the function like any other exception.
+//!
+//! The checks run are those of the frameworks in [`Config::frameworks`],
+//! then those in [`Config::checks`], which starts with the built-in ones and
+//! can be extended with checks from other crates by [`Config::add_check`].
+//! Checks whose rule isn't enabled aren't run, and the first check to find
+//! an exception in a node decides what it raises.
+
+use crate::{
+    environ_message, format_missing_keys, has_default_mapping_annotation, has_known_key,
+    is_default_mapping, is_environ_subscript, is_guarded_by_membership, is_guarded_by_walrus_get,
+    is_required_env_var, is_store, pop_exception, subscript_exception, typed_dict::Field,
+    typed_dict_field, unpacking_may_fail, Config, FunctionCall, FunctionId, Functions, Guard, Rule,
+};
+use tree_sitter::Node;
+
+/// A check for an exception some nodes may raise.
+pub trait Check: Send + Sync {
+    /// The rule the check reports under, such as `Rule::Custom("ACME001")`
+    /// for checks from other crates.
+    fn rule(&self) -> Rule;
+
+    /// A descriptive name, the name of the rule by default.
+    fn name(&self) -> &str {
+        self.rule().name()
+    }
+
+    /// Kinds of node the check is run on, such as `subscript` or `call`.
+    fn node_kinds(&self) -> &[&str];
+
+    /// The exception the node may raise, if any.
+    fn check(&self, node: Node, context: &CheckContext) -> Option<Finding>;
+}
+
+/// An exception a node may raise, reported under the rule of the check
+/// that found it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub exception: String,
+    pub message: String,
+}
+
+/// What a check knows about where a node appears.
+pub struct CheckContext<'a> {
+    /// The function containing the node, such as `Store.get`, or `<module>`.
+    pub function: &'a str,
+    pub source_code: &'a str,
+    pub config: &'a Config,
+    functions: &'a Functions<'a>,
+    calls: &'a [FunctionCall<'a>],
+}
+
+impl<'a> CheckContext<'a> {
+    /// The source text of a node.
+    pub fn text(&self, node: Node) -> &'a str {
+        node.utf8_text(self.source_code.as_bytes()).unwrap()
+    }
+}
+
+/// The checks run by default.
+pub(crate) fn builtin() -> Vec<Box<dyn Check>> {
+    vec![
+        Box::new(SubscriptCheck(Rule::UnguardedDictAccess)),
+        Box::new(SubscriptCheck(Rule::UnguardedIndexAccess)),
+        Box::new(EnvironCheck),
+        Box::new(PopCheck(Rule::UnguardedDictAccess)),
+        Box::new(PopCheck(Rule::UnguardedIndexAccess)),
+        Box::new(FormatCheck),
+        Box::new(UnpackCheck),
+    ]
+}
+
+// Every finding in the function's own scope that no handler counted by
+// `guard` catches, in source order. Type annotations are never evaluated, and
+// nested functions and lambdas are analyzed as functions of their own, so
+// they are skipped.
+pub(crate) fn run<'a>(
+    function: &FunctionId,
+    functions: &Functions<'a>,
+    source_code: &str,
+    config: &Config,
+    guard: Guard,
+) -> Vec<(Node<'a>, Rule, Finding)> {
+    let context = CheckContext {
+        function: &function.name,
+        source_code,
+        config,
+        functions,
+        calls: &functions[function].calls,
+    };
+    let framework_checks: Vec<Box<dyn Check>> = config
+        .frameworks
+        .iter()
+        .flat_map(|framework| framework.checks())
+        .collect();
+    let mut findings = Vec::new();
+    let mut pending = vec![functions[function].node];
+    while let Some(node) = pending.pop() {
+        let finding = framework_checks
+            .iter()
+            .chain(&config.checks)
+            .filter(|check| {
+                config.is_enabled(check.rule()) && check.node_kinds().contains(&node.kind())
+            })
+            .find_map(|check| Some((check.rule(), check.check(node, &context)?)));
+        if let Some((rule, finding)) = finding {
+            if !guard.catches(node, &finding.exception, source_code) {
+                findings.push((node, rule, finding));
+            }
+        }
+
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        pending.extend(
+            children
+                .into_iter()
+                .rev()
+                .filter(|child| !matches!(child.kind(), "type" | "lambda" | "function_definition")),
+        );
+    }
+    findings
+}
+
+// Whether a subscript can't fail: a slice such as `s[1:3]`, which never
+// raises for out-of-range bounds, a key known to be present, or a mapping
+// that fills in missing keys. Subscripts on user-defined classes are left to
+// their `__getitem__`, which the function's calls include.
+pub(crate) fn is_safe_subscript(node: Node, context: &CheckContext) -> bool {
+    let mut cursor = node.walk();
+    let is_slice = node
+        .children_by_field_name("subscript", &mut cursor)
+        .any(|subscript| subscript.kind() == "slice");
+    is_slice
+        || is_guarded_by_walrus_get(node, context.source_code, context.config.python_version)
+        || is_guarded_by_membership(node, context.source_code)
+        || is_default_mapping(node, context.source_code)
+        || (context.config.check_annotations
+            && has_default_mapping_annotation(node, context.functions.module, context.source_code))
+        || has_known_key(node, context.source_code)
+        || context.calls.iter().any(|call| call.node.id() == node.id())
+}
+
+// The rule of a failed lookup: `PW005` for IndexError, `PW001` for KeyError
+// and LookupError
+fn index_rule(exception: &str) -> Rule {
+    if exception == "IndexError" {
+        Rule::UnguardedIndexAccess
+    } else {
+        Rule::UnguardedDictAccess
+    }
+}
+
+// `PW001` and `PW005`: KeyError, IndexError or LookupError from `d[k]`, one
+// check for each rule
+struct SubscriptCheck(Rule);
+
+impl Check for SubscriptCheck {
+    fn rule(&self) -> Rule {
+        self.0
+    }
+
+    fn node_kinds(&self) -> &[&str] {
+        &["subscript"]
+    }
+
+    fn check(&self, node: Node, context: &CheckContext) -> Option<Finding> {
+        if is_environ_subscript(node, context.functions.module, context.source_code)
+            || is_safe_subscript(node, context)
+        {
+            return None;
+        }
+        // Storing a key never raises, but storing past the end of a list does
+        let store = is_store(node);
+        // Required fields of a TypedDict are always present
+        if let Some((class, key, field)) =
+            typed_dict_field(node, context.functions.module, context.source_code)
+        {
+            if store || self.0 != Rule::UnguardedDictAccess {
+                return None;
+            }
+            let message = match field {
+                Field::Required => return None,
+                Field::NotRequired => {
+                    format!("Possible KeyError in function '{}'", context.function)
+                }
+                Field::Undeclared => format!(
+                    "Key {} is not declared in TypedDict '{}' in function '{}'",
+                    key, class, context.function
+                ),
+            };
+            return Some(Finding {
+                exception: "KeyError".to_string(),
+                message,
+            });
+        }
+        let exception =
+            subscript_exception(node, context.functions, context.source_code, context.config);
+        if (store && exception != "IndexError") || index_rule(exception) != self.0 {
+            return None;
+        }
+        Some(Finding {
+            exception: exception.to_string(),
+            message: format!("Possible {} in function '{}'", exception, context.function),
+        })
+    }
+}
+
+// `PW006`: KeyError from `os.environ[name]`
+struct EnvironCheck;
+
+impl Check for EnvironCheck {
+    fn rule(&self) -> Rule {
+        Rule::UnguardedEnvironAccess
+    }
+
+    fn node_kinds(&self) -> &[&str] {
+        &["subscript"]
+    }
+
+    fn check(&self, node: Node, context: &CheckContext) -> Option<Finding> {
+        if !is_environ_subscript(node, context.functions.module, context.source_code)
+            || is_store(node)
+            || is_safe_subscript(node, context)
+            || is_required_env_var(node, context.source_code, context.config)
+        {
+            return None;
+        }
+        Some(Finding {
+            exception: "KeyError".to_string(),
+            message: environ_message(node, context.source_code),
+        })
+    }
+}
+
+// `PW001` and `PW005`: KeyError or IndexError from `d.pop(k)` without a
+// default, one check for each rule
+struct PopCheck(Rule);
+
+impl Check for PopCheck {
+    fn rule(&self) -> Rule {
+        self.0
+    }
+
+    fn node_kinds(&self) -> &[&str] {
+        &["call"]
+    }
+
+    fn check(&self, node: Node, context: &CheckContext) -> Option<Finding> {
+        let exception = pop_exception(node, context.functions, context.source_code)?;
+        if index_rule(exception) != self.0 || is_guarded_by_membership(node, context.source_code) {
+            return None;
+        }
+        Some(Finding {
+            exception: exception.to_string(),
+            message: format!(
+                "Possible {} from pop() in function '{}'",
+                exception, context.function
+            ),
+        })
+    }
+}
+
+// `PW001`: KeyError from filling in a template from a mapping, as in
+// `"{name}".format_map(d)`, `"{name}".format(**d)` or
+// `Template("$name").substitute(d)`
+struct FormatCheck;
+
+impl Check for FormatCheck {
+    fn rule(&self) -> Rule {
+        Rule::UnguardedDictAccess
+    }
+
+    fn node_kinds(&self) -> &[&str] {
+        &["call"]
+    }
+
+    fn check(&self, node: Node, context: &CheckContext) -> Option<Finding> {
+        let (method, missing) =
+            format_missing_keys(node, context.functions.module, context.source_code)?;
+        let missing: Vec<String> = missing.iter().map(|key| format!("'{}'", key)).collect();
+        Some(Finding {
+            exception: "KeyError".to_string(),
+            message: format!(
+                "Possible KeyError from {}() in function '{}': {} may be missing from the mapping",
+                method,
+                context.function,
+                missing.join(", ")
+            ),
+        })
+    }
+}
+
+// `PW016`: ValueError from `a, b = parts`
+struct UnpackCheck;
+
+impl Check for UnpackCheck {
+    fn rule(&self) -> Rule {
+        Rule::UncheckedUnpacking
+    }
+
+    fn node_kinds(&self) -> &[&str] {
+        &["pattern_list", "tuple_pattern", "list_pattern"]
+    }
+
+    fn check(&self, node: Node, context: &CheckContext) -> Option<Finding> {
+        if !unpacking_may_fail(node, context.source_code) {
+            return None;
+        }
+        Some(Finding {
+            exception: "ValueError".to_string(),
+            message: format!(
+                "Possible ValueError from unpacking in function '{}'",
+                context.function
+            ),
+        })
+    }
+}
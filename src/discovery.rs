@@ -0,0 +1,264 @@
+//! Finding the Python files and notebooks to analyze. Files named on the
+//! command line are taken as they are; directories are searched recursively
+//! for files matching the include patterns, minus those matching an exclude
+//! pattern or, optionally, a `.gitignore` rule.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Which files to pick up when searching directories.
+pub struct Discovery {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    respect_gitignore: bool,
+}
+
+impl Discovery {
+    /// Search with the given include and exclude globs. Without include
+    /// patterns, every `.py` file and `.ipynb` notebook is included.
+    pub fn new(include: &[String], exclude: &[String], respect_gitignore: bool) -> Self {
+        let include = if include.is_empty() {
+            vec![Pattern::new("*.py"), Pattern::new("*.ipynb")]
+        } else {
+            include.iter().map(|glob| Pattern::new(glob)).collect()
+        };
+        Discovery {
+            include,
+            exclude: exclude.iter().map(|glob| Pattern::new(glob)).collect(),
+            respect_gitignore,
+        }
+    }
+
+    /// The files to analyze for the given paths, in a stable order.
+    pub fn files(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                let mut rules = Vec::new();
+                if self.respect_gitignore {
+                    for dir in gitignore_ancestors(path) {
+                        read_gitignore(&dir, &mut rules)?;
+                    }
+                }
+                self.search(path, path, &mut rules, &mut files)?;
+            } else {
+                files.push(path.clone());
+            }
+        }
+        Ok(files)
+    }
+
+    // Hidden directories and `__pycache__` are always skipped
+    fn search(
+        &self,
+        root: &Path,
+        dir: &Path,
+        rules: &mut Vec<IgnoreRule>,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        let inherited = rules.len();
+        if self.respect_gitignore && dir != root {
+            read_gitignore(dir, rules)?;
+        }
+
+        let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+            .with_context(|| format!("failed to read directory '{}'", dir.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<_>>()?;
+        entries.sort();
+        for path in entries {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let relative = relative_path(root, &path);
+            let is_dir = path.is_dir();
+            if self
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches(&relative))
+                || is_ignored(rules, &path, is_dir)
+            {
+                continue;
+            }
+            if is_dir {
+                if !name.starts_with('.') && name != "__pycache__" {
+                    self.search(root, &path, rules, files)?;
+                }
+            } else if self
+                .include
+                .iter()
+                .any(|pattern| pattern.matches(&relative))
+            {
+                files.push(path);
+            }
+        }
+
+        // Rules from this directory's `.gitignore` don't apply to its siblings
+        rules.truncate(inherited);
+        Ok(())
+    }
+}
+
+// The path relative to `root`, with `/` separators
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A glob such as `*.py`, `tests/**` or `**/migrations/*.py`. Globs without
+/// a `/` match the name of a file or directory at any depth; others match
+/// the whole path relative to the searched directory.
+struct Pattern {
+    glob: String,
+    anchored: bool,
+}
+
+impl Pattern {
+    fn new(glob: &str) -> Self {
+        let glob = glob.trim_end_matches('/');
+        Pattern {
+            glob: glob.trim_start_matches('/').to_string(),
+            anchored: glob.contains('/'),
+        }
+    }
+
+    fn matches(&self, relative: &str) -> bool {
+        if self.anchored {
+            glob_match(self.glob.as_bytes(), relative.as_bytes())
+        } else {
+            let name = relative.rsplit('/').next().unwrap();
+            glob_match(self.glob.as_bytes(), name.as_bytes())
+        }
+    }
+}
+
+// Match a path against a glob: `*` matches within one path component, `**`
+// across components, `?` one character and `[a-z]` or `[!a-z]` a class
+pub(crate) fn glob_match(glob: &[u8], path: &[u8]) -> bool {
+    match glob.first() {
+        None => path.is_empty(),
+        Some(b'*') if glob.get(1) == Some(&b'*') => {
+            // `**/` also matches no directories at all
+            let rest = &glob[2..];
+            let rest_after_slash = rest.strip_prefix(b"/").unwrap_or(rest);
+            (0..=path.len()).any(|i| {
+                glob_match(rest, &path[i..])
+                    || (i == 0 || path[i - 1] == b'/') && glob_match(rest_after_slash, &path[i..])
+            })
+        }
+        Some(b'*') => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != b'/')
+            .any(|i| glob_match(&glob[1..], &path[i..])),
+        Some(b'?') => {
+            path.first().is_some_and(|&c| c != b'/') && glob_match(&glob[1..], &path[1..])
+        }
+        Some(b'[') => {
+            let Some(end) = glob.iter().skip(2).position(|&c| c == b']').map(|i| i + 2) else {
+                return path.first() == Some(&b'[') && glob_match(&glob[1..], &path[1..]);
+            };
+            let Some(&c) = path.first() else {
+                return false;
+            };
+            let (negated, class) = match glob[1] {
+                b'!' | b'^' => (true, &glob[2..end]),
+                _ => (false, &glob[1..end]),
+            };
+            let mut in_class = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == b'-' {
+                    in_class |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    in_class |= class[i] == c;
+                    i += 1;
+                }
+            }
+            c != b'/' && in_class != negated && glob_match(&glob[end + 1..], &path[1..])
+        }
+        Some(&c) => path.first() == Some(&c) && glob_match(&glob[1..], &path[1..]),
+    }
+}
+
+// A line of a `.gitignore` file, relative to the directory containing it
+struct IgnoreRule {
+    base: PathBuf,
+    pattern: Pattern,
+    negated: bool,
+    dir_only: bool,
+}
+
+// The directories above `dir` whose `.gitignore` applies to it, up to the
+// root of the repository, outermost first, and `dir` itself
+fn gitignore_ancestors(dir: &Path) -> Vec<PathBuf> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mut dirs = Vec::new();
+    for ancestor in dir.ancestors() {
+        dirs.push(ancestor.to_path_buf());
+        if ancestor.join(".git").exists() {
+            break;
+        }
+    }
+    if !dirs.last().is_some_and(|top| top.join(".git").exists()) {
+        // Outside a repository only the directory's own rules apply
+        dirs.truncate(1);
+    }
+    dirs.reverse();
+    dirs
+}
+
+fn read_gitignore(dir: &Path, rules: &mut Vec<IgnoreRule>) -> Result<()> {
+    let path = dir.join(".gitignore");
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to read '{}'", path.display()));
+        }
+    };
+    let base = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    for line in contents.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        rules.push(IgnoreRule {
+            base: base.clone(),
+            pattern: Pattern::new(line),
+            negated,
+            dir_only: line.ends_with('/'),
+        });
+    }
+    Ok(())
+}
+
+// Later rules override earlier ones, so `!keep.py` can undo `*.py`
+fn is_ignored(rules: &[IgnoreRule], path: &Path, is_dir: bool) -> bool {
+    if rules.is_empty() {
+        return false;
+    }
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut ignored = false;
+    for rule in rules {
+        if rule.dir_only && !is_dir {
+            continue;
+        }
+        if let Ok(relative) = path.strip_prefix(&rule.base) {
+            if rule
+                .pattern
+                .matches(&relative_path(Path::new(""), relative))
+            {
+                ignored = !rule.negated;
+            }
+        }
+    }
+    ignored
+}
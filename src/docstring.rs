@@ -0,0 +1,122 @@
+//! Exceptions documented in the `Raises` section of a docstring, in Google
+//! (`Raises:`), NumPy (`Raises` over a `------` underline) or Sphinx
+//! (`:raises KeyError:`) style.
+
+use std::str::FromStr;
+
+/// The docstring convention of a project, for reading only the `Raises`
+/// sections written in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DocstringStyle {
+    Google,
+    Numpy,
+    Sphinx,
+}
+
+impl FromStr for DocstringStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "google" => Ok(DocstringStyle::Google),
+            "numpy" => Ok(DocstringStyle::Numpy),
+            "sphinx" => Ok(DocstringStyle::Sphinx),
+            _ => Err(format!(
+                "unknown docstring style '{}'; expected google, numpy or sphinx",
+                value
+            )),
+        }
+    }
+}
+
+/// Exception names listed in the docstring, in order of appearance, in the
+/// given style or else in any of them.
+pub(crate) fn documented_raises(docstring: &str, style: Option<DocstringStyle>) -> Vec<String> {
+    let reads = |wanted: DocstringStyle| style.is_none_or(|style| style == wanted);
+    let lines: Vec<&str> = docstring.lines().collect();
+    let mut raises = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+        let indent = indentation(line);
+
+        // Sphinx fields can appear anywhere
+        for field in [":raises ", ":raise "]
+            .into_iter()
+            .filter(|_| reads(DocstringStyle::Sphinx))
+        {
+            if let Some(rest) = trimmed.strip_prefix(field) {
+                if let Some((names, _)) = rest.split_once(':') {
+                    raises.extend(exception_names(names));
+                }
+            }
+        }
+
+        if trimmed == "Raises:" && reads(DocstringStyle::Google) {
+            // Entries are the lines one level deeper than the header
+            let mut entry_indent = None;
+            i += 1;
+            while i < lines.len() {
+                let entry = lines[i];
+                if entry.trim().is_empty() {
+                    i += 1;
+                    continue;
+                }
+                if indentation(entry) <= indent {
+                    break;
+                }
+                let entry_indent = *entry_indent.get_or_insert(indentation(entry));
+                if indentation(entry) == entry_indent {
+                    let names = entry.trim().split(':').next().unwrap();
+                    raises.extend(exception_names(names));
+                }
+                i += 1;
+            }
+            continue;
+        }
+
+        let underlined = lines
+            .get(i + 1)
+            .is_some_and(|next| next.trim().len() >= 3 && next.trim().chars().all(|c| c == '-'));
+        if trimmed == "Raises" && underlined && reads(DocstringStyle::Numpy) {
+            // Entries are at the header's indentation, with descriptions
+            // indented below them, up to the next underlined section
+            i += 2;
+            while i < lines.len() {
+                let entry = lines[i];
+                let next_is_underline = lines
+                    .get(i + 1)
+                    .is_some_and(|next| next.trim().starts_with("---"));
+                if next_is_underline {
+                    break;
+                }
+                if !entry.trim().is_empty() && indentation(entry) == indent {
+                    raises.extend(exception_names(entry.trim()));
+                }
+                i += 1;
+            }
+            continue;
+        }
+        i += 1;
+    }
+    raises
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+// `KeyError`, `KeyError, ValueError` or `KeyError or ValueError`; anything
+// that isn't a dotted name ends the list
+fn exception_names(text: &str) -> Vec<String> {
+    text.split([',', ' '])
+        .map(|part| part.trim_matches(|c: char| c == '`' || c == '~' || c == '!'))
+        .filter(|part| !part.is_empty() && *part != "or")
+        .take_while(|part| {
+            part.chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        })
+        .map(|part| part.to_string())
+        .collect()
+}
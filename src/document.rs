@@ -0,0 +1,92 @@
+//! Documents kept open for repeated analysis, as in an editor. Each keeps
+//! its syntax tree, so an edit only reparses the part of the file it touched.
+
+use crate::{analyze_trees, Config, ProjectFile, Warning};
+use std::path::Path;
+use tree_sitter::{InputEdit, Parser, Point, Tree};
+
+/// A source file and its syntax tree, updated in place as it is edited.
+pub struct Document {
+    filename: String,
+    module: String,
+    source: String,
+    parser: Parser,
+    tree: Tree,
+}
+
+impl Document {
+    /// Parse the source of `filename`.
+    pub fn new(filename: &str, source: String) -> Self {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_python::LANGUAGE.into())
+            .expect("Error loading Python grammar");
+        let tree = parser.parse(&source, None).unwrap();
+        Document {
+            filename: filename.to_string(),
+            module: Path::new(filename)
+                .file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into_owned()),
+            source,
+            parser,
+            tree,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Replace the whole source, parsing it from scratch.
+    pub fn set_source(&mut self, source: String) {
+        self.tree = self.parser.parse(&source, None).unwrap();
+        self.source = source;
+    }
+
+    /// Replace the bytes `start..end` of the source with `text`, reparsing
+    /// only the affected part of the tree. Offsets inside a character are
+    /// moved back to its start.
+    pub fn edit(&mut self, start: usize, end: usize, text: &str) {
+        let start = floor_char_boundary(&self.source, start.min(self.source.len()));
+        let end = floor_char_boundary(&self.source, end.clamp(start, self.source.len()));
+        let edit = InputEdit {
+            start_byte: start,
+            old_end_byte: end,
+            new_end_byte: start + text.len(),
+            start_position: point(&self.source, start),
+            old_end_position: point(&self.source, end),
+            new_end_position: Point::default(),
+        };
+        self.source.replace_range(start..end, text);
+        let edit = InputEdit {
+            new_end_position: point(&self.source, edit.new_end_byte),
+            ..edit
+        };
+        self.tree.edit(&edit);
+        self.tree = self.parser.parse(&self.source, Some(&self.tree)).unwrap();
+    }
+
+    /// The warnings found in the document's current source.
+    pub fn analyze(&self, config: &Config) -> Vec<Warning> {
+        let file = ProjectFile {
+            filename: &self.filename,
+            module: &self.module,
+            source: &self.source,
+        };
+        analyze_trees(&[file], std::slice::from_ref(&self.tree), config, None)
+    }
+}
+
+// The row and byte column of an offset
+fn point(source: &str, offset: usize) -> Point {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Point::new(before.matches('\n').count(), offset - line_start)
+}
+
+fn floor_char_boundary(source: &str, mut offset: usize) -> usize {
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
@@ -0,0 +1,196 @@
+//! Source files decoded as Python decodes them: by their byte order mark,
+//! or else by a PEP 263 declaration such as `# -*- coding: latin-1 -*-` on
+//! one of the first two lines, or else as UTF-8. Files rewritten, as when
+//! fixing them, are encoded back the way they were read.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::path::Path;
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+// The characters of the bytes 0x80 to 0x9F in Windows code page 1252, with
+// 0 for the five bytes it leaves undefined. The other bytes are those of
+// Latin-1.
+const WINDOWS_1252: [u16; 32] = [
+    0x20ac, 0, 0x201a, 0x0192, 0x201e, 0x2026, 0x2020, 0x2021, 0x02c6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0, 0x017d, 0, 0, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013, 0x2014, 0x02dc,
+    0x2122, 0x0161, 0x203a, 0x0153, 0, 0x017e, 0x0178,
+];
+
+/// The encoding a source file was read in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// UTF-8 after a byte order mark, which isn't part of the decoded text.
+    Utf8Bom,
+    /// ISO-8859-1, whose bytes are the first 256 code points.
+    Latin1,
+    /// Windows code page 1252, which has printable characters in place of
+    /// most of Latin-1's control characters.
+    Windows1252,
+}
+
+/// Read a source file and decode it.
+pub fn read(path: &Path) -> Result<(String, Encoding)> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read '{}'", path.display()))?;
+    decode(&bytes).with_context(|| format!("failed to decode '{}'", path.display()))
+}
+
+/// Decode a source file, returning the encoding it is in.
+pub fn decode(bytes: &[u8]) -> Result<(String, Encoding)> {
+    let declared = declared_encoding(bytes.strip_prefix(BOM).unwrap_or(bytes));
+    let declared = match declared {
+        Some(name) => Some(
+            Encoding::from_name(&name).ok_or_else(|| anyhow!("unsupported encoding '{}'", name))?,
+        ),
+        None => None,
+    };
+    let encoding = match (bytes.starts_with(BOM), declared) {
+        (true, None | Some(Encoding::Utf8)) => Encoding::Utf8Bom,
+        (true, Some(_)) => bail!("the declared encoding contradicts the UTF-8 byte order mark"),
+        (false, declared) => declared.unwrap_or_default(),
+    };
+    Ok((encoding.decode(bytes)?, encoding))
+}
+
+impl Encoding {
+    /// The encoding of a name as declared in a file, such as `latin-1` or
+    /// `UTF8`, if it is supported.
+    pub fn from_name(name: &str) -> Option<Self> {
+        // Names are normalized as by Python's tokenizer, which also takes
+        // `utf-8-sig` or `latin-1-unix` for their prefix
+        let name = name.to_ascii_lowercase().replace('_', "-");
+        let is = |prefix: &str| name == prefix || name.starts_with(&format!("{}-", prefix));
+        if is("utf-8") || matches!(name.as_str(), "utf8" | "ascii" | "us-ascii") {
+            Some(Encoding::Utf8)
+        } else if is("latin-1")
+            || is("iso-8859-1")
+            || is("iso-latin-1")
+            || matches!(name.as_str(), "latin1" | "iso8859-1" | "l1")
+        {
+            Some(Encoding::Latin1)
+        } else if matches!(name.as_str(), "cp1252" | "windows-1252") {
+            Some(Encoding::Windows1252)
+        } else {
+            None
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> Result<String> {
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => {
+                let bytes = bytes.strip_prefix(BOM).unwrap_or(bytes);
+                String::from_utf8(bytes.to_vec()).map_err(|e| {
+                    let valid = e.utf8_error().valid_up_to();
+                    anyhow!(
+                        "invalid UTF-8 on line {}; declare the encoding of the file, as in `# -*- coding: latin-1 -*-`",
+                        line_of(bytes, valid)
+                    )
+                })
+            }
+            Encoding::Latin1 => Ok(bytes.iter().map(|&byte| char::from(byte)).collect()),
+            Encoding::Windows1252 => bytes
+                .iter()
+                .enumerate()
+                .map(|(i, &byte)| match byte {
+                    0x80..=0x9f => char::from_u32(WINDOWS_1252[usize::from(byte - 0x80)].into())
+                        .filter(|&c| c != '\0')
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "byte 0x{:x} on line {} isn't a character of cp1252",
+                                byte,
+                                line_of(bytes, i)
+                            )
+                        }),
+                    _ => Ok(char::from(byte)),
+                })
+                .collect(),
+        }
+    }
+
+    /// Encode text decoded from a file back into the file's encoding.
+    pub fn encode(self, text: &str) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Utf8Bom => Ok([BOM, text.as_bytes()].concat()),
+            Encoding::Latin1 | Encoding::Windows1252 => text
+                .chars()
+                .map(|c| {
+                    self.byte_of(c)
+                        .ok_or_else(|| anyhow!("'{}' can't be written in {}", c, self.name()))
+                })
+                .collect(),
+        }
+    }
+
+    /// The byte offset in the file of a byte offset in the decoded text.
+    pub fn file_offset(self, text: &str, offset: usize) -> usize {
+        match self {
+            Encoding::Utf8 => offset,
+            Encoding::Utf8Bom => offset + BOM.len(),
+            // One byte per character
+            Encoding::Latin1 | Encoding::Windows1252 => text[..offset].chars().count(),
+        }
+    }
+
+    fn byte_of(self, c: char) -> Option<u8> {
+        let code = u32::from(c);
+        let defined = WINDOWS_1252
+            .iter()
+            .position(|&mapped| mapped != 0 && u32::from(mapped) == code);
+        match (self, defined) {
+            (Encoding::Windows1252, Some(index)) => Some(0x80 + index as u8),
+            (Encoding::Windows1252, None) if (0x80..0xa0).contains(&code) => None,
+            _ => u8::try_from(code).ok(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => "utf-8",
+            Encoding::Latin1 => "latin-1",
+            Encoding::Windows1252 => "cp1252",
+        }
+    }
+}
+
+// The encoding named by a `coding:` or `coding=` comment on the first line,
+// or on the second if the first is blank or a comment as well
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    for line in bytes.split(|&byte| byte == b'\n').take(2) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_start_matches([' ', '\t', '\x0c']);
+        if !line.starts_with('#') {
+            if line.trim_end().is_empty() {
+                continue;
+            }
+            return None;
+        }
+        for (index, _) in line.match_indices("coding") {
+            let rest = &line[index + "coding".len()..];
+            let Some(rest) = rest.strip_prefix([':', '=']) else {
+                continue;
+            };
+            let name: String = rest
+                .trim_start_matches([' ', '\t'])
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                .collect();
+            if !name.is_empty() {
+                return Some(name);
+            }
+        }
+    }
+    None
+}
+
+// The 1-based line of a byte offset
+fn line_of(bytes: &[u8], offset: usize) -> usize {
+    bytes[..offset]
+        .iter()
+        .filter(|&&byte| byte == b'\n')
+        .count()
+        + 1
+}
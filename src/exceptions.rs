@@ -0,0 +1,186 @@
+//! The builtin and standard library exception hierarchy, extended with
+//! exception classes defined in the analyzed module, used to decide which
+//! handlers catch an exception.
+
+use std::collections::{HashMap, HashSet};
+use tree_sitter::Node;
+
+// Each builtin or standard library exception with its base class
+const BUILTIN_BASES: &[(&str, &str)] = &[
+    ("Exception", "BaseException"),
+    ("SystemExit", "BaseException"),
+    ("KeyboardInterrupt", "BaseException"),
+    ("GeneratorExit", "BaseException"),
+    ("BaseExceptionGroup", "BaseException"),
+    ("ExceptionGroup", "Exception"),
+    ("ArithmeticError", "Exception"),
+    ("FloatingPointError", "ArithmeticError"),
+    ("OverflowError", "ArithmeticError"),
+    ("ZeroDivisionError", "ArithmeticError"),
+    ("AssertionError", "Exception"),
+    ("AttributeError", "Exception"),
+    ("BufferError", "Exception"),
+    ("EOFError", "Exception"),
+    ("ImportError", "Exception"),
+    ("ModuleNotFoundError", "ImportError"),
+    ("LookupError", "Exception"),
+    ("IndexError", "LookupError"),
+    ("KeyError", "LookupError"),
+    ("MemoryError", "Exception"),
+    ("NameError", "Exception"),
+    ("UnboundLocalError", "NameError"),
+    ("OSError", "Exception"),
+    ("BlockingIOError", "OSError"),
+    ("ChildProcessError", "OSError"),
+    ("ConnectionError", "OSError"),
+    ("BrokenPipeError", "ConnectionError"),
+    ("ConnectionAbortedError", "ConnectionError"),
+    ("ConnectionRefusedError", "ConnectionError"),
+    ("ConnectionResetError", "ConnectionError"),
+    ("FileExistsError", "OSError"),
+    ("FileNotFoundError", "OSError"),
+    ("InterruptedError", "OSError"),
+    ("IsADirectoryError", "OSError"),
+    ("NotADirectoryError", "OSError"),
+    ("PermissionError", "OSError"),
+    ("ProcessLookupError", "OSError"),
+    ("TimeoutError", "OSError"),
+    ("ReferenceError", "Exception"),
+    ("RuntimeError", "Exception"),
+    ("NotImplementedError", "RuntimeError"),
+    ("RecursionError", "RuntimeError"),
+    ("StopIteration", "Exception"),
+    ("StopAsyncIteration", "Exception"),
+    ("SyntaxError", "Exception"),
+    ("IndentationError", "SyntaxError"),
+    ("TabError", "IndentationError"),
+    ("SystemError", "Exception"),
+    ("TypeError", "Exception"),
+    ("ValueError", "Exception"),
+    ("UnicodeError", "ValueError"),
+    ("UnicodeDecodeError", "UnicodeError"),
+    ("UnicodeEncodeError", "UnicodeError"),
+    ("UnicodeTranslateError", "UnicodeError"),
+    ("Warning", "Exception"),
+    ("binascii.Error", "ValueError"),
+    ("json.JSONDecodeError", "ValueError"),
+    ("pickle.PickleError", "Exception"),
+    ("pickle.UnpicklingError", "pickle.PickleError"),
+    ("re.error", "Exception"),
+    ("statistics.StatisticsError", "ValueError"),
+    ("subprocess.SubprocessError", "Exception"),
+    (
+        "subprocess.CalledProcessError",
+        "subprocess.SubprocessError",
+    ),
+    ("subprocess.TimeoutExpired", "subprocess.SubprocessError"),
+    ("urllib.error.URLError", "OSError"),
+    ("urllib.error.HTTPError", "urllib.error.URLError"),
+    // Raised by the lookups `--framework` knows about
+    ("MultiValueDictKeyError", "KeyError"),
+    ("BadRequestKeyError", "KeyError"),
+];
+
+// Old names that still refer to a builtin exception
+fn canonical_name(name: &str) -> &str {
+    match name {
+        "IOError" | "EnvironmentError" | "socket.error" | "select.error" => "OSError",
+        _ => name,
+    }
+}
+
+/// Base classes of the exception classes defined in a module, keyed by
+/// class name. Classes with no bases are left out, since they can't be
+/// raised.
+pub(crate) fn module_exception_bases(
+    root: Node,
+    source_code: &str,
+) -> HashMap<String, Vec<String>> {
+    let mut bases = HashMap::new();
+    let mut classes = Vec::new();
+    collect_class_definitions(root, &mut classes);
+    for class in classes {
+        let (Some(name), Some(superclasses)) = (
+            class.child_by_field_name("name"),
+            class.child_by_field_name("superclasses"),
+        ) else {
+            continue;
+        };
+        let mut cursor = superclasses.walk();
+        let class_bases: Vec<String> = superclasses
+            .named_children(&mut cursor)
+            .filter(|base| matches!(base.kind(), "identifier" | "attribute"))
+            .map(|base| base.utf8_text(source_code.as_bytes()).unwrap().to_string())
+            .collect();
+        if !class_bases.is_empty() {
+            bases.insert(
+                name.utf8_text(source_code.as_bytes()).unwrap().to_string(),
+                class_bases,
+            );
+        }
+    }
+    bases
+}
+
+fn collect_class_definitions<'a>(node: Node<'a>, classes: &mut Vec<Node<'a>>) {
+    if node.kind() == "class_definition" {
+        classes.push(node);
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_class_definitions(child, classes);
+    }
+}
+
+/// The exception itself and all of its base classes. Exceptions that can't
+/// be traced back to `BaseException`, such as ones imported from other
+/// modules, are assumed to derive from `Exception`.
+pub(crate) fn exception_ancestors(
+    exception: &str,
+    module_bases: &HashMap<String, Vec<String>>,
+) -> HashSet<String> {
+    let mut ancestors = HashSet::new();
+    let mut pending = vec![canonical_name(exception).to_string()];
+    while let Some(name) = pending.pop() {
+        if !ancestors.insert(name.clone()) {
+            continue;
+        }
+        if let Some(bases) = module_bases.get(&name) {
+            pending.extend(bases.iter().map(|base| canonical_name(base).to_string()));
+        } else if let Some((_, base)) = BUILTIN_BASES.iter().find(|(child, _)| *child == name) {
+            pending.push(base.to_string());
+        }
+    }
+    if !ancestors.contains("BaseException") {
+        ancestors.insert("Exception".to_string());
+        ancestors.insert("BaseException".to_string());
+    }
+    ancestors
+}
+
+/// Whether the exception is a builtin or standard library one, or one
+/// defined in the module, so that its base classes are known.
+pub(crate) fn is_known_exception(name: &str, module_bases: &HashMap<String, Vec<String>>) -> bool {
+    let name = canonical_name(name);
+    name == "BaseException"
+        || module_bases.contains_key(name)
+        || BUILTIN_BASES.iter().any(|(child, _)| *child == name)
+}
+
+/// Whether `except handler:` catches an exception with these ancestors.
+/// Handlers are matched by their name as written and by the name they were
+/// imported under, so `from json import JSONDecodeError` also catches
+/// `json.JSONDecodeError`.
+pub(crate) fn handler_catches(
+    handler: &str,
+    qualified_handler: &str,
+    ancestors: &HashSet<String>,
+) -> bool {
+    ancestors.contains(canonical_name(handler))
+        || ancestors.contains(canonical_name(qualified_handler))
+        // Exceptions raised in other modules are known by their class name,
+        // so `except yaml.YAMLError` catches a YAMLError from the package
+        || qualified_handler
+            .rsplit_once('.')
+            .is_some_and(|(_, class)| ancestors.contains(class))
+}
@@ -0,0 +1,288 @@
+//! Applying suggested fixes to source files, and showing the changes they
+//! would make as a unified diff.
+
+use pysleuth::{Applicability, Edit, Warning};
+
+// Lines of context around each change in a diff
+const CONTEXT: usize = 3;
+
+/// The source with the fixes suggested for `warnings` applied, and whether
+/// each warning was fixed. Unsafe fixes are only applied with
+/// `unsafe_fixes`, and the ones that are only shown never are. A fix
+/// overlapping one applied before it is left for the next run.
+pub fn apply(source_code: &str, warnings: &[Warning], unsafe_fixes: bool) -> (String, Vec<bool>) {
+    let applies = |warning: &Warning| {
+        warning
+            .suggestion
+            .as_ref()
+            .is_some_and(|suggestion| match suggestion.applicability {
+                Applicability::Safe => true,
+                Applicability::Unsafe => unsafe_fixes,
+                Applicability::Display => false,
+            })
+    };
+    let mut order: Vec<usize> = (0..warnings.len())
+        .filter(|&i| applies(&warnings[i]))
+        .collect();
+    order.sort_by_key(|&i| warnings[i].suggestion.as_ref().unwrap().edit.start);
+
+    let mut fixed = vec![false; warnings.len()];
+    let mut output = String::with_capacity(source_code.len());
+    let mut position = 0;
+    for i in order {
+        let edit = &warnings[i].suggestion.as_ref().unwrap().edit;
+        if edit.start < position {
+            continue;
+        }
+        output.push_str(&source_code[position..edit.start]);
+        output.push_str(&edit.text);
+        position = edit.end;
+        fixed[i] = true;
+    }
+    output.push_str(&source_code[position..]);
+    (output, fixed)
+}
+
+/// Drop the warnings `apply` fixed and move the others to where their code
+/// is in the fixed source, so that they point into the file as written.
+pub fn relocate(
+    warnings: &mut Vec<Warning>,
+    fixed: &[bool],
+    source_code: &str,
+    fixed_source: &str,
+) {
+    let mut edits: Vec<Edit> = warnings
+        .iter()
+        .zip(fixed)
+        .filter(|(_, &fixed)| fixed)
+        .filter_map(|(warning, _)| warning.suggestion.as_ref())
+        .map(|suggestion| suggestion.edit.clone())
+        .collect();
+    edits.sort_by_key(|edit| edit.start);
+    let mut fixed = fixed.iter();
+    warnings.retain(|_| !fixed.next().unwrap());
+
+    // Code after an edit moves by how much longer it made the source, and
+    // code inside one moves to where it starts
+    let moved = |offset: usize| {
+        let mut shift = 0isize;
+        for edit in &edits {
+            if edit.end <= offset {
+                shift += edit.text.len() as isize - (edit.end - edit.start) as isize;
+            } else if edit.start < offset {
+                return (edit.start as isize + shift) as usize;
+            } else {
+                break;
+            }
+        }
+        (offset as isize + shift) as usize
+    };
+    let old_lines = line_starts(source_code);
+    let new_lines = line_starts(fixed_source);
+    let relocate_position = |line: &mut usize, column: &mut usize| {
+        let start = old_lines[(*line - 1).min(old_lines.len() - 1)];
+        let offset = start
+            + source_code[start..]
+                .chars()
+                .take(*column - 1)
+                .map(char::len_utf8)
+                .sum::<usize>();
+        let offset = moved(offset).min(fixed_source.len());
+        let index = new_lines.partition_point(|&start| start <= offset);
+        *line = index;
+        *column = fixed_source[new_lines[index - 1]..offset].chars().count() + 1;
+    };
+    for warning in warnings {
+        relocate_position(&mut warning.line, &mut warning.column);
+        relocate_position(&mut warning.end_line, &mut warning.end_column);
+        for related in &mut warning.related {
+            if related.filename == warning.filename {
+                relocate_position(&mut related.line, &mut related.column);
+                relocate_position(&mut related.end_line, &mut related.end_column);
+            }
+        }
+        if let Some(suggestion) = &mut warning.suggestion {
+            suggestion.edit.start = moved(suggestion.edit.start);
+            suggestion.edit.end = moved(suggestion.edit.end);
+        }
+    }
+}
+
+// The byte offset each line of the source starts at
+fn line_starts(source_code: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(source_code.match_indices('\n').map(|(i, _)| i + 1));
+    starts
+}
+
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A unified diff from `old` to `new`, empty when they are the same.
+pub fn unified_diff(filename: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old_lines, &new_lines);
+
+    let mut diff = String::new();
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&i| !matches!(lines[i], Line::Same(_)))
+        .collect();
+    if changed.is_empty() {
+        return diff;
+    }
+    let path = filename.trim_start_matches("./").trim_start_matches('/');
+    diff.push_str(&format!("--- a/{}\n+++ b/{}\n", path, path));
+
+    // Changes close enough for their context to touch share a hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        // Line numbers of the hunk's first line in the old and new file
+        let (mut old_line, mut new_line) = (1, 1);
+        for line in &lines[..start] {
+            match line {
+                Line::Same(_) => {
+                    old_line += 1;
+                    new_line += 1;
+                }
+                Line::Removed(_) => old_line += 1,
+                Line::Added(_) => new_line += 1,
+            }
+        }
+        let hunk = &lines[start..end];
+        let old_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            if old_count == 0 {
+                old_line - 1
+            } else {
+                old_line
+            },
+            old_count,
+            if new_count == 0 {
+                new_line - 1
+            } else {
+                new_line
+            },
+            new_count
+        ));
+        for line in hunk {
+            let (prefix, text) = match line {
+                Line::Same(text) => (' ', text),
+                Line::Removed(text) => ('-', text),
+                Line::Added(text) => ('+', text),
+            };
+            diff.push(prefix);
+            diff.push_str(text);
+            diff.push('\n');
+        }
+    }
+    diff
+}
+
+// The shortest edit script between two files, found with Myers' algorithm
+// after setting aside the lines they start and end with in common
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // `trace[d][k]` is the furthest position in `a` reached with `d` edits
+    // on diagonal `k`, offset so that negative diagonals can be indexed
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let offset = n + m + 1;
+    let mut furthest = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = Vec::new();
+    'search: for d in 0..=n + m {
+        trace.push(furthest.clone());
+        for k in (-d..=d).step_by(2) {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && furthest[index - 1] < furthest[index + 1]) {
+                furthest[index + 1]
+            } else {
+                furthest[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[index] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk back from the end, one edit per step
+    let mut middle = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..trace.len() as isize).rev() {
+        let furthest = &trace[d as usize];
+        let k = x - y;
+        let previous_k = if k == -d
+            || (k != d && furthest[(k - 1 + offset) as usize] < furthest[(k + 1 + offset) as usize])
+        {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = furthest[(previous_k + offset) as usize];
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            middle.push(Line::Same(a[x as usize - 1]));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == previous_x {
+                middle.push(Line::Added(b[y as usize - 1]));
+            } else {
+                middle.push(Line::Removed(a[x as usize - 1]));
+            }
+            x = previous_x;
+            y = previous_y;
+        }
+    }
+    middle.reverse();
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+    lines.extend(middle);
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| Line::Same(line)),
+    );
+    lines
+}
@@ -0,0 +1,117 @@
+//! `--format-template`: one line per warning in a layout of the user's,
+//! such as `{file}:{line}:{col}: {code} {message}`, to match the error
+//! format of an editor or a log parser.
+
+use pysleuth::Warning;
+use std::str::FromStr;
+
+/// The fields a template may refer to.
+pub const FIELDS: [&str; 12] = [
+    "file",
+    "cell",
+    "line",
+    "col",
+    "end_line",
+    "end_col",
+    "severity",
+    "code",
+    "rule",
+    "exceptions",
+    "function",
+    "message",
+];
+
+/// A parsed template, where `{{` and `}}` stand for literal braces.
+#[derive(Clone, Debug)]
+pub struct Template(Vec<Part>);
+
+#[derive(Clone, Debug)]
+enum Part {
+    Text(String),
+    Field(&'static str),
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(
+                                    "unclosed '{' in format template; write '{{' for a brace"
+                                        .to_string(),
+                                )
+                            }
+                        }
+                    }
+                    let field = FIELDS.iter().find(|&&field| field == name).ok_or_else(|| {
+                        format!(
+                            "unknown field '{{{}}}' in format template; expected one of {}",
+                            name,
+                            FIELDS.map(|field| format!("{{{}}}", field)).join(", ")
+                        )
+                    })?;
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                    parts.push(Part::Field(field));
+                }
+                '}' => {
+                    return Err(
+                        "unmatched '}' in format template; write '}}' for a brace".to_string()
+                    )
+                }
+                c => text.push(c),
+            }
+        }
+        parts.push(Part::Text(text));
+        Ok(Template(parts))
+    }
+}
+
+impl Template {
+    /// The warning's line. Fields a warning lacks, such as the cell of a
+    /// warning outside a notebook, are left empty.
+    pub fn render(&self, warning: &Warning) -> String {
+        self.0
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(field) => field_value(warning, field),
+            })
+            .collect()
+    }
+}
+
+fn field_value(warning: &Warning, field: &str) -> String {
+    match field {
+        "file" => warning.filename.clone(),
+        "cell" => warning.cell.map_or(String::new(), |cell| cell.to_string()),
+        "line" => warning.line.to_string(),
+        "col" => warning.column.to_string(),
+        "end_line" => warning.end_line.to_string(),
+        "end_col" => warning.end_column.to_string(),
+        "severity" => warning.severity.to_string(),
+        "code" => warning.rule.code().to_string(),
+        "rule" => warning.rule.name().to_string(),
+        "exceptions" => warning.exceptions.join(", "),
+        "function" => warning.function.clone(),
+        _ => warning.message.clone(),
+    }
+}
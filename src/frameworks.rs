@@ -0,0 +1,185 @@
+//! Idioms of web frameworks, recognized when the framework is named with
+//! `--framework`: what reading the parameters of a request raises, and
+//! which functions are views, where an exception that isn't handled becomes
+//! a 500 response.
+
+use crate::checks::{is_safe_subscript, Check, CheckContext, Finding};
+use crate::{is_store, Rule};
+use std::fmt;
+use std::str::FromStr;
+use tree_sitter::Node;
+
+/// A web framework whose idioms are checked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Framework {
+    Django,
+    Flask,
+}
+
+impl FromStr for Framework {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "django" => Ok(Framework::Django),
+            "flask" => Ok(Framework::Flask),
+            _ => Err(format!(
+                "unknown framework '{}'; expected django or flask",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Framework {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Framework::Django => "django",
+            Framework::Flask => "flask",
+        })
+    }
+}
+
+// Decorators of Flask app and blueprint methods that register views, as in
+// `@app.route("/")` or `@bp.post("/items")`
+const FLASK_ROUTE_DECORATORS: &[&str] = &["route", "get", "post", "put", "patch", "delete"];
+
+impl Framework {
+    /// The checks for the framework's idioms, run before the others so
+    /// that they decide what a request's parameters raise.
+    pub(crate) fn checks(self) -> Vec<Box<dyn Check>> {
+        vec![Box::new(RequestCheck(self))]
+    }
+
+    /// Whether a function is a view: for Django, a function or method
+    /// taking the request as its first argument; for Flask, a function
+    /// registered with a route decorator.
+    pub(crate) fn is_view(self, function_node: Node, source_code: &str) -> bool {
+        match self {
+            Framework::Django => {
+                let Some(parameters) = function_node.child_by_field_name("parameters") else {
+                    return false;
+                };
+                let mut cursor = parameters.walk();
+                let names: Vec<&str> = parameters
+                    .named_children(&mut cursor)
+                    .filter_map(|parameter| match parameter.kind() {
+                        "identifier" => Some(parameter),
+                        "typed_parameter" | "default_parameter" | "typed_default_parameter" => {
+                            parameter
+                                .child_by_field_name("name")
+                                .or_else(|| parameter.named_child(0))
+                        }
+                        _ => None,
+                    })
+                    .map(|name| name.utf8_text(source_code.as_bytes()).unwrap())
+                    .take(2)
+                    .collect();
+                matches!(names.as_slice(), ["request", ..] | ["self", "request"])
+            }
+            Framework::Flask => {
+                let Some(definition) = function_node
+                    .parent()
+                    .filter(|parent| parent.kind() == "decorated_definition")
+                else {
+                    return false;
+                };
+                let mut cursor = definition.walk();
+                let is_view = definition
+                    .named_children(&mut cursor)
+                    .filter(|child| child.kind() == "decorator")
+                    .filter_map(|decorator| decorator.named_child(0))
+                    .filter(|expression| expression.kind() == "call")
+                    .filter_map(|call| call.child_by_field_name("function"))
+                    .filter(|function| function.kind() == "attribute")
+                    .filter_map(|function| function.child_by_field_name("attribute"))
+                    .any(|attribute| {
+                        let name = attribute.utf8_text(source_code.as_bytes()).unwrap();
+                        FLASK_ROUTE_DECORATORS.contains(&name)
+                    });
+                is_view
+            }
+        }
+    }
+
+    /// Whether the framework turns an exception escaping a view into a
+    /// response of its own, such as a 404 or a 400, rather than a 500.
+    pub(crate) fn handles(self, exception: &str) -> bool {
+        let handled: &[&str] = match self {
+            Framework::Django => &[
+                "Http404",
+                "PermissionDenied",
+                "SuspiciousOperation",
+                "BadRequest",
+            ],
+            Framework::Flask => &[
+                "BadRequestKeyError",
+                "HTTPException",
+                "NotFound",
+                "BadRequest",
+            ],
+        };
+        handled.contains(&exception)
+    }
+}
+
+// `PW001`: the exception a lookup in the parameters of a request raises,
+// such as MultiValueDictKeyError from Django's `request.GET["q"]`
+struct RequestCheck(Framework);
+
+impl Check for RequestCheck {
+    fn rule(&self) -> Rule {
+        Rule::UnguardedDictAccess
+    }
+
+    fn node_kinds(&self) -> &[&str] {
+        &["subscript"]
+    }
+
+    fn check(&self, node: Node, context: &CheckContext) -> Option<Finding> {
+        let value = node.child_by_field_name("value")?;
+        let (parameters, exception) = match (self.0, value.kind()) {
+            // `request.get_json()["x"]`
+            (Framework::Flask, "call") => {
+                let function = value.child_by_field_name("function")?;
+                let object = function.child_by_field_name("object")?;
+                let attribute = function.child_by_field_name("attribute")?;
+                (context.text(attribute) == "get_json" && is_request(context.text(object)))
+                    .then_some((context.text(value), "KeyError"))?
+            }
+            (framework, "attribute") => {
+                let object = value.child_by_field_name("object")?;
+                if !is_request(context.text(object)) {
+                    return None;
+                }
+                let attribute = context.text(value.child_by_field_name("attribute")?);
+                let exception = match (framework, attribute) {
+                    (Framework::Django, "GET" | "POST" | "FILES") => "MultiValueDictKeyError",
+                    (Framework::Flask, "json") => "KeyError",
+                    (Framework::Flask, "args" | "form" | "files" | "values") => {
+                        "BadRequestKeyError"
+                    }
+                    _ => return None,
+                };
+                (context.text(value), exception)
+            }
+            _ => return None,
+        };
+        if is_store(node) || is_safe_subscript(node, context) {
+            return None;
+        }
+        Some(Finding {
+            exception: exception.to_string(),
+            message: format!(
+                "Possible {} from {} in function '{}'",
+                exception, parameters, context.function
+            ),
+        })
+    }
+}
+
+// Whether an expression is the request of a view, as `request` or a
+// class-based view's `self.request`
+fn is_request(text: &str) -> bool {
+    text == "request" || text.ends_with(".request")
+}
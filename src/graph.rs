@@ -0,0 +1,60 @@
+//! The call graph of a project as a Graphviz DOT graph, with each call
+//! labelled by the exceptions flowing along it, and the functions where
+//! exceptions start outlined in red.
+
+use pysleuth::ExceptionFlow;
+use std::fmt::Write;
+
+/// A `digraph` with a node for each function and an edge for each call.
+pub fn dot(flow: &ExceptionFlow) -> String {
+    let mut dot = String::from("digraph exceptions {\n");
+    dot.push_str("  rankdir=LR;\n");
+    dot.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+    for function in &flow.functions {
+        let mut label = function.name.clone();
+        if !function.raises.is_empty() {
+            label.push_str(&format!("\nraises {}", function.raises.join(", ")));
+        }
+        let style = if function.originates.is_empty() {
+            ""
+        } else {
+            ", color=red"
+        };
+        writeln!(
+            dot,
+            "  {} [label={}, tooltip={}{}];",
+            quote(&function.name),
+            quote(&label),
+            quote(&format!("{}:{}", function.filename, function.line)),
+            style
+        )
+        .unwrap();
+    }
+    for call in &flow.calls {
+        let attributes = if call.exceptions.is_empty() {
+            " [style=dashed]".to_string()
+        } else {
+            format!(" [label={}, color=red]", quote(&call.exceptions.join(", ")))
+        };
+        writeln!(
+            dot,
+            "  {} -> {}{};",
+            quote(&call.caller),
+            quote(&call.callee),
+            attributes
+        )
+        .unwrap();
+    }
+    dot.push('}');
+    dot
+}
+
+// A DOT string, with newlines as line breaks
+fn quote(text: &str) -> String {
+    format!(
+        "\"{}\"",
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
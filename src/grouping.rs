@@ -0,0 +1,137 @@
+//! Grouping and folding of the warnings printed as text, so that a large
+//! file doesn't bury the output in near-duplicates. Reports and the exit
+//! status always cover every warning; only what is printed is folded.
+
+use pysleuth::Warning;
+use std::collections::HashMap;
+
+/// What warnings are printed under headers by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    File,
+    Function,
+    Rule,
+}
+
+impl std::str::FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "file" => Ok(GroupBy::File),
+            "function" => Ok(GroupBy::Function),
+            "rule" => Ok(GroupBy::Rule),
+            _ => Err(format!(
+                "unknown grouping '{}'; expected file, function or rule",
+                value
+            )),
+        }
+    }
+}
+
+/// How many warnings of a function are printed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Folding {
+    /// Print at most this many warnings per function.
+    pub max_per_function: Option<usize>,
+    /// Print only the first warning of a function for an exception from the
+    /// same place, as when the same call is made several times.
+    pub dedup: bool,
+}
+
+/// A line of the printed output.
+#[derive(Debug, PartialEq)]
+pub enum Entry<'a> {
+    /// The start of a group, with its number of warnings.
+    Header(String, usize),
+    Warning(&'a Warning),
+    /// The number of warnings of a function left out after the one before.
+    Folded(&'a str, usize),
+}
+
+/// The entries to print for `warnings`, which are in file and position
+/// order.
+pub fn arrange<'a>(
+    warnings: &'a [Warning],
+    group_by: Option<GroupBy>,
+    folding: Folding,
+) -> Vec<Entry<'a>> {
+    // Groups in the order they first appear, except rules, which are listed
+    // in rule order
+    let mut groups: Vec<(String, Vec<&Warning>)> = Vec::new();
+    for warning in warnings {
+        let name = match group_by {
+            None => String::new(),
+            Some(GroupBy::File) => warning.filename.clone(),
+            Some(GroupBy::Function) => {
+                format!("{}: function '{}'", warning.filename, warning.function)
+            }
+            Some(GroupBy::Rule) => format!("{} {}", warning.rule, warning.rule.name()),
+        };
+        match groups.iter_mut().find(|(group, _)| *group == name) {
+            Some((_, members)) => members.push(warning),
+            None => groups.push((name, vec![warning])),
+        }
+    }
+    if group_by == Some(GroupBy::Rule) {
+        groups.sort_by_key(|(_, members)| members[0].rule);
+    }
+
+    let mut entries = Vec::new();
+    for (name, members) in groups {
+        if group_by.is_some() {
+            entries.push(Entry::Header(name, members.len()));
+        }
+        entries.extend(fold(&members, folding));
+    }
+    entries
+}
+
+// The warnings of a group with those over the cap of their function, or
+// repeating an earlier one, left out. What is left out of a function is
+// noted after its last warning that is kept.
+fn fold<'a>(warnings: &[&'a Warning], folding: Folding) -> Vec<Entry<'a>> {
+    type Function<'a> = (&'a str, &'a str);
+    let mut kept: HashMap<Function, usize> = HashMap::new();
+    let mut left_out: HashMap<Function, usize> = HashMap::new();
+    let mut origins = Vec::new();
+    let mut shown = Vec::new();
+    for warning in warnings {
+        let function = (warning.filename.as_str(), warning.function.as_str());
+        let repeated = folding.dedup
+            && origin(warning).is_some_and(|origin| {
+                let key = (function, warning.rule, &warning.exceptions, origin);
+                let repeated = origins.contains(&key);
+                origins.push(key);
+                repeated
+            });
+        let count = kept.entry(function).or_insert(0);
+        if repeated || folding.max_per_function.is_some_and(|max| *count >= max) {
+            *left_out.entry(function).or_insert(0) += 1;
+        } else {
+            *count += 1;
+            shown.push(*warning);
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (i, warning) in shown.iter().enumerate() {
+        entries.push(Entry::Warning(warning));
+        let function = (warning.filename.as_str(), warning.function.as_str());
+        let last_of_function = shown[i + 1..]
+            .iter()
+            .all(|later| (later.filename.as_str(), later.function.as_str()) != function);
+        if let Some(count) = left_out.get(&function).filter(|_| last_of_function) {
+            entries.push(Entry::Folded(&warning.function, *count));
+        }
+    }
+    entries
+}
+
+// Where the exception of a warning is raised, for warnings on calls
+fn origin(warning: &Warning) -> Option<(&str, usize, usize)> {
+    warning
+        .related
+        .last()
+        .map(|related| (related.filename.as_str(), related.line, related.column))
+}
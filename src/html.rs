@@ -0,0 +1,316 @@
+//! A standalone HTML report, for sharing results with people who don't run
+//! the CLI: a table of every warning that can be filtered by rule and
+//! exception, then a section per file with highlighted snippets and, for
+//! unhandled calls, the chain of calls the exception escapes through.
+
+use pysleuth::notebook;
+use pysleuth::Warning;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use tree_sitter::{Node, Parser};
+
+// Lines shown before and after a span
+const CONTEXT: usize = 1;
+
+// The span of a snippet longer than this is cut short
+const MAX_SPAN_LINES: usize = 6;
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; margin: 2em; color: #1f2328; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.3em 0.6em; border-bottom: 1px solid #d0d7de; }
+.filters { margin: 1em 0; }
+.filters label { margin-right: 1em; }
+section { margin-top: 2em; }
+.warning { margin: 1em 0 1.5em; }
+.warning h3 { font-size: 1em; margin: 0 0 0.4em; }
+.rule { color: #57606a; font-weight: normal; }
+pre { background: #f6f8fa; padding: 0.5em; overflow-x: auto; margin: 0.3em 0; }
+.ln { color: #8c959f; user-select: none; }
+.k { color: #cf222e; }
+.s { color: #0a3069; }
+.c { color: #6e7781; font-style: italic; }
+.n { color: #0550ae; }
+.f { color: #8250df; }
+.m { background: #ffebe9; text-decoration: underline wavy #cf222e; }
+.chain { margin: 0.3em 0; }
+.chain .m { background: #ddf4ff; text-decoration-color: #0969da; }
+";
+
+const SCRIPT: &str = "
+function filter() {
+  const rule = document.getElementById('rule').value;
+  const exception = document.getElementById('exception').value;
+  const shown = el => (!rule || el.dataset.rule === rule)
+    && (!exception || el.dataset.exceptions.split(' ').includes(exception));
+  document.querySelectorAll('[data-rule]').forEach(el => {
+    el.hidden = !shown(el);
+  });
+  document.querySelectorAll('section').forEach(section => {
+    section.hidden = !section.querySelector('.warning:not([hidden])');
+  });
+}
+document.getElementById('rule').addEventListener('change', filter);
+document.getElementById('exception').addEventListener('change', filter);
+";
+
+/// The report for `warnings`, with snippets from `sources`, the
+/// `(filename, cell, source)` of each analyzed file, or of each code cell of
+/// a notebook.
+pub fn report(warnings: &[Warning], sources: &[(&str, Option<usize>, &str)]) -> String {
+    let highlighted: HashMap<(&str, Option<usize>), Highlighted> = sources
+        .iter()
+        .map(|(filename, cell, source)| ((*filename, *cell), Highlighted::new(source)))
+        .collect();
+    let rules: BTreeSet<String> = warnings.iter().map(|w| w.rule.to_string()).collect();
+    let exceptions: BTreeSet<&str> = warnings
+        .iter()
+        .flat_map(|w| w.exceptions.iter().map(String::as_str))
+        .collect();
+    let files: BTreeSet<&str> = warnings.iter().map(|w| w.filename.as_str()).collect();
+
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{name} report</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>{name} report</h1>\n<p>{} warning(s) in {} file(s)</p>",
+        warnings.len(),
+        files.len(),
+        name = env!("CARGO_PKG_NAME"),
+    )
+    .unwrap();
+
+    // The filters and the table of every warning
+    html.push_str("<div class=\"filters\">\n");
+    for (id, label, values) in [
+        (
+            "rule",
+            "Rule",
+            rules.iter().map(String::as_str).collect::<Vec<_>>(),
+        ),
+        ("exception", "Exception", exceptions.into_iter().collect()),
+    ] {
+        write!(
+            html,
+            "<label>{label} <select id=\"{id}\"><option value=\"\">All</option>"
+        )
+        .unwrap();
+        for value in values {
+            write!(html, "<option>{}</option>", escape(value)).unwrap();
+        }
+        html.push_str("</select></label>\n");
+    }
+    html.push_str(
+        "</div>\n<table>\n<thead><tr><th>Location</th><th>Rule</th><th>Exceptions</th>\
+         <th>Message</th></tr></thead>\n<tbody>\n",
+    );
+    for (i, warning) in warnings.iter().enumerate() {
+        writeln!(
+            html,
+            "<tr {}><td><a href=\"#w{i}\">{}:{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            data_attributes(warning),
+            escape(&warning.filename),
+            notebook::position(warning.cell, warning.line, warning.column),
+            warning.rule,
+            escape(&warning.exceptions.join(", ")),
+            escape(&warning.message),
+        )
+        .unwrap();
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    // A section per file, in the order the files were analyzed
+    let mut sections = BTreeSet::new();
+    for (filename, _, _) in sources {
+        if !files.contains(filename) || !sections.insert(*filename) {
+            continue;
+        }
+        writeln!(html, "<section>\n<h2>{}</h2>", escape(filename)).unwrap();
+        for (i, warning) in warnings.iter().enumerate() {
+            if warning.filename != *filename {
+                continue;
+            }
+            writeln!(
+                html,
+                "<div class=\"warning\" id=\"w{i}\" {}>\n<h3>{} {} <span class=\"rule\">[{}]</span></h3>",
+                data_attributes(warning),
+                notebook::position(warning.cell, warning.line, warning.column),
+                escape(&warning.message),
+                warning.rule,
+            )
+            .unwrap();
+            if let Some(source) = highlighted.get(&(*filename, warning.cell)) {
+                html.push_str(&source.snippet(
+                    (warning.line, warning.column),
+                    (warning.end_line, warning.end_column),
+                ));
+            }
+            if !warning.related.is_empty() {
+                html.push_str("<ol class=\"chain\">\n");
+                for related in &warning.related {
+                    write!(
+                        html,
+                        "<li>{}:{}: {}",
+                        escape(&related.filename),
+                        notebook::position(related.cell, related.line, related.column),
+                        escape(&related.message)
+                    )
+                    .unwrap();
+                    if let Some(source) =
+                        highlighted.get(&(related.filename.as_str(), related.cell))
+                    {
+                        html.push_str(&source.snippet(
+                            (related.line, related.column),
+                            (related.end_line, related.end_column),
+                        ));
+                    }
+                    html.push_str("</li>\n");
+                }
+                html.push_str("</ol>\n");
+            }
+            html.push_str("</div>\n");
+        }
+        html.push_str("</section>\n");
+    }
+
+    writeln!(html, "<script>{SCRIPT}</script>\n</body>\n</html>").unwrap();
+    html
+}
+
+fn data_attributes(warning: &Warning) -> String {
+    format!(
+        "data-rule=\"{}\" data-exceptions=\"{}\"",
+        warning.rule,
+        escape(&warning.exceptions.join(" "))
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// A source file with the highlighting class of each byte
+struct Highlighted<'a> {
+    source: &'a str,
+    classes: Vec<Option<&'static str>>,
+    // The byte offset each line starts at
+    line_starts: Vec<usize>,
+}
+
+impl<'a> Highlighted<'a> {
+    fn new(source: &'a str) -> Self {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_python::LANGUAGE.into())
+            .expect("Error loading Python grammar");
+        let tree = parser.parse(source, None).unwrap();
+        let mut classes = vec![None; source.len()];
+        highlight(tree.root_node(), &mut classes);
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Highlighted {
+            source,
+            classes,
+            line_starts,
+        }
+    }
+
+    // The lines of a span, with 1-based positions as in warnings, marked
+    // within the lines around it
+    fn snippet(
+        &self,
+        (line, column): (usize, usize),
+        (end_line, end_column): (usize, usize),
+    ) -> String {
+        // Columns count characters, the source is marked by byte
+        let offset = |line: usize, column: usize| {
+            self.line_starts
+                .get(line - 1)
+                .map_or(self.source.len(), |&start| {
+                    let bytes: usize = self.source[start..]
+                        .chars()
+                        .take(column - 1)
+                        .map(char::len_utf8)
+                        .sum();
+                    start + bytes
+                })
+                .min(self.source.len())
+        };
+        let marked = offset(line, column)..offset(end_line, end_column);
+        let last = end_line.min(line + MAX_SPAN_LINES - 1) + CONTEXT;
+        let first = line.saturating_sub(CONTEXT).max(1);
+        let width = last.to_string().len();
+
+        let mut html = String::from("<pre>");
+        for number in first..=last.min(self.source.lines().count()) {
+            let start = self.line_starts[number - 1];
+            let end = self
+                .line_starts
+                .get(number)
+                .map_or(self.source.len(), |next| next - 1);
+            write!(html, "<span class=\"ln\">{:>width$} | </span>", number).unwrap();
+
+            // Runs of bytes with the same class and marking become one span
+            let key = |i: usize| (self.classes[i], marked.contains(&i));
+            let mut run_start = start;
+            for i in start..=end {
+                if i < end && key(i) == key(run_start) {
+                    continue;
+                }
+                if run_start < i {
+                    let (class, marked) = key(run_start);
+                    let text = escape(self.source[run_start..i].trim_end_matches('\r'));
+                    match (class, marked) {
+                        (None, false) => html.push_str(&text),
+                        (Some(class), false) => {
+                            write!(html, "<span class=\"{class}\">{text}</span>").unwrap()
+                        }
+                        (None, true) => write!(html, "<span class=\"m\">{text}</span>").unwrap(),
+                        (Some(class), true) => {
+                            write!(html, "<span class=\"{class} m\">{text}</span>").unwrap()
+                        }
+                    }
+                }
+                run_start = i;
+            }
+            html.push('\n');
+        }
+        html.push_str("</pre>\n");
+        html
+    }
+}
+
+// Keywords, strings, comments, numbers and names being defined
+fn highlight(node: Node, classes: &mut [Option<&'static str>]) {
+    let class = match node.kind() {
+        "comment" => Some("c"),
+        "string" | "concatenated_string" => Some("s"),
+        "integer" | "float" => Some("n"),
+        "true" | "false" | "none" => Some("k"),
+        "identifier"
+            if node.parent().is_some_and(|parent| {
+                matches!(parent.kind(), "function_definition" | "class_definition")
+                    && parent
+                        .child_by_field_name("name")
+                        .is_some_and(|name| name.id() == node.id())
+            }) =>
+        {
+            Some("f")
+        }
+        kind if !node.is_named() && kind.chars().all(|c| c.is_ascii_alphabetic()) => Some("k"),
+        _ => None,
+    };
+    if let Some(class) = class {
+        classes[node.start_byte()..node.end_byte()].fill(Some(class));
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        highlight(child, classes);
+    }
+}
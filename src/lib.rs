@@ -0,0 +1,6471 @@
+//! Static analysis of Python source for exceptions that may go unhandled.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::{Cell, OnceCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
+use timings::{Phase, Timings};
+use tree_sitter::{Node, Parser, Tree};
+
+mod analyzer;
+pub mod baseline;
+pub mod cache;
+pub mod checks;
+pub mod discovery;
+mod docstring;
+mod document;
+pub mod encoding;
+mod exceptions;
+mod frameworks;
+pub mod notebook;
+pub mod site_packages;
+mod stdlib;
+pub mod stubs;
+mod suppression;
+mod templates;
+pub mod timings;
+mod typed_dict;
+
+pub use analyzer::{Analyzer, SourceFile};
+pub use docstring::DocstringStyle;
+pub use document::Document;
+pub use frameworks::Framework;
+
+/// Options controlling the analysis.
+pub struct Config {
+    /// Target Python version for version-specific checks, or the oldest one
+    /// targeted, which decides what syntax is expected and which handlers
+    /// work on every targeted version.
+    pub python_version: PythonVersion,
+    /// The newest targeted Python version, for code that runs on a range of
+    /// versions; the same as `python_version` if unset.
+    pub newest_python_version: Option<PythonVersion>,
+    /// Attach a suggested fix to each possible KeyError warning.
+    pub suggest_fixes: bool,
+    /// Rules to check; all of them by default.
+    pub enabled_rules: HashSet<Rule>,
+    /// Use type annotations to tell mapping subscripts (KeyError) from
+    /// sequence subscripts (IndexError), and to skip subscripts on mappings
+    /// that fill in missing keys, such as `defaultdict`.
+    pub check_annotations: bool,
+    /// Only report functions exported through `__all__`, and the functions
+    /// they call, when the module defines `__all__`.
+    pub public_only: bool,
+    /// Treat `assert` statements as raising AssertionError, for libraries
+    /// that validate their arguments with them. Off by default, as asserts
+    /// usually check invariants and are stripped by `python -O`.
+    pub strict_asserts: bool,
+    /// Only report the syntax errors of files that don't parse, instead of
+    /// also analyzing what does.
+    pub skip_syntax_errors: bool,
+    /// Stub signatures used to work out the receiver of method calls into
+    /// the standard library.
+    pub stubs: Option<stubs::Stubs>,
+    /// Installed third-party packages, whose source tells what calls into
+    /// them may raise.
+    pub site_packages: Option<site_packages::SitePackages>,
+    /// Decorators that catch the exceptions of the functions they wrap, such
+    /// as `retry` or `tenacity.retry`. Other decorators, like
+    /// `functools.lru_cache`, are assumed to let exceptions through.
+    pub swallowing_decorators: HashSet<String>,
+    /// Functions that call the functions passed to them, such as `map` or
+    /// `executor.submit`, so that the callbacks' exceptions escape at the
+    /// call. Names match by their last component, as for decorators.
+    pub higher_order_functions: HashSet<String>,
+    /// Functions assumed never to raise, such as wrappers that log and
+    /// swallow errors. Their exceptions aren't propagated to callers.
+    pub safe_functions: HashSet<String>,
+    /// The convention of the `Raises` sections of docstrings; any of them
+    /// if unset.
+    pub docstring_style: Option<DocstringStyle>,
+    /// Only report exceptions escaping the entry points of the program: the
+    /// `if __name__ == "__main__":` block, module-level functions named
+    /// `main` and these functions, matched by their last component as for
+    /// decorators. Off if unset.
+    pub entry_points: Option<HashSet<String>>,
+    /// Web frameworks whose idioms are recognized: what looking up the
+    /// parameters of a request raises and, in entry point mode, views as
+    /// entry points.
+    pub frameworks: Vec<Framework>,
+    /// Exceptions handlers may ignore without a warning, such as
+    /// `FileNotFoundError`. Subclasses of them are allowed too.
+    pub allowed_swallowed: HashSet<String>,
+    /// Exceptions acceptable to propagate, such as an application's own
+    /// `AppError`, which aren't warned about though they are still reported
+    /// as raised. Subclasses of them are accepted too.
+    pub acceptable_exceptions: HashSet<String>,
+    /// Exceptions acceptable to propagate from the functions matching a
+    /// glob pattern, as `("validate_*", "ValueError")`. Patterns match the
+    /// qualified name of a function or its last component.
+    pub acceptable_by_function: HashSet<(String, String)>,
+    /// Environment variables checked at startup, which `os.environ[...]`
+    /// may read without a warning.
+    pub required_env_vars: HashSet<String>,
+    /// Severities overriding the default of each rule.
+    pub severities: HashMap<Rule, Severity>,
+    /// Checks run on the nodes of each function, the built-in ones by
+    /// default. Checks from other crates are added by [`Config::add_check`],
+    /// and are only run while their rule is in `enabled_rules`.
+    pub checks: Vec<Box<dyn checks::Check>>,
+}
+
+// Builtins and common APIs that invoke their callable arguments
+const HIGHER_ORDER_FUNCTIONS: &[&str] = &[
+    "map",
+    "filter",
+    "sorted",
+    "sort",
+    "min",
+    "max",
+    "reduce",
+    "starmap",
+    "submit",
+    "apply_async",
+    "run_in_executor",
+];
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            python_version: PythonVersion::new(3, 11),
+            newest_python_version: None,
+            suggest_fixes: false,
+            enabled_rules: Rule::ALL
+                .into_iter()
+                .filter(|rule| rule.is_default())
+                .collect(),
+            check_annotations: false,
+            strict_asserts: false,
+            skip_syntax_errors: false,
+            public_only: false,
+            stubs: None,
+            site_packages: None,
+            swallowing_decorators: HashSet::new(),
+            higher_order_functions: HIGHER_ORDER_FUNCTIONS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            safe_functions: HashSet::new(),
+            docstring_style: None,
+            entry_points: None,
+            frameworks: Vec::new(),
+            allowed_swallowed: HashSet::new(),
+            acceptable_exceptions: HashSet::new(),
+            acceptable_by_function: HashSet::new(),
+            required_env_vars: HashSet::new(),
+            severities: HashMap::new(),
+            checks: checks::builtin(),
+        }
+    }
+}
+
+impl Config {
+    pub(crate) fn is_enabled(&self, rule: Rule) -> bool {
+        self.enabled_rules.contains(&rule)
+    }
+
+    /// Add a check, such as one from another crate, and enable its rule.
+    pub fn add_check(&mut self, check: Box<dyn checks::Check>) {
+        self.enabled_rules.insert(check.rule());
+        self.checks.push(check);
+    }
+
+    /// Every rule, the built-in ones followed by those of the checks from
+    /// other crates.
+    pub fn rules(&self) -> Vec<Rule> {
+        let mut rules = Rule::ALL.to_vec();
+        for check in &self.checks {
+            if !rules.contains(&check.rule()) {
+                rules.push(check.rule());
+            }
+        }
+        rules
+    }
+
+    /// The rule with a code or slug, such as `PW001` or `acme001`, among
+    /// [`Config::rules`], as for validating the rules of `--select` or
+    /// `--ignore`.
+    pub fn rule(&self, value: &str) -> Result<Rule, String> {
+        self.rules()
+            .into_iter()
+            .find(|rule| rule.code().eq_ignore_ascii_case(value) || rule.slug() == value)
+            .ok_or_else(|| format!("unknown rule '{}'", value))
+    }
+
+    /// The severity of the rule's warnings.
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.severities
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_severity())
+    }
+}
+
+/// A check with a stable identifier, e.g. `PW001`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Rule {
+    /// `PW001`: KeyError from an unguarded subscript or `pop()`, or
+    /// LookupError when the container may be either a mapping or a sequence.
+    UnguardedDictAccess,
+    /// `PW002`: an exception from a called function is not handled.
+    UnhandledCall,
+    /// `PW003`: StopIteration from `next()`, or StopAsyncIteration from
+    /// `anext()`, without a default. Inside a generator the StopIteration
+    /// is raised as RuntimeError.
+    NextWithoutDefault,
+    /// `PW004`: StopIteration raised inside a generator.
+    GeneratorStopIteration,
+    /// `PW005`: IndexError from an unguarded subscript or `pop()` on a
+    /// sequence.
+    UnguardedIndexAccess,
+    /// `PW006`: KeyError from reading an unset environment variable.
+    UnguardedEnvironAccess,
+    /// `PW007`: ZeroDivisionError from a divisor that may be zero.
+    PossibleZeroDivision,
+    /// `PW008`: AttributeError from an attribute access on a value that may
+    /// be None.
+    PossibleNoneAttribute,
+    /// `PW009`: ValueError from converting a string that may be malformed,
+    /// as in `int(s)`.
+    UncheckedConversion,
+    /// `PW010`: a `# pywrong: ignore` comment that silences no warning.
+    UnusedSuppression,
+    /// `PW011`: an except clause that never runs, because an earlier
+    /// handler of the same try statement catches everything it would.
+    UnreachableHandler,
+    /// `PW012`: an `except Exception:` or `except BaseException:` around a
+    /// try body that can only raise narrower exceptions.
+    BroadExcept,
+    /// `PW013`: a bare `except:`, which also catches KeyboardInterrupt and
+    /// SystemExit.
+    BareExcept,
+    /// `PW014`: a handler that does nothing, such as `except KeyError:
+    /// pass`, for exceptions the try body is found to raise. Off unless
+    /// selected, as such handlers are often deliberate.
+    SwallowedException,
+    /// `PW015`: an exception from a property getter is not handled where
+    /// the property is read.
+    UnhandledPropertyAccess,
+    /// `PW016`: ValueError from unpacking a value of unknown length, as in
+    /// `key, value = line.split("=")`.
+    UncheckedUnpacking,
+    /// `PW017`: the file doesn't parse as Python, so warnings may be
+    /// missing or wrong around the error.
+    SyntaxError,
+    /// `PW018`: syntax newer than the oldest targeted Python version, such
+    /// as a `match` statement when Python 3.8 is targeted.
+    UnsupportedSyntax,
+    /// `PW019`: a docstring whose `Raises` section leaves out exceptions the
+    /// function may raise, or lists ones it can't. Off unless selected, for
+    /// libraries that document their exceptions.
+    DocstringRaises,
+    /// `PW020`: an except clause for exceptions the try body, including the
+    /// functions it calls, can't raise.
+    UselessHandler,
+    /// `PW021`: a handler that swallows SystemExit or KeyboardInterrupt,
+    /// such as `except BaseException:` without re-raising, so the program
+    /// can't be stopped.
+    SwallowedExit,
+    /// `PW022`: an exception escapes an entry point of the program, such as
+    /// `main` or the `if __name__ == "__main__":` block, and would crash it.
+    /// Only reported with [`Config::entry_points`], in place of the
+    /// warnings along the way.
+    EntryPointEscape,
+    /// `PW023`: a star import of a module of the project, such as `from
+    /// utils import *`, so which function a call refers to is guessed.
+    StarImport,
+    /// A rule of a check defined outside this crate, by its code.
+    Custom(&'static str),
+}
+
+impl Rule {
+    pub const ALL: [Rule; 23] = [
+        Rule::UnguardedDictAccess,
+        Rule::UnhandledCall,
+        Rule::NextWithoutDefault,
+        Rule::GeneratorStopIteration,
+        Rule::UnguardedIndexAccess,
+        Rule::UnguardedEnvironAccess,
+        Rule::PossibleZeroDivision,
+        Rule::PossibleNoneAttribute,
+        Rule::UncheckedConversion,
+        Rule::UnusedSuppression,
+        Rule::UnreachableHandler,
+        Rule::BroadExcept,
+        Rule::BareExcept,
+        Rule::SwallowedException,
+        Rule::UnhandledPropertyAccess,
+        Rule::UncheckedUnpacking,
+        Rule::SyntaxError,
+        Rule::UnsupportedSyntax,
+        Rule::DocstringRaises,
+        Rule::UselessHandler,
+        Rule::SwallowedExit,
+        Rule::EntryPointEscape,
+        Rule::StarImport,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Rule::UnguardedDictAccess => "PW001",
+            Rule::UnhandledCall => "PW002",
+            Rule::NextWithoutDefault => "PW003",
+            Rule::GeneratorStopIteration => "PW004",
+            Rule::UnguardedIndexAccess => "PW005",
+            Rule::UnguardedEnvironAccess => "PW006",
+            Rule::PossibleZeroDivision => "PW007",
+            Rule::PossibleNoneAttribute => "PW008",
+            Rule::UncheckedConversion => "PW009",
+            Rule::UnusedSuppression => "PW010",
+            Rule::UnreachableHandler => "PW011",
+            Rule::BroadExcept => "PW012",
+            Rule::BareExcept => "PW013",
+            Rule::SwallowedException => "PW014",
+            Rule::UnhandledPropertyAccess => "PW015",
+            Rule::UncheckedUnpacking => "PW016",
+            Rule::SyntaxError => "PW017",
+            Rule::UnsupportedSyntax => "PW018",
+            Rule::DocstringRaises => "PW019",
+            Rule::UselessHandler => "PW020",
+            Rule::SwallowedExit => "PW021",
+            Rule::EntryPointEscape => "PW022",
+            Rule::StarImport => "PW023",
+            Rule::Custom(code) => code,
+        }
+    }
+
+    /// A descriptive name, such as `UnguardedDictAccess`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnguardedDictAccess => "UnguardedDictAccess",
+            Rule::UnhandledCall => "UnhandledCall",
+            Rule::NextWithoutDefault => "NextWithoutDefault",
+            Rule::GeneratorStopIteration => "GeneratorStopIteration",
+            Rule::UnguardedIndexAccess => "UnguardedIndexAccess",
+            Rule::UnguardedEnvironAccess => "UnguardedEnvironAccess",
+            Rule::PossibleZeroDivision => "PossibleZeroDivision",
+            Rule::PossibleNoneAttribute => "PossibleNoneAttribute",
+            Rule::UncheckedConversion => "UncheckedConversion",
+            Rule::UnusedSuppression => "UnusedSuppression",
+            Rule::UnreachableHandler => "UnreachableHandler",
+            Rule::BroadExcept => "BroadExcept",
+            Rule::BareExcept => "BareExcept",
+            Rule::SwallowedException => "SwallowedException",
+            Rule::UnhandledPropertyAccess => "UnhandledPropertyAccess",
+            Rule::UncheckedUnpacking => "UncheckedUnpacking",
+            Rule::SyntaxError => "SyntaxError",
+            Rule::UnsupportedSyntax => "UnsupportedSyntax",
+            Rule::DocstringRaises => "DocstringRaises",
+            Rule::UselessHandler => "UselessHandler",
+            Rule::SwallowedExit => "SwallowedExit",
+            Rule::EntryPointEscape => "EntryPointEscape",
+            Rule::StarImport => "StarImport",
+            Rule::Custom(code) => code,
+        }
+    }
+
+    /// The rule's name in kebab case, such as `unguarded-dict-access`.
+    pub fn slug(self) -> String {
+        if let Rule::Custom(code) = self {
+            return code.to_ascii_lowercase();
+        }
+        let mut slug = String::new();
+        for c in self.name().chars() {
+            if c.is_ascii_uppercase() && !slug.is_empty() {
+                slug.push('-');
+            }
+            slug.push(c.to_ascii_lowercase());
+        }
+        slug
+    }
+
+    /// The rules a selector stands for: a code such as `PW001`, a code
+    /// prefix such as `PW00`, a kebab-case name, or `ALL`.
+    pub fn select(selector: &str) -> Result<Vec<Rule>, String> {
+        let rules: Vec<Rule> = Rule::ALL
+            .into_iter()
+            .filter(|rule| {
+                selector.eq_ignore_ascii_case("ALL")
+                    || rule.code().starts_with(&selector.to_ascii_uppercase())
+                    || rule.slug() == selector
+            })
+            .collect();
+        if rules.is_empty() || selector.is_empty() {
+            return Err(format!("unknown rule '{}'", selector));
+        }
+        Ok(rules)
+    }
+
+    /// Whether the rule is enabled when no rules are selected.
+    pub fn is_default(self) -> bool {
+        !matches!(self, Rule::SwallowedException | Rule::DocstringRaises)
+    }
+
+    /// The severity of the rule's warnings unless configured otherwise.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Rule::UnusedSuppression | Rule::StarImport => Severity::Info,
+            Rule::SyntaxError => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+
+    /// A one-line summary of what the rule reports.
+    pub fn description(self) -> &'static str {
+        match self {
+            Rule::UnguardedDictAccess => "KeyError from a subscript without a membership check",
+            Rule::UnhandledCall => "Exception from a called function is not handled",
+            Rule::NextWithoutDefault => "StopIteration from next() without a default",
+            Rule::GeneratorStopIteration => "StopIteration raised inside a generator",
+            Rule::UnguardedIndexAccess => "IndexError from a subscript on a sequence",
+            Rule::UnguardedEnvironAccess => "KeyError from reading an unset environment variable",
+            Rule::PossibleZeroDivision => "ZeroDivisionError from a divisor that may be zero",
+            Rule::PossibleNoneAttribute => {
+                "AttributeError from an attribute of a value that may be None"
+            }
+            Rule::UncheckedConversion => {
+                "ValueError from converting a string that may be malformed"
+            }
+            Rule::UnusedSuppression => "Suppression comment that silences no warning",
+            Rule::UnreachableHandler => "Except clause covered by an earlier handler",
+            Rule::BroadExcept => "Handler for Exception where narrower exceptions would do",
+            Rule::BareExcept => "Bare except that also catches KeyboardInterrupt",
+            Rule::SwallowedException => "Handler that silently ignores an exception",
+            Rule::UnhandledPropertyAccess => "Exception from a property getter is not handled",
+            Rule::UncheckedUnpacking => "ValueError from unpacking a value of unknown length",
+            Rule::SyntaxError => "File that doesn't parse as Python",
+            Rule::UnsupportedSyntax => "Syntax the oldest targeted Python version lacks",
+            Rule::DocstringRaises => "Raises section that disagrees with what the function raises",
+            Rule::UselessHandler => "Except clause for an exception the try body can't raise",
+            Rule::SwallowedExit => "Handler that swallows SystemExit or KeyboardInterrupt",
+            Rule::EntryPointEscape => "Exception escapes an entry point of the program",
+            Rule::StarImport => "Star import that makes calls resolve imprecisely",
+            Rule::Custom(_) => "Exception found by a custom check",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.code().eq_ignore_ascii_case(value) || rule.slug() == value)
+            .ok_or_else(|| format!("unknown rule '{}'", value))
+    }
+}
+
+impl Serialize for Rule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Number of warnings per rule, in rule order.
+pub fn count_by_rule(warnings: &[Warning]) -> BTreeMap<Rule, usize> {
+    let mut counts = BTreeMap::new();
+    for warning in warnings {
+        *counts.entry(warning.rule).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Whether the source fails to parse as Python, in which case only the
+/// parts that do parse are analyzed, unless [`Config::skip_syntax_errors`]
+/// is set.
+pub fn has_syntax_errors(source_code: &str) -> bool {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_python::LANGUAGE.into())
+        .expect("Error loading Python grammar");
+    parser
+        .parse(source_code, None)
+        .unwrap()
+        .root_node()
+        .has_error()
+}
+
+/// A `major.minor` Python version, e.g. `3.11`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PythonVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl PythonVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        PythonVersion { major, minor }
+    }
+
+    // Assignment expressions (PEP 572) were introduced in Python 3.8
+    fn supports_walrus(self) -> bool {
+        self >= PythonVersion::new(3, 8)
+    }
+
+    // `except*` handlers (PEP 654) were introduced in Python 3.11
+    fn supports_except_star(self) -> bool {
+        self >= PythonVersion::new(3, 11)
+    }
+
+    /// Parses a version such as `3.11`, or a range of versions such as
+    /// `3.8..3.12`, into the oldest and newest version.
+    pub fn parse_range(value: &str) -> Result<(PythonVersion, PythonVersion), String> {
+        let Some((oldest, newest)) = value.split_once("..") else {
+            let version = value.parse()?;
+            return Ok((version, version));
+        };
+        let (oldest, newest): (PythonVersion, PythonVersion) = (oldest.parse()?, newest.parse()?);
+        if oldest > newest {
+            return Err(format!("{} is newer than {}", oldest, newest));
+        }
+        Ok((oldest, newest))
+    }
+}
+
+impl fmt::Display for PythonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for PythonVersion {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (major, minor) = value
+            .split_once('.')
+            .ok_or_else(|| format!("expected <major.minor>, got '{}'", value))?;
+        let major = major
+            .parse()
+            .map_err(|_| format!("invalid major version '{}'", major))?;
+        let minor = minor
+            .parse()
+            .map_err(|_| format!("invalid minor version '{}'", minor))?;
+        Ok(PythonVersion::new(major, minor))
+    }
+}
+
+/// How serious a warning is, from least to most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "hint" => Ok(Severity::Hint),
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!("unknown severity '{}'", value)),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Hint => write!(f, "Hint"),
+            Severity::Info => write!(f, "Info"),
+            Severity::Warning => write!(f, "Warning"),
+            Severity::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// A problem found in the analyzed source.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Warning {
+    pub filename: String,
+    /// The 1-based number of the notebook cell holding the offending node,
+    /// for notebooks, whose lines are counted from the start of the cell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell: Option<usize>,
+    /// 1-based line of the offending node.
+    pub line: usize,
+    /// 1-based column where the offending node starts, counted in
+    /// characters.
+    pub column: usize,
+    /// 1-based line where the offending node ends.
+    pub end_line: usize,
+    /// 1-based column just past the end of the offending node on its last
+    /// line, counted in characters.
+    pub end_column: usize,
+    pub rule: Rule,
+    /// The function containing the offending node, or `<module>`.
+    pub function: String,
+    /// The exceptions that may escape from the offending node.
+    pub exceptions: Vec<String>,
+    pub message: String,
+    pub severity: Severity,
+    pub suggestion: Option<Suggestion>,
+    /// Where the exception comes from, for calls to functions in the file:
+    /// the call or statement it escapes from in the called function, then
+    /// in each function that one calls, ending where it is raised.
+    #[serde(default)]
+    pub related: Vec<Related>,
+}
+
+/// A secondary location of a warning, such as the subscript in the called
+/// function that makes a call unsafe.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Related {
+    pub filename: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell: Option<usize>,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub message: String,
+}
+
+/// A proposed rewrite of the source lines around a warning.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub original: String,
+    pub replacement: String,
+    /// The byte-precise change to the source that applies the rewrite.
+    pub edit: Edit,
+    pub applicability: Applicability,
+}
+
+/// Whether `--fix` may apply a suggestion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Applicability {
+    /// The rewrite only changes what happens when the key is missing, as
+    /// `d.get(k, 0) + 1` does.
+    Safe,
+    /// The rewrite restructures the code around the access, such as
+    /// wrapping it in a try/except block, and is applied only when asked for.
+    Unsafe,
+    /// The source doesn't tell what a missing key should give, so the
+    /// rewrite is only shown.
+    Display,
+}
+
+/// Replace the bytes `start..end` of a file's source with `text`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Analyze Python source code and return the warnings found in it, ordered
+/// by position.
+pub fn analyze_source(source_code: &str, filename: &str, config: &Config) -> Vec<Warning> {
+    analyze_source_with_timings(source_code, filename, config, None)
+}
+
+pub(crate) fn analyze_source_with_timings(
+    source_code: &str,
+    filename: &str,
+    config: &Config,
+    timings: Option<&Timings>,
+) -> Vec<Warning> {
+    let module = std::path::Path::new(filename)
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    analyze_project_with_timings(
+        &[ProjectFile {
+            filename,
+            module: &module,
+            source: source_code,
+        }],
+        config,
+        timings,
+    )
+}
+
+/// A source file analyzed as part of a project.
+pub struct ProjectFile<'a> {
+    pub filename: &'a str,
+    /// Dotted module name, such as `pkg.util` for `pkg/util.py` or `pkg` for
+    /// `pkg/__init__.py`.
+    pub module: &'a str,
+    pub source: &'a str,
+}
+
+/// The dotted module name of a Python file under the project root.
+pub fn module_name(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path).with_extension("");
+    let mut parts: Vec<String> = relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if parts.last().is_some_and(|last| last == "__init__") {
+        parts.pop();
+    }
+    parts.join(".")
+}
+
+/// Analyze several files together, so that exceptions propagate through
+/// calls to functions imported from other files of the project.
+pub fn analyze_project(files: &[ProjectFile<'_>], config: &Config) -> Vec<Warning> {
+    analyze_project_with_timings(files, config, None)
+}
+
+/// Like [`analyze_project`], counting the time of each phase in `timings`.
+pub fn analyze_project_with_timings(
+    files: &[ProjectFile<'_>],
+    config: &Config,
+    timings: Option<&Timings>,
+) -> Vec<Warning> {
+    let started = Instant::now();
+    // Initialize the parser with the Python grammar
+    let language = tree_sitter_python::LANGUAGE;
+    let mut parser = Parser::new();
+    parser
+        .set_language(&language.into())
+        .expect("Error loading Python grammar");
+
+    // Parse the source code
+    let trees: Vec<Tree> = files
+        .iter()
+        .map(|file| parser.parse(file.source, None).unwrap())
+        .collect();
+    if let Some(timings) = timings {
+        timings.add(Phase::Parse, started.elapsed());
+    }
+    analyze_trees(files, &trees, config, timings)
+}
+
+// The exceptions each function of the files may raise, keyed by module and
+// name as for `project_functions`
+pub(crate) fn function_exceptions(
+    files: &[ProjectFile<'_>],
+    config: &Config,
+) -> HashMap<String, HashSet<String>> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_python::LANGUAGE.into())
+        .expect("Error loading Python grammar");
+    let trees: Vec<Tree> = files
+        .iter()
+        .map(|file| parser.parse(file.source, None).unwrap())
+        .collect();
+    let scopes = collect_module_scopes(files, &trees);
+    let mut modules = collect_modules(files, &trees, &scopes, config);
+    propagate_exceptions(&mut modules, files, config);
+    project_functions(&modules)
+        .into_iter()
+        .map(|(name, function)| (name, function.may_raise))
+        .collect()
+}
+
+// All functions of each file, including the module-level code as a function
+fn collect_modules<'a>(
+    files: &[ProjectFile<'_>],
+    trees: &'a [Tree],
+    scopes: &'a [ModuleScope<'a>],
+    config: &Config,
+) -> Vec<Functions<'a>> {
+    trees
+        .iter()
+        .zip(files)
+        .zip(scopes)
+        .map(|((tree, file), module)| {
+            let mut functions = HashMap::new();
+            if config.skip_syntax_errors && tree.root_node().has_error() {
+                return Functions::new(functions, module);
+            }
+            collect_functions(tree.root_node(), &mut functions, file.module, file.source);
+            functions.insert(
+                FunctionId::new(file.module, "<module>".to_string(), tree.root_node()),
+                FunctionInfo::new(tree.root_node()),
+            );
+            let mut functions = Functions::new(functions, module);
+            cache_calls(&mut functions, file.source);
+            functions
+        })
+        .collect()
+}
+
+// What each file's functions need to know about the file as a whole,
+// worked out once for all of them
+fn collect_module_scopes<'a>(files: &[ProjectFile<'_>], trees: &'a [Tree]) -> Vec<ModuleScope<'a>> {
+    trees
+        .iter()
+        .zip(files)
+        .map(|(tree, file)| ModuleScope::new(tree.root_node(), file.source))
+        .collect()
+}
+
+// Determine exceptions each function may raise, repeating until the
+// exceptions of imported functions stop changing
+fn propagate_exceptions(modules: &mut [Functions<'_>], files: &[ProjectFile<'_>], config: &Config) {
+    let mut imported = HashMap::new();
+    loop {
+        for (functions, file) in modules.iter_mut().zip(files) {
+            let project = ProjectScope::new(file, functions.module, &imported);
+            determine_exceptions(functions, file.source, config, &project);
+        }
+        let updated = project_functions(modules);
+        if updated == imported {
+            break;
+        }
+        imported = updated;
+    }
+}
+
+/// The calls between the functions of a project, with the exceptions that
+/// flow along them.
+#[derive(Debug, Default, Serialize)]
+pub struct ExceptionFlow {
+    pub functions: Vec<FlowFunction>,
+    pub calls: Vec<FlowCall>,
+}
+
+/// A function of an [`ExceptionFlow`].
+#[derive(Debug, Serialize)]
+pub struct FlowFunction {
+    /// The function qualified by its module, such as `pkg.util.load`, or
+    /// `<module>` for the module-level code.
+    pub name: String,
+    pub filename: String,
+    pub line: usize,
+    /// The exceptions that may escape from the function.
+    pub raises: Vec<String>,
+    /// Those of `raises` that start in the function itself, rather than in
+    /// a function it calls.
+    pub originates: Vec<String>,
+}
+
+/// A call from one function of an [`ExceptionFlow`] to another.
+#[derive(Debug, Serialize)]
+pub struct FlowCall {
+    pub caller: String,
+    pub callee: String,
+    /// The exceptions the callee may raise that the caller doesn't handle
+    /// around the call.
+    pub exceptions: Vec<String>,
+}
+
+/// Work out how exceptions flow between the functions of the files, in
+/// source order.
+pub fn exception_flow(files: &[ProjectFile<'_>], config: &Config) -> ExceptionFlow {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&tree_sitter_python::LANGUAGE.into())
+        .expect("Error loading Python grammar");
+    let trees: Vec<Tree> = files
+        .iter()
+        .map(|file| parser.parse(file.source, None).unwrap())
+        .collect();
+    let scopes = collect_module_scopes(files, &trees);
+    let mut modules = collect_modules(files, &trees, &scopes, config);
+    propagate_exceptions(&mut modules, files, config);
+    let imported = project_functions(&modules);
+
+    let qualified = |module: &str, name: &str| match module {
+        "" => name.to_string(),
+        module => format!("{}.{}", module, name),
+    };
+    let sorted = |exceptions: &mut Vec<String>| {
+        exceptions.sort();
+        exceptions.dedup();
+    };
+    let mut flow = ExceptionFlow::default();
+    for (functions, file) in modules.iter().zip(files) {
+        let project = ProjectScope::new(file, functions.module, &imported);
+        let mut ids: Vec<&FunctionId> = functions.keys().collect();
+        ids.sort_by_key(|id| (id.is_module(), id.start_byte));
+        for id in ids {
+            let info = &functions[id];
+            let name = qualified(&id.module, &id.name);
+            let mut raises: Vec<String> = info.may_raise.iter().cloned().collect();
+            let mut originates: Vec<String> = info
+                .raise_sites
+                .iter()
+                .filter(|(_, site)| site.callee.is_none())
+                .map(|(exception, _)| exception.clone())
+                .collect();
+            sorted(&mut raises);
+            sorted(&mut originates);
+            flow.functions.push(FlowFunction {
+                name: name.clone(),
+                filename: file.filename.to_string(),
+                line: info.node.start_position().row + 1,
+                raises,
+                originates,
+            });
+
+            let mut calls: Vec<FlowCall> = Vec::new();
+            for call in scope_calls(id, functions, &project, config, file.source) {
+                let callee = match resolve_call_name(call.node, &call.name, functions, file.source)
+                {
+                    Some(callee) => Some((
+                        qualified(&callee.module, &callee.name),
+                        &functions[callee].may_raise,
+                    )),
+                    None => project
+                        .resolve_name(call.node, &call.name, file.source)
+                        .map(|(name, imported)| (name, &imported.may_raise)),
+                };
+                let Some((callee, may_raise)) = callee else {
+                    continue;
+                };
+                let exceptions = uncaught_exceptions(
+                    call.node,
+                    may_raise,
+                    file.source,
+                    Guard::all(config.python_version, functions.module),
+                );
+                // Several calls to the same function make one edge
+                match calls.iter_mut().find(|edge| edge.callee == callee) {
+                    Some(edge) => {
+                        edge.exceptions.extend(exceptions);
+                        sorted(&mut edge.exceptions);
+                    }
+                    None => calls.push(FlowCall {
+                        caller: name.clone(),
+                        callee,
+                        exceptions,
+                    }),
+                }
+            }
+            flow.calls.extend(calls);
+        }
+    }
+    flow
+}
+
+// Analyze files already parsed into `trees`
+fn analyze_trees(
+    files: &[ProjectFile<'_>],
+    trees: &[Tree],
+    config: &Config,
+    timings: Option<&Timings>,
+) -> Vec<Warning> {
+    let mut started = Instant::now();
+    let mut lap = |phase| {
+        if let Some(timings) = timings {
+            timings.add(phase, started.elapsed());
+        }
+        started = Instant::now();
+    };
+    let scopes = collect_module_scopes(files, trees);
+    let mut modules = collect_modules(files, trees, &scopes, config);
+    lap(Phase::Collect);
+    propagate_exceptions(&mut modules, files, config);
+    lap(Phase::Propagate);
+
+    // Analyze functions in source order, leaving the module-level code for
+    // last so calls are attributed to the function that contains them
+    let ordered: Vec<Vec<&FunctionId>> = modules
+        .iter()
+        .zip(trees)
+        .zip(files)
+        .map(|((functions, tree), file)| {
+            let public = if config.public_only {
+                public_functions(tree.root_node(), functions, file.source)
+            } else {
+                None
+            };
+            let mut func_names: Vec<&FunctionId> = functions
+                .iter()
+                .filter(|(id, info)| {
+                    public
+                        .as_ref()
+                        .is_none_or(|public| id.is_module() || public.contains(*id))
+                        && !is_swallowed(info.node, functions.module, file.source, config)
+                })
+                .map(|(id, _)| id)
+                .collect();
+            func_names.sort_by_key(|id| (id.is_module(), id.start_byte));
+            func_names
+        })
+        .collect();
+
+    // Report direct accesses first, so call sites of functions that already
+    // have warnings of their own can be skipped regardless of order
+    let mut warnings = Vec::new();
+    for ((functions, func_names), file) in modules.iter().zip(&ordered).zip(files) {
+        let (source_code, filename) = (file.source, file.filename);
+        for func_name in func_names {
+            report_unguarded_accesses(
+                func_name,
+                functions,
+                source_code,
+                filename,
+                config,
+                &mut warnings,
+            );
+            report_unguarded_next_calls(
+                func_name,
+                functions,
+                source_code,
+                filename,
+                config,
+                &mut warnings,
+            );
+            report_possible_zero_divisions(
+                func_name,
+                functions,
+                source_code,
+                filename,
+                config,
+                &mut warnings,
+            );
+            report_possible_none_attributes(
+                func_name,
+                functions,
+                source_code,
+                filename,
+                config,
+                &mut warnings,
+            );
+            report_unchecked_conversions(
+                func_name,
+                functions,
+                source_code,
+                filename,
+                config,
+                &mut warnings,
+            );
+            report_generator_stop_iteration(
+                func_name,
+                functions,
+                source_code,
+                filename,
+                config,
+                &mut warnings,
+            );
+            report_unreachable_handlers(
+                func_name,
+                functions,
+                source_code,
+                filename,
+                config,
+                &mut warnings,
+            );
+        }
+    }
+
+    let imported = project_functions(&modules);
+    for ((functions, func_names), file) in modules.iter().zip(&ordered).zip(files) {
+        let project = ProjectScope::new(file, functions.module, &imported);
+        let mut reported_calls = HashSet::new();
+        for func_name in func_names {
+            report_unhandled_calls(
+                func_name,
+                functions,
+                &project,
+                config,
+                &mut reported_calls,
+                &mut warnings,
+            );
+            report_broad_handlers(func_name, functions, &project, config, &mut warnings);
+            report_swallowed_exceptions(func_name, functions, &project, config, &mut warnings);
+            report_docstring_raises(func_name, functions, &project, config, &mut warnings);
+            report_useless_handlers(func_name, functions, &project, config, &mut warnings);
+            report_swallowed_exits(func_name, functions, &project, config, &mut warnings);
+        }
+    }
+    if config.is_enabled(Rule::StarImport) {
+        for ((file, tree), module) in files.iter().zip(trees).zip(&scopes) {
+            let project = ProjectScope::new(file, module, &imported);
+            report_star_imports(tree.root_node(), &project, &mut warnings);
+        }
+    }
+
+    // The exceptions found along the way are reported where they escape the
+    // program instead
+    if let Some(entry_points) = &config.entry_points {
+        warnings.retain(|warning| !is_exception_site(warning.rule));
+        for (functions, file) in modules.iter().zip(files) {
+            let project = ProjectScope::new(file, functions.module, &imported);
+            report_entry_point_escapes(functions, &project, entry_points, config, &mut warnings);
+        }
+    }
+
+    // Exceptions that are acceptable to propagate aren't warned about where
+    // they escape, leaving warnings that are only about such exceptions out
+    if !config.acceptable_exceptions.is_empty() || !config.acceptable_by_function.is_empty() {
+        for (file, module) in files.iter().zip(&scopes) {
+            warnings.retain(|warning| {
+                let accepted = warning.filename == file.filename
+                    && (is_exception_site(warning.rule) || warning.rule == Rule::EntryPointEscape)
+                    && !warning.exceptions.is_empty()
+                    && warning.exceptions.iter().all(|exception| {
+                        let ancestors =
+                            exceptions::exception_ancestors(exception, &module.class_bases);
+                        is_acceptable(&ancestors, &warning.function, config)
+                    });
+                !accepted
+            });
+        }
+    }
+
+    if config.is_enabled(Rule::SyntaxError) {
+        for (file, tree) in files.iter().zip(trees) {
+            report_syntax_errors(tree.root_node(), file.filename, &mut warnings);
+        }
+    }
+    if config.is_enabled(Rule::UnsupportedSyntax) {
+        for (file, tree) in files.iter().zip(trees) {
+            report_unsupported_syntax(tree.root_node(), file.filename, config, &mut warnings);
+        }
+    }
+
+    // Drop the warnings silenced by suppression comments, file by file
+    let mut suppressed = Vec::new();
+    for (file, tree) in files.iter().zip(trees) {
+        let (in_file, rest) = warnings
+            .into_iter()
+            .partition(|warning| warning.filename == file.filename);
+        warnings = rest;
+        suppressed.extend(suppression::apply(
+            in_file,
+            tree.root_node(),
+            file.source,
+            file.filename,
+            config,
+        ));
+    }
+    let mut warnings = suppressed;
+    for warning in &mut warnings {
+        warning.severity = config.severity(warning.rule);
+    }
+    character_columns(&mut warnings, files);
+
+    warnings.sort_by(|a, b| (&a.filename, a.line, a.column).cmp(&(&b.filename, b.line, b.column)));
+    lap(Phase::Report);
+    warnings
+}
+
+// Whether an exception with the given ancestors may propagate from a
+// function, by the configured acceptable exceptions
+fn is_acceptable(ancestors: &HashSet<String>, function: &str, config: &Config) -> bool {
+    let last = function.rsplit('.').next().unwrap_or(function);
+    ancestors.iter().any(|ancestor| {
+        config.acceptable_exceptions.contains(ancestor)
+            || config
+                .acceptable_by_function
+                .iter()
+                .any(|(pattern, exception)| {
+                    exception == ancestor
+                        && (discovery::glob_match(pattern.as_bytes(), function.as_bytes())
+                            || discovery::glob_match(pattern.as_bytes(), last.as_bytes()))
+                })
+    })
+}
+
+// Whether the rule reports where an exception may escape from, rather than
+// a handler or the source itself
+fn is_exception_site(rule: Rule) -> bool {
+    matches!(
+        rule,
+        Rule::UnguardedDictAccess
+            | Rule::UnhandledCall
+            | Rule::NextWithoutDefault
+            | Rule::UnguardedIndexAccess
+            | Rule::UnguardedEnvironAccess
+            | Rule::PossibleZeroDivision
+            | Rule::PossibleNoneAttribute
+            | Rule::UncheckedConversion
+            | Rule::UnhandledPropertyAccess
+            | Rule::UncheckedUnpacking
+            | Rule::Custom(_)
+    )
+}
+
+// The exceptions escaping each entry point of a file: each function taken
+// as one, at its name, and each place in the `__main__` block an exception
+// escapes from
+fn report_entry_point_escapes(
+    functions: &Functions<'_>,
+    project: &ProjectScope<'_>,
+    entry_points: &HashSet<String>,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    if !config.is_enabled(Rule::EntryPointEscape) {
+        return;
+    }
+    let (source_code, filename) = (project.file.source, project.file.filename);
+    let escaping = |exceptions: Vec<String>| -> Vec<String> {
+        let mut exceptions: Vec<String> = exceptions
+            .into_iter()
+            .filter(|exception| !EXIT_EXCEPTIONS.contains(&exception.as_str()))
+            .collect();
+        exceptions.sort();
+        exceptions.dedup();
+        exceptions
+    };
+    let mut ids: Vec<&FunctionId> = functions.keys().collect();
+    ids.sort_by_key(|id| id.start_byte);
+    for id in ids {
+        let info = &functions[id];
+        if id.is_module() {
+            let Some(block) = main_block(info.node, source_code) else {
+                continue;
+            };
+            let in_block = |node: Node| {
+                node.start_byte() >= block.start_byte() && node.end_byte() <= block.end_byte()
+            };
+            let guard = Guard::all(config.python_version, functions.module);
+            // Each place with the exceptions escaping from it
+            let mut sites: Vec<(Node, Option<FunctionId>, Vec<String>)> = Vec::new();
+            for (exception, site) in
+                exception_sites(id, functions, source_code, config, project, guard)
+            {
+                if !in_block(site.node) {
+                    continue;
+                }
+                match sites
+                    .iter_mut()
+                    .find(|(node, _, _)| node.id() == site.node.id())
+                {
+                    Some((_, _, exceptions)) => exceptions.push(exception),
+                    None => sites.push((site.node, site.callee, vec![exception])),
+                }
+            }
+            for (node, callee, exceptions) in sites {
+                let exceptions = escaping(exceptions);
+                if exceptions.is_empty() {
+                    continue;
+                }
+                let mut warning = make_warning(
+                    node,
+                    filename,
+                    Rule::EntryPointEscape,
+                    (&id.name, exceptions.clone()),
+                    format!(
+                        "Possible {} escapes the __main__ block, crashing the program",
+                        exceptions.join(", ")
+                    ),
+                    None,
+                );
+                if let Some(callee) = &callee {
+                    warning.related = raise_chain(callee, &exceptions[0], functions, filename);
+                }
+                warnings.push(warning);
+            }
+            continue;
+        }
+
+        let last = id.name.rsplit('.').next().unwrap();
+        let qualified = format!("{}.{}", id.module, id.name);
+        // Only a module's own `main` is taken for the program's; methods
+        // and nested functions named main are entry points when given
+        let is_entry_point = id.name == "main"
+            || [id.name.as_str(), last, qualified.as_str()]
+                .iter()
+                .any(|name| entry_points.contains(*name));
+        // Views serve requests instead, and the framework turns some of
+        // their exceptions into error responses
+        let view = config
+            .frameworks
+            .iter()
+            .copied()
+            .find(|framework| framework.is_view(info.node, source_code));
+        if !is_entry_point && view.is_none() {
+            continue;
+        }
+        let mut exceptions = escaping(info.raise_sites.keys().cloned().collect());
+        if let Some(framework) = view.filter(|_| !is_entry_point) {
+            exceptions.retain(|exception| !framework.handles(exception));
+        }
+        if exceptions.is_empty() {
+            continue;
+        }
+        let name = info.node.child_by_field_name("name").unwrap_or(info.node);
+        let message = match view {
+            Some(_) if !is_entry_point => format!(
+                "Possible {} escapes view '{}', becoming a 500 response",
+                exceptions.join(", "),
+                id.name
+            ),
+            _ => format!(
+                "Possible {} escapes entry point '{}', crashing the program",
+                exceptions.join(", "),
+                id.name
+            ),
+        };
+        let mut warning = make_warning(
+            name,
+            filename,
+            Rule::EntryPointEscape,
+            (&id.name, exceptions.clone()),
+            message,
+            None,
+        );
+        warning.related = raise_chain(id, &exceptions[0], functions, filename);
+        warnings.push(warning);
+    }
+}
+
+// The body of the `if __name__ == "__main__":` statement at the top level
+// of a module
+fn main_block<'a>(root: Node<'a>, source_code: &str) -> Option<Node<'a>> {
+    let mut cursor = root.walk();
+    let statement = root.named_children(&mut cursor).find(|statement| {
+        statement.kind() == "if_statement"
+            && statement
+                .child_by_field_name("condition")
+                .is_some_and(|condition| {
+                    let text = condition.utf8_text(source_code.as_bytes()).unwrap();
+                    let text: String = text.split_whitespace().collect();
+                    matches!(
+                        text.replace('\'', "\"").as_str(),
+                        "__name__==\"__main__\"" | "\"__main__\"==__name__"
+                    )
+                })
+    })?;
+    statement.child_by_field_name("consequence")
+}
+
+// Turn the byte columns tree-sitter gives into the character columns
+// editors count in, for the warnings and their related locations
+fn character_columns(warnings: &mut [Warning], files: &[ProjectFile<'_>]) {
+    let lines: HashMap<&str, Vec<&str>> = files
+        .iter()
+        .map(|file| (file.filename, file.source.split('\n').collect()))
+        .collect();
+    let convert = |filename: &str, line: usize, column: &mut usize| {
+        let Some(text) = lines.get(filename).and_then(|lines| lines.get(line - 1)) else {
+            return;
+        };
+        let byte_offset = (*column - 1).min(text.len());
+        if let Some(prefix) = text.get(..byte_offset) {
+            *column = prefix.chars().count() + 1;
+        }
+    };
+    for warning in warnings {
+        convert(&warning.filename, warning.line, &mut warning.column);
+        convert(&warning.filename, warning.end_line, &mut warning.end_column);
+        for related in &mut warning.related {
+            convert(&related.filename, related.line, &mut related.column);
+            convert(&related.filename, related.end_line, &mut related.end_column);
+        }
+    }
+}
+
+// Where the parser gave up on part of the file, or filled in a missing
+// token, such as an unclosed parenthesis. Errors inside other errors come
+// from the same mistake and are left out.
+fn report_syntax_errors(node: Node, filename: &str, warnings: &mut Vec<Warning>) {
+    if node.is_error() || node.is_missing() {
+        let position = node.start_position();
+        let mut message = format!(
+            "Syntax error at {}:{}",
+            position.row + 1,
+            position.column + 1
+        );
+        if node.is_missing() {
+            message.push_str(&format!(": missing '{}'", node.kind()));
+        }
+        warnings.push(make_warning(
+            node,
+            filename,
+            Rule::SyntaxError,
+            ("<module>", Vec::new()),
+            message,
+            None,
+        ));
+        return;
+    }
+    if !node.has_error() {
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        report_syntax_errors(child, filename, warnings);
+    }
+}
+
+// Constructs introduced after the oldest targeted Python version, which
+// that version can't even parse
+fn report_unsupported_syntax(
+    node: Node,
+    filename: &str,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let introduced = match node.kind() {
+        "named_expression" => Some(("assignment expression", PythonVersion::new(3, 8))),
+        "match_statement" => Some(("match statement", PythonVersion::new(3, 10))),
+        "except_group_clause" => Some(("except* clause", PythonVersion::new(3, 11))),
+        "type_alias_statement" => Some(("type statement", PythonVersion::new(3, 12))),
+        _ => None,
+    };
+    if let Some((construct, version)) = introduced {
+        if config.python_version < version {
+            warnings.push(make_warning(
+                node,
+                filename,
+                Rule::UnsupportedSyntax,
+                ("<module>", Vec::new()),
+                format!(
+                    "The {} requires Python {}, but Python {} is targeted",
+                    construct, version, config.python_version
+                ),
+                None,
+            ));
+        }
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        report_unsupported_syntax(child, filename, config, warnings);
+    }
+}
+
+// What is known about a function of the project when calling it from
+// another file
+#[derive(PartialEq)]
+struct ImportedFunction {
+    may_raise: HashSet<String>,
+    reported_in_function: bool,
+}
+
+// Every function of the project, keyed by its module and name, such as
+// `pkg.util.load` or `pkg.util.Store.get`. Of functions defined more than
+// once, the last definition is the one importers see.
+fn project_functions(modules: &[Functions<'_>]) -> HashMap<String, ImportedFunction> {
+    let mut imported = HashMap::new();
+    for functions in modules {
+        let mut ids: Vec<&FunctionId> = functions.keys().collect();
+        ids.sort_by_key(|id| id.start_byte);
+        for id in ids {
+            // Nested functions can't be imported
+            if id.is_module() || id.name.contains("<locals>") {
+                continue;
+            }
+            let info = &functions[id];
+            imported.insert(
+                format!("{}.{}", id.module, id.name),
+                ImportedFunction {
+                    may_raise: info.may_raise.clone(),
+                    reported_in_function: info.reported_in_function.get(),
+                },
+            );
+        }
+    }
+    imported
+}
+
+// The file being analyzed, with the functions it can import from the rest
+// of the project
+struct ProjectScope<'p> {
+    file: &'p ProjectFile<'p>,
+    module: &'p ModuleScope<'p>,
+    functions: &'p HashMap<String, ImportedFunction>,
+}
+
+impl<'p> ProjectScope<'p> {
+    fn new(
+        file: &'p ProjectFile<'p>,
+        module: &'p ModuleScope<'p>,
+        functions: &'p HashMap<String, ImportedFunction>,
+    ) -> Self {
+        ProjectScope {
+            file,
+            module,
+            functions,
+        }
+    }
+
+    // The function of another file a call refers to through the file's
+    // imports, such as `helper()` after `from .util import helper`
+    fn resolve(
+        &self,
+        call_node: Node,
+        name: &str,
+        source_code: &str,
+    ) -> Option<&'p ImportedFunction> {
+        self.resolve_name(call_node, name, source_code)
+            .map(|(_, function)| function)
+    }
+
+    // The same, with the qualified name of the function
+    fn resolve_name(
+        &self,
+        call_node: Node,
+        name: &str,
+        source_code: &str,
+    ) -> Option<(String, &'p ImportedFunction)> {
+        // Methods inherited from classes imported from other files
+        if let Some(attribute) = ["self.", "cls."]
+            .iter()
+            .find_map(|receiver| name.strip_prefix(receiver))
+            .filter(|attribute| !attribute.contains('.'))
+        {
+            let class_name =
+                enclosing_function(call_node).and_then(|f| enclosing_class_name(f, source_code))?;
+            return class_lineage(&class_name, &self.module.class_bases)
+                .iter()
+                .skip(1)
+                .find_map(|base| {
+                    self.resolve_name(call_node, &format!("{}.{}", base, attribute), source_code)
+                });
+        }
+        let name = self.module.qualified_name(name);
+        self.lookup(&name).or_else(|| {
+            // A name no import binds may come from a star import, the last
+            // of which shadows the ones before
+            self.module
+                .star_imports
+                .iter()
+                .rev()
+                .find_map(|module| self.lookup(&join_module(module, &name)))
+        })
+    }
+
+    // The function of the project with a qualified name, or the constructor
+    // of the class, as calling an imported class runs it
+    fn lookup(&self, name: &str) -> Option<(String, &'p ImportedFunction)> {
+        let name = self.absolute(name)?;
+        [
+            name.clone(),
+            format!("{}.__init__", name),
+            format!("{}.__new__", name),
+        ]
+        .into_iter()
+        .find_map(|name| self.functions.get_key_value(&name))
+        .map(|(name, function)| (name.clone(), function))
+    }
+
+    // A qualified name with relative imports resolved: they start from the
+    // file's package and go up one package for each extra dot
+    fn absolute(&self, name: &str) -> Option<String> {
+        if !name.starts_with('.') {
+            return Some(name.to_string());
+        }
+        let level = name.len() - name.trim_start_matches('.').len();
+        let mut package: Vec<&str> = self.file.module.split('.').collect();
+        if !self.file.filename.ends_with("__init__.py") {
+            package.pop();
+        }
+        for _ in 1..level {
+            package.pop()?;
+        }
+        package.push(name.trim_start_matches('.'));
+        Some(package.join("."))
+    }
+
+    // Whether a module, as imported by the file, defines functions of the
+    // project
+    fn has_module(&self, module: &str) -> bool {
+        let Some(prefix) = self.absolute(&join_module(module, "")) else {
+            return false;
+        };
+        self.functions.keys().any(|name| name.starts_with(&prefix))
+    }
+}
+
+// The modules of the `from module import *` statements at the top level of
+// the file, as written
+fn star_imports(root: Node, source_code: &str) -> Vec<String> {
+    let mut cursor = root.walk();
+    let modules = root
+        .named_children(&mut cursor)
+        .filter(|statement| statement.kind() == "import_from_statement")
+        .filter(|statement| {
+            let mut cursor = statement.walk();
+            let has_wildcard = statement
+                .named_children(&mut cursor)
+                .any(|child| child.kind() == "wildcard_import");
+            has_wildcard
+        })
+        .filter_map(|statement| statement.child_by_field_name("module_name"))
+        .map(|module| {
+            module
+                .utf8_text(source_code.as_bytes())
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    modules
+}
+
+// A name in a module, where `from . import x` has a module of just `.`
+fn join_module(module: &str, name: &str) -> String {
+    match module.ends_with('.') {
+        true => format!("{}{}", module, name),
+        false => format!("{}.{}", module, name),
+    }
+}
+
+// Star imports of modules of the project, whose names calls are only
+// matched to by looking for them in each imported module
+fn report_star_imports(root: Node, project: &ProjectScope<'_>, warnings: &mut Vec<Warning>) {
+    let (source_code, filename) = (project.file.source, project.file.filename);
+    let mut cursor = root.walk();
+    for statement in root.named_children(&mut cursor) {
+        if statement.kind() != "import_from_statement" {
+            continue;
+        }
+        let mut statement_cursor = statement.walk();
+        let Some(wildcard) = statement
+            .named_children(&mut statement_cursor)
+            .find(|child| child.kind() == "wildcard_import")
+        else {
+            continue;
+        };
+        let Some(module) = statement.child_by_field_name("module_name") else {
+            continue;
+        };
+        let module = module.utf8_text(source_code.as_bytes()).unwrap();
+        if !project.has_module(module) {
+            continue;
+        }
+        warnings.push(make_warning(
+            wildcard,
+            filename,
+            Rule::StarImport,
+            ("<module>", Vec::new()),
+            format!(
+                "Calls to names from 'from {} import *' are matched to what it may define, which can be wrong; import the names used instead",
+                module
+            ),
+            None,
+        ));
+    }
+}
+
+// Functions reachable from the names listed in the module's `__all__`,
+// including methods of exported classes. `None` if there is no `__all__`.
+fn public_functions<'f>(
+    root: Node,
+    functions: &'f Functions<'_>,
+    source_code: &str,
+) -> Option<HashSet<&'f FunctionId>> {
+    let exported = module_all_names(root, source_code)?;
+
+    let mut pending: Vec<&FunctionId> = functions
+        .iter()
+        .filter(|(id, info)| {
+            exported.contains(&id.name)
+                || enclosing_class_name(info.node, source_code)
+                    .is_some_and(|class_name| exported.contains(&class_name))
+        })
+        .map(|(id, _)| id)
+        .collect();
+
+    let mut reachable = HashSet::new();
+    while let Some(id) = pending.pop() {
+        if !reachable.insert(id) {
+            continue;
+        }
+        for call in &functions[id].calls {
+            if let Some(callee) = resolve_call_name(call.node, &call.name, functions, source_code) {
+                pending.push(callee);
+            }
+        }
+    }
+    Some(reachable)
+}
+
+// The string literals of a module-level `__all__ = [...]` (or tuple)
+fn module_all_names(root: Node, source_code: &str) -> Option<HashSet<String>> {
+    let mut cursor = root.walk();
+    for statement in root.named_children(&mut cursor) {
+        let Some(assignment) = statement.named_child(0) else {
+            continue;
+        };
+        if statement.kind() != "expression_statement" || assignment.kind() != "assignment" {
+            continue;
+        }
+        let (Some(left), Some(right)) = (
+            assignment.child_by_field_name("left"),
+            assignment.child_by_field_name("right"),
+        ) else {
+            continue;
+        };
+        if left.utf8_text(source_code.as_bytes()).unwrap() != "__all__"
+            || !matches!(right.kind(), "list" | "tuple")
+        {
+            continue;
+        }
+
+        let mut names = HashSet::new();
+        let mut cursor = right.walk();
+        for element in right.named_children(&mut cursor) {
+            if let Some(name) = string_literal_value(element, source_code) {
+                names.insert(name);
+            }
+        }
+        return Some(names);
+    }
+    None
+}
+
+// The contents of a plain string literal such as `'name'`
+fn string_literal_value(node: Node, source_code: &str) -> Option<String> {
+    if node.kind() != "string" {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let content = node
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "string_content");
+    Some(content.map_or(String::new(), |content| {
+        content
+            .utf8_text(source_code.as_bytes())
+            .unwrap()
+            .to_string()
+    }))
+}
+
+// A function of the project: the module it is defined in, its name
+// qualified by the classes and functions around it, and where its
+// definition starts, which tells apart functions of the same name, as when
+// one is defined in each branch of an `if`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct FunctionId {
+    module: String,
+    name: String,
+    start_byte: usize,
+}
+
+// Whether to leave out what a function's code may raise because it is
+// module-level code, which is only reported in notebooks, whose cells run
+// at the top level
+fn skips_module_code(function: &FunctionId, filename: &str) -> bool {
+    function.is_module() && !notebook::is_notebook(filename)
+}
+
+impl FunctionId {
+    fn new(module: &str, name: String, node: Node) -> Self {
+        FunctionId {
+            module: module.to_string(),
+            name,
+            start_byte: node.start_byte(),
+        }
+    }
+
+    fn is_module(&self) -> bool {
+        self.name == "<module>"
+    }
+}
+
+impl fmt::Display for FunctionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+// The functions of a file, with what the analysis of each of them needs to
+// know about the file as a whole
+struct Functions<'a> {
+    functions: HashMap<FunctionId, FunctionInfo<'a>>,
+    module: &'a ModuleScope<'a>,
+    // The functions by name, as several may be defined under one
+    by_name: HashMap<String, Vec<FunctionId>>,
+}
+
+impl<'a> Functions<'a> {
+    fn new(functions: HashMap<FunctionId, FunctionInfo<'a>>, module: &'a ModuleScope<'a>) -> Self {
+        let mut by_name: HashMap<String, Vec<FunctionId>> = HashMap::new();
+        for id in functions.keys() {
+            by_name.entry(id.name.clone()).or_default().push(id.clone());
+        }
+        Functions {
+            functions,
+            module,
+            by_name,
+        }
+    }
+
+    // The functions defined under a name, such as `Store.get`
+    fn named(&self, name: &str) -> &[FunctionId] {
+        self.by_name.get(name).map_or(&[], Vec::as_slice)
+    }
+}
+
+impl<'a> std::ops::Deref for Functions<'a> {
+    type Target = HashMap<FunctionId, FunctionInfo<'a>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.functions
+    }
+}
+
+impl std::ops::DerefMut for Functions<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.functions
+    }
+}
+
+// What the analysis needs to know about a file as a whole, worked out once
+// rather than for each node that asks
+struct ModuleScope<'a> {
+    // The bases of the classes defined in the file, keyed by class name
+    class_bases: HashMap<String, Vec<String>>,
+    // What the names bound by the top-level imports refer to
+    imports: HashMap<String, String>,
+    // The modules of the top-level `from module import *` statements
+    star_imports: Vec<String>,
+    // The exceptions raised inside an `ExceptionGroup(...)`, the only ones
+    // a handler for the whole group catches
+    grouped: HashSet<String>,
+    // The annotations of module-level variables, such as `CACHE: dict[str, int]`
+    annotations: HashMap<String, Node<'a>>,
+}
+
+impl<'a> ModuleScope<'a> {
+    fn new(root: Node<'a>, source_code: &str) -> Self {
+        ModuleScope {
+            class_bases: exceptions::module_exception_bases(root, source_code),
+            imports: module_imports(root, source_code),
+            star_imports: star_imports(root, source_code),
+            grouped: grouped_exceptions(root, source_code),
+            annotations: module_annotations(root, source_code),
+        }
+    }
+
+    // The dotted name `name` refers to, following the module's imports: with
+    // `import contextlib as cl`, `cl.suppress` is `contextlib.suppress`, and
+    // with `from os import environ`, `environ` is `os.environ`
+    fn qualified_name(&self, name: &str) -> String {
+        let (head, rest) = match name.split_once('.') {
+            Some((head, rest)) => (head, Some(rest)),
+            None => (name, None),
+        };
+        let target = self.imports.get(head).map_or(head, String::as_str);
+        match rest {
+            Some(rest) => format!("{}.{}", target, rest),
+            None => target.to_string(),
+        }
+    }
+}
+
+// The names bound by the top-level imports of a module with what each refers
+// to. The first import of a name wins.
+fn module_imports(root: Node, source_code: &str) -> HashMap<String, String> {
+    let mut imports = HashMap::new();
+    let mut cursor = root.walk();
+    let statements: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|statement| {
+            matches!(
+                statement.kind(),
+                "import_statement" | "import_from_statement"
+            )
+        })
+        .collect();
+    for import in statements {
+        let module = import
+            .child_by_field_name("module_name")
+            .map(|module| module.utf8_text(source_code.as_bytes()).unwrap());
+        let mut cursor = import.walk();
+        let names: Vec<Node> = import.children_by_field_name("name", &mut cursor).collect();
+        for name in names {
+            let (imported, bound) = if name.kind() == "aliased_import" {
+                (
+                    name.child_by_field_name("name").unwrap(),
+                    name.child_by_field_name("alias").unwrap(),
+                )
+            } else {
+                (name, name)
+            };
+            let imported = imported.utf8_text(source_code.as_bytes()).unwrap();
+            let bound = bound.utf8_text(source_code.as_bytes()).unwrap();
+            // `import a.b` binds `a`
+            let bound = bound.split('.').next().unwrap();
+            let target = match module {
+                // `from . import x` has a module of just `.`
+                Some(module) if module.ends_with('.') => format!("{}{}", module, imported),
+                Some(module) => format!("{}.{}", module, imported),
+                None if name.kind() == "aliased_import" => imported.to_string(),
+                None => bound.to_string(),
+            };
+            imports.entry(bound.to_string()).or_insert(target);
+        }
+    }
+    imports
+}
+
+struct FunctionInfo<'a> {
+    node: Node<'a>,
+    may_raise: HashSet<String>,
+    // Where each exception of `may_raise` first escapes from in the body,
+    // unless it is only documented
+    raise_sites: HashMap<String, RaiseSite<'a>>,
+    reported_in_function: Cell<bool>,
+    // The calls in the function's own scope, counting subscripts through
+    // `__getitem__`, property reads and context managers, collected once
+    // for all the phases that look at them
+    calls: Vec<FunctionCall<'a>>,
+    // The try statements in the function's own scope
+    try_statements: Vec<Node<'a>>,
+}
+
+impl<'a> FunctionInfo<'a> {
+    fn new(node: Node<'a>) -> Self {
+        let mut try_statements = Vec::new();
+        collect_scope_nodes(node, &["try_statement"], &mut try_statements);
+        FunctionInfo {
+            node,
+            may_raise: HashSet::new(),
+            raise_sites: HashMap::new(),
+            reported_in_function: Cell::new(false),
+            calls: Vec::new(),
+            try_statements,
+        }
+    }
+}
+
+struct RaiseSite<'a> {
+    node: Node<'a>,
+    // The function in the file the exception comes from, for calls
+    callee: Option<FunctionId>,
+    // For `raise X from e` in a handler, the exception it translates and
+    // where that was raised in the try body
+    cause: Option<(String, Box<RaiseSite<'a>>)>,
+}
+
+#[derive(Clone)]
+struct FunctionCall<'a> {
+    name: String,
+    node: Node<'a>,
+}
+
+// Fill in the calls of each function, which takes all the functions of the
+// file to be known
+fn cache_calls(functions: &mut Functions<'_>, source_code: &str) {
+    let collected: Vec<(FunctionId, Vec<FunctionCall>)> = functions
+        .iter()
+        .map(|(id, info)| {
+            let mut calls = Vec::new();
+            collect_function_calls(info.node, &mut calls, source_code);
+            calls.extend(collect_getitem_calls(info.node, functions, source_code));
+            calls.extend(collect_property_calls(info.node, functions, source_code));
+            calls.extend(collect_context_manager_calls(
+                info.node,
+                functions,
+                source_code,
+            ));
+            (id.clone(), calls)
+        })
+        .collect();
+    for (id, calls) in collected {
+        functions.get_mut(&id).unwrap().calls = calls;
+    }
+}
+
+fn collect_functions<'a>(
+    node: Node<'a>,
+    functions: &mut HashMap<FunctionId, FunctionInfo<'a>>,
+    module: &str,
+    source_code: &str,
+) {
+    let mut cursor = node.walk();
+    if node.kind() == "function_definition" {
+        functions.insert(
+            FunctionId::new(module, function_key(node, source_code), node),
+            FunctionInfo::new(node),
+        );
+    } else if node.kind() == "lambda" && node.is_named() {
+        functions.insert(
+            FunctionId::new(module, lambda_name(node, source_code), node),
+            FunctionInfo::new(node),
+        );
+    }
+
+    // Traverse child nodes
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            collect_functions(child, functions, module, source_code);
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+// The key a function is collected under: its name, prefixed by its class for
+// methods, so `Foo.load` and `Bar.load` don't collide, and for nested
+// functions by the function they are local to, as in `outer.<locals>.inner`
+fn function_key(function_node: Node, source_code: &str) -> String {
+    let name_node = function_node.child_by_field_name("name").unwrap();
+    let mut name = name_node
+        .utf8_text(source_code.as_bytes())
+        .unwrap()
+        .to_string();
+    if let Some(class_name) = enclosing_class_name(function_node, source_code) {
+        name = format!("{}.{}", class_name, name);
+    }
+    match enclosing_function(function_node) {
+        Some(outer) => format!("{}.<locals>.{}", function_key(outer, source_code), name),
+        None => name,
+    }
+}
+
+fn collect_function_calls<'a>(
+    node: Node<'a>,
+    calls: &mut Vec<FunctionCall<'a>>,
+    source_code: &str,
+) {
+    let mut cursor = node.walk();
+    if node.kind() == "call" {
+        if let Some(function_node) = node.child_by_field_name("function") {
+            let name = function_node
+                .utf8_text(source_code.as_bytes())
+                .unwrap()
+                .to_string();
+            calls.push(FunctionCall { name, node });
+        }
+    }
+
+    // Traverse child nodes
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            if !matches!(child.kind(), "lambda" | "function_definition") {
+                collect_function_calls(child, calls, source_code);
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+// Whether the function is wrapped by one of the configured swallowing
+// decorators, matched by the name as written, its qualified name or its last
+// component, so `@retry`, `@retry(3)` and `@tenacity.retry` all match `retry`
+fn is_swallowed(
+    function_node: Node,
+    module: &ModuleScope,
+    source_code: &str,
+    config: &Config,
+) -> bool {
+    if config.swallowing_decorators.is_empty() {
+        return false;
+    }
+    let Some(definition) = function_node
+        .parent()
+        .filter(|parent| parent.kind() == "decorated_definition")
+    else {
+        return false;
+    };
+    let mut cursor = definition.walk();
+    let decorators: Vec<Node> = definition
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() == "decorator")
+        .filter_map(|decorator| decorator.named_child(0))
+        .collect();
+    decorators.into_iter().any(|expression| {
+        let expression = if expression.kind() == "call" {
+            expression.child_by_field_name("function").unwrap()
+        } else {
+            expression
+        };
+        matches_configured_name(
+            expression,
+            &config.swallowing_decorators,
+            module,
+            source_code,
+        )
+    })
+}
+
+// Whether a name in the source, such as `tenacity.retry`, is in a set of
+// configured names by the name as written, its qualified name or its last
+// component
+fn matches_configured_name(
+    node: Node,
+    names: &HashSet<String>,
+    module: &ModuleScope,
+    source_code: &str,
+) -> bool {
+    let name = node.utf8_text(source_code.as_bytes()).unwrap();
+    let qualified = module.qualified_name(name);
+    let last = name.rsplit('.').next().unwrap();
+    [name, qualified.as_str(), last]
+        .iter()
+        .any(|candidate| names.contains(*candidate))
+}
+
+// Calls to generator functions don't run the body; it runs, and raises, as
+// the generator is iterated. Such calls are moved to where the generator is
+// consumed: the call itself in `for x in gen()` or `list(gen())`, or the
+// uses of `g` after `g = gen()`. Generators consumed elsewhere are dropped.
+fn move_generator_calls<'a>(
+    calls: Vec<FunctionCall<'a>>,
+    functions: &Functions<'a>,
+    source_code: &str,
+) -> Vec<FunctionCall<'a>> {
+    let mut moved = Vec::new();
+    for call in calls {
+        let is_generator_call = call.node.kind() == "call"
+            && resolve_call_name(call.node, &call.name, functions, source_code)
+                .is_some_and(|name| is_generator(functions[name].node));
+        if !is_generator_call {
+            moved.push(call);
+            continue;
+        }
+        if is_iterated(call.node, source_code) {
+            moved.push(call);
+            continue;
+        }
+        let Some(variable) = call
+            .node
+            .parent()
+            .filter(|parent| parent.kind() == "assignment")
+            .and_then(|assignment| assignment.child_by_field_name("left"))
+            .filter(|left| left.kind() == "identifier")
+        else {
+            continue;
+        };
+        let variable = variable.utf8_text(source_code.as_bytes()).unwrap();
+        let scope = enclosing_function(call.node).unwrap_or_else(|| {
+            let mut root = call.node;
+            while let Some(parent) = root.parent() {
+                root = parent;
+            }
+            root
+        });
+        let mut identifiers = Vec::new();
+        collect_scope_nodes(scope, &["identifier"], &mut identifiers);
+        moved.extend(
+            identifiers
+                .into_iter()
+                .filter(|identifier| {
+                    identifier.utf8_text(source_code.as_bytes()).unwrap() == variable
+                        && is_iterated(*identifier, source_code)
+                })
+                .map(|node| FunctionCall {
+                    name: call.name.clone(),
+                    node,
+                }),
+        );
+    }
+    moved
+}
+
+// Whether the function body contains `yield`
+fn is_generator(function_node: Node) -> bool {
+    let Some(body) = function_node.child_by_field_name("body") else {
+        return false;
+    };
+    let mut yields = Vec::new();
+    collect_scope_nodes(body, &["yield"], &mut yields);
+    !yields.is_empty()
+}
+
+// Whether the value is iterated where it appears: looped over by a `for` or
+// a comprehension, delegated to by `yield from`, or passed to a builtin that
+// consumes it
+fn is_iterated(node: Node, source_code: &str) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    match parent.kind() {
+        "for_statement" | "for_in_clause" => {
+            parent.child_by_field_name("right").map(|right| right.id()) == Some(node.id())
+        }
+        "yield" => parent.child(1).is_some_and(|child| child.kind() == "from"),
+        "argument_list" => parent
+            .parent()
+            .and_then(|call| call.child_by_field_name("function"))
+            .is_some_and(|function| {
+                matches!(
+                    function.utf8_text(source_code.as_bytes()).unwrap(),
+                    "next"
+                        | "list"
+                        | "tuple"
+                        | "set"
+                        | "dict"
+                        | "frozenset"
+                        | "sorted"
+                        | "sum"
+                        | "min"
+                        | "max"
+                        | "any"
+                        | "all"
+                )
+            }),
+        _ => false,
+    }
+}
+
+// Whether the call is to one of the functions configured as never raising,
+// matched like decorators
+fn is_safe_call(call: &FunctionCall, config: &Config, module: &ModuleScope) -> bool {
+    if config.safe_functions.is_empty() {
+        return false;
+    }
+    let qualified = module.qualified_name(&call.name);
+    let last = call.name.rsplit('.').next().unwrap();
+    [call.name.as_str(), qualified.as_str(), last]
+        .iter()
+        .any(|candidate| config.safe_functions.contains(*candidate))
+}
+
+// Functions passed to higher-order functions, as in `map(load, items)` or
+// `sorted(xs, key=lambda x: x['k'])`, as calls made where they are passed.
+// Only arguments that name a known function are kept.
+fn collect_callback_calls<'a>(
+    calls: &[FunctionCall<'a>],
+    functions: &Functions<'a>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+    source_code: &str,
+) -> Vec<FunctionCall<'a>> {
+    let mut callbacks = Vec::new();
+    for call in calls {
+        let (Some(function), Some(arguments)) = (
+            call.node.child_by_field_name("function"),
+            call.node.child_by_field_name("arguments"),
+        ) else {
+            continue;
+        };
+        if !matches_configured_name(
+            function,
+            &config.higher_order_functions,
+            functions.module,
+            source_code,
+        ) {
+            continue;
+        }
+        let mut cursor = arguments.walk();
+        for argument in arguments.named_children(&mut cursor) {
+            let argument = if argument.kind() == "keyword_argument" {
+                argument.child_by_field_name("value").unwrap()
+            } else {
+                argument
+            };
+            let name = match argument.kind() {
+                "lambda" => lambda_name(argument, source_code),
+                "identifier" | "attribute" => argument
+                    .utf8_text(source_code.as_bytes())
+                    .unwrap()
+                    .to_string(),
+                _ => continue,
+            };
+            if resolve_call_name(call.node, &name, functions, source_code).is_some()
+                || project.resolve(call.node, &name, source_code).is_some()
+            {
+                callbacks.push(FunctionCall {
+                    name,
+                    node: call.node,
+                });
+            }
+        }
+    }
+    callbacks
+}
+
+// A lambda bound by `name = lambda ...` is called by that name like any
+// function; other lambdas are named after their position, as in
+// `<lambda:3:12>`
+fn lambda_name(node: Node, source_code: &str) -> String {
+    let assigned_name = node
+        .parent()
+        .filter(|parent| parent.kind() == "assignment")
+        .and_then(|assignment| assignment.child_by_field_name("left"))
+        .filter(|left| left.kind() == "identifier");
+    match assigned_name {
+        Some(name) => name.utf8_text(source_code.as_bytes()).unwrap().to_string(),
+        None => {
+            let position = node.start_position();
+            format!("<lambda:{}:{}>", position.row + 1, position.column + 1)
+        }
+    }
+}
+
+// Name of the class whose body directly contains the function definition
+fn enclosing_class_name(function_node: Node, source_code: &str) -> Option<String> {
+    let mut parent = function_node.parent()?;
+    if parent.kind() == "decorated_definition" {
+        parent = parent.parent()?;
+    }
+    if parent.kind() != "block" {
+        return None;
+    }
+    let class_node = parent.parent()?;
+    if class_node.kind() != "class_definition" {
+        return None;
+    }
+    let name_node = class_node.child_by_field_name("name")?;
+    Some(
+        name_node
+            .utf8_text(source_code.as_bytes())
+            .unwrap()
+            .to_string(),
+    )
+}
+
+// Collect subscripts on instances of classes defining `__getitem__`, as calls
+// to that method. Instances are recognized from `x = ClassName(...)`
+// assignments in the scope, `self` inside the class, and `ClassName(...)[k]`.
+fn collect_getitem_calls<'a>(
+    scope_node: Node<'a>,
+    functions: &Functions<'a>,
+    source_code: &str,
+) -> Vec<FunctionCall<'a>> {
+    let mut instances = HashMap::new();
+    collect_instances(scope_node, &mut instances, source_code);
+
+    let mut subscripts = Vec::new();
+    collect_subscripts(scope_node, &mut subscripts);
+
+    let mut calls = Vec::new();
+    for node in subscripts {
+        let value_node = node.child_by_field_name("value").unwrap();
+        if let Some(class_name) = instance_class(value_node, &instances, source_code) {
+            let name = format!("{}.__getitem__", class_name);
+            if !functions.named(&name).is_empty() {
+                calls.push(FunctionCall { name, node });
+            }
+        }
+    }
+    calls
+}
+
+// Collect reads of properties on instances of known classes, such as
+// `store.value` or `self.value`, as calls to the getter. Instances are
+// recognized as for `__getitem__`; assignments and `del` go to the setter
+// and deleter instead, and are left out.
+fn collect_property_calls<'a>(
+    scope_node: Node<'a>,
+    functions: &Functions<'a>,
+    source_code: &str,
+) -> Vec<FunctionCall<'a>> {
+    let mut instances = HashMap::new();
+    collect_instances(scope_node, &mut instances, source_code);
+
+    let mut attributes = Vec::new();
+    collect_scope_nodes(scope_node, &["attribute"], &mut attributes);
+
+    let mut calls = Vec::new();
+    for node in attributes {
+        let is_read = node.parent().is_none_or(|parent| match parent.kind() {
+            "call" => parent.child_by_field_name("function").map(|f| f.id()) != Some(node.id()),
+            "assignment" | "augmented_assignment" => {
+                parent.child_by_field_name("left").map(|l| l.id()) != Some(node.id())
+                    || parent.kind() == "augmented_assignment"
+            }
+            "delete_statement" => false,
+            _ => true,
+        });
+        if !is_read {
+            continue;
+        }
+        let object = node.child_by_field_name("object").unwrap();
+        let attribute = node.child_by_field_name("attribute").unwrap();
+        let Some(class_name) = instance_class(object, &instances, source_code) else {
+            continue;
+        };
+        let name = format!(
+            "{}.{}",
+            class_name,
+            attribute.utf8_text(source_code.as_bytes()).unwrap()
+        );
+        let is_property = functions
+            .named(&name)
+            .iter()
+            .any(|id| is_property(functions[id].node, source_code));
+        if is_property {
+            calls.push(FunctionCall { name, node });
+        }
+    }
+    calls
+}
+
+// Collect the context managers of with statements that are instances of
+// known classes, as calls to their `__enter__` and `__exit__` methods (or
+// `__aenter__` and `__aexit__` for `async with`) made at the with item.
+// Instances are recognized as for `__getitem__`.
+fn collect_context_manager_calls<'a>(
+    scope_node: Node<'a>,
+    functions: &Functions<'a>,
+    source_code: &str,
+) -> Vec<FunctionCall<'a>> {
+    let mut instances = HashMap::new();
+    collect_instances(scope_node, &mut instances, source_code);
+
+    let mut items = Vec::new();
+    collect_scope_nodes(scope_node, &["with_item"], &mut items);
+
+    let mut calls = Vec::new();
+    for item in items {
+        let Some(mut value) = item.child_by_field_name("value") else {
+            continue;
+        };
+        if value.kind() == "as_pattern" {
+            value = value.named_child(0).unwrap();
+        }
+        let Some(class_name) = instance_class(value, &instances, source_code) else {
+            continue;
+        };
+        let is_async = item
+            .parent()
+            .and_then(|clause| clause.parent())
+            .and_then(|statement| statement.child(0))
+            .is_some_and(|first| first.kind() == "async");
+        let methods = if is_async {
+            ["__aenter__", "__aexit__"]
+        } else {
+            ["__enter__", "__exit__"]
+        };
+        for method in methods {
+            let name = format!("{}.{}", class_name, method);
+            if !functions.named(&name).is_empty() {
+                calls.push(FunctionCall { name, node: item });
+            }
+        }
+    }
+    calls
+}
+
+// The class a value is an instance of: `ClassName(...)`, `self` inside a
+// class, or a variable of `instances`
+fn instance_class(
+    value: Node,
+    instances: &HashMap<String, String>,
+    source_code: &str,
+) -> Option<String> {
+    let text = value.utf8_text(source_code.as_bytes()).unwrap();
+    if value.kind() == "call" {
+        value
+            .child_by_field_name("function")
+            .map(|f| f.utf8_text(source_code.as_bytes()).unwrap().to_string())
+    } else if text == "self" {
+        enclosing_function(value).and_then(|f| enclosing_class_name(f, source_code))
+    } else {
+        instances.get(text).cloned()
+    }
+}
+
+// The class and its base classes, as written in the file, in the order
+// methods are looked up: depth first and left to right, each class once,
+// which is Python's method resolution order for hierarchies without
+// diamonds. Bases defined elsewhere end the walk.
+fn class_lineage(class_name: &str, bases: &HashMap<String, Vec<String>>) -> Vec<String> {
+    let mut lineage = Vec::new();
+    let mut pending = vec![class_name.to_string()];
+    while let Some(class) = pending.pop() {
+        if lineage.contains(&class) {
+            continue;
+        }
+        if let Some(class_bases) = bases.get(&class) {
+            pending.extend(class_bases.iter().rev().cloned());
+        }
+        lineage.push(class);
+    }
+    lineage
+}
+
+// Whether the method is decorated with `@property` or `@cached_property`
+fn is_property(function_node: Node, source_code: &str) -> bool {
+    let Some(definition) = function_node
+        .parent()
+        .filter(|parent| parent.kind() == "decorated_definition")
+    else {
+        return false;
+    };
+    let mut cursor = definition.walk();
+    let is_property = definition
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() == "decorator")
+        .filter_map(|decorator| decorator.named_child(0))
+        .any(|expression| {
+            matches!(
+                expression.utf8_text(source_code.as_bytes()).unwrap(),
+                "property" | "cached_property" | "functools.cached_property"
+            )
+        });
+    is_property
+}
+
+// Map variables to the class they were constructed from, e.g. `x = Foo()`
+fn collect_instances(node: Node, instances: &mut HashMap<String, String>, source_code: &str) {
+    let mut cursor = node.walk();
+    if node.kind() == "assignment" {
+        if let (Some(left), Some(right)) = (
+            node.child_by_field_name("left"),
+            node.child_by_field_name("right"),
+        ) {
+            if left.kind() == "identifier" && right.kind() == "call" {
+                let function_node = right.child_by_field_name("function").unwrap();
+                if function_node.kind() == "identifier" {
+                    instances.insert(
+                        left.utf8_text(source_code.as_bytes()).unwrap().to_string(),
+                        function_node
+                            .utf8_text(source_code.as_bytes())
+                            .unwrap()
+                            .to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    // Traverse child nodes
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            collect_instances(child, instances, source_code);
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+fn collect_subscripts<'a>(node: Node<'a>, subscripts: &mut Vec<Node<'a>>) {
+    let mut cursor = node.walk();
+    if node.kind() == "subscript" {
+        subscripts.push(node);
+    }
+
+    // Traverse child nodes
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            if !matches!(child.kind(), "lambda" | "function_definition") {
+                collect_subscripts(child, subscripts);
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+fn enclosing_function(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if parent.kind() == "function_definition" {
+            return Some(parent);
+        }
+        current = parent.parent();
+    }
+    None
+}
+
+// The key of the function a call refers to. Functions are looked up by the
+// name as written, so `helper()` and `Foo.load()` resolve directly, trying
+// the functions local to each enclosing function first; method
+// calls on `self` or `cls` resolve within the enclosing class, and calls on a
+// variable assigned `Foo(...)` in the same scope resolve within `Foo`.
+// Otherwise `module.helper()` falls back to a module-level function named
+// `helper`; a method is never guessed from its name alone, as `yaml.load()`
+// has nothing to do with `Store.load`. Plain names may also be variables
+// bound to a function, as in `handler = parse_config`. Calling a class such as `Config(path)` runs its
+// `__init__`, or its `__new__` if it only defines that.
+fn resolve_call_name<'b>(
+    call_node: Node,
+    name: &str,
+    functions: &'b Functions<'_>,
+    source_code: &str,
+) -> Option<&'b FunctionId> {
+    let lookup = |name: &str| {
+        let mut scope = enclosing_function(call_node);
+        while let Some(function) = scope {
+            let local = format!("{}.<locals>.{}", function_key(function, source_code), name);
+            if let Some(id) = function_named(call_node, &local, functions) {
+                return Some(id);
+            }
+            scope = enclosing_function(function);
+        }
+        function_named(call_node, name, functions)
+    };
+    let key = |name: &str| {
+        lookup(name)
+            .or_else(|| lookup(&format!("{}.__init__", name)))
+            .or_else(|| lookup(&format!("{}.__new__", name)))
+    };
+    if let Some(key) = key(name) {
+        return Some(key);
+    }
+    let Some((receiver, attribute)) = name.rsplit_once('.') else {
+        return aliased_function(call_node, name, functions, source_code);
+    };
+
+    let class_name = if matches!(receiver, "self" | "cls") {
+        enclosing_function(call_node).and_then(|f| enclosing_class_name(f, source_code))
+    } else {
+        let scope = enclosing_function(call_node).unwrap_or_else(|| {
+            let mut root = call_node;
+            while let Some(parent) = root.parent() {
+                root = parent;
+            }
+            root
+        });
+        let mut instances = HashMap::new();
+        collect_instances(scope, &mut instances, source_code);
+        instances.remove(receiver)
+    };
+    // Methods are looked up through the base classes defined in the file,
+    // as inherited ones are
+    if let Some(class_name) = class_name {
+        return class_lineage(&class_name, &functions.module.class_bases)
+            .iter()
+            .find_map(|class| key(&format!("{}.{}", class, attribute)));
+    }
+
+    key(attribute)
+}
+
+// The function a name refers to at a call. Of functions defined more than
+// once, module-level code sees the last definition before it, and function
+// bodies, which run later, the last one of all.
+fn function_named<'b>(
+    call_node: Node,
+    name: &str,
+    functions: &'b Functions<'_>,
+) -> Option<&'b FunctionId> {
+    let before = if enclosing_function(call_node).is_some() {
+        usize::MAX
+    } else {
+        call_node.start_byte()
+    };
+    let candidates = functions.named(name);
+    candidates
+        .iter()
+        .filter(|id| id.start_byte < before)
+        .max_by_key(|id| id.start_byte)
+        .or_else(|| candidates.iter().min_by_key(|id| id.start_byte))
+}
+
+// The function a variable is bound to, following chains such as
+// `handler = load; load = parse_config`. Assignments in the enclosing
+// function are tried before module-level ones.
+fn aliased_function<'b>(
+    node: Node,
+    name: &str,
+    functions: &'b Functions<'_>,
+    source_code: &str,
+) -> Option<&'b FunctionId> {
+    let mut seen = HashSet::new();
+    let (mut node, mut name) = (node, name);
+    while seen.insert(name) {
+        let mut values = assigned_values(node, name, source_code);
+        if enclosing_function(node).is_some() {
+            let mut root = node;
+            while let Some(parent) = root.parent() {
+                root = parent;
+            }
+            values.extend(assigned_values(root, name, source_code));
+        }
+
+        let targets: Vec<(Node, &str)> = values
+            .into_iter()
+            .filter(|value| matches!(value.kind(), "identifier" | "attribute"))
+            .map(|value| (value, value.utf8_text(source_code.as_bytes()).unwrap()))
+            .collect();
+        // Plain names are looked up directly here, and followed by the loop
+        if let Some(key) = targets.iter().find_map(|(value, target)| {
+            if target.contains('.') {
+                resolve_call_name(*value, target, functions, source_code)
+            } else {
+                function_named(*value, target, functions)
+            }
+        }) {
+            return Some(key);
+        }
+        (node, name) = *targets.first()?;
+    }
+    None
+}
+
+fn determine_exceptions<'a>(
+    functions: &mut Functions<'a>,
+    source_code: &str,
+    config: &Config,
+    project: &ProjectScope<'_>,
+) {
+    let mut function_names: Vec<FunctionId> = functions.keys().cloned().collect();
+    // In a fixed order, so the same raise sites are recorded from run to run
+    function_names.sort_by_key(|id| id.start_byte);
+
+    // Seed each function with the exceptions its docstring documents
+    for func_info in functions.values_mut() {
+        if let Some(docstring) = function_docstring(func_info.node, source_code) {
+            func_info.may_raise.extend(docstring::documented_raises(
+                docstring,
+                config.docstring_style,
+            ));
+        }
+    }
+
+    // Callees come before their callers, so most functions are analyzed
+    // once, when all they call is known. Only the functions of a recursive
+    // cycle are revisited, until what they raise stops changing.
+    let callees = call_graph(&function_names, functions, source_code, config, project);
+    let mut callers: HashMap<&FunctionId, Vec<&FunctionId>> = HashMap::new();
+    for (caller, called) in &callees {
+        for callee in called {
+            callers.entry(callee).or_default().push(caller);
+        }
+    }
+    for component in strongly_connected_components(&function_names, &callees) {
+        let members: HashSet<&FunctionId> = component.iter().copied().collect();
+        let mut pending: Vec<&FunctionId> = component.iter().rev().copied().collect();
+        while let Some(func_name) = pending.pop() {
+            if is_swallowed(
+                functions[func_name].node,
+                functions.module,
+                source_code,
+                config,
+            ) {
+                continue;
+            }
+            // Each exception with the node it first escapes from
+            let mut new_exceptions: HashMap<String, RaiseSite<'a>> = HashMap::new();
+            let guard = Guard::all(config.python_version, functions.module);
+            for (exception, site) in
+                exception_sites(func_name, functions, source_code, config, project, guard)
+            {
+                new_exceptions.entry(exception).or_insert(site);
+            }
+
+            // Sites are kept up to date, as what a handler translates may be
+            // found later
+            let func_info = functions.get_mut(func_name).unwrap();
+            let mut changed = false;
+            for (exception, site) in new_exceptions {
+                changed |= func_info.may_raise.insert(exception.clone());
+                func_info.raise_sites.insert(exception, site);
+            }
+            if changed {
+                for caller in callers.get(func_name).into_iter().flatten() {
+                    if members.contains(caller) && !pending.contains(caller) {
+                        pending.push(caller);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The calls made in a function's own scope, adding callbacks, with generator
+// calls moved to where the generator is consumed
+fn scope_calls<'a>(
+    function: &FunctionId,
+    functions: &Functions<'a>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+    source_code: &str,
+) -> Vec<FunctionCall<'a>> {
+    let mut calls = functions[function].calls.clone();
+    let callbacks = collect_callback_calls(&calls, functions, project, config, source_code);
+    calls.extend(callbacks);
+    move_generator_calls(calls, functions, source_code)
+}
+
+// The functions of the file each function calls
+fn call_graph(
+    ids: &[FunctionId],
+    functions: &Functions<'_>,
+    source_code: &str,
+    config: &Config,
+    project: &ProjectScope<'_>,
+) -> HashMap<FunctionId, Vec<FunctionId>> {
+    ids.iter()
+        .map(|id| {
+            let calls = scope_calls(id, functions, project, config, source_code);
+            let mut callees: Vec<FunctionId> = calls
+                .iter()
+                .filter_map(|call| resolve_call_name(call.node, &call.name, functions, source_code))
+                .cloned()
+                .collect();
+            callees.sort_by_key(|callee| callee.start_byte);
+            callees.dedup();
+            (id.clone(), callees)
+        })
+        .collect()
+}
+
+// The strongly connected components of the call graph, callees before
+// callers, by Tarjan's algorithm. Written with an explicit stack, as call
+// chains in generated code can be deep.
+fn strongly_connected_components<'g>(
+    ids: &'g [FunctionId],
+    callees: &'g HashMap<FunctionId, Vec<FunctionId>>,
+) -> Vec<Vec<&'g FunctionId>> {
+    let mut index: HashMap<&FunctionId, usize> = HashMap::new();
+    let mut low_link: HashMap<&FunctionId, usize> = HashMap::new();
+    let mut stack: Vec<&FunctionId> = Vec::new();
+    let mut on_stack: HashSet<&FunctionId> = HashSet::new();
+    let mut components = Vec::new();
+
+    for root in ids {
+        if index.contains_key(root) {
+            continue;
+        }
+        // Each function being visited, with how many of its callees are done
+        let mut visiting: Vec<(&FunctionId, usize)> = vec![(root, 0)];
+        while let Some(&mut (id, ref mut next)) = visiting.last_mut() {
+            if *next == 0 && !index.contains_key(id) {
+                let order = index.len();
+                index.insert(id, order);
+                low_link.insert(id, order);
+                stack.push(id);
+                on_stack.insert(id);
+            }
+            let called = &callees[id];
+            if let Some(callee) = called.get(*next) {
+                *next += 1;
+                if !index.contains_key(callee) {
+                    visiting.push((callee, 0));
+                } else if on_stack.contains(callee) {
+                    let low = low_link[id].min(index[callee]);
+                    low_link.insert(id, low);
+                }
+                continue;
+            }
+
+            visiting.pop();
+            if let Some(&(parent, _)) = visiting.last() {
+                let low = low_link[parent].min(low_link[id]);
+                low_link.insert(parent, low);
+            }
+            if low_link[id] == index[id] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack.remove(member);
+                    component.push(member);
+                    if member == id {
+                        break;
+                    }
+                }
+                component.sort_by_key(|member| member.start_byte);
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+// Each exception that may escape from the function's own scope, with the
+// node it escapes from, in the order they are found. `guard` decides which
+// handlers catch them.
+fn exception_sites<'a>(
+    func: &FunctionId,
+    functions: &Functions<'a>,
+    source_code: &str,
+    config: &Config,
+    project: &ProjectScope<'_>,
+    guard: Guard<'a>,
+) -> Vec<(String, RaiseSite<'a>)> {
+    let mut sites = Vec::new();
+    let func_info = &functions[func];
+
+    // Collect exceptions found by the checks, such as from unguarded
+    // subscripts in the function
+    for (node, _, finding) in checks::run(func, functions, source_code, config, guard) {
+        sites.push((
+            finding.exception,
+            RaiseSite {
+                node,
+                callee: None,
+                cause: None,
+            },
+        ));
+    }
+
+    // Collect exceptions from `next()` calls without a default
+    let mut next_calls = Vec::new();
+    find_unguarded_next_calls(func_info.node, &mut next_calls, source_code, guard);
+    if config.is_enabled(Rule::NextWithoutDefault) {
+        for call in next_calls {
+            if let Some(exception) = next_call_exception(call, source_code, guard.version) {
+                sites.push((
+                    exception.to_string(),
+                    RaiseSite {
+                        node: call,
+                        callee: None,
+                        cause: None,
+                    },
+                ));
+            }
+        }
+    }
+
+    // Collect exceptions from divisors that may be zero
+    let mut divisions = Vec::new();
+    find_possible_zero_divisions(func_info.node, &mut divisions, source_code, guard);
+    if config.is_enabled(Rule::PossibleZeroDivision) {
+        for node in divisions {
+            sites.push((
+                "ZeroDivisionError".to_string(),
+                RaiseSite {
+                    node,
+                    callee: None,
+                    cause: None,
+                },
+            ));
+        }
+    }
+
+    // Collect exceptions from attribute accesses on values that may be None
+    if config.is_enabled(Rule::PossibleNoneAttribute) {
+        let attributes =
+            find_possible_none_attributes(func_info.node, functions, source_code, guard);
+        for (node, _) in attributes {
+            sites.push((
+                "AttributeError".to_string(),
+                RaiseSite {
+                    node,
+                    callee: None,
+                    cause: None,
+                },
+            ));
+        }
+    }
+
+    // Collect exceptions from conversions of strings that may be malformed
+    let mut conversions = Vec::new();
+    find_unchecked_conversions(func_info.node, &mut conversions, source_code, guard);
+    if config.is_enabled(Rule::UncheckedConversion) {
+        for node in conversions {
+            sites.push((
+                "ValueError".to_string(),
+                RaiseSite {
+                    node,
+                    callee: None,
+                    cause: None,
+                },
+            ));
+        }
+    }
+
+    // Collect exceptions from explicit `raise` statements
+    let mut raises = Vec::new();
+    collect_scope_nodes(func_info.node, &["raise_statement"], &mut raises);
+    for raise_node in raises {
+        // An exception group stands for the exceptions it contains, which
+        // `except*` handlers catch one by one
+        if let Some(members) = exception_group_members(raise_node, source_code) {
+            for exception in members {
+                if !guard.catches(raise_node, &exception, source_code) {
+                    sites.push((
+                        exception,
+                        RaiseSite {
+                            node: raise_node,
+                            callee: None,
+                            cause: None,
+                        },
+                    ));
+                }
+            }
+            continue;
+        }
+        if let Some(exception) = raised_exception_name(raise_node, source_code) {
+            if !guard.catches(raise_node, &exception, source_code) {
+                sites.push((
+                    exception,
+                    RaiseSite {
+                        node: raise_node,
+                        callee: None,
+                        cause: translated_exception(
+                            raise_node,
+                            func,
+                            functions,
+                            source_code,
+                            config,
+                            project,
+                            guard,
+                        ),
+                    },
+                ));
+            }
+        }
+    }
+
+    // With strict asserts, collect AssertionError from `assert` statements
+    if config.strict_asserts {
+        let mut asserts = Vec::new();
+        collect_scope_nodes(func_info.node, &["assert_statement"], &mut asserts);
+        for node in asserts {
+            if !guard.catches(node, "AssertionError", source_code) {
+                sites.push((
+                    "AssertionError".to_string(),
+                    RaiseSite {
+                        node,
+                        callee: None,
+                        cause: None,
+                    },
+                ));
+            }
+        }
+    }
+
+    // Collect exceptions from called functions
+    let calls = scope_calls(func, functions, project, config, source_code);
+    for call in calls {
+        if is_safe_call(&call, config, functions.module) {
+            continue;
+        }
+        let callee = resolve_call_name(call.node, &call.name, functions, source_code);
+        let uncaught = if let Some(called_func) = callee.map(|name| &functions[name]) {
+            uncaught_exceptions(call.node, &called_func.may_raise, source_code, guard)
+        } else if let Some(imported) = project.resolve(call.node, &call.name, source_code) {
+            uncaught_exceptions(call.node, &imported.may_raise, source_code, guard)
+        } else {
+            let raises = external_call_exceptions(&call, functions.module, source_code, config);
+            uncaught_exceptions(call.node, &raises, source_code, guard)
+        };
+        for exception in uncaught {
+            sites.push((
+                exception,
+                RaiseSite {
+                    node: call.node,
+                    callee: callee.cloned(),
+                    cause: None,
+                },
+            ));
+        }
+    }
+    sites
+}
+
+// The exception a `raise X from e` in a handler translates into X: the first
+// one from the try body that reaches the handler, with where it is raised
+fn translated_exception<'a>(
+    raise_node: Node<'a>,
+    func: &FunctionId,
+    functions: &Functions<'a>,
+    source_code: &str,
+    config: &Config,
+    project: &ProjectScope<'_>,
+    guard: Guard<'a>,
+) -> Option<(String, Box<RaiseSite<'a>>)> {
+    // Within a try statement, this is already working out what reaches it
+    if guard.within.is_some() || raise_node.child_by_field_name("cause").is_none() {
+        return None;
+    }
+    let handler = enclosing_handler(raise_node)?;
+    let try_statement = handler
+        .parent()
+        .filter(|parent| parent.kind() == "try_statement")?;
+    let body = try_statement.child_by_field_name("body")?;
+    let module_bases = &functions.module.class_bases;
+    let mut cursor = try_statement.walk();
+    let earlier: Vec<Node> = try_statement
+        .named_children(&mut cursor)
+        .take_while(|clause| clause.id() != handler.id())
+        .filter(|clause| clause.kind() == "except_clause")
+        .collect();
+
+    let within = Guard::within(guard.version, guard.module, try_statement);
+    exception_sites(func, functions, source_code, config, project, within)
+        .into_iter()
+        .filter(|(exception, site)| {
+            let ancestors = exceptions::exception_ancestors(exception, module_bases);
+            site.node.start_byte() >= body.start_byte()
+                && site.node.end_byte() <= body.end_byte()
+                && clause_catches(
+                    handler,
+                    exception,
+                    &ancestors,
+                    functions.module,
+                    source_code,
+                )
+                && !earlier.iter().any(|clause| {
+                    clause_catches(
+                        *clause,
+                        exception,
+                        &ancestors,
+                        functions.module,
+                        source_code,
+                    )
+                })
+        })
+        .min_by_key(|(_, site)| site.node.start_byte())
+        .map(|(exception, site)| (exception, Box::new(site)))
+}
+
+// The docstring of a function: a string literal as the first statement of
+// its body
+fn function_docstring<'a>(function_node: Node, source_code: &'a str) -> Option<&'a str> {
+    if function_node.kind() != "function_definition" {
+        return None;
+    }
+    let body = function_node.child_by_field_name("body")?;
+    let statement = body.named_child(0)?;
+    let string = statement.named_child(0)?;
+    if statement.kind() != "expression_statement" || string.kind() != "string" {
+        return None;
+    }
+    Some(string.utf8_text(source_code.as_bytes()).unwrap())
+}
+
+// Exceptions a call into the standard library or an installed third-party
+// package may raise
+fn external_call_exceptions(
+    call: &FunctionCall,
+    module: &ModuleScope,
+    source_code: &str,
+    config: &Config,
+) -> HashSet<String> {
+    // Subscripts and attributes are only calls of the project's classes
+    if call.node.kind() != "call" {
+        return HashSet::new();
+    }
+    let raises = stdlib_call_exceptions(call.node, module, source_code, config.stubs.as_ref());
+    match &config.site_packages {
+        Some(site_packages) if raises.is_empty() => {
+            site_packages.call_exceptions(&module.qualified_name(&call.name), config)
+        }
+        _ => raises.iter().cloned().collect(),
+    }
+}
+
+// Exceptions a call to a builtin or standard library callable may raise,
+// such as `json.loads(s)`, or `d.pop(k)` on a value known to be a dict.
+// With stubs, the receiver of a method call may also be traced to a class,
+// as for `Path(p).read_text()`.
+fn stdlib_call_exceptions(
+    call: Node,
+    module: &ModuleScope,
+    source_code: &str,
+    stubs: Option<&stubs::Stubs>,
+) -> &'static [String] {
+    let function_node = call.child_by_field_name("function").unwrap();
+    let Some(arguments) = call
+        .child_by_field_name("arguments")
+        .filter(|arguments| arguments.kind() == "argument_list")
+    else {
+        return &[];
+    };
+    let argument_count = arguments.named_child_count();
+
+    let mut names = Vec::new();
+    if matches!(function_node.kind(), "identifier" | "attribute") {
+        let function = function_node.utf8_text(source_code.as_bytes()).unwrap();
+        names.push(module.qualified_name(function));
+    }
+    if function_node.kind() == "attribute" {
+        let object = function_node.child_by_field_name("object").unwrap();
+        let method = function_node.child_by_field_name("attribute").unwrap();
+        let method = method.utf8_text(source_code.as_bytes()).unwrap();
+        if let Some(type_name) = builtin_type_name(object, source_code) {
+            names.push(format!("{}.{}", type_name, method));
+        }
+        if let Some(type_name) =
+            stubs.and_then(|stubs| stub_type(object, module, source_code, stubs))
+        {
+            names.push(format!("{}.{}", type_name, method));
+        }
+    }
+    names
+        .iter()
+        .map(|name| stdlib::call_exceptions(name, argument_count))
+        .find(|raises| !raises.is_empty())
+        .unwrap_or(&[])
+}
+
+// The qualified type of a value according to the stubs: an instance of a
+// stubbed class such as `Path(p)`, the declared return type of a call such
+// as `Path(p).resolve()`, or what the enclosing scope assigns to a variable
+fn stub_type(
+    value: Node,
+    module: &ModuleScope,
+    source_code: &str,
+    stubs: &stubs::Stubs,
+) -> Option<String> {
+    match value.kind() {
+        "call" => {
+            let function_node = value.child_by_field_name("function").unwrap();
+            if let (Some(object), Some(method)) = (
+                function_node.child_by_field_name("object"),
+                function_node.child_by_field_name("attribute"),
+            ) {
+                if let Some(receiver) = stub_type(object, module, source_code, stubs) {
+                    let method = method.utf8_text(source_code.as_bytes()).unwrap();
+                    return stubs
+                        .return_type(&format!("{}.{}", receiver, method))
+                        .map(|name| name.to_string());
+                }
+            }
+            if !matches!(function_node.kind(), "identifier" | "attribute") {
+                return None;
+            }
+            let function = function_node.utf8_text(source_code.as_bytes()).unwrap();
+            let function = module.qualified_name(function);
+            if stubs.is_class(&function) {
+                Some(function)
+            } else {
+                stubs.return_type(&function).map(|name| name.to_string())
+            }
+        }
+        "identifier" => {
+            let name = value.utf8_text(source_code.as_bytes()).unwrap();
+            let mut types = assigned_values(value, name, source_code)
+                .into_iter()
+                .map(|assigned| stub_type(assigned, module, source_code, stubs));
+            let first = types.next()??;
+            types
+                .all(|kind| kind.as_ref() == Some(&first))
+                .then_some(first)
+        }
+        _ => None,
+    }
+}
+
+// The builtin type of a value, from its literal form, its constructor, or,
+// for a variable, what the enclosing scope assigns to it
+fn builtin_type_name(value: Node, source_code: &str) -> Option<&'static str> {
+    match value.kind() {
+        "dictionary" | "dictionary_comprehension" => Some("dict"),
+        "list" | "list_comprehension" => Some("list"),
+        "set" | "set_comprehension" => Some("set"),
+        "tuple" => Some("tuple"),
+        "string" | "concatenated_string" => Some("str"),
+        "call" => {
+            let function_node = value.child_by_field_name("function").unwrap();
+            match function_node.utf8_text(source_code.as_bytes()).unwrap() {
+                "dict" => Some("dict"),
+                "list" => Some("list"),
+                "set" => Some("set"),
+                "tuple" => Some("tuple"),
+                "str" => Some("str"),
+                _ => None,
+            }
+        }
+        "identifier" => {
+            let name = value.utf8_text(source_code.as_bytes()).unwrap();
+            let mut types = assigned_values(value, name, source_code)
+                .into_iter()
+                .map(|assigned| builtin_type_name(assigned, source_code));
+            let first = types.next()??;
+            types.all(|kind| kind == Some(first)).then_some(first)
+        }
+        _ => None,
+    }
+}
+
+fn report_unguarded_accesses(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    source_code: &str,
+    filename: &str,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    let func_info = functions.get(function).unwrap();
+
+    // Run the checks, such as for unguarded subscripts, within the function.
+    // Subscripts on user-defined classes are reported as `__getitem__` calls.
+    let findings = checks::run(
+        function,
+        functions,
+        source_code,
+        config,
+        Guard::all(config.python_version, functions.module),
+    );
+
+    if !findings.is_empty() {
+        for (node, rule, finding) in findings {
+            if !skips_module_code(function, filename) {
+                let suggestion = if config.suggest_fixes
+                    && node.kind() == "subscript"
+                    && finding.exception == "KeyError"
+                {
+                    Some(suggest_fix(node, source_code))
+                } else {
+                    None
+                };
+                warnings.push(make_warning(
+                    node,
+                    filename,
+                    rule,
+                    (function_name, vec![finding.exception]),
+                    finding.message,
+                    suggestion,
+                ));
+            }
+        }
+
+        // Mark the function as having reported unhandled exceptions
+        func_info.reported_in_function.set(true);
+    }
+}
+
+// Whether the subscript reads `os.environ`, directly or through a
+// module-level `from os import environ [as name]`
+fn is_environ_subscript(node: Node, module: &ModuleScope, source_code: &str) -> bool {
+    let value_node = node.child_by_field_name("value").unwrap();
+    let value = value_node.utf8_text(source_code.as_bytes()).unwrap();
+    matches!(value_node.kind(), "identifier" | "attribute")
+        && module.qualified_name(value) == "os.environ"
+}
+
+// Whether `os.environ['NAME']` reads one of the configured required variables
+fn is_required_env_var(node: Node, source_code: &str, config: &Config) -> bool {
+    node.child_by_field_name("subscript")
+        .and_then(|key| string_literal_value(key, source_code))
+        .is_some_and(|name| config.required_env_vars.contains(&name))
+}
+
+fn environ_message(node: Node, source_code: &str) -> String {
+    let key_node = node.child_by_field_name("subscript").unwrap();
+    let key = match string_literal_value(key_node, source_code) {
+        Some(name) => format!("'{}'", name),
+        None => key_node
+            .utf8_text(source_code.as_bytes())
+            .unwrap()
+            .to_string(),
+    };
+    format!(
+        "Possible KeyError: environment variable {} may not be set; use os.environ.get({}, default), or validate it at startup and list it as required",
+        key, key
+    )
+}
+
+// The exception a subscript raises for a missing key or index: KeyError for
+// mappings, IndexError for sequences, and LookupError when the kind of
+// container can't be told. The container is classified from annotations
+// (when enabled), literal displays, and assignments to the subscripted name
+// in the enclosing scope; failing that, string keys such as `d['key']` imply
+// a mapping and integer indexes such as `t[0]` or `s[-1]` a sequence.
+fn subscript_exception(
+    node: Node,
+    functions: &Functions<'_>,
+    source_code: &str,
+    config: &Config,
+) -> &'static str {
+    let value_node = node.child_by_field_name("value").unwrap();
+
+    if config.check_annotations {
+        let annotation = match value_node.kind() {
+            "identifier" => {
+                let name = value_node.utf8_text(source_code.as_bytes()).unwrap();
+                variable_annotation(node, name, functions.module, source_code)
+            }
+            // `f()[i]` uses the return type of `f`
+            "call" => {
+                let function_node = value_node.child_by_field_name("function").unwrap();
+                let name = function_node.utf8_text(source_code.as_bytes()).unwrap();
+                function_named(value_node, name, functions)
+                    .and_then(|id| functions[id].node.child_by_field_name("return_type"))
+            }
+            _ => None,
+        };
+        match annotation.and_then(|a| annotation_base_name(a, source_code)) {
+            Some(name) if is_sequence_type(&name) => return "IndexError",
+            Some(name) if is_mapping_type(&name) => return "KeyError",
+            _ => {}
+        }
+    }
+
+    let container = if value_node.kind() == "identifier" {
+        let name = value_node.utf8_text(source_code.as_bytes()).unwrap();
+        assigned_container(node, name, source_code)
+    } else {
+        container_kind(value_node, source_code)
+    };
+    if let Some(exception) = container {
+        return exception;
+    }
+
+    match node.child_by_field_name("subscript") {
+        Some(key) if matches!(key.kind(), "string" | "concatenated_string") => "KeyError",
+        Some(key) if is_integer_literal(key) => "IndexError",
+        _ => "LookupError",
+    }
+}
+
+// The exception `x.pop(k)` raises for a missing key or index: IndexError for
+// lists, judging by the receiver as for subscripts, and KeyError otherwise.
+// `None` for pops with a default or without an argument, and for calls to a
+// `pop` defined in the project, which are analyzed like any other call.
+pub(crate) fn pop_exception(
+    node: Node,
+    functions: &Functions<'_>,
+    source_code: &str,
+) -> Option<&'static str> {
+    let (receiver, key) = pop_receiver_and_key(node, source_code)?;
+    let name = node
+        .child_by_field_name("function")
+        .unwrap()
+        .utf8_text(source_code.as_bytes())
+        .unwrap();
+    if resolve_call_name(node, name, functions, source_code).is_some() {
+        return None;
+    }
+
+    let container = if receiver.kind() == "identifier" {
+        let name = receiver.utf8_text(source_code.as_bytes()).unwrap();
+        assigned_container(node, name, source_code)
+    } else {
+        container_kind(receiver, source_code)
+    };
+    Some(match container {
+        Some(exception) => exception,
+        None if is_integer_literal(key) => "IndexError",
+        None => "KeyError",
+    })
+}
+
+// The receiver and the only argument of `x.pop(k)`
+fn pop_receiver_and_key<'a>(node: Node<'a>, source_code: &str) -> Option<(Node<'a>, Node<'a>)> {
+    let function = node.child_by_field_name("function")?;
+    if function.kind() != "attribute" {
+        return None;
+    }
+    let attribute = function.child_by_field_name("attribute")?;
+    let arguments = node.child_by_field_name("arguments")?;
+    if attribute.utf8_text(source_code.as_bytes()).unwrap() != "pop"
+        || arguments.kind() != "argument_list"
+        || arguments.named_child_count() != 1
+    {
+        return None;
+    }
+    let key = arguments.named_child(0)?;
+    if matches!(
+        key.kind(),
+        "keyword_argument" | "list_splat" | "dictionary_splat" | "comment"
+    ) {
+        return None;
+    }
+    Some((function.child_by_field_name("object")?, key))
+}
+
+// The exception a subscript on `name` raises, judging by what the enclosing
+// scope assigns to it. `None` if it is never assigned a recognizable
+// container, or assigned both kinds.
+fn assigned_container(node: Node, name: &str, source_code: &str) -> Option<&'static str> {
+    let values = assigned_values(node, name, source_code);
+    let mut kinds = values
+        .into_iter()
+        .map(|value| container_kind(value, source_code));
+    let first = kinds.next()??;
+    if kinds.all(|kind| kind == Some(first)) {
+        Some(first)
+    } else {
+        None
+    }
+}
+
+// Values assigned to `name` in the scope enclosing `node`
+fn assigned_values<'a>(node: Node<'a>, name: &str, source_code: &str) -> Vec<Node<'a>> {
+    let scope = enclosing_function(node)
+        .and_then(|f| f.child_by_field_name("body"))
+        .unwrap_or_else(|| {
+            let mut root = node;
+            while let Some(parent) = root.parent() {
+                root = parent;
+            }
+            root
+        });
+
+    let mut assignments = Vec::new();
+    collect_scope_nodes(scope, &["assignment"], &mut assignments);
+    assignments
+        .into_iter()
+        .filter_map(|assignment| {
+            let left = assignment.child_by_field_name("left")?;
+            let right = assignment.child_by_field_name("right")?;
+            (left.utf8_text(source_code.as_bytes()).unwrap() == name).then_some(right)
+        })
+        .collect()
+}
+
+// Whether the subscripted value is a `defaultdict` or `Counter`, which fill
+// in missing keys instead of raising, either constructed in place or
+// assigned one in the enclosing scope
+fn is_default_mapping(node: Node, source_code: &str) -> bool {
+    let is_constructor = |value: Node| {
+        value.kind() == "call"
+            && matches!(
+                value
+                    .child_by_field_name("function")
+                    .unwrap()
+                    .utf8_text(source_code.as_bytes())
+                    .unwrap(),
+                "defaultdict" | "collections.defaultdict" | "Counter" | "collections.Counter"
+            )
+    };
+
+    let value = node.child_by_field_name("value").unwrap();
+    if value.kind() != "identifier" {
+        return is_constructor(value);
+    }
+    let name = value.utf8_text(source_code.as_bytes()).unwrap();
+    let values = assigned_values(node, name, source_code);
+    !values.is_empty() && values.into_iter().all(is_constructor)
+}
+
+// Whether `d['key']` reads a key `d` is known to have
+pub(crate) fn has_known_key(node: Node, source_code: &str) -> bool {
+    let value = node.child_by_field_name("value").unwrap();
+    let Some(key) = node
+        .child_by_field_name("subscript")
+        .and_then(|key| constant_key(key, source_code))
+    else {
+        return false;
+    };
+    value.kind() == "identifier"
+        && known_keys(value, node, source_code).is_some_and(|known| known.contains(&key))
+}
+
+// The constant keys a mapping is known to have at `node`, written as
+// `constant_key` does: those of a dict display or `dict(key=...)` call, or
+// of the last one assigned to the variable earlier in the scope, with those
+// stored with `d['key'] = ...` since. A `del`, `pop()` or `clear()` on the
+// variable anywhere in the scope, or a reassignment that may not run, gives
+// up on it.
+fn known_keys(value: Node, node: Node, source_code: &str) -> Option<HashSet<String>> {
+    if value.kind() != "identifier" {
+        return literal_keys(value, source_code);
+    }
+    let name = value.utf8_text(source_code.as_bytes()).unwrap();
+    let scope = enclosing_function(node)
+        .and_then(|f| f.child_by_field_name("body"))
+        .unwrap_or_else(|| {
+            let mut root = node;
+            while let Some(parent) = root.parent() {
+                root = parent;
+            }
+            root
+        });
+    if removes_keys(scope, name, source_code) {
+        return None;
+    }
+
+    // The blocks from the scope down to the subscript, each with the
+    // statement in it that leads to the subscript
+    let mut path = Vec::new();
+    let mut current = node;
+    while current.id() != scope.id() {
+        let parent = current.parent()?;
+        if matches!(parent.kind(), "block" | "module") {
+            path.push((parent, current));
+        }
+        current = parent;
+    }
+
+    // Statements that run before the subscript, in order, update the keys
+    let mut known: Option<HashSet<String>> = None;
+    for (block, leading) in path.into_iter().rev() {
+        let mut cursor = block.walk();
+        for statement in block.named_children(&mut cursor) {
+            if statement.id() == leading.id() {
+                break;
+            }
+            let assignment = statement
+                .named_child(0)
+                .filter(|_| statement.kind() == "expression_statement")
+                .filter(|child| child.kind() == "assignment");
+            let Some(assignment) = assignment else {
+                // Compound statements may or may not reassign it
+                if rebinds(statement, name, source_code) {
+                    known = None;
+                }
+                continue;
+            };
+            let (Some(left), Some(right)) = (
+                assignment.child_by_field_name("left"),
+                assignment.child_by_field_name("right"),
+            ) else {
+                continue;
+            };
+            if left.utf8_text(source_code.as_bytes()).unwrap() == name {
+                known = literal_keys(right, source_code);
+            } else if left.kind() == "subscript"
+                && left
+                    .child_by_field_name("value")
+                    .is_some_and(|target| target.utf8_text(source_code.as_bytes()).unwrap() == name)
+            {
+                if let (Some(known), Some(stored)) = (
+                    known.as_mut(),
+                    left.child_by_field_name("subscript")
+                        .and_then(|key| constant_key(key, source_code)),
+                ) {
+                    known.insert(stored);
+                }
+            }
+        }
+    }
+    known
+}
+
+// The placeholders of a string template that the mapping filling it in may
+// lack, with the method doing so: `"{a}".format_map(m)`, `"{a}".format(**m)`
+// or `Template("$a").substitute(m)`. `None` if the call is none of these or
+// every placeholder is known to be present.
+pub(crate) fn format_missing_keys(
+    node: Node,
+    module: &ModuleScope,
+    source_code: &str,
+) -> Option<(&'static str, Vec<String>)> {
+    let text = |node: Node| node.utf8_text(source_code.as_bytes()).unwrap();
+    let function = node
+        .child_by_field_name("function")
+        .filter(|function| function.kind() == "attribute")?;
+    let object = function.child_by_field_name("object")?;
+    let arguments = node
+        .child_by_field_name("arguments")
+        .filter(|arguments| arguments.kind() == "argument_list")?;
+    let (method, placeholders) = match text(function.child_by_field_name("attribute")?) {
+        "format_map" => (
+            "format_map",
+            templates::format_fields(&string_literal_value(object, source_code)?),
+        ),
+        "format" => (
+            "format",
+            templates::format_fields(&string_literal_value(object, source_code)?),
+        ),
+        "substitute" => (
+            "substitute",
+            templates::template_placeholders(&template_string(object, module, source_code)?),
+        ),
+        _ => return None,
+    };
+
+    // Keys come from keyword arguments and from the mappings passed
+    let mut provided = HashSet::new();
+    let mut mappings = Vec::new();
+    let mut cursor = arguments.walk();
+    for argument in arguments.named_children(&mut cursor) {
+        match argument.kind() {
+            "keyword_argument" => {
+                provided.insert(text(argument.child_by_field_name("name")?));
+            }
+            "dictionary_splat" => mappings.push(argument.named_child(0)?),
+            "list_splat" | "comment" => {}
+            _ if method != "format" => mappings.push(argument),
+            _ => {}
+        }
+    }
+    // Without a mapping, `.format()` fields are positional or passed by name
+    if method == "format" && mappings.is_empty() {
+        return None;
+    }
+    // Mappings of unknown types may fill in missing keys, as a dict
+    // subclass with `__missing__` does
+    if mappings
+        .iter()
+        .any(|mapping| mapping.kind() == "call" && literal_keys(*mapping, source_code).is_none())
+    {
+        return None;
+    }
+
+    let mut missing: Vec<String> = Vec::new();
+    for placeholder in placeholders {
+        let key = format!("'{}'", placeholder);
+        let present = provided.contains(placeholder.as_str())
+            || mappings.iter().any(|mapping| {
+                known_keys(*mapping, node, source_code).is_some_and(|keys| keys.contains(&key))
+            });
+        if !present && !missing.contains(&placeholder) {
+            missing.push(placeholder);
+        }
+    }
+    (!missing.is_empty()).then_some((method, missing))
+}
+
+// The template string of a `string.Template`, constructed in place or
+// assigned once to a variable of the enclosing scope
+fn template_string(value: Node, module: &ModuleScope, source_code: &str) -> Option<String> {
+    match value.kind() {
+        "call" => {
+            let function = value.child_by_field_name("function")?;
+            let function = function.utf8_text(source_code.as_bytes()).unwrap();
+            if module.qualified_name(function) != "string.Template" {
+                return None;
+            }
+            let arguments = value.child_by_field_name("arguments")?;
+            string_literal_value(arguments.named_child(0)?, source_code)
+        }
+        "identifier" => {
+            let name = value.utf8_text(source_code.as_bytes()).unwrap();
+            match assigned_values(value, name, source_code)[..] {
+                [assigned] => template_string(assigned, module, source_code),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// What the TypedDict the subscripted value is annotated with declares about
+// a string literal key, with the TypedDict's name and the key as written
+pub(crate) fn typed_dict_field(
+    node: Node,
+    module: &ModuleScope,
+    source_code: &str,
+) -> Option<(String, String, typed_dict::Field)> {
+    let value = node.child_by_field_name("value")?;
+    let key = constant_key(node.child_by_field_name("subscript")?, source_code)
+        .filter(|key| key.starts_with('\''))?;
+    if value.kind() != "identifier" {
+        return None;
+    }
+    let name = value.utf8_text(source_code.as_bytes()).unwrap();
+    let annotation = variable_annotation(node, name, module, source_code)?;
+    let class = annotation_base_name(annotation, source_code)?;
+    let mut root = node;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    let typed_dicts = typed_dict::module_typed_dicts(root, source_code);
+    let field = typed_dict::field(&typed_dicts, &class, &key[1..key.len() - 1])?;
+    Some((class, key, field))
+}
+
+// The text of a key that is a constant: a plain string literal, or a number
+fn constant_key(key: Node, source_code: &str) -> Option<String> {
+    match key.kind() {
+        "string" => {
+            let mut cursor = key.walk();
+            let interpolated = key
+                .named_children(&mut cursor)
+                .any(|child| child.kind() == "interpolation");
+            let text = key.utf8_text(source_code.as_bytes()).unwrap();
+            // Byte strings are different keys from text ones
+            if interpolated || text.starts_with(['b', 'B', 'f', 'F']) {
+                return None;
+            }
+            string_literal_value(key, source_code).map(|value| format!("'{}'", value))
+        }
+        "integer" => Some(key.utf8_text(source_code.as_bytes()).unwrap().to_string()),
+        _ => None,
+    }
+}
+
+// The keys of a dict display with constant keys, like `{'a': 1}`, or of a
+// `dict(a=1)` call; `None` for anything else
+fn literal_keys(value: Node, source_code: &str) -> Option<HashSet<String>> {
+    let mut keys = HashSet::new();
+    let mut cursor = value.walk();
+    match value.kind() {
+        "dictionary" => {
+            for pair in value.named_children(&mut cursor) {
+                match pair.kind() {
+                    "pair" => {
+                        keys.insert(constant_key(pair.child_by_field_name("key")?, source_code)?)
+                    }
+                    "comment" => continue,
+                    // `**other` adds keys, but none that are known
+                    _ => continue,
+                };
+            }
+        }
+        "call" => {
+            let function = value.child_by_field_name("function")?;
+            if function.utf8_text(source_code.as_bytes()).unwrap() != "dict" {
+                return None;
+            }
+            let arguments = value.child_by_field_name("arguments")?;
+            let mut cursor = arguments.walk();
+            for argument in arguments.named_children(&mut cursor) {
+                if argument.kind() == "keyword_argument" {
+                    let name = argument.child_by_field_name("name")?;
+                    keys.insert(format!(
+                        "'{}'",
+                        name.utf8_text(source_code.as_bytes()).unwrap()
+                    ));
+                }
+            }
+        }
+        _ => return None,
+    }
+    Some(keys)
+}
+
+// Whether a statement may assign `name` anywhere inside it, as a compound
+// statement's branches or loop targets can
+fn rebinds(statement: Node, name: &str, source_code: &str) -> bool {
+    let mut nodes = Vec::new();
+    collect_scope_nodes(
+        statement,
+        &["assignment", "augmented_assignment", "for_statement"],
+        &mut nodes,
+    );
+    nodes.into_iter().any(|node| {
+        node.child_by_field_name("left")
+            .is_some_and(|left| left.utf8_text(source_code.as_bytes()).unwrap() == name)
+    })
+}
+
+// Whether the scope may remove keys from `name`, with `del name[...]` or a
+// method such as `name.pop(...)`
+fn removes_keys(scope: Node, name: &str, source_code: &str) -> bool {
+    let mut nodes = Vec::new();
+    collect_scope_nodes(scope, &["delete_statement", "call"], &mut nodes);
+    nodes.into_iter().any(|node| {
+        let text = |node: Node| node.utf8_text(source_code.as_bytes()).unwrap();
+        if node.kind() == "delete_statement" {
+            let targets = text(node).trim_start_matches("del");
+            return targets.split(',').map(str::trim).any(|target| {
+                target == name
+                    || target
+                        .strip_prefix(name)
+                        .is_some_and(|rest| rest.starts_with('['))
+            });
+        }
+        node.child_by_field_name("function")
+            .filter(|function| function.kind() == "attribute")
+            .is_some_and(|function| {
+                let object = function.child_by_field_name("object").unwrap();
+                let method = function.child_by_field_name("attribute").unwrap();
+                text(object) == name && matches!(text(method), "pop" | "popitem" | "clear")
+            })
+    })
+}
+
+// KeyError for mapping displays and constructors such as `{}` or `dict()`,
+// IndexError for sequence ones such as `[]`, `'text'` or `list()`
+fn container_kind(value: Node, source_code: &str) -> Option<&'static str> {
+    match value.kind() {
+        "dictionary" | "dictionary_comprehension" => Some("KeyError"),
+        "list" | "tuple" | "string" | "concatenated_string" | "list_comprehension" => {
+            Some("IndexError")
+        }
+        "call" => {
+            let function_node = value.child_by_field_name("function").unwrap();
+            let name = function_node.utf8_text(source_code.as_bytes()).unwrap();
+            let name = name.rsplit('.').next().unwrap();
+            if is_mapping_type(name) {
+                Some("KeyError")
+            } else if is_sequence_type(name) || matches!(name, "sorted" | "range") {
+                Some("IndexError")
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// `0`, `3` or `-1`
+fn is_integer_literal(node: Node) -> bool {
+    match node.kind() {
+        "integer" => true,
+        "unary_operator" => node
+            .child_by_field_name("argument")
+            .is_some_and(|argument| argument.kind() == "integer"),
+        _ => false,
+    }
+}
+
+// The annotation of `name` as seen from `node`: a parameter of the enclosing
+// function, an annotated assignment in its body, or one at module level
+fn variable_annotation<'a>(
+    node: Node<'a>,
+    name: &str,
+    module: &ModuleScope<'a>,
+    source_code: &str,
+) -> Option<Node<'a>> {
+    let mut scopes = Vec::new();
+    let mut current = enclosing_function(node);
+    while let Some(function_node) = current {
+        scopes.push(function_node);
+        current = enclosing_function(function_node);
+    }
+
+    for function_node in &scopes {
+        let parameters = function_node.child_by_field_name("parameters").unwrap();
+        let mut cursor = parameters.walk();
+        for parameter in parameters.named_children(&mut cursor) {
+            let parameter_name = match parameter.kind() {
+                "typed_parameter" => parameter.named_child(0),
+                "typed_default_parameter" => parameter.child_by_field_name("name"),
+                _ => None,
+            };
+            if parameter_name.is_some_and(|n| n.utf8_text(source_code.as_bytes()).unwrap() == name)
+            {
+                return parameter.child_by_field_name("type");
+            }
+        }
+
+        let body = function_node.child_by_field_name("body").unwrap();
+        if let Some(annotation) = assignment_annotation(body, name, source_code) {
+            return Some(annotation);
+        }
+    }
+
+    module.annotations.get(name).copied()
+}
+
+// The annotation of an `name: T = ...` assignment directly in the scope
+fn assignment_annotation<'a>(scope: Node<'a>, name: &str, source_code: &str) -> Option<Node<'a>> {
+    let mut assignments = Vec::new();
+    collect_scope_nodes(scope, &["assignment"], &mut assignments);
+    assignments.into_iter().find_map(|assignment| {
+        let left = assignment.child_by_field_name("left")?;
+        if left.utf8_text(source_code.as_bytes()).unwrap() == name {
+            assignment.child_by_field_name("type")
+        } else {
+            None
+        }
+    })
+}
+
+// The annotations of the `name: T = ...` assignments at module level, keeping
+// the first of each name
+fn module_annotations<'a>(root: Node<'a>, source_code: &str) -> HashMap<String, Node<'a>> {
+    let mut assignments = Vec::new();
+    collect_scope_nodes(root, &["assignment"], &mut assignments);
+    let mut annotations = HashMap::new();
+    for assignment in assignments {
+        if let (Some(left), Some(annotation)) = (
+            assignment.child_by_field_name("left"),
+            assignment.child_by_field_name("type"),
+        ) {
+            let name = left.utf8_text(source_code.as_bytes()).unwrap();
+            annotations.entry(name.to_string()).or_insert(annotation);
+        }
+    }
+    annotations
+}
+
+// The outermost type name of an annotation without its module or parameters:
+// `Dict` for `typing.Dict[str, int]`. `Optional[T]` is unwrapped to `T`.
+fn annotation_base_name(annotation: Node, source_code: &str) -> Option<String> {
+    let mut node = annotation;
+    if node.kind() == "type" {
+        node = node.named_child(0)?;
+    }
+    // `dict[str, int] | None`
+    if node.kind() == "binary_operator" {
+        let left = node.child_by_field_name("left")?;
+        let right = node.child_by_field_name("right")?;
+        let is_none = |side: Node| side.utf8_text(source_code.as_bytes()).unwrap() == "None";
+        return match (is_none(left), is_none(right)) {
+            (false, true) => annotation_base_name(left, source_code),
+            (true, false) => annotation_base_name(right, source_code),
+            _ => None,
+        };
+    }
+    let (name_node, parameter) = match node.kind() {
+        "generic_type" => (node.named_child(0)?, node.named_child(1)),
+        "subscript" => (node.child_by_field_name("value")?, None),
+        _ => (node, None),
+    };
+    let name_node = if name_node.kind() == "attribute" {
+        name_node.child_by_field_name("attribute")?
+    } else {
+        name_node
+    };
+    let name = name_node.utf8_text(source_code.as_bytes()).unwrap();
+    if name == "Optional" {
+        return annotation_base_name(parameter?.named_child(0)?, source_code);
+    }
+    // `Union[dict, None]`, like `Optional[dict]`
+    if name == "Union" {
+        let parameters = parameter?;
+        let mut cursor = parameters.walk();
+        let members: Vec<Node> = parameters
+            .named_children(&mut cursor)
+            .filter(|member| member.utf8_text(source_code.as_bytes()).unwrap() != "None")
+            .collect();
+        return match members[..] {
+            [member] => annotation_base_name(member, source_code),
+            _ => None,
+        };
+    }
+    Some(name.to_string())
+}
+
+// Whether the subscripted value is annotated as a mapping that fills in
+// missing keys, such as `counts: Counter[str]`
+pub(crate) fn has_default_mapping_annotation(
+    node: Node,
+    module: &ModuleScope,
+    source_code: &str,
+) -> bool {
+    let value = node.child_by_field_name("value").unwrap();
+    if value.kind() != "identifier" {
+        return false;
+    }
+    let name = value.utf8_text(source_code.as_bytes()).unwrap();
+    variable_annotation(node, name, module, source_code)
+        .and_then(|annotation| annotation_base_name(annotation, source_code))
+        .is_some_and(|name| matches!(name.as_str(), "defaultdict" | "DefaultDict" | "Counter"))
+}
+
+fn is_mapping_type(name: &str) -> bool {
+    matches!(
+        name,
+        "dict"
+            | "Dict"
+            | "Mapping"
+            | "MutableMapping"
+            | "OrderedDict"
+            | "defaultdict"
+            | "DefaultDict"
+            | "Counter"
+    )
+}
+
+fn is_sequence_type(name: &str) -> bool {
+    matches!(
+        name,
+        "list"
+            | "List"
+            | "tuple"
+            | "Tuple"
+            | "Sequence"
+            | "MutableSequence"
+            | "str"
+            | "bytes"
+            | "bytearray"
+    )
+}
+
+fn report_unguarded_next_calls(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    source_code: &str,
+    filename: &str,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    if skips_module_code(function, filename) || !config.is_enabled(Rule::NextWithoutDefault) {
+        return;
+    }
+    let func_info = functions.get(function).unwrap();
+
+    let mut next_calls = Vec::new();
+    find_unguarded_next_calls(
+        func_info.node,
+        &mut next_calls,
+        source_code,
+        Guard::all(config.python_version, functions.module),
+    );
+    for call_node in &next_calls {
+        let function_node = call_node.child_by_field_name("function").unwrap();
+        let exception =
+            next_call_exception(*call_node, source_code, config.python_version).unwrap();
+        let callee = function_node.utf8_text(source_code.as_bytes()).unwrap();
+        let message = if exception == "RuntimeError" {
+            format!(
+                "Possible StopIteration from {}() without a default in generator '{}'; \
+                 it is raised as RuntimeError",
+                callee, function_name
+            )
+        } else {
+            format!(
+                "Possible {} from {}() without a default in function '{}'",
+                exception, callee, function_name
+            )
+        };
+        warnings.push(make_warning(
+            *call_node,
+            filename,
+            Rule::NextWithoutDefault,
+            (function_name, vec![exception.to_string()]),
+            message,
+            None,
+        ));
+    }
+    if !next_calls.is_empty() {
+        func_info.reported_in_function.set(true);
+    }
+}
+
+fn report_unhandled_calls(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+    reported_calls: &mut HashSet<(usize, String)>,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    let (source_code, filename) = (project.file.source, project.file.filename);
+    let version = config.python_version;
+
+    // Check for unhandled exceptions at call sites
+    for call in scope_calls(function, functions, project, config, source_code) {
+        // Property reads have a rule of their own
+        let rule = if call.node.kind() == "attribute" {
+            Rule::UnhandledPropertyAccess
+        } else {
+            Rule::UnhandledCall
+        };
+        if !config.is_enabled(rule) || is_safe_call(&call, config, functions.module) {
+            continue;
+        }
+        let callee = resolve_call_name(call.node, &call.name, functions, source_code)
+            .map(|name| (name, &functions[name]));
+        let called_func = match callee {
+            Some((_, info)) => Some((&info.may_raise, info.reported_in_function.get())),
+            None => project
+                .resolve(call.node, &call.name, source_code)
+                .map(|imported| (&imported.may_raise, imported.reported_in_function)),
+        };
+        if let Some((may_raise, reported_in_function)) = called_func {
+            let mut exceptions = uncaught_exceptions(
+                call.node,
+                may_raise,
+                source_code,
+                Guard::all(version, functions.module),
+            );
+            exceptions.retain(|exception| !EXIT_EXCEPTIONS.contains(&exception.as_str()));
+            if !exceptions.is_empty() {
+                let line_number = call.node.start_position().row + 1;
+                let key = (line_number, call.name.clone());
+
+                // Only report if not already reported in the called function
+                if !reported_calls.contains(&key) && !reported_in_function {
+                    reported_calls.insert(key);
+
+                    let action = if rule == Rule::UnhandledPropertyAccess {
+                        "reading property"
+                    } else {
+                        "calling"
+                    };
+                    let mut warning = make_warning(
+                        call.node,
+                        filename,
+                        rule,
+                        (function_name, exceptions.clone()),
+                        format!(
+                            "Possible {} not handled when {} '{}' in function '{}'",
+                            exceptions.join(", "),
+                            action,
+                            call.name,
+                            function_name
+                        ),
+                        None,
+                    );
+                    if let Some((name, info)) = callee {
+                        if let Some(exception) = exceptions
+                            .iter()
+                            .find(|exception| info.raise_sites.contains_key(*exception))
+                        {
+                            warning.related = raise_chain(name, exception, functions, filename);
+                        }
+                    }
+                    warnings.push(warning);
+                }
+            }
+        }
+    }
+}
+
+// The locations an exception passes through from a call to `callee`
+// inwards, following the calls it escapes from until the one it's raised at
+fn raise_chain(
+    callee: &FunctionId,
+    exception: &str,
+    functions: &Functions<'_>,
+    filename: &str,
+) -> Vec<Related> {
+    let related = |node: Node, message: String| Related {
+        filename: filename.to_string(),
+        cell: None,
+        line: node.start_position().row + 1,
+        column: node.start_position().column + 1,
+        end_line: node.end_position().row + 1,
+        end_column: node.end_position().column + 1,
+        message,
+    };
+    let mut chain = Vec::new();
+    let mut visited = HashSet::new();
+    let mut exception = exception.to_string();
+    let mut function = Some(callee);
+    // Recursive functions would otherwise loop
+    while let Some(name) = function.filter(|name| visited.insert(*name)) {
+        let Some(mut site) = functions[name].raise_sites.get(&exception) else {
+            break;
+        };
+        chain.push(related(
+            site.node,
+            format!("{} may be raised here in '{}'", exception, name),
+        ));
+        // Follow a translated exception back to where the original is raised
+        if let Some((original, cause)) = &site.cause {
+            chain.push(related(
+                cause.node,
+                format!(
+                    "{} may be raised here in '{}', and is raised again as {}",
+                    original, name, exception
+                ),
+            ));
+            exception = original.clone();
+            site = cause;
+        }
+        function = site.callee.as_ref();
+    }
+    chain
+}
+
+// PEP 479: `raise StopIteration` inside a generator is turned into a
+// RuntimeError instead of quietly ending the iteration
+fn report_generator_stop_iteration(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    source_code: &str,
+    filename: &str,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    let func_info = functions.get(function).unwrap();
+    if func_info.node.kind() != "function_definition"
+        || !config.is_enabled(Rule::GeneratorStopIteration)
+    {
+        return;
+    }
+    let body = func_info.node.child_by_field_name("body").unwrap();
+
+    let mut nodes = Vec::new();
+    collect_scope_nodes(body, &["yield", "raise_statement"], &mut nodes);
+    if !nodes.iter().any(|node| node.kind() == "yield") {
+        return;
+    }
+
+    // What happens depends on whether the targeted versions have PEP 479
+    let pep_479 = PythonVersion::new(3, 7);
+    let newest = config
+        .newest_python_version
+        .unwrap_or(config.python_version);
+    let (exception, message) = if config.python_version >= pep_479 {
+        (
+            "RuntimeError",
+            "StopIteration raised inside generator; it is raised as RuntimeError",
+        )
+    } else if newest >= pep_479 {
+        (
+            "RuntimeError",
+            "StopIteration raised inside generator; will become RuntimeError in Python 3.7+",
+        )
+    } else {
+        (
+            "StopIteration",
+            "StopIteration raised inside generator ends it early; it becomes RuntimeError from Python 3.7",
+        )
+    };
+    for node in nodes {
+        if node.kind() == "raise_statement"
+            && raised_exception_name(node, source_code).as_deref() == Some("StopIteration")
+        {
+            warnings.push(make_warning(
+                node,
+                filename,
+                Rule::GeneratorStopIteration,
+                (function_name, vec![exception.to_string()]),
+                message.to_string(),
+                None,
+            ));
+        }
+    }
+}
+
+// Except clauses that can never run, such as `except KeyError:` after
+// `except LookupError:`, or a second handler for the same exception
+fn report_unreachable_handlers(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    source_code: &str,
+    filename: &str,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    if !config.is_enabled(Rule::UnreachableHandler) {
+        return;
+    }
+    let func_info = functions.get(function).unwrap();
+    if func_info.try_statements.is_empty() {
+        return;
+    }
+    let module_bases = &functions.module.class_bases;
+
+    for &try_statement in &func_info.try_statements {
+        // The names each earlier handler catches, with its clause
+        let mut earlier: Vec<(&str, Node)> = Vec::new();
+        let mut cursor = try_statement.walk();
+        let clauses: Vec<Node> = try_statement
+            .named_children(&mut cursor)
+            .filter(|child| matches!(child.kind(), "except_clause" | "except_group_clause"))
+            .collect();
+        for clause in clauses {
+            // A bare except must come last, so nothing follows it
+            let Some(exception_type) = except_clause_type(clause) else {
+                break;
+            };
+            let handled = handled_exception_names(exception_type, source_code);
+            let covering: Vec<(&str, Node)> = handled
+                .iter()
+                .filter_map(|name| {
+                    let mut ancestors = exceptions::exception_ancestors(name, module_bases);
+                    let qualified = functions.module.qualified_name(name);
+                    ancestors.extend(exceptions::exception_ancestors(&qualified, module_bases));
+                    earlier.iter().copied().find(|(handler, _)| {
+                        let qualified_handler = functions.module.qualified_name(handler);
+                        exceptions::handler_catches(handler, &qualified_handler, &ancestors)
+                    })
+                })
+                .collect();
+            if !handled.is_empty() && covering.len() == handled.len() {
+                let (handler, handler_clause) = covering[0];
+                warnings.push(make_warning(
+                    exception_type,
+                    filename,
+                    Rule::UnreachableHandler,
+                    (function_name, Vec::new()),
+                    format!(
+                        "Handler for {} never runs: 'except {}' on line {} already catches it",
+                        handled.join(", "),
+                        handler,
+                        handler_clause.start_position().row + 1
+                    ),
+                    None,
+                ));
+            }
+            earlier.extend(handled.into_iter().map(|name| (name, clause)));
+        }
+    }
+}
+
+// Handlers broader than the try body needs: `except Exception:` when the
+// body can only raise exceptions the analyzer knows of, and any bare
+// `except:`, suggesting the exceptions that can actually reach them
+fn report_broad_handlers(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    if !config.is_enabled(Rule::BroadExcept) && !config.is_enabled(Rule::BareExcept) {
+        return;
+    }
+    let (source_code, filename) = (project.file.source, project.file.filename);
+    for handlers in try_handlers(function, functions, project, config) {
+        for (clause, mut remaining) in handlers.clauses {
+            // Exits are what broad handlers wrongly catch, not what they
+            // should be narrowed to
+            remaining.retain(|exception| !EXIT_EXCEPTIONS.contains(&exception.as_str()));
+            match except_clause_type(clause) {
+                None if config.is_enabled(Rule::BareExcept) => {
+                    let replacement = if remaining.is_empty() || handlers.unknown_calls {
+                        "a specific exception, or 'except Exception:'".to_string()
+                    } else {
+                        remaining.join(", ")
+                    };
+                    warnings.push(make_warning(
+                        clause,
+                        filename,
+                        Rule::BareExcept,
+                        (function_name, Vec::new()),
+                        format!(
+                            "Bare 'except:' also catches KeyboardInterrupt and SystemExit; catch {} instead",
+                            replacement
+                        ),
+                        None,
+                    ));
+                }
+                Some(exception_type) if config.is_enabled(Rule::BroadExcept) => {
+                    let handler = exception_type.utf8_text(source_code.as_bytes()).unwrap();
+                    // Handlers that re-raise, as after logging, mean to see
+                    // everything
+                    if !matches!(handler, "Exception" | "BaseException")
+                        || remaining.is_empty()
+                        || handlers.unknown_calls
+                        || handler_reraises(clause, source_code)
+                    {
+                        continue;
+                    }
+                    warnings.push(make_warning(
+                        exception_type,
+                        filename,
+                        Rule::BroadExcept,
+                        (function_name, Vec::new()),
+                        format!(
+                            "'except {}' is broader than needed: the try body can only raise {}",
+                            handler,
+                            remaining.join(", ")
+                        ),
+                        None,
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Exceptions meant to stop the program, which callers are expected to let
+// through rather than handle
+const EXIT_EXCEPTIONS: &[&str] = &["SystemExit", "KeyboardInterrupt"];
+
+// Handlers that keep SystemExit or KeyboardInterrupt from stopping the
+// program: `except BaseException:` or a bare `except:` that doesn't
+// re-raise, or an empty `except SystemExit:`. A bare `except:` is left to
+// PW013 when that is enabled. KeyboardInterrupt may come anywhere, but
+// SystemExit only from where the try body is found to raise it.
+fn report_swallowed_exits(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    if !config.is_enabled(Rule::SwallowedExit) {
+        return;
+    }
+    let (source_code, filename) = (project.file.source, project.file.filename);
+    for handlers in try_handlers(function, functions, project, config) {
+        for (clause, remaining) in handlers.clauses {
+            let raises_exit = remaining.iter().any(|exception| exception == "SystemExit");
+            let exception_type = except_clause_type(clause);
+            let handled = exception_type
+                .map(|exception_type| handled_exception_names(exception_type, source_code))
+                .unwrap_or_default();
+            let swallowed: Vec<&str> =
+                if exception_type.is_none() || handled.contains(&"BaseException") {
+                    if handler_reraises(clause, source_code)
+                        || (exception_type.is_none() && config.is_enabled(Rule::BareExcept))
+                    {
+                        continue;
+                    }
+                    EXIT_EXCEPTIONS
+                        .iter()
+                        .copied()
+                        .filter(|exception| *exception != "SystemExit" || raises_exit)
+                        .collect()
+                } else if is_empty_handler(clause, source_code) {
+                    handled
+                        .iter()
+                        .copied()
+                        .filter(|handler| match *handler {
+                            "SystemExit" => raises_exit,
+                            handler => handler == "KeyboardInterrupt",
+                        })
+                        .collect()
+                } else {
+                    continue;
+                };
+            if swallowed.is_empty() {
+                continue;
+            }
+            let handler = match exception_type {
+                Some(exception_type) => format!(
+                    "except {}",
+                    exception_type.utf8_text(source_code.as_bytes()).unwrap()
+                ),
+                None => "except".to_string(),
+            };
+            warnings.push(make_warning(
+                exception_type.unwrap_or(clause),
+                filename,
+                Rule::SwallowedExit,
+                (
+                    &function.name,
+                    swallowed.iter().map(|e| e.to_string()).collect(),
+                ),
+                format!(
+                    "'{}:' swallows {}, so the program can't be stopped; re-raise {}",
+                    handler,
+                    swallowed.join(" and "),
+                    if swallowed.len() == 1 { "it" } else { "them" }
+                ),
+                None,
+            ));
+        }
+    }
+}
+
+// Exceptions a handler is never judged useless for, as nearly any code may
+// raise them, or the analyzer doesn't look for them
+const UBIQUITOUS_EXCEPTIONS: &[&str] = &[
+    "BaseException",
+    "Exception",
+    "AttributeError",
+    "TypeError",
+    "NameError",
+    "UnboundLocalError",
+    "OverflowError",
+    "MemoryError",
+    "RecursionError",
+    "KeyboardInterrupt",
+    "SystemExit",
+    "GeneratorExit",
+];
+
+// The rules that find each exception, without which it goes unnoticed
+const EXCEPTION_RULES: &[(&str, &[Rule])] = &[
+    (
+        "KeyError",
+        &[Rule::UnguardedDictAccess, Rule::UnguardedEnvironAccess],
+    ),
+    ("IndexError", &[Rule::UnguardedIndexAccess]),
+    ("StopIteration", &[Rule::NextWithoutDefault]),
+    ("StopAsyncIteration", &[Rule::NextWithoutDefault]),
+    ("ZeroDivisionError", &[Rule::PossibleZeroDivision]),
+    (
+        "ValueError",
+        &[Rule::UncheckedConversion, Rule::UncheckedUnpacking],
+    ),
+];
+
+// Except clauses naming exceptions that nothing in the try body raises.
+// Only handlers for exceptions of a known hierarchy are judged, around
+// bodies whose every call is known and that can't have exceptions thrown
+// into them, as with `yield` in a context manager.
+fn report_useless_handlers(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    if !config.is_enabled(Rule::UselessHandler) {
+        return;
+    }
+    let (source_code, filename) = (project.file.source, project.file.filename);
+    let module_bases = &functions.module.class_bases;
+    for handlers in try_handlers(function, functions, project, config) {
+        let Some((first, possible)) = handlers.clauses.first() else {
+            continue;
+        };
+        let try_statement = first.parent().unwrap();
+        let body = try_statement.child_by_field_name("body").unwrap();
+        if handlers.unknown_calls || raises_opaquely(body, source_code) {
+            continue;
+        }
+        for (clause, _) in &handlers.clauses {
+            let Some(exception_type) = except_clause_type(*clause) else {
+                continue;
+            };
+            let useless: Vec<&str> = handled_exception_names(exception_type, source_code)
+                .into_iter()
+                .filter(|handler| {
+                    let qualified = functions.module.qualified_name(handler);
+                    let ancestors = exceptions::exception_ancestors(&qualified, module_bases);
+                    let unnoticed = UBIQUITOUS_EXCEPTIONS.contains(handler)
+                        || (*handler == "AssertionError" && !config.strict_asserts)
+                        || EXCEPTION_RULES.iter().any(|(exception, rules)| {
+                            let caught = exceptions::exception_ancestors(exception, module_bases);
+                            handler_catches(
+                                handler,
+                                &qualified,
+                                exception,
+                                &caught,
+                                functions.module,
+                            ) && !rules.iter().all(|rule| config.is_enabled(*rule))
+                        });
+                    // Traced back to BaseException through known classes
+                    let known = ancestors
+                        .iter()
+                        .all(|ancestor| exceptions::is_known_exception(ancestor, module_bases));
+                    known
+                        && !unnoticed
+                        && !possible.iter().any(|exception| {
+                            let caught = exceptions::exception_ancestors(exception, module_bases);
+                            handler_catches(
+                                handler,
+                                &qualified,
+                                exception,
+                                &caught,
+                                functions.module,
+                            )
+                        })
+                })
+                .collect();
+            if useless.is_empty() {
+                continue;
+            }
+            warnings.push(make_warning(
+                exception_type,
+                filename,
+                Rule::UselessHandler,
+                (&function.name, Vec::new()),
+                format!(
+                    "Handler for {} never runs: the try body can't raise {}",
+                    useless.join(", "),
+                    if useless.len() == 1 { "it" } else { "them" }
+                ),
+                None,
+            ));
+        }
+    }
+}
+
+// Whether exceptions may reach a try body in ways the analyzer doesn't
+// follow: thrown into a generator at `yield`, or raised from a variable or
+// by a bare `raise`. Raised classes are told from variables by their
+// capitalized names.
+fn raises_opaquely(body: Node, source_code: &str) -> bool {
+    let mut nodes = Vec::new();
+    collect_scope_nodes(body, &["yield", "await", "raise_statement"], &mut nodes);
+    nodes.iter().any(|node| match node.kind() {
+        "raise_statement" => match node.named_child(0) {
+            None => true,
+            Some(raised) => match raised.kind() {
+                "call" => false,
+                "identifier" | "attribute" => {
+                    let name = raised.utf8_text(source_code.as_bytes()).unwrap();
+                    !name
+                        .rsplit('.')
+                        .next()
+                        .is_some_and(|class| class.starts_with(|c: char| c.is_ascii_uppercase()))
+                }
+                _ => true,
+            },
+        },
+        _ => true,
+    })
+}
+
+// Handlers that do nothing, as in `except KeyError: pass`, when exceptions
+// the analyzer found in the try body reach them and aren't allowed to be
+// ignored
+fn report_swallowed_exceptions(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    if !config.is_enabled(Rule::SwallowedException) {
+        return;
+    }
+    let (source_code, filename) = (project.file.source, project.file.filename);
+    let module_bases = &functions.module.class_bases;
+    for handlers in try_handlers(function, functions, project, config) {
+        for (clause, remaining) in handlers.clauses {
+            if !is_empty_handler(clause, source_code) {
+                continue;
+            }
+            let swallowed: Vec<String> = remaining
+                .into_iter()
+                .filter(|exception| {
+                    let ancestors = exceptions::exception_ancestors(exception, module_bases);
+                    clause_catches(clause, exception, &ancestors, functions.module, source_code)
+                        && !config.allowed_swallowed.contains(exception)
+                        && !ancestors
+                            .iter()
+                            .any(|ancestor| config.allowed_swallowed.contains(ancestor))
+                })
+                .collect();
+            if swallowed.is_empty() {
+                continue;
+            }
+            let exception_type = except_clause_type(clause).unwrap_or(clause);
+            warnings.push(make_warning(
+                exception_type,
+                filename,
+                Rule::SwallowedException,
+                (function_name, swallowed.clone()),
+                format!(
+                    "{} silently swallowed; handle or log {}",
+                    swallowed.join(", "),
+                    if swallowed.len() == 1 { "it" } else { "them" }
+                ),
+                None,
+            ));
+        }
+    }
+}
+
+// The except clauses of a try statement, each with the exceptions from the
+// try body that reach it past the earlier ones
+struct TryHandlers<'a> {
+    clauses: Vec<(Node<'a>, Vec<String>)>,
+    // Whether the body calls into code the analyzer knows nothing about,
+    // which may raise anything
+    unknown_calls: bool,
+}
+
+// Compare the `Raises` section of a function's docstring with what the
+// function is found to raise. Documenting a base class covers its
+// subclasses. Documented exceptions are only reported as impossible when
+// the function calls nothing unknown, which might raise them.
+fn report_docstring_raises(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    if !config.is_enabled(Rule::DocstringRaises) {
+        return;
+    }
+    let (source_code, filename) = (project.file.source, project.file.filename);
+    let func_info = &functions[function];
+    let Some(docstring) = function_docstring(func_info.node, source_code) else {
+        return;
+    };
+    let documented = docstring::documented_raises(docstring, config.docstring_style);
+    let module_bases = &functions.module.class_bases;
+    let covers = |documented: &str, exception: &str| {
+        let ancestors = exceptions::exception_ancestors(exception, module_bases);
+        handler_catches(
+            documented,
+            documented,
+            exception,
+            &ancestors,
+            functions.module,
+        )
+    };
+    // Only what the function itself raises or lets through, not the
+    // documented exceptions it was seeded with
+    let mut raised: Vec<&String> = func_info.raise_sites.keys().collect();
+    raised.sort();
+
+    let undocumented: Vec<String> = raised
+        .iter()
+        .filter(|exception| !documented.iter().any(|name| covers(name, exception)))
+        .map(|exception| exception.to_string())
+        .collect();
+    if !undocumented.is_empty() {
+        let name = func_info.node.child_by_field_name("name").unwrap();
+        warnings.push(make_warning(
+            name,
+            filename,
+            Rule::DocstringRaises,
+            (&function.name, undocumented.clone()),
+            format!(
+                "Function '{}' may raise {}, which its docstring doesn't document",
+                function.name,
+                undocumented.join(", ")
+            ),
+            None,
+        ));
+    }
+
+    let knows_all_calls = !func_info
+        .calls
+        .iter()
+        .any(|call| is_unknown_call(call, functions, project, config));
+    let impossible: Vec<String> = documented
+        .iter()
+        .filter(|name| {
+            // A subclass of what is raised, such as FileNotFoundError for
+            // OSError, may be what is actually raised
+            !raised
+                .iter()
+                .any(|exception| covers(name, exception) || covers(exception, name))
+        })
+        .cloned()
+        .collect();
+    if knows_all_calls && !impossible.is_empty() {
+        let string = func_info
+            .node
+            .child_by_field_name("body")
+            .and_then(|body| body.named_child(0))
+            .unwrap();
+        warnings.push(make_warning(
+            string,
+            filename,
+            Rule::DocstringRaises,
+            (&function.name, impossible.clone()),
+            format!(
+                "Docstring documents {}, which function '{}' doesn't raise",
+                impossible.join(", "),
+                function.name
+            ),
+            None,
+        ));
+    }
+}
+
+// The handlers of each try statement in the function's own scope
+fn try_handlers<'a>(
+    function: &FunctionId,
+    functions: &Functions<'a>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+) -> Vec<TryHandlers<'a>> {
+    let source_code = project.file.source;
+    let func_info = functions.get(function).unwrap();
+    if func_info.try_statements.is_empty() {
+        return Vec::new();
+    }
+    let module_bases = &functions.module.class_bases;
+    let calls = &func_info.calls;
+
+    let mut handlers = Vec::new();
+    for &try_statement in &func_info.try_statements {
+        let body = try_statement.child_by_field_name("body").unwrap();
+        let in_body = |node: Node| {
+            node.start_byte() >= body.start_byte() && node.end_byte() <= body.end_byte()
+        };
+
+        // The exceptions that reach the handlers of this statement, in the
+        // order they are raised
+        let guard = Guard::within(config.python_version, functions.module, try_statement);
+        let mut possible: Vec<String> = Vec::new();
+        for (exception, site) in
+            exception_sites(function, functions, source_code, config, project, guard)
+        {
+            if in_body(site.node) && !possible.contains(&exception) {
+                possible.push(exception);
+            }
+        }
+
+        let unknown_calls = calls
+            .iter()
+            .any(|call| in_body(call.node) && is_unknown_call(call, functions, project, config));
+
+        let mut cursor = try_statement.walk();
+        let clauses: Vec<Node> = try_statement
+            .named_children(&mut cursor)
+            .filter(|child| {
+                child.kind() == "except_clause"
+                    || (child.kind() == "except_group_clause"
+                        && config.python_version.supports_except_star())
+            })
+            .collect();
+        let clauses = clauses
+            .iter()
+            .enumerate()
+            .map(|(i, clause)| {
+                let remaining = possible
+                    .iter()
+                    .filter(|exception| {
+                        let ancestors = exceptions::exception_ancestors(exception, module_bases);
+                        !clauses[..i].iter().any(|earlier| {
+                            clause_catches(
+                                *earlier,
+                                exception,
+                                &ancestors,
+                                functions.module,
+                                source_code,
+                            )
+                        })
+                    })
+                    .cloned()
+                    .collect();
+                (*clause, remaining)
+            })
+            .collect();
+        handlers.push(TryHandlers {
+            clauses,
+            unknown_calls,
+        });
+    }
+    handlers
+}
+
+// Whether a call goes into code the analyzer knows nothing about, which may
+// raise anything
+fn is_unknown_call(
+    call: &FunctionCall,
+    functions: &Functions<'_>,
+    project: &ProjectScope<'_>,
+    config: &Config,
+) -> bool {
+    let source_code = project.file.source;
+    !is_safe_call(call, config, functions.module)
+        && resolve_call_name(call.node, &call.name, functions, source_code).is_none()
+        && project
+            .resolve(call.node, &call.name, source_code)
+            .is_none()
+        && external_call_exceptions(call, functions.module, source_code, config).is_empty()
+}
+
+// Whether an except clause catches an exception with the given ancestors,
+// as a bare `except:` catches everything
+fn clause_catches(
+    clause: Node,
+    exception: &str,
+    ancestors: &HashSet<String>,
+    module: &ModuleScope,
+    source_code: &str,
+) -> bool {
+    let Some(exception_type) = except_clause_type(clause) else {
+        return true;
+    };
+    handled_exception_names(exception_type, source_code)
+        .iter()
+        .any(|handler| {
+            let qualified_handler = module.qualified_name(handler);
+            handler_catches(handler, &qualified_handler, exception, ancestors, module)
+        })
+}
+
+// Whether a handler's body does nothing: only `pass`, `...` or `continue`
+fn is_empty_handler(clause: Node, source_code: &str) -> bool {
+    let mut cursor = clause.walk();
+    let Some(body) = clause
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "block")
+    else {
+        return false;
+    };
+    let mut cursor = body.walk();
+    let result = body
+        .named_children(&mut cursor)
+        .filter(|statement| statement.kind() != "comment")
+        .all(|statement| match statement.kind() {
+            "pass_statement" | "continue_statement" => true,
+            "expression_statement" => statement.utf8_text(source_code.as_bytes()).unwrap() == "...",
+            _ => false,
+        });
+    result
+}
+
+// Name of the exception class raised by `raise X` or `raise X(...)`
+fn raised_exception_name(raise_node: Node, source_code: &str) -> Option<String> {
+    // Re-raised exceptions are accounted for by the handler they escape
+    if is_reraise(raise_node, source_code) {
+        return None;
+    }
+    let mut exception = raise_node.named_child(0)?;
+    if raise_node.child_by_field_name("cause").map(|c| c.id()) == Some(exception.id()) {
+        return None;
+    }
+    if exception.kind() == "call" {
+        exception = exception.child_by_field_name("function")?;
+    }
+    Some(
+        exception
+            .utf8_text(source_code.as_bytes())
+            .unwrap()
+            .to_string(),
+    )
+}
+
+// The exceptions listed in the exception groups raised anywhere in the file
+fn grouped_exceptions(root: Node, source_code: &str) -> HashSet<String> {
+    let mut grouped = HashSet::new();
+    let mut pending = vec![root];
+    while let Some(node) = pending.pop() {
+        if node.kind() == "raise_statement" {
+            grouped.extend(
+                exception_group_members(node, source_code)
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+        let mut cursor = node.walk();
+        pending.extend(node.named_children(&mut cursor));
+    }
+    grouped
+}
+
+// The exceptions listed in `raise ExceptionGroup("...", [KeyError(k), ...])`,
+// or None for other raises and groups of exceptions not listed in place
+fn exception_group_members(raise_node: Node, source_code: &str) -> Option<Vec<String>> {
+    let call = raise_node
+        .named_child(0)
+        .filter(|node| node.kind() == "call")?;
+    let group = call
+        .child_by_field_name("function")?
+        .utf8_text(source_code.as_bytes())
+        .unwrap();
+    if !matches!(group, "ExceptionGroup" | "BaseExceptionGroup") {
+        return None;
+    }
+    let list = call.child_by_field_name("arguments")?.named_child(1)?;
+    if !matches!(list.kind(), "list" | "tuple") {
+        return None;
+    }
+    let mut cursor = list.walk();
+    let members: Vec<String> = list
+        .named_children(&mut cursor)
+        .filter_map(|member| {
+            let class = match member.kind() {
+                "call" => member.child_by_field_name("function")?,
+                _ => member,
+            };
+            matches!(class.kind(), "identifier" | "attribute")
+                .then(|| class.utf8_text(source_code.as_bytes()).unwrap().to_string())
+        })
+        .collect();
+    (!members.is_empty()).then_some(members)
+}
+
+// Collect nodes of the given kinds that belong to this scope, without
+// descending into nested functions, lambdas or classes
+fn collect_scope_nodes<'a>(node: Node<'a>, kinds: &[&str], nodes: &mut Vec<Node<'a>>) {
+    let mut cursor = node.walk();
+    if kinds.contains(&node.kind()) {
+        nodes.push(node);
+    }
+
+    // Traverse child nodes
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            if !matches!(
+                child.kind(),
+                "function_definition" | "lambda" | "class_definition"
+            ) {
+                collect_scope_nodes(child, kinds, nodes);
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+fn make_warning(
+    node: Node,
+    filename: &str,
+    rule: Rule,
+    (function, exceptions): (&str, Vec<String>),
+    message: String,
+    suggestion: Option<Suggestion>,
+) -> Warning {
+    let start_position = node.start_position();
+    let end_position = node.end_position();
+    Warning {
+        filename: filename.to_string(),
+        cell: None,
+        line: start_position.row + 1,
+        column: start_position.column + 1,
+        end_line: end_position.row + 1,
+        end_column: end_position.column + 1,
+        rule,
+        function: function.to_string(),
+        exceptions,
+        message,
+        severity: Severity::Warning,
+        suggestion,
+        related: Vec::new(),
+    }
+}
+
+// `.get()` is only suggested where a missing key can't cause a different
+// error instead, so `d[k].name` or `d[k] + n` without an inferable default
+// are wrapped in a try/except block
+fn suggest_fix(access_node: Node, source_code: &str) -> Suggestion {
+    if is_assignment_target(access_node) || is_dereferenced(access_node) {
+        return suggest_try_except(access_node, source_code);
+    }
+    match access_node.parent() {
+        Some(parent) if parent.kind() == "binary_operator" => {
+            match inferred_default(access_node, parent) {
+                Some(default) => {
+                    suggest_get(access_node, Some(default), Applicability::Safe, source_code)
+                }
+                None => suggest_try_except(access_node, source_code),
+            }
+        }
+        Some(parent)
+            if matches!(parent.kind(), "for_statement" | "for_in_clause")
+                && parent
+                    .child_by_field_name("right")
+                    .is_some_and(|right| right.id() == access_node.id()) =>
+        {
+            suggest_get(access_node, Some("()"), Applicability::Safe, source_code)
+        }
+        // `x = d[k]` or `if d[k]:` give no hint of what a missing key
+        // should be instead
+        _ => suggest_get(access_node, None, Applicability::Display, source_code),
+    }
+}
+
+// Whether the subscript's value is used as an object, as in `d[k].name`,
+// `d[k][j]` or `d[k]()`, which would fail on `None`
+fn is_dereferenced(node: Node) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    let field = match parent.kind() {
+        "attribute" => "object",
+        "subscript" => "value",
+        "call" => "function",
+        _ => return false,
+    };
+    parent
+        .child_by_field_name(field)
+        .is_some_and(|child| child.id() == node.id())
+}
+
+// The default for a subscript in arithmetic with a literal, such as `0` in
+// `d[k] + 1`
+fn inferred_default(node: Node, operator: Node) -> Option<&'static str> {
+    let left = operator.child_by_field_name("left")?;
+    let right = operator.child_by_field_name("right")?;
+    let other = if left.id() == node.id() { right } else { left };
+    match other.kind() {
+        "integer" => Some("0"),
+        "float" => Some("0.0"),
+        "string" | "concatenated_string" => Some("''"),
+        _ => None,
+    }
+}
+
+// Rewrite `d[key]` as `d.get(key)`, or `d.get(key, default)`, keeping the
+// rest of the affected lines
+fn suggest_get(
+    access_node: Node,
+    default: Option<&str>,
+    applicability: Applicability,
+    source_code: &str,
+) -> Suggestion {
+    let value_node = access_node.child_by_field_name("value").unwrap();
+    let value = value_node.utf8_text(source_code.as_bytes()).unwrap();
+    let text = access_node.utf8_text(source_code.as_bytes()).unwrap();
+
+    // The key is everything between the outermost brackets
+    let key_start = value_node.end_byte() - access_node.start_byte();
+    let key = text[key_start..]
+        .trim_start()
+        .trim_start_matches('[')
+        .trim_end()
+        .trim_end_matches(']')
+        .trim();
+
+    let text = match default {
+        Some(default) => format!("{}.get({}, {})", value, key, default),
+        None => format!("{}.get({})", value, key),
+    };
+
+    let (line_start, line_end) = line_span(access_node, source_code);
+    Suggestion {
+        original: source_code[line_start..line_end].to_string(),
+        replacement: format!(
+            "{}{}{}",
+            &source_code[line_start..access_node.start_byte()],
+            text,
+            &source_code[access_node.end_byte()..line_end]
+        ),
+        edit: Edit {
+            start: access_node.start_byte(),
+            end: access_node.end_byte(),
+            text,
+        },
+        applicability,
+    }
+}
+
+// Wrap the statement containing the access in a `try/except KeyError` block
+// that re-raises, leaving the handling to be written
+fn suggest_try_except(access_node: Node, source_code: &str) -> Suggestion {
+    let mut statement = access_node;
+    while let Some(parent) = statement.parent() {
+        if matches!(parent.kind(), "block" | "module") {
+            break;
+        }
+        statement = parent;
+    }
+
+    let (line_start, line_end) = line_span(statement, source_code);
+    let original = &source_code[line_start..line_end];
+    let indent = " ".repeat(statement.start_position().column);
+
+    let mut fixed = format!("{}try:\n", indent);
+    let mut offset = line_start;
+    for line in original.split_inclusive('\n') {
+        // Lines inside a multi-line string are part of its value
+        if offset == line_start || !in_string(statement, offset) {
+            fixed.push_str("    ");
+        }
+        fixed.push_str(line);
+        offset += line.len();
+    }
+    fixed.push_str(&format!(
+        "\n{}except KeyError:\n{}    # TODO: handle the missing key\n{}    raise",
+        indent, indent, indent
+    ));
+    Suggestion {
+        original: original.to_string(),
+        replacement: fixed.clone(),
+        edit: Edit {
+            start: line_start,
+            end: line_end,
+            text: fixed,
+        },
+        applicability: Applicability::Unsafe,
+    }
+}
+
+// Whether the byte is inside a string literal of the node
+fn in_string(node: Node, byte: usize) -> bool {
+    let mut current = node.descendant_for_byte_range(byte, byte);
+    while let Some(descendant) = current {
+        if descendant.kind() == "string" && descendant.start_byte() < byte {
+            return true;
+        }
+        if descendant.id() == node.id() {
+            break;
+        }
+        current = descendant.parent();
+    }
+    false
+}
+
+// Byte range of the full source lines covered by `node`
+fn line_span(node: Node, source_code: &str) -> (usize, usize) {
+    let start = source_code[..node.start_byte()]
+        .rfind('\n')
+        .map_or(0, |i| i + 1);
+    let end = source_code[node.end_byte()..]
+        .find('\n')
+        .map_or(source_code.len(), |i| node.end_byte() + i);
+    (start, end)
+}
+
+// Whether the subscript is written to rather than read, e.g. `d[k] = v`,
+// `d[k] += 1`, `a, d[k] = pair` or `del d[k]`
+fn is_assignment_target(node: Node) -> bool {
+    target_statement(node).is_some()
+}
+
+// Whether the subscript is only written to, as in `d[k] = v` or `for d[k] in
+// items`, which can't raise KeyError. `d[k] += 1` reads the key first, and
+// `del d[k]` needs it to be present.
+pub(crate) fn is_store(node: Node) -> bool {
+    target_statement(node)
+        .is_some_and(|statement| matches!(statement.kind(), "assignment" | "for_statement"))
+}
+
+// The statement the node is a target of, if it is one
+fn target_statement(node: Node) -> Option<Node> {
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        match parent.kind() {
+            "pattern_list" | "tuple_pattern" | "list_pattern" | "expression_list" => {
+                current = parent;
+            }
+            "assignment" | "augmented_assignment" | "for_statement" => {
+                return parent
+                    .child_by_field_name("left")
+                    .filter(|left| left.id() == current.id())
+                    .map(|_| parent);
+            }
+            "delete_statement" => return Some(parent),
+            _ => return None,
+        }
+    }
+    None
+}
+
+// Exceptions from `exceptions` that no enclosing handler catches at `node`,
+// in sorted order
+fn uncaught_exceptions(
+    node: Node,
+    exceptions: &HashSet<String>,
+    source_code: &str,
+    guard: Guard,
+) -> Vec<String> {
+    let mut uncaught: Vec<String> = exceptions
+        .iter()
+        .filter(|exception| !guard.catches(node, exception, source_code))
+        .cloned()
+        .collect();
+    uncaught.sort();
+    uncaught
+}
+
+fn report_possible_zero_divisions(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    source_code: &str,
+    filename: &str,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    if skips_module_code(function, filename) || !config.is_enabled(Rule::PossibleZeroDivision) {
+        return;
+    }
+    let func_info = functions.get(function).unwrap();
+
+    let mut divisions = Vec::new();
+    find_possible_zero_divisions(
+        func_info.node,
+        &mut divisions,
+        source_code,
+        Guard::all(config.python_version, functions.module),
+    );
+    for division in &divisions {
+        warnings.push(make_warning(
+            *division,
+            filename,
+            Rule::PossibleZeroDivision,
+            (function_name, vec!["ZeroDivisionError".to_string()]),
+            format!("Possible ZeroDivisionError in function '{}'", function_name),
+            None,
+        ));
+    }
+    if !divisions.is_empty() {
+        func_info.reported_in_function.set(true);
+    }
+}
+
+// Find `/`, `//` and `%` operations, including augmented assignments, whose
+// divisor isn't a non-zero constant and isn't checked to be non-zero first.
+// `%` on a string literal is formatting rather than modulo.
+fn find_possible_zero_divisions<'a>(
+    node: Node<'a>,
+    divisions: &mut Vec<Node<'a>>,
+    source_code: &str,
+    guard: Guard,
+) {
+    let mut cursor = node.walk();
+    if matches!(node.kind(), "binary_operator" | "augmented_assignment") {
+        let operator = node.child_by_field_name("operator").unwrap().kind();
+        let left = node.child_by_field_name("left").unwrap();
+        let divisor = node.child_by_field_name("right").unwrap();
+        let is_division = matches!(operator, "/" | "//" | "/=" | "//=")
+            || (matches!(operator, "%" | "%=")
+                && !matches!(left.kind(), "string" | "concatenated_string"));
+        if is_division
+            && !is_nonzero_constant(divisor, source_code)
+            && !matches!(divisor.kind(), "string" | "concatenated_string")
+            && !is_guarded_nonzero(node, divisor, source_code)
+            && !guard.catches(node, "ZeroDivisionError", source_code)
+        {
+            divisions.push(node);
+        }
+    }
+
+    // Traverse child nodes
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            if !matches!(child.kind(), "lambda" | "function_definition") {
+                find_possible_zero_divisions(child, divisions, source_code, guard);
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+// Whether the node is a numeric literal such as `2`, `0.5` or `-1e3` whose
+// value isn't zero
+fn is_nonzero_constant(node: Node, source_code: &str) -> bool {
+    match node.kind() {
+        "parenthesized_expression" => node
+            .named_child(0)
+            .is_some_and(|inner| is_nonzero_constant(inner, source_code)),
+        "unary_operator" => node
+            .child_by_field_name("argument")
+            .is_some_and(|argument| is_nonzero_constant(argument, source_code)),
+        "integer" | "float" => {
+            let text = node
+                .utf8_text(source_code.as_bytes())
+                .unwrap()
+                .to_lowercase();
+            let text = text.trim_end_matches(['j', 'l']);
+            let digits = match text.get(..2) {
+                Some("0x" | "0o" | "0b") => &text[2..],
+                // The exponent doesn't matter once the mantissa is zero
+                _ => text.split('e').next().unwrap(),
+            };
+            digits
+                .chars()
+                .any(|c| c.is_ascii_alphanumeric() && c != '0')
+        }
+        _ => false,
+    }
+}
+
+// Whether a division only runs once its divisor is known to be non-zero,
+// as in `if n:` or `if n != 0:`, or after `if not n: return`
+fn is_guarded_nonzero(node: Node, divisor: Node, source_code: &str) -> bool {
+    let divisor = divisor.utf8_text(source_code.as_bytes()).unwrap();
+    is_guarded_by_condition(
+        node,
+        &|condition, holds| asserts_nonzero(condition, divisor, holds, source_code),
+        &ends_in_exit,
+    )
+}
+
+// Whether `condition` being true implies `divisor` is non-zero (or, with
+// `nonzero` false, that it is zero)
+fn asserts_nonzero(condition: Node, divisor: &str, nonzero: bool, source_code: &str) -> bool {
+    let text = |node: Node| node.utf8_text(source_code.as_bytes()).unwrap();
+    match condition.kind() {
+        "parenthesized_expression" => condition
+            .named_child(0)
+            .is_some_and(|inner| asserts_nonzero(inner, divisor, nonzero, source_code)),
+        "not_operator" => condition
+            .child_by_field_name("argument")
+            .is_some_and(|inner| asserts_nonzero(inner, divisor, !nonzero, source_code)),
+        "boolean_operator" => {
+            let operator = condition.child_by_field_name("operator").unwrap().kind();
+            let combines = if nonzero { "and" } else { "or" };
+            operator == combines
+                && ["left", "right"].iter().any(|side| {
+                    asserts_nonzero(
+                        condition.child_by_field_name(side).unwrap(),
+                        divisor,
+                        nonzero,
+                        source_code,
+                    )
+                })
+        }
+        "comparison_operator" if condition.named_child_count() == 2 => {
+            let operator = condition.child(1).unwrap().kind();
+            let left = text(condition.named_child(0).unwrap());
+            let right = text(condition.named_child(1).unwrap());
+            let expected: &[&str] = if nonzero { &["!=", ">"] } else { &["=="] };
+            expected.contains(&operator) && left == divisor && right == "0"
+        }
+        _ => nonzero && text(condition) == divisor,
+    }
+}
+
+fn report_possible_none_attributes(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    source_code: &str,
+    filename: &str,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    if skips_module_code(function, filename) || !config.is_enabled(Rule::PossibleNoneAttribute) {
+        return;
+    }
+    let func_info = functions.get(function).unwrap();
+
+    let accesses = find_possible_none_attributes(
+        func_info.node,
+        functions,
+        source_code,
+        Guard::all(config.python_version, functions.module),
+    );
+    for (access, name) in &accesses {
+        warnings.push(make_warning(
+            *access,
+            filename,
+            Rule::PossibleNoneAttribute,
+            (function_name, vec!["AttributeError".to_string()]),
+            format!(
+                "Possible AttributeError: '{}' may be None in function '{}'",
+                name, function_name
+            ),
+            None,
+        ));
+    }
+    if !accesses.is_empty() {
+        func_info.reported_in_function.set(true);
+    }
+}
+
+// Attribute accesses such as `x.name` or `x.method()` on a variable that may
+// be None, with the variable's name. A variable may be None when its latest
+// assignment before the access is `None` or the result of a function that
+// returns None on some path, or, before any assignment, when it is a
+// parameter annotated `Optional[T]` or `T | None`. Accesses are skipped once
+// a check such as `if x is not None:` or `if x is None: return` rules None out.
+fn find_possible_none_attributes<'a>(
+    function_node: Node<'a>,
+    functions: &Functions<'_>,
+    source_code: &str,
+    guard: Guard,
+) -> Vec<(Node<'a>, String)> {
+    if function_node.kind() != "function_definition" {
+        return Vec::new();
+    }
+    let mut attributes = Vec::new();
+    collect_scope_nodes(function_node, &["attribute"], &mut attributes);
+    let mut assignments = Vec::new();
+    collect_scope_nodes(function_node, &["assignment"], &mut assignments);
+
+    let mut accesses = Vec::new();
+    for attribute in attributes {
+        let object = attribute.child_by_field_name("object").unwrap();
+        if object.kind() != "identifier" {
+            continue;
+        }
+        let name = object.utf8_text(source_code.as_bytes()).unwrap();
+
+        let latest_value = assignments
+            .iter()
+            .rev()
+            .find(|assignment| {
+                assignment.end_byte() <= attribute.start_byte()
+                    && assignment
+                        .child_by_field_name("left")
+                        .is_some_and(|left| left.utf8_text(source_code.as_bytes()).unwrap() == name)
+            })
+            .and_then(|assignment| assignment.child_by_field_name("right"));
+        let may_be_none = match latest_value {
+            Some(value) => value.kind() == "none" || returns_none(value, functions, source_code),
+            None => variable_annotation(attribute, name, functions.module, source_code)
+                .is_some_and(|annotation| is_optional_type(annotation, source_code)),
+        };
+
+        if may_be_none
+            && !is_guarded_not_none(attribute, name, source_code)
+            && !guard.catches(attribute, "AttributeError", source_code)
+        {
+            accesses.push((attribute, name.to_string()));
+        }
+    }
+    accesses
+}
+
+// Whether the value is a call to a function that returns None on some path,
+// through `return None` or a bare `return`
+fn returns_none(value: Node, functions: &Functions<'_>, source_code: &str) -> bool {
+    if value.kind() != "call" {
+        return false;
+    }
+    let function_node = value.child_by_field_name("function").unwrap();
+    let name = function_node.utf8_text(source_code.as_bytes()).unwrap();
+    let Some(called) =
+        resolve_call_name(value, name, functions, source_code).map(|name| &functions[name])
+    else {
+        return false;
+    };
+    let mut returns = Vec::new();
+    collect_scope_nodes(called.node, &["return_statement"], &mut returns);
+    returns.into_iter().any(|statement| {
+        statement
+            .named_child(0)
+            .is_none_or(|returned| returned.kind() == "none")
+    })
+}
+
+// Whether an annotation such as `Optional[T]`, `T | None` or
+// `"Optional[T]"` allows None
+fn is_optional_type(annotation: Node, source_code: &str) -> bool {
+    let text = annotation.utf8_text(source_code.as_bytes()).unwrap();
+    let text = text.trim_matches(|c| c == '"' || c == '\'');
+    text.starts_with("Optional[")
+        || text.starts_with("typing.Optional[")
+        || text.split('|').any(|part| part.trim() == "None")
+}
+
+// Whether an access on `name` only runs once it is known not to be None,
+// as in `if x is not None:`, `if x:` or `x and x.name`, or after
+// `if x is None: return` or `if x is None: x = ...`
+fn is_guarded_not_none(node: Node, name: &str, source_code: &str) -> bool {
+    is_guarded_by_condition(
+        node,
+        &|condition, holds| asserts_not_none(condition, name, holds, source_code),
+        &|block| ends_in_exit(block) || assigns_name(block, name, source_code),
+    )
+}
+
+// Whether `condition` being true implies `name` isn't None (or, with
+// `not_none` false, that it is None)
+fn asserts_not_none(condition: Node, name: &str, not_none: bool, source_code: &str) -> bool {
+    let text = |node: Node| node.utf8_text(source_code.as_bytes()).unwrap();
+    match condition.kind() {
+        "parenthesized_expression" => condition
+            .named_child(0)
+            .is_some_and(|inner| asserts_not_none(inner, name, not_none, source_code)),
+        "not_operator" => condition
+            .child_by_field_name("argument")
+            .is_some_and(|inner| asserts_not_none(inner, name, !not_none, source_code)),
+        "boolean_operator" => {
+            let operator = condition.child_by_field_name("operator").unwrap().kind();
+            let combines = if not_none { "and" } else { "or" };
+            operator == combines
+                && ["left", "right"].iter().any(|side| {
+                    asserts_not_none(
+                        condition.child_by_field_name(side).unwrap(),
+                        name,
+                        not_none,
+                        source_code,
+                    )
+                })
+        }
+        "comparison_operator" if condition.named_child_count() == 2 => {
+            let operator = condition.child(1).unwrap().kind();
+            let expected: &[&str] = if not_none {
+                &["is not", "!="]
+            } else {
+                &["is", "=="]
+            };
+            expected.contains(&operator)
+                && text(condition.named_child(0).unwrap()) == name
+                && condition.named_child(1).unwrap().kind() == "none"
+        }
+        // `isinstance(x, T)` rules out None for any T but NoneType
+        "call" if not_none => {
+            let function_node = condition.child_by_field_name("function").unwrap();
+            let arguments = condition.child_by_field_name("arguments").unwrap();
+            text(function_node) == "isinstance"
+                && arguments
+                    .named_child(0)
+                    .is_some_and(|argument| text(argument) == name)
+        }
+        _ => not_none && text(condition) == name,
+    }
+}
+
+// Whether a block assigns to `name`, which may be a subscript such as
+// `d[k]`, as one of its own statements
+fn assigns_name(block: Node, name: &str, source_code: &str) -> bool {
+    let mut cursor = block.walk();
+    let assigned = block.named_children(&mut cursor).any(|statement| {
+        statement.kind() == "expression_statement"
+            && statement.named_child(0).is_some_and(|assignment| {
+                assignment.kind() == "assignment"
+                    && assignment
+                        .child_by_field_name("left")
+                        .is_some_and(|left| left.utf8_text(source_code.as_bytes()).unwrap() == name)
+            })
+    });
+    assigned
+}
+
+fn report_unchecked_conversions(
+    function: &FunctionId,
+    functions: &Functions<'_>,
+    source_code: &str,
+    filename: &str,
+    config: &Config,
+    warnings: &mut Vec<Warning>,
+) {
+    let function_name = function.name.as_str();
+    if skips_module_code(function, filename) || !config.is_enabled(Rule::UncheckedConversion) {
+        return;
+    }
+    let func_info = functions.get(function).unwrap();
+
+    let mut conversions = Vec::new();
+    find_unchecked_conversions(
+        func_info.node,
+        &mut conversions,
+        source_code,
+        Guard::all(config.python_version, functions.module),
+    );
+    for call_node in &conversions {
+        let function_node = call_node.child_by_field_name("function").unwrap();
+        warnings.push(make_warning(
+            *call_node,
+            filename,
+            Rule::UncheckedConversion,
+            (function_name, vec!["ValueError".to_string()]),
+            format!(
+                "Possible ValueError from {}() in function '{}'",
+                function_node.utf8_text(source_code.as_bytes()).unwrap(),
+                function_name
+            ),
+            None,
+        ));
+    }
+    if !conversions.is_empty() {
+        func_info.reported_in_function.set(true);
+    }
+}
+
+// Functions that raise ValueError when given a malformed string
+const CONVERSION_FUNCTIONS: &[&str] = &[
+    "int",
+    "float",
+    "datetime.datetime.strptime",
+    "time.strptime",
+    "uuid.UUID",
+];
+
+// Find conversion calls such as `int(s)` outside a ValueError handler.
+// Conversions of numeric literals, or of string literals that hold a number,
+// can't fail.
+fn find_unchecked_conversions<'a>(
+    node: Node<'a>,
+    calls: &mut Vec<Node<'a>>,
+    source_code: &str,
+    guard: Guard,
+) {
+    let mut cursor = node.walk();
+    if node.kind() == "call" {
+        let function_node = node.child_by_field_name("function").unwrap();
+        let function = function_node.utf8_text(source_code.as_bytes()).unwrap();
+        let arguments = node.child_by_field_name("arguments").unwrap();
+        let first_argument = arguments.named_child(0);
+        if arguments.kind() == "argument_list"
+            && matches!(function_node.kind(), "identifier" | "attribute")
+            && CONVERSION_FUNCTIONS.contains(&guard.module.qualified_name(function).as_str())
+            && first_argument.is_some_and(|argument| !is_numeric_literal(argument, source_code))
+            && !guard.catches(node, "ValueError", source_code)
+        {
+            calls.push(node);
+        }
+    }
+
+    // Traverse child nodes
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            if !matches!(child.kind(), "lambda" | "function_definition") {
+                find_unchecked_conversions(child, calls, source_code, guard);
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+// Whether the node is a number, or a string literal holding one, such as
+// `3.5` or `"42"`
+fn is_numeric_literal(node: Node, source_code: &str) -> bool {
+    match node.kind() {
+        "integer" | "float" => true,
+        "unary_operator" => node
+            .child_by_field_name("argument")
+            .is_some_and(|argument| is_numeric_literal(argument, source_code)),
+        "string" => string_literal_value(node, source_code)
+            .is_some_and(|value| value.trim().parse::<f64>().is_ok()),
+        _ => false,
+    }
+}
+
+// `next(iterator)` raises StopIteration once the iterator is exhausted, while
+// `next(iterator, default)` returns the default instead. `anext()` does the
+// same for async iterators with StopAsyncIteration.
+fn find_unguarded_next_calls<'a>(
+    node: Node<'a>,
+    calls: &mut Vec<Node<'a>>,
+    source_code: &str,
+    guard: Guard,
+) {
+    let mut cursor = node.walk();
+    if node.kind() == "call" {
+        let arguments = node.child_by_field_name("arguments").unwrap();
+        if let Some(exception) = next_call_exception(node, source_code, guard.version) {
+            // Handlers inside the generator still see the StopIteration
+            let raised = if exception == "RuntimeError" {
+                "StopIteration"
+            } else {
+                exception
+            };
+            if arguments.kind() == "argument_list"
+                && arguments.named_child_count() == 1
+                && !guard.catches(node, raised, source_code)
+            {
+                calls.push(node);
+            }
+        }
+    }
+
+    // Traverse child nodes
+    if cursor.goto_first_child() {
+        loop {
+            let child = cursor.node();
+            if !matches!(child.kind(), "lambda" | "function_definition") {
+                find_unguarded_next_calls(child, calls, source_code, guard);
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+// The exception an exhausted iterator raises from a `next()` or, from
+// Python 3.10, `anext()` call. Inside a generator, PEP 479 turns the
+// StopIteration into a RuntimeError from Python 3.7.
+fn next_call_exception(
+    call: Node,
+    source_code: &str,
+    version: PythonVersion,
+) -> Option<&'static str> {
+    let function_node = call.child_by_field_name("function").unwrap();
+    match function_node.utf8_text(source_code.as_bytes()).unwrap() {
+        "next"
+            if version >= PythonVersion::new(3, 7)
+                && enclosing_function(call).is_some_and(is_generator) =>
+        {
+            Some("RuntimeError")
+        }
+        "next" => Some("StopIteration"),
+        "anext" if version >= PythonVersion::new(3, 10) => Some("StopAsyncIteration"),
+        _ => None,
+    }
+}
+
+// Whether `d[k]` only runs once `k in d` is known to hold: inside
+// `if k in d:` (or `elif`, or a conditional expression), after
+// `k in d and`, in the `else` of `if k not in d:`, or after an earlier
+// `if k not in d:` that returns, raises, skips the loop or fills in the key.
+// The same goes for `d.pop(k)`.
+fn is_guarded_by_membership(node: Node, source_code: &str) -> bool {
+    let (value, key) = if node.kind() == "call" {
+        let Some((value, key)) = pop_receiver_and_key(node, source_code) else {
+            return false;
+        };
+        (value, key)
+    } else {
+        let Some(key) = node.child_by_field_name("subscript") else {
+            return false;
+        };
+        (node.child_by_field_name("value").unwrap(), key)
+    };
+    let value = value.utf8_text(source_code.as_bytes()).unwrap();
+    let key = key.utf8_text(source_code.as_bytes()).unwrap();
+    is_guarded_by_condition(
+        node,
+        &|condition, holds| asserts_membership(condition, key, value, holds, source_code),
+        &|block| ensures_key(block, key, value, source_code),
+    )
+}
+
+// Whether `node` only runs while some condition holds. `asserts(c, true)`
+// tells whether `c` being true implies the condition, and `asserts(c, false)`
+// whether it implies the opposite; `ensures(block)` whether running `block`
+// when the condition fails leaves it holding (or leaves the enclosing code).
+fn is_guarded_by_condition(
+    node: Node,
+    asserts: &dyn Fn(Node, bool) -> bool,
+    ensures: &dyn Fn(Node) -> bool,
+) -> bool {
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        let guarded = match parent.kind() {
+            // The body of a function runs when it is called, so the
+            // conditions around its definition don't guard it
+            "function_definition" | "lambda" => return false,
+            "if_statement" | "elif_clause" => {
+                let condition = parent.child_by_field_name("condition").unwrap();
+                if parent.child_by_field_name("consequence").map(|c| c.id()) == Some(current.id()) {
+                    asserts(condition, true)
+                } else {
+                    matches!(current.kind(), "elif_clause" | "else_clause")
+                        && asserts(condition, false)
+                }
+            }
+            // `[d[k] for k in keys if k in d]`
+            "list_comprehension"
+            | "set_comprehension"
+            | "dictionary_comprehension"
+            | "generator_expression" => {
+                let mut cursor = parent.walk();
+                let conditions: Vec<Node> = parent
+                    .named_children(&mut cursor)
+                    .filter(|child| child.kind() == "if_clause")
+                    .filter_map(|clause| clause.named_child(0))
+                    .collect();
+                parent.child_by_field_name("body").map(|b| b.id()) == Some(current.id())
+                    && conditions
+                        .into_iter()
+                        .any(|condition| asserts(condition, true))
+            }
+            "conditional_expression" => {
+                parent.named_child(0).map(|c| c.id()) == Some(current.id())
+                    && parent
+                        .named_child(1)
+                        .is_some_and(|condition| asserts(condition, true))
+            }
+            "boolean_operator" => {
+                parent.child_by_field_name("operator").unwrap().kind() == "and"
+                    && parent.child_by_field_name("right").map(|c| c.id()) == Some(current.id())
+                    && asserts(parent.child_by_field_name("left").unwrap(), true)
+            }
+            "block" | "module" => {
+                let mut previous = current.prev_named_sibling();
+                let mut found = false;
+                while let Some(statement) = previous {
+                    if statement.kind() == "if_statement"
+                        && asserts(statement.child_by_field_name("condition").unwrap(), false)
+                        && statement
+                            .child_by_field_name("consequence")
+                            .is_some_and(ensures)
+                    {
+                        found = true;
+                        break;
+                    }
+                    previous = statement.prev_named_sibling();
+                }
+                found
+            }
+            _ => false,
+        };
+        if guarded {
+            return true;
+        }
+        current = parent;
+    }
+    false
+}
+
+// Whether `condition` being true implies `key in value` (or, with
+// `contained` false, `key not in value`)
+fn asserts_membership(
+    condition: Node,
+    key: &str,
+    value: &str,
+    contained: bool,
+    source_code: &str,
+) -> bool {
+    match condition.kind() {
+        "parenthesized_expression" => condition
+            .named_child(0)
+            .is_some_and(|inner| asserts_membership(inner, key, value, contained, source_code)),
+        "not_operator" => condition
+            .child_by_field_name("argument")
+            .is_some_and(|inner| asserts_membership(inner, key, value, !contained, source_code)),
+        // Either side of `and` holds when the whole is true; for the negated
+        // form, `k not in d or ...` is what an early exit tests
+        "boolean_operator" => {
+            let operator = condition.child_by_field_name("operator").unwrap().kind();
+            let combines = if contained { "and" } else { "or" };
+            operator == combines
+                && ["left", "right"].iter().any(|side| {
+                    asserts_membership(
+                        condition.child_by_field_name(side).unwrap(),
+                        key,
+                        value,
+                        contained,
+                        source_code,
+                    )
+                })
+        }
+        "comparison_operator" if condition.named_child_count() == 2 => {
+            let operator = condition.child(1).unwrap().kind();
+            let expected = if contained { "in" } else { "not in" };
+            let text = |i| {
+                condition
+                    .named_child(i)
+                    .unwrap()
+                    .utf8_text(source_code.as_bytes())
+                    .unwrap()
+            };
+            operator == expected && text(0) == key && text(1) == value
+        }
+        _ => false,
+    }
+}
+
+// Whether a block run when `key not in value` leaves the key safe to read
+// afterwards, by leaving the enclosing code or by assigning `value[key]`
+fn ensures_key(block: Node, key: &str, value: &str, source_code: &str) -> bool {
+    if ends_in_exit(block) {
+        return true;
+    }
+    assigns_name(block, &format!("{}[{}]", value, key), source_code)
+}
+
+// Whether unpacking into the targets of `pattern`, the left side of an
+// assignment such as `a, b = parts`, may raise ValueError for a wrong number
+// of values. Only values whose length is usually unchecked are considered:
+// variables, subscripts and attributes, and the result of `split()`, unless
+// a literal of the right length is all a variable is assigned, or an
+// enclosing `if len(parts) == 2:` checks the length. Other calls, such as to
+// functions returning a tuple, are assumed to return the right number.
+pub(crate) fn unpacking_may_fail(pattern: Node, source_code: &str) -> bool {
+    let Some(assignment) = pattern
+        .parent()
+        .filter(|parent| parent.kind() == "assignment")
+    else {
+        return false;
+    };
+    if assignment.child_by_field_name("left").map(|left| left.id()) != Some(pattern.id()) {
+        return false;
+    }
+    let Some(value) = assignment.child_by_field_name("right") else {
+        return false;
+    };
+    let mut cursor = pattern.walk();
+    let targets: Vec<Node> = pattern.named_children(&mut cursor).collect();
+    let starred = targets
+        .iter()
+        .any(|target| target.kind() == "list_splat_pattern");
+    let count = targets.len() - usize::from(starred);
+    let fits = |length: usize| length == count || (starred && length >= count);
+
+    let text = |node: Node| node.utf8_text(source_code.as_bytes()).unwrap();
+    let may_fail = match value.kind() {
+        "expression_list" | "tuple" | "list" => {
+            literal_length(value).is_some_and(|length| !fits(length))
+        }
+        "identifier" => {
+            let values = assigned_values(value, text(value), source_code);
+            values.is_empty()
+                || !values
+                    .into_iter()
+                    .all(|assigned| literal_length(assigned).is_some_and(fits))
+        }
+        "subscript" | "attribute" => true,
+        "call" => {
+            let function = value.child_by_field_name("function").unwrap();
+            function.kind() == "attribute"
+                && matches!(
+                    text(function.child_by_field_name("attribute").unwrap()),
+                    "split" | "rsplit" | "splitlines"
+                )
+        }
+        _ => false,
+    };
+    may_fail
+        && !is_guarded_by_condition(
+            assignment,
+            &|condition, holds| asserts_length(condition, text(value), count, holds, source_code),
+            &ends_in_exit,
+        )
+}
+
+// The number of values of a tuple or list display, unless it has `*x`
+fn literal_length(node: Node) -> Option<usize> {
+    if !matches!(node.kind(), "expression_list" | "tuple" | "list") {
+        return None;
+    }
+    let mut cursor = node.walk();
+    let elements: Vec<Node> = node
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() != "comment")
+        .collect();
+    if elements
+        .iter()
+        .any(|element| element.kind() == "list_splat")
+    {
+        return None;
+    }
+    Some(elements.len())
+}
+
+// Whether a condition tests `len(value) == count`, or with `equal` false,
+// `len(value) != count`
+fn asserts_length(
+    condition: Node,
+    value: &str,
+    count: usize,
+    equal: bool,
+    source_code: &str,
+) -> bool {
+    match condition.kind() {
+        "parenthesized_expression" => condition
+            .named_child(0)
+            .is_some_and(|inner| asserts_length(inner, value, count, equal, source_code)),
+        "not_operator" => condition
+            .child_by_field_name("argument")
+            .is_some_and(|inner| asserts_length(inner, value, count, !equal, source_code)),
+        "boolean_operator" => {
+            let operator = condition.child_by_field_name("operator").unwrap().kind();
+            let combines = if equal { "and" } else { "or" };
+            operator == combines
+                && ["left", "right"].iter().any(|side| {
+                    asserts_length(
+                        condition.child_by_field_name(side).unwrap(),
+                        value,
+                        count,
+                        equal,
+                        source_code,
+                    )
+                })
+        }
+        "comparison_operator" if condition.named_child_count() == 2 => {
+            let operator = condition.child(1).unwrap().kind();
+            let expected = if equal { "==" } else { "!=" };
+            let text = |i| {
+                condition
+                    .named_child(i)
+                    .unwrap()
+                    .utf8_text(source_code.as_bytes())
+                    .unwrap()
+            };
+            operator == expected
+                && text(0).replace(' ', "") == format!("len({})", value)
+                && text(1) == count.to_string()
+        }
+        _ => false,
+    }
+}
+
+// Whether a block ends by returning, raising, or skipping the rest of a loop
+fn ends_in_exit(block: Node) -> bool {
+    block
+        .named_children(&mut block.walk())
+        .last()
+        .is_some_and(|last| {
+            matches!(
+                last.kind(),
+                "return_statement" | "raise_statement" | "continue_statement" | "break_statement"
+            )
+        })
+}
+
+// Whether `d[key]` is inside the branch of an `if (v := d.get(key)):` or
+// `if (v := d.get(key)) is not None:` check, which proves the key is present
+fn is_guarded_by_walrus_get(node: Node, source_code: &str, version: PythonVersion) -> bool {
+    if !version.supports_walrus() {
+        return false;
+    }
+    let value = node
+        .child_by_field_name("value")
+        .unwrap()
+        .utf8_text(source_code.as_bytes())
+        .unwrap();
+    let Some(key) = node.child_by_field_name("subscript") else {
+        return false;
+    };
+    let key = key.utf8_text(source_code.as_bytes()).unwrap();
+
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        if matches!(parent.kind(), "if_statement" | "elif_clause")
+            && parent.child_by_field_name("consequence").map(|c| c.id()) == Some(current.id())
+        {
+            let condition = parent.child_by_field_name("condition").unwrap();
+            let mut walruses = Vec::new();
+            collect_asserted_walruses(condition, &mut walruses);
+            for walrus in walruses {
+                let walrus_value = walrus.child_by_field_name("value").unwrap();
+                if is_get_call(walrus_value, value, key, source_code) {
+                    return true;
+                }
+            }
+        }
+        current = parent;
+    }
+    false
+}
+
+// Assignment expressions whose value is known to be truthy or not None when
+// the condition holds
+fn collect_asserted_walruses<'a>(condition: Node<'a>, walruses: &mut Vec<Node<'a>>) {
+    match condition.kind() {
+        "named_expression" => walruses.push(condition),
+        "parenthesized_expression" => {
+            if let Some(inner) = condition.named_child(0) {
+                collect_asserted_walruses(inner, walruses);
+            }
+        }
+        "boolean_operator" => {
+            let operator = condition.child_by_field_name("operator").unwrap();
+            if operator.kind() == "and" {
+                collect_asserted_walruses(condition.child_by_field_name("left").unwrap(), walruses);
+                collect_asserted_walruses(
+                    condition.child_by_field_name("right").unwrap(),
+                    walruses,
+                );
+            }
+        }
+        // `(v := ...) is not None` and `(v := ...) != None`
+        "comparison_operator" if condition.named_child_count() == 2 => {
+            let operator = condition.child(1).unwrap();
+            let right = condition.named_child(1).unwrap();
+            if matches!(operator.kind(), "is not" | "!=") && right.kind() == "none" {
+                collect_asserted_walruses(condition.named_child(0).unwrap(), walruses);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Whether `call` is `<value>.get(<key>, ...)`
+fn is_get_call(call: Node, value: &str, key: &str, source_code: &str) -> bool {
+    if call.kind() != "call" {
+        return false;
+    }
+    let function = call.child_by_field_name("function").unwrap();
+    if function.kind() != "attribute" {
+        return false;
+    }
+    let object = function.child_by_field_name("object").unwrap();
+    let attribute = function.child_by_field_name("attribute").unwrap();
+    let first_argument = call
+        .child_by_field_name("arguments")
+        .and_then(|arguments| arguments.named_child(0));
+    attribute.utf8_text(source_code.as_bytes()).unwrap() == "get"
+        && object.utf8_text(source_code.as_bytes()).unwrap() == value
+        && first_argument.is_some_and(|arg| arg.utf8_text(source_code.as_bytes()).unwrap() == key)
+}
+
+// The exception type an except clause handles, without any `as` binding:
+// `KeyError` for both `except KeyError:` and `except KeyError as e:`.
+// Returns `None` for a bare `except:`.
+fn except_clause_type(clause: Node) -> Option<Node> {
+    let mut cursor = clause.walk();
+    let exception_type = clause
+        .named_children(&mut cursor)
+        .find(|child| !matches!(child.kind(), "block" | "comment"))?;
+    if exception_type.kind() == "as_pattern" {
+        exception_type.named_child(0)
+    } else {
+        Some(exception_type)
+    }
+}
+
+// The name an except clause binds its exception to: `e` for
+// `except KeyError as e:`
+fn except_clause_binding<'a>(clause: Node, source_code: &'a str) -> Option<&'a str> {
+    let mut cursor = clause.walk();
+    let pattern = clause
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "as_pattern")?;
+    let alias = pattern.child_by_field_name("alias")?;
+    Some(alias.utf8_text(source_code.as_bytes()).unwrap())
+}
+
+// The except clause whose body contains the node, within the same function
+fn enclosing_handler(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        match parent.kind() {
+            "except_clause" | "except_group_clause" => return Some(parent),
+            "function_definition" | "lambda" | "class_definition" => return None,
+            _ => current = parent.parent(),
+        }
+    }
+    None
+}
+
+// Whether a raise statement re-raises the exception being handled: a bare
+// `raise`, or `raise e` for the name bound by the enclosing handler
+fn is_reraise(raise_node: Node, source_code: &str) -> bool {
+    let Some(exception) = raise_node.named_child(0) else {
+        return true;
+    };
+    if exception.kind() != "identifier" {
+        return false;
+    }
+    let name = exception.utf8_text(source_code.as_bytes()).unwrap();
+    enclosing_handler(raise_node).and_then(|handler| except_clause_binding(handler, source_code))
+        == Some(name)
+}
+
+// Whether an except clause may re-raise the exception it caught, letting it
+// escape the try statement
+fn handler_reraises(handler: Node, source_code: &str) -> bool {
+    let mut raises = Vec::new();
+    collect_scope_nodes(handler, &["raise_statement"], &mut raises);
+    raises.into_iter().any(|raise_node| {
+        is_reraise(raise_node, source_code)
+            && enclosing_handler(raise_node).map(|h| h.id()) == Some(handler.id())
+    })
+}
+
+// Names of the exceptions an except clause type covers, expanding tuples:
+// `(KeyError, ValueError)` gives both names
+fn handled_exception_names<'a>(exception_type: Node, source_code: &'a str) -> Vec<&'a str> {
+    match exception_type.kind() {
+        "tuple" | "parenthesized_expression" => {
+            let mut cursor = exception_type.walk();
+            let elements: Vec<Node> = exception_type.named_children(&mut cursor).collect();
+            elements
+                .into_iter()
+                .flat_map(|element| handled_exception_names(element, source_code))
+                .collect()
+        }
+        _ => vec![exception_type.utf8_text(source_code.as_bytes()).unwrap()],
+    }
+}
+
+// Exceptions named in `suppress(...)` items of a with statement
+fn suppressed_exceptions<'a>(
+    with_statement: Node,
+    module: &ModuleScope,
+    source_code: &'a str,
+) -> Vec<&'a str> {
+    let mut cursor = with_statement.walk();
+    let items: Vec<Node> = with_statement
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() == "with_clause")
+        .flat_map(|clause| {
+            let mut cursor = clause.walk();
+            clause.named_children(&mut cursor).collect::<Vec<_>>()
+        })
+        .collect();
+    let mut exceptions = Vec::new();
+    for item in items {
+        let Some(call) = item.child_by_field_name("value") else {
+            continue;
+        };
+        let call = if call.kind() == "as_pattern" {
+            call.named_child(0).unwrap()
+        } else {
+            call
+        };
+        if call.kind() != "call" {
+            continue;
+        }
+        let function = call.child_by_field_name("function").unwrap();
+        let function = function.utf8_text(source_code.as_bytes()).unwrap();
+        if module.qualified_name(function) != "contextlib.suppress" {
+            continue;
+        }
+        let arguments = call.child_by_field_name("arguments").unwrap();
+        let mut cursor = arguments.walk();
+        exceptions.extend(
+            arguments
+                .named_children(&mut cursor)
+                .filter(|argument| matches!(argument.kind(), "identifier" | "attribute"))
+                .map(|argument| argument.utf8_text(source_code.as_bytes()).unwrap()),
+        );
+    }
+    exceptions
+}
+
+// Whether an `except <handler>:` clause catches an exception with the given
+// ancestors
+fn handler_catches(
+    handler: &str,
+    qualified_handler: &str,
+    exception: &str,
+    ancestors: &HashSet<String>,
+    module: &ModuleScope,
+) -> bool {
+    exceptions::handler_catches(handler, qualified_handler, ancestors)
+        // A LookupError is reported when the container type is unknown, so
+        // a handler for either kind of lookup is taken to cover it
+        || (exception == "LookupError" && matches!(handler, "KeyError" | "IndexError"))
+        // Exceptions raised in a group are known by those it contains, so a
+        // handler for the whole group is taken to catch them, though not
+        // the same exceptions raised on their own
+        || (module.grouped.contains(exception)
+            && (handler == "BaseExceptionGroup"
+                || (handler == "ExceptionGroup" && ancestors.contains("Exception"))))
+}
+
+// Which handlers guard a node: every enclosing one, or only those inside
+// `within`, to tell what reaches the handlers of a given try statement
+#[derive(Clone, Copy)]
+pub(crate) struct Guard<'a> {
+    version: PythonVersion,
+    // The file, to tell which handlers catch its own exceptions and what
+    // the names of the handlers refer to
+    module: &'a ModuleScope<'a>,
+    within: Option<Node<'a>>,
+}
+
+// Whether an expression is an argument of a call, given by position or by
+// keyword
+fn is_call_argument(node: Node) -> bool {
+    let parent = node
+        .parent()
+        .filter(|parent| parent.kind() == "keyword_argument")
+        .map_or(node.parent(), |keyword| keyword.parent());
+    parent.is_some_and(|parent| parent.kind() == "argument_list")
+}
+
+impl<'a> Guard<'a> {
+    fn all(version: PythonVersion, module: &'a ModuleScope<'a>) -> Self {
+        Guard {
+            version,
+            module,
+            within: None,
+        }
+    }
+
+    fn within(version: PythonVersion, module: &'a ModuleScope<'a>, node: Node<'a>) -> Self {
+        Guard {
+            version,
+            module,
+            within: Some(node),
+        }
+    }
+
+    // Whether a handler catches `exception` when raised at `node`
+    pub(crate) fn catches(&self, node: Node, exception: &str, source_code: &str) -> bool {
+        let version = self.version;
+        // Only worked out once a handler is found, as most nodes have none
+        let ancestors = OnceCell::new();
+        let catches = |handler: &&str| {
+            let ancestors = ancestors.get_or_init(|| {
+                exceptions::exception_ancestors(exception, &self.module.class_bases)
+            });
+            let qualified_handler = self.module.qualified_name(handler);
+            handler_catches(
+                handler,
+                &qualified_handler,
+                exception,
+                ancestors,
+                self.module,
+            )
+        };
+
+        let mut current_node = node;
+        let mut previous_node = node;
+        loop {
+            if self
+                .within
+                .is_some_and(|within| within.id() == current_node.id())
+            {
+                break;
+            }
+            // The body of a function runs when it is called, not where it
+            // is defined, so the handlers around the definition don't cover
+            // it. Lambdas passed to a call, as in `sorted(xs, key=lambda x:
+            // x['k'])`, are taken to be called by it.
+            if current_node.id() != node.id()
+                && (current_node.kind() == "function_definition"
+                    || current_node.kind() == "lambda" && !is_call_argument(current_node))
+            {
+                break;
+            }
+            // Only the try body is covered by the handlers; they don't catch
+            // what the handlers themselves, the `else` block or the
+            // `finally` block raise
+            if current_node.kind() == "try_statement"
+                && current_node.child_by_field_name("body").map(|b| b.id())
+                    == Some(previous_node.id())
+            {
+                // Check except clauses. Only the first matching handler runs, and
+                // if it re-raises, the exception escapes the whole statement.
+                let mut cursor = current_node.walk();
+                if cursor.goto_first_child() {
+                    loop {
+                        let child = cursor.node();
+                        let is_handler = child.kind() == "except_clause"
+                            || (child.kind() == "except_group_clause"
+                                && version.supports_except_star());
+                        // A bare except catches everything
+                        let matches = is_handler
+                            && except_clause_type(child).is_none_or(|exception_type| {
+                                handled_exception_names(exception_type, source_code)
+                                    .iter()
+                                    .any(catches)
+                            });
+                        if matches {
+                            if !handler_reraises(child, source_code) {
+                                return true;
+                            }
+                            break;
+                        }
+                        if !cursor.goto_next_sibling() {
+                            break;
+                        }
+                    }
+                }
+            }
+            // A `finally` block that ends in `return`, `raise`, `break` or
+            // `continue` discards the exception in flight from the body or the
+            // handlers, replacing it with its own outcome
+            if current_node.kind() == "try_statement"
+                && previous_node.kind() != "finally_clause"
+                && current_node
+                    .named_children(&mut current_node.walk())
+                    .filter(|child| child.kind() == "finally_clause")
+                    .filter_map(|clause| {
+                        clause
+                            .named_children(&mut clause.walk())
+                            .find(|child| child.kind() == "block")
+                    })
+                    .any(ends_in_exit)
+            {
+                return true;
+            }
+            // `with contextlib.suppress(...):` handles the listed exceptions in
+            // its body
+            if current_node.kind() == "with_statement"
+                && current_node.child_by_field_name("body").map(|b| b.id())
+                    == Some(previous_node.id())
+                && suppressed_exceptions(current_node, self.module, source_code)
+                    .iter()
+                    .any(catches)
+            {
+                return true;
+            }
+            if let Some(parent) = current_node.parent() {
+                previous_node = current_node;
+                current_node = parent;
+            } else {
+                break;
+            }
+        }
+        false
+    }
+}
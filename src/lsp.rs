@@ -0,0 +1,277 @@
+//! A minimal language server: open documents are kept in memory, with their
+//! syntax trees, and analyzed whenever they are opened or changed. Editors
+//! send just the changed ranges, and the warnings are published as
+//! diagnostics.
+
+use anyhow::{anyhow, Context, Result};
+use lsp_types::notification::{Notification, PublishDiagnostics};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, InitializeResult, NumberOrString, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, ServerInfo, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Uri,
+};
+use pysleuth::{Config, Document, Severity, Warning};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+// JSON-RPC error codes for messages that can't be read and for requests
+// the server doesn't implement
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+pub fn run(config: &Config) -> Result<()> {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut documents: HashMap<String, Document> = HashMap::new();
+
+    while let Some(message) = read_message(&mut input)? {
+        // A bad message is answered and skipped rather than ending the
+        // session, though whether it was a request can't be told
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Skipping malformed message: {:#}", e);
+                respond_error(&mut output, Value::Null, PARSE_ERROR, &format!("{:#}", e))?;
+                continue;
+            }
+        };
+        let method = message["method"].as_str().unwrap_or_default();
+        let id = message.get("id").cloned();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        match method {
+            "initialize" => {
+                let result = InitializeResult {
+                    capabilities: ServerCapabilities {
+                        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                            TextDocumentSyncKind::INCREMENTAL,
+                        )),
+                        ..ServerCapabilities::default()
+                    },
+                    server_info: Some(ServerInfo {
+                        name: env!("CARGO_PKG_NAME").to_string(),
+                        version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                    }),
+                };
+                respond(&mut output, id, serde_json::to_value(result)?)?;
+            }
+            "shutdown" => respond(&mut output, id, Value::Null)?,
+            "exit" => break,
+            "textDocument/didOpen" => {
+                let Some(params) = notification_params::<DidOpenTextDocumentParams>(method, params)
+                else {
+                    continue;
+                };
+                let uri = params.text_document.uri;
+                let document = Document::new(uri.path().as_str(), params.text_document.text);
+                publish(
+                    &mut output,
+                    uri.clone(),
+                    &document.analyze(config),
+                    &document,
+                )?;
+                documents.insert(uri.as_str().to_string(), document);
+            }
+            "textDocument/didChange" => {
+                let Some(params) =
+                    notification_params::<DidChangeTextDocumentParams>(method, params)
+                else {
+                    continue;
+                };
+                let uri = params.text_document.uri;
+                let Some(document) = documents.get_mut(uri.as_str()) else {
+                    continue;
+                };
+                // Changes apply one after another, each to the text left by
+                // the one before; a change without a range replaces the text
+                for change in params.content_changes {
+                    match change.range {
+                        Some(range) => {
+                            let start = offset(document.source(), range.start);
+                            let end = offset(document.source(), range.end);
+                            document.edit(start, end, &change.text);
+                        }
+                        None => document.set_source(change.text),
+                    }
+                }
+                publish(&mut output, uri, &document.analyze(config), document)?;
+            }
+            "textDocument/didClose" => {
+                let Some(params) =
+                    notification_params::<DidCloseTextDocumentParams>(method, params)
+                else {
+                    continue;
+                };
+                let uri = params.text_document.uri;
+                if let Some(document) = documents.remove(uri.as_str()) {
+                    publish(&mut output, uri, &[], &document)?;
+                }
+            }
+            _ => {
+                // Unknown notifications are ignored, unknown requests rejected
+                if let Some(id) = id {
+                    let message = format!("method not found: {}", method);
+                    respond_error(&mut output, id, METHOD_NOT_FOUND, &message)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn publish(
+    output: &mut impl Write,
+    uri: Uri,
+    warnings: &[Warning],
+    document: &Document,
+) -> Result<()> {
+    let source_lines: Vec<&str> = document.source().lines().collect();
+    let diagnostics = warnings
+        .iter()
+        .map(|warning| to_diagnostic(warning, &source_lines))
+        .collect();
+
+    let params = PublishDiagnosticsParams {
+        uri,
+        diagnostics,
+        version: None,
+    };
+    write_message(
+        output,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": PublishDiagnostics::METHOD,
+            "params": params,
+        }),
+    )
+}
+
+fn to_diagnostic(warning: &Warning, source_lines: &[&str]) -> Diagnostic {
+    Diagnostic {
+        range: Range {
+            start: position(warning.line, warning.column, source_lines),
+            end: position(warning.end_line, warning.end_column, source_lines),
+        },
+        severity: Some(match warning.severity {
+            Severity::Hint => DiagnosticSeverity::HINT,
+            Severity::Info => DiagnosticSeverity::INFORMATION,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Error => DiagnosticSeverity::ERROR,
+        }),
+        code: Some(NumberOrString::String(warning.rule.code().to_string())),
+        source: Some(env!("CARGO_PKG_NAME").to_string()),
+        message: warning.message.clone(),
+        ..Diagnostic::default()
+    }
+}
+
+fn position(line: usize, column: usize, source_lines: &[&str]) -> Position {
+    // Warnings count columns in characters, LSP in UTF-16 code units
+    let text = source_lines.get(line - 1).copied().unwrap_or_default();
+    let character: usize = text.chars().take(column - 1).map(char::len_utf16).sum();
+    Position {
+        line: (line - 1) as u32,
+        character: character as u32,
+    }
+}
+
+// The byte offset of an LSP position, the inverse of `position`
+fn offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+// The parameters of a notification, or `None`, once logged, if they aren't
+// what the method takes. Notifications have no response to report it in.
+fn notification_params<T: DeserializeOwned>(method: &str, params: Value) -> Option<T> {
+    serde_json::from_value(params)
+        .map_err(|e| eprintln!("Ignoring malformed {} notification: {}", method, e))
+        .ok()
+}
+
+fn respond(output: &mut impl Write, id: Option<Value>, result: Value) -> Result<()> {
+    write_message(
+        output,
+        &json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+    )
+}
+
+fn respond_error(output: &mut impl Write, id: Value, code: i64, message: &str) -> Result<()> {
+    let error = json!({ "code": code, "message": message });
+    write_message(
+        output,
+        &json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    )
+}
+
+// Read one `Content-Length` framed message, or `None` at end of input. A
+// message that can't be made sense of is an error inside, after which the
+// next one can still be read; failing to read the input is the outer one.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Result<Value>>> {
+    let mut content_length = None;
+    let mut malformed = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                match value.trim().parse::<usize>() {
+                    Ok(length) => content_length = Some(length),
+                    Err(e) => {
+                        malformed =
+                            Some(anyhow!("invalid Content-Length '{}': {}", value.trim(), e))
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(e) = malformed {
+        return Ok(Some(Err(e)));
+    }
+    let Some(content_length) = content_length else {
+        return Ok(Some(Err(anyhow!(
+            "message without a Content-Length header"
+        ))));
+    };
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+    let message = serde_json::from_slice(&content).context("invalid JSON-RPC message");
+    Ok(Some(message))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let content = serde_json::to_string(message)?;
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()?;
+    Ok(())
+}
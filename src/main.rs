@@ -1,409 +1,1674 @@
-use anyhow::Result;
-use clap::{Arg, Command};
+use anyhow::{anyhow, bail, Context, Result};
+use changes::ChangedLines;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use colored::*;
-use std::cell::Cell;
+use format_template::Template;
+use grouping::{Entry, Folding, GroupBy};
+use progress::Progress;
+use pysleuth::baseline::Baseline;
+use pysleuth::cache::Cache;
+use pysleuth::discovery::Discovery;
+use pysleuth::encoding::Encoding;
+use pysleuth::notebook::{self, Notebook};
+use pysleuth::site_packages::SitePackages;
+use pysleuth::stubs::Stubs;
+use pysleuth::timings::Timings;
+use pysleuth::{
+    count_by_rule, exception_flow, has_syntax_errors, module_name, Analyzer, Config,
+    DocstringStyle, ExceptionFlow, Framework, ProjectFile, PythonVersion, Rule, Severity,
+    SourceFile, Warning,
+};
+use serde_json::json;
+use settings::Settings;
+use statistics::Statistics;
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
-use tree_sitter::{Node, Parser};
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
-fn main() -> Result<()> {
+mod changes;
+mod fix;
+mod format_template;
+mod graph;
+mod grouping;
+mod html;
+mod lsp;
+mod progress;
+mod raises_report;
+mod sarif;
+mod settings;
+mod statistics;
+mod tui;
+mod watch;
+mod xml;
+
+const FORMATS: [&str; 8] = [
+    "text",
+    "short",
+    "json",
+    "sarif",
+    "github",
+    "html",
+    "checkstyle",
+    "junit",
+];
+
+// Formats written as a whole once every file is analyzed
+const REPORT_FORMATS: [&str; 5] = ["json", "sarif", "html", "checkstyle", "junit"];
+
+// Exit codes: warnings at or above the `--fail-on` severity were reported,
+// or the analysis itself failed, as with unreadable files or syntax errors
+const EXIT_WARNINGS: u8 = 1;
+const EXIT_FAILURE: u8 = 2;
+
+// How many of the slowest files `--timings` lists
+const SLOWEST_FILES: usize = 10;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
+}
+
+fn run() -> Result<ExitCode> {
+    let matches = command().get_matches();
+    colored::control::set_override(use_color(matches.get_one::<String>("color").unwrap()));
+
+    // Settings from a configuration file fill in the options that aren't
+    // given on the command line
+    let settings = match matches.get_one::<PathBuf>("config") {
+        Some(path) => Settings::load(path)?,
+        None => {
+            let subcommand = matches.subcommand().filter(|(name, _)| *name != "lsp");
+            let dir = settings_dir(subcommand.map_or(&matches, |(_, matches)| matches));
+            Settings::discover(&dir)?.unwrap_or_default()
+        }
+    };
+    let verbose = matches.get_flag("verbose");
+    if verbose {
+        match &settings.path {
+            Some(path) => eprintln!("Using settings from '{}'", path.display()),
+            None => eprintln!("No settings file found"),
+        }
+    }
+    let analyzer = analyzer(&matches, &settings)?;
+    let config = analyzer.config();
+
+    if matches.get_flag("lsp") || matches.subcommand_name() == Some("lsp") {
+        lsp::run(config)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(matches) = matches.subcommand_matches("graph") {
+        let flow = project_flow(matches, &settings, config)?;
+        let graph = match matches.get_one::<String>("format").unwrap().as_str() {
+            "json" => serde_json::to_string_pretty(&flow)?,
+            _ => graph::dot(&flow),
+        };
+        write_output(matches, graph)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(matches) = matches.subcommand_matches("report-raises") {
+        let flow = project_flow(matches, &settings, config)?;
+        let functions = raises_report::public_functions(&flow);
+        let report = match matches.get_one::<String>("format").unwrap().as_str() {
+            "json" => serde_json::to_string_pretty(&functions)?,
+            "markdown" => raises_report::markdown(&functions),
+            _ => raises_report::text(&functions),
+        };
+        write_output(matches, report)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(matches) = matches.subcommand_matches("tui") {
+        let discovery = Discovery::new(
+            &settings.include,
+            &settings.exclude,
+            settings.respect_gitignore,
+        );
+        tui::run(matches, &discovery, &analyzer)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    check(&matches, &settings, &analyzer)
+}
+
+// The command line: the options of a check, and the subcommands
+fn command() -> Command {
     // Fetch metadata from Cargo.toml using env! macros
     let name = env!("CARGO_PKG_NAME");
     let version = env!("CARGO_PKG_VERSION");
     let author = env!("CARGO_PKG_AUTHORS");
     let description = env!("CARGO_PKG_DESCRIPTION");
 
-    let matches = Command::new(name)
+    Command::new(name)
         .version(version)
         .author(author)
         .about(description)
         .arg(
             Arg::new("files")
-                .help("Python files to analyze")
-                .required(true)
+                .help("Python files or directories to analyze, or '-' to read from stdin")
+                .required_unless_present_any(["lsp", "project", "watch"])
                 .num_args(1..)
                 .value_hint(clap::ValueHint::FilePath),
         )
-        .get_matches();
+        .arg(
+            Arg::new("project")
+                .long("project")
+                .help("Analyze every Python file under this directory together, following imports between them")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::DirPath)
+                .conflicts_with("files"),
+        )
+        .arg(
+            Arg::new("include")
+                .long("include")
+                .help("Only analyze files in directories matching this glob, e.g. 'src/**/*.py' (repeatable; default '*.py')")
+                .value_name("GLOB")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .help("Skip files and directories matching this glob, e.g. 'tests' or '*_pb2.py' (repeatable)")
+                .value_name("GLOB")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("respect-gitignore")
+                .long("respect-gitignore")
+                .help("Skip files ignored by .gitignore when searching directories")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .help("Read settings from this file instead of the nearest pywrong.toml or pyproject.toml")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("python-version")
+                .long("python-version")
+                .help("Target Python version (major.minor), or range of versions such as 3.8..3.12, for version-specific checks")
+                .value_name("VERSION")
+                .default_value("3.11")
+                .value_parser(PythonVersion::parse_range),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Output format: colored text with source snippets, one line per warning, a JSON array of warnings, a SARIF log, GitHub Actions annotations, a standalone HTML report, or Checkstyle or JUnit XML")
+                .value_name("FORMAT")
+                .default_value("text")
+                .value_parser(FORMATS),
+        )
+        .arg(
+            Arg::new("format-template")
+                .long("format-template")
+                .help("Print each warning on a line laid out by this template instead of as text, e.g. '{file}:{line}:{col}: {code} [{exceptions}] {message}'; the fields are file, cell, line, col, end_line, end_col, severity, code, rule, exceptions, function and message")
+                .value_name("TEMPLATE")
+                .value_parser(|s: &str| s.parse::<Template>()),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .help("Print only the warnings, one line each, without summaries")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("verbose"),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .help("Show a progress bar on stderr while analyzing, when it is a terminal and the format isn't json or sarif")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("timings")
+                .long("timings")
+                .help("Print the time spent in each phase of the analysis and the slowest files on stderr")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Log the settings used, the files found and how long the analysis took to stderr")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("Write the JSON, SARIF, HTML or XML report to this file instead of stdout")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .help("When to color the output: when it is a terminal and NO_COLOR isn't set, always, or never")
+                .value_name("WHEN")
+                .default_value("auto")
+                .value_parser(["auto", "always", "never"]),
+        )
+        .arg(
+            Arg::new("fail-on")
+                .long("fail-on")
+                .help("Exit with status 1 when a warning of at least this severity is reported")
+                .value_name("SEVERITY")
+                .default_value("warning")
+                .value_parser(|s: &str| s.parse::<Severity>()),
+        )
+        .arg(
+            Arg::new("error")
+                .long("error")
+                .help("Exit with status 1 only when an error is reported, like --fail-on error")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("fail-on"),
+        )
+        .arg(
+            Arg::new("max-warnings")
+                .long("max-warnings")
+                .help("Exit with status 1 only when more than N warnings of at least the --fail-on severity are reported")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("exit-zero")
+                .long("exit-zero")
+                .help("Exit with status 0 even when warnings are reported")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("suggest-fixes")
+                .long("suggest-fixes")
+                .help("Print a suggested fix after each possible KeyError warning")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fix")
+                .long("fix")
+                .help("Rewrite files to apply the suggested fixes, reporting only the warnings left")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fix-dry-run")
+                .long("fix-dry-run")
+                .help("Print the changes --fix would make as a unified diff, without writing them")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("fix"),
+        )
+        .arg(
+            Arg::new("unsafe-fixes")
+                .long("unsafe-fixes")
+                .help("Let --fix and --fix-dry-run also wrap statements in try/except blocks, which changes the structure of the code")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("check-annotations")
+                .long("check-annotations")
+                .help("Use type annotations to report sequence subscripts as IndexError")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strict-asserts")
+                .long("strict-asserts")
+                .help("Treat assert statements as raising AssertionError")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("docstring-style")
+                .long("docstring-style")
+                .help("Read only the Raises sections of this docstring convention: google, numpy or sphinx")
+                .value_name("STYLE")
+                .value_parser(|s: &str| s.parse::<DocstringStyle>()),
+        )
+        .arg(
+            Arg::new("skip-syntax-errors")
+                .long("skip-syntax-errors")
+                .help("Only report syntax errors for files that don't parse")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("public-only")
+                .long("public-only")
+                .help("Only check functions reachable from the module's __all__")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("stubs")
+                .long("stubs")
+                .help("Read .pyi stubs from this directory, such as typeshed's stdlib, to resolve method calls")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::DirPath),
+        )
+        .arg(
+            Arg::new("site-packages")
+                .long("site-packages")
+                .help("Analyze the third-party packages installed in this directory to learn what calls into them raise (default: the site-packages of the virtualenv in VIRTUAL_ENV)")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::DirPath),
+        )
+        .arg(
+            Arg::new("baseline")
+                .long("baseline")
+                .help("Suppress warnings recorded in this baseline file")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("update-baseline")
+                .long("update-baseline")
+                .visible_alias("write-baseline")
+                .help("Write the current warnings to this baseline file instead of printing them")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath)
+                .conflicts_with_all(["baseline", "fix", "fix-dry-run"]),
+        )
+        .arg(
+            Arg::new("diff")
+                .long("diff")
+                .help("Only report warnings on lines changed since this git revision, or added by a patch read from stdin with '-'")
+                .value_name("BASE"),
+        )
+        .arg(
+            Arg::new("group-by")
+                .long("group-by")
+                .help("Print the text warnings under a header for each file, function or rule")
+                .value_name("GROUP")
+                .value_parser(|s: &str| s.parse::<GroupBy>()),
+        )
+        .arg(
+            Arg::new("max-per-function")
+                .long("max-per-function")
+                .help("Print at most N text warnings per function, folding the rest into a count; reports and the exit status still cover them all")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("dedup")
+                .long("dedup")
+                .help("Print only the first text warning of a function for an exception raised at the same place, folding the repeats into a count")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .help("Follow each warning on a call with the chain of calls leading to where the exception is raised")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("statistics")
+                .long("statistics")
+                .help("Print totals by rule, exception and file after the warnings, listing the N files with the most (default: 10); with --format json, output an object with the warnings and the totals")
+                .value_name("N")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("10")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .help("Analyze this many files at once (default: the number of CPUs)")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            Arg::new("cache-dir")
+                .long("cache-dir")
+                .help("Keep the results for unchanged files in this directory (default: the platform's cache directory)")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::DirPath),
+        )
+        .arg(
+            Arg::new("no-cache")
+                .long("no-cache")
+                .help("Analyze every file, without reading or writing cached results")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("cache-dir"),
+        )
+        .arg(
+            Arg::new("stdin-filename")
+                .long("stdin-filename")
+                .help("Filename to report for source read from stdin")
+                .value_name("PATH")
+                .value_hint(clap::ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("lsp")
+                .long("lsp")
+                .help("Run as a language server over stdin/stdout, like the lsp command")
+                .hide(true)
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["files", "project", "baseline", "update-baseline"]),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("Analyze this directory and re-analyze files as they are saved, until interrupted; the directory is polled, not watched with a filesystem notifier")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::DirPath)
+                .conflicts_with_all(["files", "project", "lsp", "update-baseline", "fix", "fix-dry-run"]),
+        )
+        .arg(
+            Arg::new("swallowing-decorator")
+                .long("swallowing-decorator")
+                .help("Treat functions with this decorator, e.g. retry, as handling their own exceptions (repeatable)")
+                .value_name("NAME")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("entry-points")
+                .long("entry-points")
+                .help("Only report exceptions escaping the program's entry points: the __main__ block, module-level functions named main and those given with --entry-point")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("entry-point")
+                .long("entry-point")
+                .help("Take this function, e.g. cli.run, as an entry point; implies --entry-points (repeatable)")
+                .value_name("NAME")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("framework")
+                .long("framework")
+                .help("Recognize the idioms of this web framework, django or flask: what reading request parameters raises and, with --entry-points, views as entry points (repeatable)")
+                .value_name("NAME")
+                .value_parser(|s: &str| s.parse::<Framework>())
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("safe-function")
+                .long("safe-function")
+                .help("Assume calls to this function, e.g. log_and_ignore, never raise (repeatable)")
+                .value_name("NAME")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("allow-swallowed")
+                .long("allow-swallowed")
+                .help("Allow handlers to silently ignore this exception, e.g. FileNotFoundError (repeatable)")
+                .value_name("EXCEPTION")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("accept")
+                .long("accept")
+                .help("Don't warn about this exception propagating, e.g. AppError, or only from matching functions, e.g. 'validate_*:ValueError' (repeatable)")
+                .value_name("[FUNCTION:]EXCEPTION")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("required-env-var")
+                .long("required-env-var")
+                .help("Treat this environment variable, e.g. DATABASE_URL, as checked at startup (repeatable)")
+                .value_name("NAME")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("higher-order")
+                .long("higher-order")
+                .help("Also treat this function, e.g. run_later, as calling the functions passed to it (repeatable)")
+                .value_name("NAME")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("severity")
+                .long("severity")
+                .help("Override the severity of a rule, e.g. PW002=error; one of error, warning, info or hint (repeatable)")
+                .value_name("RULE=SEVERITY")
+                .action(ArgAction::Append)
+                .value_parser(parse_severity_override),
+        )
+        .arg(
+            Arg::new("select")
+                .long("select")
+                .help("Only check these rules, by code, code prefix or name, e.g. PW001 or unhandled-call (repeatable)")
+                .value_name("RULE")
+                .action(ArgAction::Append)
+                .value_parser(Rule::select),
+        )
+        .arg(
+            Arg::new("ignore")
+                .long("ignore")
+                .visible_alias("disable")
+                .help("Don't check these rules, by code, code prefix or name, e.g. PW002 (repeatable)")
+                .value_name("RULE")
+                .action(ArgAction::Append)
+                .value_parser(Rule::select),
+        )
+        .subcommand(
+            Command::new("lsp")
+                .about("Run as a language server over stdin/stdout, publishing warnings as diagnostics"),
+        )
+        .subcommand(flow_command(
+            Command::new("graph")
+                .about("Print the call graph, with the exceptions flowing along each call"),
+            "Output format: a Graphviz DOT graph, or JSON",
+            &["dot", "json"],
+        ))
+        .subcommand(flow_command(
+            Command::new("report-raises").about(
+                "List the exceptions each public function may raise, including those of the functions it calls",
+            ),
+            "Output format: text, JSON or a Markdown table",
+            &["text", "json", "markdown"],
+        ))
+        .subcommand(
+            input_args(Command::new("tui").about(
+                "Triage warnings in a terminal UI: browse them by file, follow the calls exceptions come through and suppress them",
+            ))
+            .arg(
+                Arg::new("baseline")
+                    .long("baseline")
+                    .help("Leave out the warnings recorded in this baseline file, and record those suppressed with b in it")
+                    .value_name("FILE")
+                    .value_parser(clap::value_parser!(PathBuf)),
+            ),
+        )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+}
+
+// Whether the option was given on the command line, so it overrides the
+// settings, rather than left at its default
+fn from_command_line(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+// The values of a repeatable option, or the configured ones when it isn't
+// given on the command line
+fn strings(matches: &ArgMatches, id: &str, configured: &[String]) -> Vec<String> {
+    if from_command_line(matches, id) {
+        matches
+            .get_many::<String>(id)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    } else {
+        configured.to_vec()
+    }
+}
+
+// Like `strings`, for options naming rules
+fn rules(matches: &ArgMatches, id: &str, configured: &[Rule]) -> Vec<Rule> {
+    if from_command_line(matches, id) {
+        matches
+            .get_many::<Vec<Rule>>(id)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .collect()
+    } else {
+        configured.to_vec()
+    }
+}
+
+// The configuration given by the options and settings, and an analyzer
+// using it
+fn analyzer(matches: &ArgMatches, settings: &Settings) -> Result<Analyzer> {
+    let verbose = matches.get_flag("verbose");
+    let from_command_line = |id: &str| from_command_line(matches, id);
+    let strings = |id: &str, configured: &[String]| strings(matches, id, configured);
+    let rules = |id: &str, configured: &[Rule]| rules(matches, id, configured);
+    let mut enabled_rules: HashSet<Rule> = match &settings.select {
+        _ if from_command_line("select") => rules("select", &[]).into_iter().collect(),
+        Some(selected) => selected.iter().copied().collect(),
+        None => Rule::ALL
+            .into_iter()
+            .filter(|rule| rule.is_default())
+            .collect(),
+    };
+    for rule in rules("ignore", &settings.ignore) {
+        enabled_rules.remove(&rule);
+    }
+
+    let mut higher_order_functions = Config::default().higher_order_functions;
+    higher_order_functions.extend(strings("higher-order", &settings.higher_order));
+
+    // Severities from the command line override those from the settings
+    let mut severities = settings.severities.clone();
+    for (rules, severity) in matches
+        .get_many::<(Vec<Rule>, Severity)>("severity")
+        .into_iter()
+        .flatten()
+    {
+        for rule in rules {
+            severities.insert(*rule, *severity);
+        }
+    }
+
+    let (python_version, newest_python_version) = match settings.python_version {
+        Some(versions) if !from_command_line("python-version") => versions,
+        _ => *matches
+            .get_one::<(PythonVersion, PythonVersion)>("python-version")
+            .unwrap(),
+    };
+    let stubs = matches
+        .get_one::<PathBuf>("stubs")
+        .or(settings.stubs.as_ref());
+    let site_packages = matches
+        .get_one::<PathBuf>("site-packages")
+        .or(settings.site_packages.as_ref())
+        .cloned()
+        .or_else(SitePackages::detect);
+
+    // Acceptable exceptions are qualified by a function pattern when given
+    // as `PATTERN:EXCEPTION`
+    let mut acceptable_exceptions = HashSet::new();
+    let mut acceptable_by_function = HashSet::new();
+    for accepted in strings("accept", &settings.accept) {
+        match accepted.rsplit_once(':') {
+            Some((pattern, exception)) => {
+                acceptable_by_function.insert((pattern.to_string(), exception.to_string()));
+            }
+            None => {
+                acceptable_exceptions.insert(accepted);
+            }
+        }
+    }
+
+    let config = Config {
+        python_version,
+        newest_python_version: Some(newest_python_version),
+        suggest_fixes: matches.get_flag("suggest-fixes")
+            || settings.suggest_fixes
+            || matches.get_flag("fix")
+            || matches.get_flag("fix-dry-run"),
+        enabled_rules,
+        check_annotations: matches.get_flag("check-annotations") || settings.check_annotations,
+        strict_asserts: matches.get_flag("strict-asserts") || settings.strict_asserts,
+        skip_syntax_errors: matches.get_flag("skip-syntax-errors") || settings.skip_syntax_errors,
+        public_only: matches.get_flag("public-only") || settings.public_only,
+        stubs: match stubs {
+            Some(dir) => Some(Stubs::load(dir)?),
+            None => None,
+        },
+        site_packages: match &site_packages {
+            Some(dir) => Some(SitePackages::new(dir)?),
+            None => None,
+        },
+        swallowing_decorators: strings("swallowing-decorator", &settings.swallowing_decorators)
+            .into_iter()
+            .collect(),
+        higher_order_functions,
+        safe_functions: strings("safe-function", &settings.safe_functions)
+            .into_iter()
+            .collect(),
+        entry_points: match &settings.entry_points {
+            _ if from_command_line("entry-point") => {
+                Some(strings("entry-point", &[]).into_iter().collect())
+            }
+            _ if matches.get_flag("entry-points") => {
+                Some(settings.entry_points.iter().flatten().cloned().collect())
+            }
+            Some(names) => Some(names.iter().cloned().collect()),
+            None => None,
+        },
+        frameworks: match &settings.frameworks {
+            _ if from_command_line("framework") => matches
+                .get_many::<Framework>("framework")
+                .into_iter()
+                .flatten()
+                .copied()
+                .collect(),
+            frameworks => frameworks.clone(),
+        },
+        docstring_style: match settings.docstring_style {
+            Some(style) if !from_command_line("docstring-style") => Some(style),
+            _ => matches
+                .get_one::<DocstringStyle>("docstring-style")
+                .copied(),
+        },
+        allowed_swallowed: strings("allow-swallowed", &settings.allow_swallowed)
+            .into_iter()
+            .collect(),
+        acceptable_exceptions,
+        acceptable_by_function,
+        required_env_vars: strings("required-env-var", &settings.required_env_vars)
+            .into_iter()
+            .collect(),
+        severities,
+        ..Config::default()
+    };
+    let mut analyzer = Analyzer::new(config);
+    if let Some(jobs) = matches.get_one::<u32>("jobs") {
+        analyzer = analyzer.with_jobs(*jobs as usize);
+    }
+    if matches.get_flag("timings") {
+        analyzer = analyzer.with_timings();
+    }
+    let cache_dir = match matches.get_one::<PathBuf>("cache-dir") {
+        _ if matches.get_flag("no-cache") => None,
+        Some(dir) => Some(dir.clone()),
+        None => Cache::default_dir(),
+    };
+    if verbose {
+        match &cache_dir {
+            Some(dir) => eprintln!("Caching results in '{}'", dir.display()),
+            None => eprintln!("Not caching results"),
+        }
+    }
+    if let Some(dir) = cache_dir {
+        analyzer = analyzer.with_cache(&dir);
+    }
+    let config = analyzer.config();
+    if verbose {
+        let mut rules: Vec<&Rule> = config.enabled_rules.iter().collect();
+        rules.sort();
+        let rules: Vec<String> = rules.iter().map(|rule| rule.to_string()).collect();
+        match config.newest_python_version {
+            Some(newest) if newest != config.python_version => {
+                eprintln!("Python versions: {}..{}", config.python_version, newest)
+            }
+            _ => eprintln!("Python version: {}", config.python_version),
+        }
+        eprintln!("Enabled rules: {}", rules.join(", "));
+        if let Some(stubs) = stubs {
+            eprintln!("Using stubs from '{}'", stubs.display());
+        }
+        if let Some(site_packages) = &config.site_packages {
+            eprintln!(
+                "Using packages installed in '{}'",
+                site_packages.dir().display()
+            );
+        }
+    }
+
+    Ok(analyzer)
+}
 
+// Check the files given, printing or writing out the warnings, and exit
+// with a status telling whether any should fail the run
+fn check(matches: &ArgMatches, settings: &Settings, analyzer: &Analyzer) -> Result<ExitCode> {
+    let config = analyzer.config();
+    let quiet = matches.get_flag("quiet");
+    let verbose = matches.get_flag("verbose");
+    let from_command_line = |id: &str| from_command_line(matches, id);
+    let strings = |id: &str, configured: &[String]| strings(matches, id, configured);
     // Get the list of files to analyze
     let files: Vec<&str> = matches
         .get_many::<String>("files")
-        .unwrap()
+        .into_iter()
+        .flatten()
         .map(|s| s.as_str())
         .collect();
 
-    // Process each file
-    for filename in files {
-        match fs::read_to_string(filename) {
-            Ok(source_code) => {
-                if let Err(e) = analyze_file(filename, &source_code) {
-                    eprintln!("Error analyzing file '{}': {}", filename, e);
-                }
-            }
-            Err(e) => {
-                eprintln!("Error reading file '{}': {}", filename, e);
-            }
+    let baseline = match matches.get_one::<PathBuf>("baseline") {
+        Some(path) => Baseline::load(path)?,
+        None => Baseline::default(),
+    };
+    let update_baseline = matches.get_one::<PathBuf>("update-baseline");
+    let changes = match matches.get_one::<String>("diff") {
+        Some(base) if base == "-" && files.contains(&"-") => {
+            bail!("--diff - and source read from stdin can't both use stdin");
         }
+        Some(base) => Some(ChangedLines::load(base)?),
+        None => None,
+    };
+    let format = match &settings.format {
+        Some(format) if !from_command_line("format") => format.as_str(),
+        _ => matches.get_one::<String>("format").unwrap().as_str(),
+    };
+    // Quiet text output is one line per warning
+    let format = if quiet && format == "text" {
+        "short"
+    } else {
+        format
+    };
+    if !FORMATS.contains(&format) {
+        bail!("unknown format '{}' in settings", format);
+    }
+    let template = match &settings.format_template {
+        Some(template) if !from_command_line("format-template") => {
+            Some(template.parse::<Template>().map_err(|e| anyhow!(e))?)
+        }
+        _ => matches.get_one::<Template>("format-template").cloned(),
+    };
+    if template.is_some() && !matches!(format, "text" | "short") {
+        bail!(
+            "a format template lays out text output, so it can't be used with --format {}",
+            format
+        );
+    }
+    let output = matches.get_one::<PathBuf>("output");
+    if output.is_some() && !REPORT_FORMATS.contains(&format) {
+        bail!("--output needs --format json, sarif, html, checkstyle or junit");
     }
 
-    Ok(())
-}
-
-fn analyze_file(filename: &str, source_code: &str) -> Result<()> {
-    // Initialize the parser with the Python grammar
-    let language = tree_sitter_python::LANGUAGE;
-    let mut parser = Parser::new();
-    parser
-        .set_language(&language.into())
-        .expect("Error loading Python grammar");
-
-    // Parse the source code
-    let tree = parser.parse(source_code, None).unwrap();
-
-    // Collect all functions
-    let mut functions = HashMap::new();
-    collect_functions(tree.root_node(), &mut functions, source_code);
-
-    // Include the module-level code as a function
-    functions.insert(
-        "<module>".to_string(),
-        FunctionInfo {
-            node: tree.root_node(),
-            may_raise: HashSet::new(),
-            reported_in_function: Cell::new(false),
-        },
+    // Read each file
+    let stdin_filename = matches
+        .get_one::<String>("stdin-filename")
+        .map_or("<stdin>", |s| s.as_str());
+    let project_root = matches.get_one::<PathBuf>("project");
+    let discovery = Discovery::new(
+        &strings("include", &settings.include),
+        &strings("exclude", &settings.exclude),
+        matches.get_flag("respect-gitignore") || settings.respect_gitignore,
     );
+    if let Some(dir) = matches.get_one::<PathBuf>("watch") {
+        watch::run(dir, &discovery, analyzer, &baseline)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let paths = match project_root {
+        Some(root) => discovery.files(std::slice::from_ref(root))?,
+        None => discovery.files(&files.iter().map(PathBuf::from).collect::<Vec<_>>())?,
+    };
+    let fix = matches.get_flag("fix");
+    let fix_dry_run = matches.get_flag("fix-dry-run");
+    let unsafe_fixes = matches.get_flag("unsafe-fixes");
+    if fix && paths.iter().any(|path| path.as_os_str() == "-") {
+        bail!("--fix can't rewrite source read from stdin; use --fix-dry-run to print the changes");
+    }
+    if verbose {
+        eprintln!(
+            "Found {} file(s) to analyze on up to {} thread(s)",
+            paths.len(),
+            analyzer.jobs()
+        );
+    }
+    let (sources, modules, unreadable) =
+        read_files(&paths, project_root, stdin_filename, analyzer, verbose);
 
-    // Determine exceptions each function may raise
-    determine_exceptions(&mut functions, source_code);
+    // Analyze the files one by one, or all together as a project. The
+    // progress bar would get in the way of verbose logs and of reports
+    // read by machines.
+    let progress = (matches.get_flag("progress")
+        && !verbose
+        && io::stderr().is_terminal()
+        && !matches!(format, "json" | "sarif"))
+    .then(|| Progress::new(sources.len()));
+    let started = Instant::now();
+    let warnings: Vec<Vec<Warning>> = match project_root {
+        Some(_) => {
+            if let Some(progress) = &progress {
+                progress.status(&format!(
+                    "Analyzing {} file(s) as a project...",
+                    sources.len()
+                ));
+            }
+            analyzer.analyze_project(&sources, &modules)
+        }
+        None => match &progress {
+            Some(progress) => analyzer
+                .analyze_files_with_progress(&sources, &|filename| progress.file_done(filename)),
+            None => analyzer.analyze_files(&sources),
+        },
+    };
+    if let Some(progress) = &progress {
+        progress.finish();
+    }
 
-    // Analyze each function
-    let mut reported_calls = HashSet::new();
-    for func_name in functions.keys() {
-        analyze_function(
-            func_name,
-            functions[func_name].node,
-            &functions,
-            source_code,
-            filename,
-            &mut reported_calls,
+    if verbose {
+        eprintln!(
+            "Analyzed {} file(s) in {:.2?}",
+            sources.len(),
+            started.elapsed()
+        );
+    }
+    if let Some(timings) = analyzer.timings() {
+        print_timings(
+            timings,
+            started.elapsed(),
+            analyzer.jobs(),
+            project_root.is_some(),
         );
     }
 
-    Ok(())
-}
-
-struct FunctionInfo<'a> {
-    node: Node<'a>,
-    may_raise: HashSet<String>,
-    reported_in_function: Cell<bool>,
-}
+    let mut all_warnings = Vec::new();
+    let mut reported = Vec::new();
+    let mut fixed_count = 0;
+    let mut filtered = 0;
+    // The sources as `--fix` rewrote them, which the warnings left point into
+    let mut fixed_sources: HashMap<&str, String> = HashMap::new();
+    for (file, mut warnings) in sources.iter().zip(warnings) {
+        let SourceFile {
+            filename,
+            source_code,
+            encoding,
+            notebook,
+        } = file;
+        if update_baseline.is_some() {
+            all_warnings.extend(warnings.into_iter().map(|w| {
+                let source_code = file.source_of(&w);
+                (w, source_code)
+            }));
+            continue;
+        }
 
-struct FunctionCall<'a> {
-    name: String,
-    node: Node<'a>,
-}
+        let count = warnings.len();
+        warnings.retain(|warning| {
+            !baseline.contains(warning, file.source_of(warning))
+                && changes
+                    .as_ref()
+                    .is_none_or(|changes| changes.contains(warning))
+        });
+        filtered += count - warnings.len();
+        // The fixes of notebooks would have to be written back into cells
+        if (fix || fix_dry_run) && notebook.is_none() {
+            let (fixed_source, fixed) = fix::apply(source_code, &warnings, unsafe_fixes);
+            fixed_count += fixed.iter().filter(|&&fixed| fixed).count();
+            if fix_dry_run {
+                print!(
+                    "{}",
+                    fix::unified_diff(filename, source_code, &fixed_source)
+                );
+                to_file_offsets(&mut warnings, source_code, *encoding);
+                reported.extend(warnings);
+                continue;
+            }
+            if fixed_source != *source_code {
+                fs::write(filename, encoding.encode(&fixed_source)?)
+                    .with_context(|| format!("failed to write '{}'", filename))?;
+            }
+            fix::relocate(&mut warnings, &fixed, source_code, &fixed_source);
+            to_file_offsets(&mut warnings, &fixed_source, *encoding);
+            reported.extend(warnings);
+            fixed_sources.insert(filename, fixed_source);
+            continue;
+        }
 
-fn collect_functions<'a>(
-    node: Node<'a>,
-    functions: &mut HashMap<String, FunctionInfo<'a>>,
-    source_code: &str,
-) {
-    let mut cursor = node.walk();
-    if node.kind() == "function_definition" {
-        let name_node = node.child_by_field_name("name").unwrap();
-        let name = name_node
-            .utf8_text(source_code.as_bytes())
-            .unwrap()
-            .to_string();
-        functions.insert(
-            name.clone(),
-            FunctionInfo {
-                node,
-                may_raise: HashSet::new(),
-                reported_in_function: Cell::new(false),
-            },
-        );
+        to_file_offsets(&mut warnings, source_code, *encoding);
+        reported.extend(warnings);
     }
 
-    // Traverse child nodes
-    if cursor.goto_first_child() {
-        loop {
-            let child = cursor.node();
-            collect_functions(child, functions, source_code);
-            if !cursor.goto_next_sibling() {
-                break;
-            }
+    // Text output is printed once every file is done, so it can be grouped
+    if !fix_dry_run && matches!(format, "text" | "short" | "github") {
+        let mut source_lines = SourceLines::default();
+        for file in &sources {
+            let source = fixed_sources
+                .get(file.filename.as_str())
+                .unwrap_or(&file.source_code);
+            source_lines.add(&file.filename, source, file.notebook.as_ref());
         }
+        print_text(
+            matches,
+            settings,
+            format,
+            template.as_ref(),
+            &reported,
+            &source_lines,
+        )?;
     }
-}
 
-fn collect_function_calls<'a>(
-    node: Node<'a>,
-    calls: &mut Vec<FunctionCall<'a>>,
-    source_code: &str,
-) {
-    let mut cursor = node.walk();
-    if node.kind() == "call" {
-        if let Some(function_node) = node.child_by_field_name("function") {
-            let name = function_node
-                .utf8_text(source_code.as_bytes())
-                .unwrap()
-                .to_string();
-            calls.push(FunctionCall { name, node });
+    let statistics = matches.get_one::<usize>("statistics").map(|top| {
+        let parse_failures = sources
+            .iter()
+            .filter(|file| has_syntax_errors(&file.source_code))
+            .count();
+        Statistics::new(
+            sources.len() + unreadable,
+            unreadable + parse_failures,
+            &reported,
+            *top,
+        )
+    });
+    let report = match format {
+        // The diff is the output, and the warnings stay until it is applied
+        _ if fix_dry_run => None,
+        _ => report(
+            format,
+            &reported,
+            statistics.as_ref(),
+            &sources,
+            &fixed_sources,
+            config,
+        )?,
+    };
+    if let Some(report) = report {
+        match output {
+            Some(path) => fs::write(path, report + "\n")
+                .with_context(|| format!("failed to write '{}'", path.display()))?,
+            None => println!("{}", report),
         }
+    } else if !fix_dry_run && !quiet && matches!(format, "text" | "short") && !reported.is_empty() {
+        let counts: Vec<String> = count_by_rule(&reported)
+            .into_iter()
+            .map(|(rule, count)| format!("{}: {}", rule, count))
+            .collect();
+        println!(
+            "Found {} warning(s) ({})",
+            reported.len(),
+            counts.join(", ")
+        );
+    }
+    let fail_on = match settings.fail_on {
+        _ if matches.get_flag("error") => Severity::Error,
+        Some(severity) if !from_command_line("fail-on") => severity,
+        _ => *matches.get_one::<Severity>("fail-on").unwrap(),
+    };
+    let failing = reported
+        .iter()
+        .filter(|warning| warning.severity >= fail_on)
+        .count();
+    let max_warnings = matches
+        .get_one::<usize>("max-warnings")
+        .copied()
+        .or(settings.max_warnings);
+    if let Some(budget) = max_warnings.filter(|_| !quiet && !fix_dry_run) {
+        // Only the warnings that would fail the run count against it
+        if matches!(format, "text" | "short") {
+            let status = if failing > budget {
+                format!("{} over", failing - budget).red().bold()
+            } else {
+                "within budget".green()
+            };
+            println!(
+                "Budget: {} of {} warning(s) allowed ({})",
+                failing, budget, status
+            );
+        }
+    }
+    if let Some(statistics) = statistics.filter(|_| matches!(format, "text" | "short")) {
+        statistics.print();
     }
 
-    // Traverse child nodes
-    if cursor.goto_first_child() {
-        loop {
-            let child = cursor.node();
-            collect_function_calls(child, calls, source_code);
-            if !cursor.goto_next_sibling() {
-                break;
-            }
+    if verbose && (matches.contains_id("baseline") || changes.is_some()) {
+        eprintln!(
+            "Left out {} warning(s) in the baseline or outside the changed lines",
+            filtered
+        );
+    }
+    if quiet {
+        // Only the warnings are printed
+    } else if fix {
+        eprintln!("Fixed {} warning(s)", fixed_count);
+    } else if fix_dry_run {
+        eprintln!("Would fix {} warning(s)", fixed_count);
+    }
+
+    if let Some(path) = update_baseline {
+        Baseline::write(path, &all_warnings)?;
+        if !quiet {
+            eprintln!(
+                "Wrote {} warning(s) to baseline '{}'",
+                all_warnings.len(),
+                path.display()
+            );
         }
     }
+
+    let syntax_errors = reported
+        .iter()
+        .any(|warning| warning.rule == Rule::SyntaxError);
+    if unreadable > 0 || syntax_errors {
+        Ok(ExitCode::from(EXIT_FAILURE))
+    } else if !matches.get_flag("exit-zero")
+        && update_baseline.is_none()
+        && failing > max_warnings.unwrap_or(0)
+    {
+        Ok(ExitCode::from(EXIT_WARNINGS))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
 }
 
-fn determine_exceptions(functions: &mut HashMap<String, FunctionInfo<'_>>, source_code: &str) {
-    let function_names: Vec<String> = functions.keys().cloned().collect();
-    let mut changed = true;
-    while changed {
-        changed = false;
-        for func_name in &function_names {
-            let mut new_exceptions = HashSet::new();
-
-            // Use an immutable reference to `func_info`
-            let func_info = &functions[func_name];
-
-            // Collect exceptions from unguarded dict accesses in the function
-            let mut unguarded_accesses = Vec::new();
-            find_unguarded_dict_accesses(func_info.node, &mut unguarded_accesses, source_code);
-            for access_node in unguarded_accesses {
-                if !is_within_keyerror_try_except(access_node, source_code) {
-                    new_exceptions.insert("KeyError".to_string());
-                }
+// Print the warnings as text, one line each or with source snippets, or as
+// GitHub annotations, grouped and folded as the options say
+fn print_text(
+    matches: &ArgMatches,
+    settings: &Settings,
+    format: &str,
+    template: Option<&Template>,
+    warnings: &[Warning],
+    source_lines: &SourceLines,
+) -> Result<()> {
+    let quiet = matches.get_flag("quiet");
+    let explain = matches.get_flag("explain");
+    let group_by = match &settings.group_by {
+        Some(group_by) if !from_command_line(matches, "group-by") => {
+            Some(group_by.parse().map_err(|e: String| anyhow!(e))?)
+        }
+        _ => matches.get_one::<GroupBy>("group-by").copied(),
+    };
+    let folding = Folding {
+        max_per_function: matches
+            .get_one::<usize>("max-per-function")
+            .copied()
+            .or(settings.max_per_function),
+        dedup: matches.get_flag("dedup") || settings.dedup,
+    };
+    let entries = match format {
+        // Annotations go on the lines they are about, so all are kept
+        "github" => grouping::arrange(warnings, None, Folding::default()),
+        _ => grouping::arrange(warnings, group_by, folding),
+    };
+    for entry in entries {
+        match entry {
+            Entry::Header(name, count) if !quiet => {
+                println!("{} ({} warning(s))", name.bold(), count)
             }
-
-            // Collect exceptions from called functions
-            let mut calls = Vec::new();
-            collect_function_calls(func_info.node, &mut calls, source_code);
-            for call in calls {
-                if let Some(called_func) = functions.get(&call.name) {
-                    let exceptions = &called_func.may_raise;
-                    if !exceptions.is_empty()
-                        && !is_within_keyerror_try_except(call.node, source_code)
-                    {
-                        new_exceptions.extend(exceptions.clone());
+            Entry::Header(..) => {}
+            Entry::Warning(warning) => match (format, template) {
+                (_, Some(template)) => println!("{}", template.render(warning)),
+                ("text", None) => print_warning(warning, source_lines, explain),
+                ("short", None) => {
+                    print_short_warning(warning);
+                    if explain {
+                        print_explanation(warning, source_lines);
                     }
                 }
-            }
-
-            // Now, limit the mutable borrow of `func_info` to this block
-            {
-                let func_info_mut = functions.get_mut(func_name).unwrap();
-
-                // Check if the exceptions set has changed
-                if !new_exceptions.is_subset(&func_info_mut.may_raise) {
-                    func_info_mut.may_raise.extend(new_exceptions);
-                    changed = true;
+                _ => print_github_annotation(warning),
+            },
+            Entry::Folded(function, count) => {
+                let note = format!("… and {} more in function '{}'", count, function);
+                match format {
+                    "text" => println!("{}\n", note.dimmed()),
+                    _ => println!("{}", note.dimmed()),
                 }
-            } // Mutable borrow ends here
+            }
         }
     }
+    Ok(())
 }
 
-fn analyze_function<'a>(
-    function_name: &str,
-    _function_node: Node<'a>,
-    functions: &HashMap<String, FunctionInfo<'a>>,
-    source_code: &str,
-    filename: &str,
-    reported_calls: &mut HashSet<(usize, String)>,
-) {
-    let func_info = functions.get(function_name).unwrap();
-
-    // Split source code into lines
-    let source_lines: Vec<&str> = source_code.lines().collect();
-
-    // Check for unguarded dict accesses within the function
-    let mut unguarded_accesses = Vec::new();
-    find_unguarded_dict_accesses(func_info.node, &mut unguarded_accesses, source_code);
-
-    if !unguarded_accesses.is_empty() {
-        // Report warning for unguarded dict access
-        for access_node in unguarded_accesses {
-            if !is_within_keyerror_try_except(access_node, source_code) {
-                let start_position = access_node.start_position();
-                let end_position = access_node.end_position();
-                let line_number = start_position.row + 1;
-                let column_start = start_position.column;
-                let column_end = end_position.column;
-
-                let line = source_lines.get(start_position.row).unwrap_or(&"");
-                if function_name != "<module>" {
-                    println!(
-                        "{}:{}:{}: {} Possible KeyError in function '{}'",
+// The report of the warnings in one of the `REPORT_FORMATS`, or nothing for
+// the text formats. The html report shows `fixed_sources` in place of the
+// sources `--fix` rewrote.
+fn report(
+    format: &str,
+    reported: &[Warning],
+    statistics: Option<&Statistics>,
+    sources: &[SourceFile],
+    fixed_sources: &HashMap<&str, String>,
+    config: &Config,
+) -> Result<Option<String>> {
+    Ok(match format {
+        "json" => Some(match statistics {
+            Some(statistics) => serde_json::to_string_pretty(&json!({
+                "warnings": reported,
+                "statistics": statistics,
+            }))?,
+            None => serde_json::to_string_pretty(&reported)?,
+        }),
+        "sarif" => Some(serde_json::to_string_pretty(&sarif::log(reported, config))?),
+        "html" => {
+            let mut units = Vec::new();
+            for file in sources {
+                let filename = file.filename.as_str();
+                match &file.notebook {
+                    Some(notebook) => units.extend(
+                        notebook
+                            .cells()
+                            .map(|(cell, source)| (filename, Some(cell), source)),
+                    ),
+                    None => units.push((
                         filename,
-                        line_number,
-                        column_start + 1,
-                        "Warning:".yellow().bold(),
-                        function_name
-                    );
-
-                    // Print the code line
-                    println!("{}|", line_number.to_string().blue());
-                    println!(
-                        "{}| {}",
-                        " ".repeat(line_number.to_string().len()).blue(),
-                        line
-                    );
-
-                    // Print the indicator line
-                    let indicator = format!(
-                        "{}{}",
-                        " ".repeat(column_start),
-                        "^".repeat(std::cmp::max(1, column_end - column_start))
-                    );
-                    println!(
-                        "{}| {}",
-                        " ".repeat(line_number.to_string().len()).blue(),
-                        indicator.bright_red()
-                    );
-
-                    // Add a blank line for better readability
-                    println!();
+                        None,
+                        fixed_sources.get(filename).unwrap_or(&file.source_code),
+                    )),
                 }
             }
+            Some(html::report(reported, &units))
+        }
+        "checkstyle" => Some(xml::checkstyle(reported)),
+        "junit" => {
+            let filenames: Vec<&str> = sources.iter().map(|file| file.filename.as_str()).collect();
+            Some(xml::junit(reported, &filenames))
+        }
+        _ => None,
+    })
+}
+
+// Read each file, or stdin for `-`, with the module name of each under the
+// project root. Files that can't be read are reported and counted, so the
+// others are still analyzed.
+fn read_files(
+    paths: &[PathBuf],
+    project_root: Option<&PathBuf>,
+    stdin_filename: &str,
+    analyzer: &Analyzer,
+    verbose: bool,
+) -> (Vec<SourceFile>, Vec<String>, usize) {
+    let mut files = Vec::new();
+    let mut modules = Vec::new();
+    let mut unreadable = 0;
+    for path in paths {
+        let file = if path.as_os_str() == "-" {
+            if verbose {
+                eprintln!("Reading '{}'", stdin_filename);
+            }
+            let mut bytes = Vec::new();
+            io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(anyhow::Error::from)
+                .and_then(|_| SourceFile::decode(stdin_filename.to_string(), &bytes))
+                .with_context(|| format!("failed to read '{}'", stdin_filename))
+        } else {
+            if verbose {
+                eprintln!("Reading '{}'", path.display());
+            }
+            analyzer.read(path)
+        };
+        match file {
+            Ok(file) => {
+                files.push(file);
+                modules.push(project_root.map_or(String::new(), |root| module_name(root, path)));
+            }
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                unreadable += 1;
+            }
         }
+    }
+    (files, modules, unreadable)
+}
 
-        // Mark the function as having reported unhandled exceptions
-        func_info.reported_in_function.set(true);
+// The time of each phase and the slowest files, on stderr. Files analyzed
+// as a project are analyzed together, so they aren't timed one by one.
+fn print_timings(timings: &Timings, elapsed: Duration, jobs: usize, project: bool) {
+    let threads = if project { 1 } else { jobs };
+    eprintln!("Time per phase, summed over {} thread(s):", threads);
+    for (phase, duration) in timings.phases() {
+        eprintln!("  {:<20} {:>10.2?}", phase.to_string(), duration);
+    }
+    eprintln!("  {:<20} {:>10.2?}", "analysis, wall time", elapsed);
+    let slowest = timings.slowest_files(SLOWEST_FILES);
+    if !project && !slowest.is_empty() {
+        eprintln!("Slowest files:");
+        for (filename, duration) in slowest {
+            eprintln!("  {:>10.2?}  {}", duration, filename);
+        }
     }
+}
 
-    // Check for unhandled exceptions at call sites
-    let mut calls = Vec::new();
-    collect_function_calls(func_info.node, &mut calls, source_code);
+// Point the edits of suggestions at the bytes of the file rather than at
+// those of its decoded source, which differ for files that aren't UTF-8
+fn to_file_offsets(warnings: &mut [Warning], source_code: &str, encoding: Encoding) {
+    for suggestion in warnings.iter_mut().filter_map(|w| w.suggestion.as_mut()) {
+        suggestion.edit.start = encoding.file_offset(source_code, suggestion.edit.start);
+        suggestion.edit.end = encoding.file_offset(source_code, suggestion.edit.end);
+    }
+}
 
-    for call in calls {
-        if let Some(called_func) = functions.get(&call.name) {
-            let exceptions = &called_func.may_raise;
-            if !exceptions.is_empty() && !is_within_keyerror_try_except(call.node, source_code) {
-                let start_position = call.node.start_position();
-                let end_position = call.node.end_position();
-                let line_number = call.node.start_position().row + 1;
-                let column_start = start_position.column;
-                let column_end = end_position.column;
-                let key = (line_number, call.name.clone());
+// `PW002=error`, where the rule may be any selector, such as `PW00=hint`
+fn parse_severity_override(value: &str) -> Result<(Vec<Rule>, Severity), String> {
+    let (rule, severity) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected RULE=SEVERITY, got '{}'", value))?;
+    Ok((Rule::select(rule.trim())?, severity.trim().parse()?))
+}
 
-                // Only report if not already reported in the called function
-                if !reported_calls.contains(&key) && !called_func.reported_in_function.get() {
-                    reported_calls.insert(key);
+// The directory whose configuration file applies: that of the project, or of
+// the first file or directory given or the filename given for stdin, or
+// else the current one. Subcommands such as `graph` lack some of these
+// options.
+fn settings_dir(matches: &ArgMatches) -> PathBuf {
+    let first = matches
+        .get_one::<PathBuf>("project")
+        .or_else(|| matches.try_get_one::<PathBuf>("watch").ok().flatten())
+        .cloned()
+        .or_else(|| {
+            matches
+                .get_many::<String>("files")
+                .into_iter()
+                .flatten()
+                .find(|file| *file != "-")
+                .or_else(|| {
+                    matches
+                        .try_get_one::<String>("stdin-filename")
+                        .ok()
+                        .flatten()
+                })
+                .map(PathBuf::from)
+        });
+    match first {
+        Some(path) if path.is_dir() => path,
+        Some(path) => path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf),
+        None => PathBuf::from("."),
+    }
+}
 
-                    let line = source_lines.get(start_position.row).unwrap_or(&"");
-                    println!(
-                        "{}:{}:{}: {} Possible {} not handled when calling '{}' in function '{}'",
-                        filename,
-                        line_number,
-                        column_start + 1,
-                        "Warning:".yellow().bold(),
-                        exceptions
-                            .iter()
-                            .cloned()
-                            .collect::<Vec<String>>()
-                            .join(", "),
-                        call.name,
-                        function_name
-                    );
-
-                    // Print the code line
-                    println!("{}|", line_number.to_string().blue());
-                    println!(
-                        "{}| {}",
-                        " ".repeat(line_number.to_string().len()).blue(),
-                        line
-                    );
-
-                    // Print the indicator line
-                    let indicator = format!(
-                        "{}{}",
-                        " ".repeat(column_start),
-                        "^".repeat(std::cmp::max(1, column_end - column_start))
-                    );
-                    println!(
-                        "{}| {}",
-                        " ".repeat(line_number.to_string().len()).blue(),
-                        indicator.bright_red()
-                    );
-
-                    // Add a blank line for better readability
-                    println!();
-                }
-            }
+// A subcommand working on the exception flow of some files or of a project,
+// printed in one of `formats`, the first being the default
+fn flow_command(command: Command, format_help: &'static str, formats: &[&'static str]) -> Command {
+    input_args(command)
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help(format_help)
+                .value_name("FORMAT")
+                .default_value(formats[0])
+                .value_parser(formats.to_vec()),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("Write to this file instead of stdout")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+}
+
+// The files or the project a subcommand analyzes
+fn input_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("files")
+                .help("Python files or directories to analyze")
+                .required_unless_present("project")
+                .num_args(1..)
+                .value_hint(clap::ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("project")
+                .long("project")
+                .help("Analyze every Python file under this directory, following imports between them")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::DirPath)
+                .conflicts_with("files"),
+        )
+}
+
+// The exception flow of the files of a `flow_command`, or of every file of
+// the project
+fn project_flow(
+    matches: &ArgMatches,
+    settings: &Settings,
+    config: &Config,
+) -> Result<ExceptionFlow> {
+    let discovery = Discovery::new(
+        &settings.include,
+        &settings.exclude,
+        settings.respect_gitignore,
+    );
+    let project_root = matches.get_one::<PathBuf>("project");
+    let paths = match project_root {
+        Some(root) => discovery.files(std::slice::from_ref(root))?,
+        None => {
+            let files: Vec<PathBuf> = matches
+                .get_many::<String>("files")
+                .into_iter()
+                .flatten()
+                .map(PathBuf::from)
+                .collect();
+            discovery.files(&files)?
         }
+    };
+    let mut sources = Vec::new();
+    for path in &paths {
+        let source = SourceFile::read(path)?.source_code;
+        // Files given one by one are told apart by their names
+        let module = match project_root {
+            Some(root) => module_name(root, path),
+            None => path
+                .file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into_owned()),
+        };
+        sources.push((path.display().to_string(), module, source));
     }
+    let files: Vec<ProjectFile> = sources
+        .iter()
+        .map(|(filename, module, source)| ProjectFile {
+            filename,
+            module,
+            source,
+        })
+        .collect();
+
+    Ok(exception_flow(&files, config))
 }
 
-fn find_unguarded_dict_accesses<'a>(
-    node: Node<'a>,
-    accesses: &mut Vec<Node<'a>>,
-    source_code: &str,
-) {
-    let mut cursor = node.walk();
-    if node.kind() == "subscript" {
-        // Check if it's inside a try/except KeyError block
-        if !is_within_keyerror_try_except(node, source_code) {
-            accesses.push(node);
-        }
+// Print the output of a `flow_command`, or write it to the file given
+fn write_output(matches: &ArgMatches, output: String) -> Result<()> {
+    match matches.get_one::<PathBuf>("output") {
+        Some(path) => fs::write(path, output + "\n")
+            .with_context(|| format!("failed to write '{}'", path.display()))?,
+        None => println!("{}", output),
     }
+    Ok(())
+}
 
-    // Traverse child nodes
-    if cursor.goto_first_child() {
-        loop {
-            let child = cursor.node();
-            find_unguarded_dict_accesses(child, accesses, source_code);
-            if !cursor.goto_next_sibling() {
-                break;
-            }
+// A workflow command that makes GitHub Actions annotate the line, e.g.
+// `::warning file=app.py,line=3,col=12,title=PW001::Possible KeyError ...`
+fn print_github_annotation(warning: &Warning) {
+    // Values are percent-encoded; properties also escape their separators
+    let escape_data = |value: &str| {
+        value
+            .replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let escape_property = |value: &str| escape_data(value).replace(':', "%3A").replace(',', "%2C");
+    let command = match warning.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info | Severity::Hint => "notice",
+    };
+    println!(
+        "::{} file={},line={},col={},endLine={},endColumn={},title={}::{}",
+        command,
+        escape_property(&warning.filename),
+        warning.line,
+        warning.column,
+        warning.end_line,
+        warning.end_column,
+        warning.rule,
+        escape_data(&warning.message)
+    );
+}
+
+fn severity_label(severity: Severity) -> ColoredString {
+    let label = format!("{}:", severity);
+    match severity {
+        Severity::Error => label.red().bold(),
+        Severity::Warning => label.yellow().bold(),
+        Severity::Info => label.blue().bold(),
+        Severity::Hint => label.cyan().bold(),
+    }
+}
+
+// Whether `--color WHEN` colors the output. An explicit choice wins over
+// NO_COLOR, which disables colors when set to anything but the empty string
+// (https://no-color.org).
+fn use_color(when: &str) -> bool {
+    match when {
+        "always" => true,
+        "never" => false,
+        _ => {
+            io::stdout().is_terminal()
+                && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
         }
     }
 }
 
-fn is_within_keyerror_try_except(node: Node, source_code: &str) -> bool {
-    let mut current_node = node;
-    loop {
-        if current_node.kind() == "try_statement" {
-            // Check except clauses
-            let mut cursor = current_node.walk();
-            if cursor.goto_first_child() {
-                loop {
-                    let child = cursor.node();
-                    if child.kind() == "except_clause" {
-                        if let Some(exception_type) = child.child_by_field_name("type") {
-                            let exception_text =
-                                exception_type.utf8_text(source_code.as_bytes()).unwrap();
-                            if exception_text == "KeyError" || exception_text == "Exception" {
-                                return true;
-                            }
-                        } else {
-                            // Bare except
-                            return true;
-                        }
-                    }
-                    if !cursor.goto_next_sibling() {
-                        break;
-                    }
+/// The lines of each analyzed file, or of each code cell of a notebook, for
+/// printing snippets.
+#[derive(Default)]
+pub struct SourceLines<'a>(HashMap<&'a str, HashMap<Option<usize>, Vec<&'a str>>>);
+
+impl<'a> SourceLines<'a> {
+    pub fn add(&mut self, filename: &'a str, source: &'a str, notebook: Option<&'a Notebook>) {
+        let lines = self.0.entry(filename).or_default();
+        match notebook {
+            Some(notebook) => {
+                for (cell, source) in notebook.cells() {
+                    lines.insert(Some(cell), source.lines().collect());
                 }
             }
+            None => {
+                lines.insert(None, source.lines().collect());
+            }
         }
-        if let Some(parent) = current_node.parent() {
-            current_node = parent;
-        } else {
-            break;
+    }
+
+    fn get(&self, filename: &str, cell: Option<usize>) -> &[&'a str] {
+        self.0
+            .get(filename)
+            .and_then(|lines| lines.get(&cell))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+fn print_warning(warning: &Warning, sources: &SourceLines, explain: bool) {
+    print_short_warning(warning);
+
+    // The gutter is wide enough for the line numbers of the warning and of
+    // each related location
+    let width = warning
+        .related
+        .iter()
+        .map(|related| related.line)
+        .chain([warning.line])
+        .max()
+        .unwrap()
+        .to_string()
+        .len();
+    println!("{}", format!("{:width$} |", "").blue());
+    print_snippet(
+        sources.get(&warning.filename, warning.cell),
+        (warning.line, warning.column),
+        (warning.end_line, warning.end_column),
+        width,
+        ('^', Color::BrightRed),
+        "",
+    );
+
+    // Point at where the exception comes from in the called functions
+    for related in &warning.related {
+        println!("{}", format!("{:width$} |", "").blue());
+        print_snippet(
+            sources.get(&related.filename, related.cell),
+            (related.line, related.column),
+            (related.end_line, related.end_column),
+            width,
+            ('-', Color::BrightBlue),
+            &related.message,
+        );
+    }
+
+    if explain {
+        print_explanation(warning, sources);
+    }
+
+    if let Some(suggestion) = &warning.suggestion {
+        println!("{}", "Suggested fix:".green().bold());
+        for line in suggestion.original.lines() {
+            println!("{}", format!("- {}", line).red());
+        }
+        for line in suggestion.replacement.lines() {
+            println!("{}", format!("+ {}", line).green());
         }
     }
-    false
+
+    // Add a blank line for better readability
+    println!();
+}
+
+// The first line of a span with the span underlined by `marker`, followed
+// by the label. Spans over several lines are underlined to the end of the
+// first.
+fn print_snippet(
+    source_lines: &[&str],
+    (line, column): (usize, usize),
+    (end_line, end_column): (usize, usize),
+    width: usize,
+    (marker, color): (char, Color),
+    label: &str,
+) {
+    let code = source_lines.get(line - 1).unwrap_or(&"");
+    let end_column = if end_line > line {
+        code.chars().count() + 1
+    } else {
+        end_column
+    };
+    println!(
+        "{} {} {}",
+        format!("{:>width$}", line).blue(),
+        "|".blue(),
+        code
+    );
+    let underline = marker
+        .to_string()
+        .repeat(std::cmp::max(1, end_column.saturating_sub(column)));
+    let underline = format!("{} {}", underline, label);
+    println!(
+        "{} {}{}",
+        format!("{:width$} |", "").blue(),
+        " ".repeat(column - 1),
+        underline.trim_end().color(color)
+    );
+}
+
+// The chain from the call to where the exception is raised, on one line:
+// `load(c) at a.py:10:5 → c["key"] at a.py:3:12`
+fn print_explanation(warning: &Warning, sources: &SourceLines) {
+    if warning.related.is_empty() {
+        return;
+    }
+    let steps: Vec<String> = std::iter::once((
+        (&warning.filename, warning.cell),
+        (warning.line, warning.column),
+        (warning.end_line, warning.end_column),
+    ))
+    .chain(warning.related.iter().map(|related| {
+        (
+            (&related.filename, related.cell),
+            (related.line, related.column),
+            (related.end_line, related.end_column),
+        )
+    }))
+    .map(|((filename, cell), start, end)| {
+        format!(
+            "{} at {}:{}",
+            span_text(sources.get(filename, cell), start, end).bold(),
+            filename,
+            notebook::position(cell, start.0, start.1)
+        )
+    })
+    .collect();
+    println!("  {} {}", "= because:".bright_blue(), steps.join(" → "));
+}
+
+// The source of a span, cut at the end of its first line
+fn span_text(
+    source_lines: &[&str],
+    (line, column): (usize, usize),
+    (end_line, end_column): (usize, usize),
+) -> String {
+    let code = source_lines.get(line - 1).unwrap_or(&"");
+    let rest: String = code.chars().skip(column - 1).collect();
+    if end_line > line {
+        return format!("{}…", rest.trim_end());
+    }
+    rest.chars()
+        .take(end_column.saturating_sub(column))
+        .collect()
+}
+
+// `file:line:column: severity message [rule]`, as `--format short` prints
+fn print_short_warning(warning: &Warning) {
+    println!(
+        "{}:{}: {} {} {}",
+        warning.filename,
+        notebook::position(warning.cell, warning.line, warning.column),
+        severity_label(warning.severity),
+        warning.message,
+        format!("[{}]", warning.rule).dimmed()
+    );
 }
@@ -0,0 +1,144 @@
+//! Jupyter notebooks, analyzed as the Python of their code cells one after
+//! the other. Warnings are then pointed at the cell they are in, with lines
+//! counted from the start of the cell, as notebook editors show them.
+
+use crate::Warning;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::ops::Range;
+
+/// The code cells of a notebook.
+pub struct Notebook {
+    /// The source of the code cells, each starting on the line after the
+    /// previous one ends. IPython magics and shell escapes are replaced by
+    /// `pass`, so the rest still parses.
+    pub source: String,
+    // The 1-based number of each code cell among all the cells of the
+    // notebook, markdown ones included, its lines in `source` and its bytes
+    cells: Vec<(usize, Range<usize>, Range<usize>)>,
+}
+
+#[derive(Deserialize)]
+struct File {
+    cells: Vec<Cell>,
+}
+
+#[derive(Deserialize)]
+struct Cell {
+    cell_type: String,
+    source: Text,
+}
+
+// Cell sources are stored as one string or as a list of lines
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Text {
+    String(String),
+    Lines(Vec<String>),
+}
+
+/// Whether `filename` is that of a notebook.
+pub fn is_notebook(filename: &str) -> bool {
+    filename.ends_with(".ipynb")
+}
+
+/// A position as `line:column`, or as `cell 3:line 7:12` in a notebook.
+pub fn position(cell: Option<usize>, line: usize, column: usize) -> String {
+    match cell {
+        Some(cell) => format!("cell {}:line {}:{}", cell, line, column),
+        None => format!("{}:{}", line, column),
+    }
+}
+
+/// The source a warning's lines are counted in: that of its cell in a
+/// notebook, or the file's `source_code` otherwise.
+pub fn source_of<'a>(
+    warning: &Warning,
+    source_code: &'a str,
+    notebook: Option<&'a Notebook>,
+) -> &'a str {
+    match (notebook, warning.cell) {
+        (Some(notebook), Some(cell)) => notebook.cell_source(cell),
+        _ => source_code,
+    }
+}
+
+impl Notebook {
+    /// Read the code cells of a notebook's JSON, in the nbformat 4 layout.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let file: File = serde_json::from_str(contents).context("invalid notebook")?;
+        let mut source = String::new();
+        let mut cells = Vec::new();
+        let mut line = 0;
+        for (index, cell) in file.cells.into_iter().enumerate() {
+            if cell.cell_type != "code" {
+                continue;
+            }
+            let text = match cell.source {
+                Text::String(text) => text,
+                Text::Lines(lines) => lines.concat(),
+            };
+            // A cell magic such as `%%bash` makes the whole cell another
+            // language
+            let cell_magic = text.trim_start().starts_with("%%");
+            let (start, start_byte) = (line, source.len());
+            for code in text.lines() {
+                let indent = &code[..code.len() - code.trim_start().len()];
+                if cell_magic {
+                    source.push_str("pass");
+                } else if code.trim_start().starts_with(['%', '!']) {
+                    source.push_str(indent);
+                    source.push_str("pass");
+                } else {
+                    source.push_str(code);
+                }
+                source.push('\n');
+                line += 1;
+            }
+            cells.push((index + 1, start..line, start_byte..source.len()));
+        }
+        Ok(Notebook { source, cells })
+    }
+
+    /// The number and source of each code cell.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.cells
+            .iter()
+            .map(|(number, _, bytes)| (*number, &self.source[bytes.clone()]))
+    }
+
+    /// The source of the cell numbered `number`, empty if it isn't a code
+    /// cell.
+    pub fn cell_source(&self, number: usize) -> &str {
+        self.cells()
+            .find(|(cell, _)| *cell == number)
+            .map_or("", |(_, source)| source)
+    }
+
+    /// Point warnings on lines of `source` at the cells they are in instead,
+    /// with their lines counted from the start of the cell.
+    pub fn locate(&self, warnings: &mut [Warning]) {
+        for warning in warnings {
+            let (cell, first) = self.cell_at(warning.line);
+            warning.cell = Some(cell);
+            warning.line -= first;
+            warning.end_line -= first;
+            for related in &mut warning.related {
+                let (cell, first) = self.cell_at(related.line);
+                related.cell = Some(cell);
+                related.line -= first;
+                related.end_line -= first;
+            }
+        }
+    }
+
+    // The number of the cell holding a 1-based line of `source`, and how
+    // many lines come before the cell
+    fn cell_at(&self, line: usize) -> (usize, usize) {
+        self.cells
+            .iter()
+            .rev()
+            .find(|(_, lines, _)| lines.start < line)
+            .map_or((1, 0), |(number, lines, _)| (*number, lines.start))
+    }
+}
@@ -0,0 +1,86 @@
+//! A progress bar on stderr for long runs: how many files are done out of
+//! how many, the time taken so far and the file last analyzed, redrawn in
+//! place on one line.
+
+use std::env;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// How often the bar is redrawn at most, so that small files don't spend
+// their time writing to the terminal
+const REDRAW_INTERVAL: Duration = Duration::from_millis(50);
+
+const BAR_WIDTH: usize = 24;
+
+/// The progress of analyzing `total` files.
+pub struct Progress {
+    total: usize,
+    started: Instant,
+    // Files done, and when the bar was last drawn
+    state: Mutex<(usize, Option<Instant>)>,
+}
+
+impl Progress {
+    pub fn new(total: usize) -> Self {
+        Progress {
+            total,
+            started: Instant::now(),
+            state: Mutex::new((0, None)),
+        }
+    }
+
+    /// Count `filename` as done.
+    pub fn file_done(&self, filename: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.0 += 1;
+        let due = state
+            .1
+            .is_none_or(|drawn| drawn.elapsed() >= REDRAW_INTERVAL);
+        if due || state.0 == self.total {
+            state.1 = Some(Instant::now());
+            self.draw(state.0, filename);
+        }
+    }
+
+    /// Show a message in place of the bar, for work that isn't counted in
+    /// files.
+    pub fn status(&self, message: &str) {
+        let _lock = self.state.lock().unwrap();
+        write_line(message);
+    }
+
+    /// Clear the line, so what is printed next starts on an empty one.
+    pub fn finish(&self) {
+        let _lock = self.state.lock().unwrap();
+        eprint!("\r\x1b[2K");
+        let _ = io::stderr().flush();
+    }
+
+    fn draw(&self, done: usize, filename: &str) {
+        let filled = (done * BAR_WIDTH)
+            .checked_div(self.total)
+            .unwrap_or(BAR_WIDTH);
+        let bar = format!("{}{}", "=".repeat(filled), " ".repeat(BAR_WIDTH - filled));
+        write_line(&format!(
+            "[{}] {}/{} files, {:.1}s {}",
+            bar,
+            done,
+            self.total,
+            self.started.elapsed().as_secs_f64(),
+            filename
+        ));
+    }
+}
+
+// Overwrite the line with `text`, cut to the width of the terminal so it
+// doesn't wrap
+fn write_line(text: &str) {
+    let width = env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse::<usize>().ok())
+        .unwrap_or(80);
+    let text: String = text.chars().take(width.saturating_sub(1)).collect();
+    eprint!("\r\x1b[2K{}", text);
+    let _ = io::stderr().flush();
+}
@@ -0,0 +1,70 @@
+//! The exceptions each public function of a project may raise, for writing
+//! the "Raises:" sections of its documentation and reviewing its error API.
+
+use pysleuth::{ExceptionFlow, FlowFunction};
+use std::fmt::Write;
+
+/// The functions of `flow` that are public: those whose module, class and
+/// own name don't start with an underscore, except for dunder methods such
+/// as `__init__`. Module-level code is left out.
+pub fn public_functions(flow: &ExceptionFlow) -> Vec<&FlowFunction> {
+    let private = |part: &str| {
+        part.starts_with('_') && !(part.len() > 4 && part.starts_with("__") && part.ends_with("__"))
+    };
+    flow.functions
+        .iter()
+        .filter(|function| !function.name.ends_with("<module>"))
+        .filter(|function| !function.name.split('.').any(private))
+        .collect()
+}
+
+/// A line per function, such as `pkg.load raises KeyError, ValueError`,
+/// with where it is defined.
+pub fn text(functions: &[&FlowFunction]) -> String {
+    let mut text = String::new();
+    for function in functions {
+        writeln!(
+            text,
+            "{}:{}: {} raises {}",
+            function.filename,
+            function.line,
+            function.name,
+            raises(function)
+        )
+        .unwrap();
+    }
+    write!(text, "{} public function(s)", functions.len()).unwrap();
+    text
+}
+
+/// A Markdown table of the functions and what they raise.
+pub fn markdown(functions: &[&FlowFunction]) -> String {
+    let mut markdown = String::from("| Function | Raises | Defined in |\n");
+    markdown.push_str("| --- | --- | --- |\n");
+    for function in functions {
+        let raises = match function.raises.is_empty() {
+            true => "—".to_string(),
+            false => function
+                .raises
+                .iter()
+                .map(|exception| format!("`{}`", exception))
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        writeln!(
+            markdown,
+            "| `{}` | {} | {}:{} |",
+            function.name, raises, function.filename, function.line
+        )
+        .unwrap();
+    }
+    markdown.pop();
+    markdown
+}
+
+fn raises(function: &FlowFunction) -> String {
+    match function.raises.is_empty() {
+        true => "nothing".to_string(),
+        false => function.raises.join(", "),
+    }
+}
@@ -0,0 +1,98 @@
+//! SARIF 2.1.0 logs, the format code scanning platforms such as GitHub Code
+//! Scanning and Azure DevOps accept for uploaded results.
+
+use pysleuth::{Config, Rule, Severity, Warning};
+use serde_json::{json, Value};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// A log with a single run describing every rule and reporting `warnings`.
+pub fn log(warnings: &[Warning], config: &Config) -> Value {
+    let rules: Vec<Value> = Rule::ALL
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.code(),
+                "name": rule.name(),
+                "shortDescription": { "text": rule.description() },
+                "defaultConfiguration": { "level": level(config.severity(*rule)) },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = warnings
+        .iter()
+        .map(|warning| {
+            let related: Vec<Value> = warning
+                .related
+                .iter()
+                .map(|related| {
+                    json!({
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": artifact_uri(&related.filename) },
+                            "region": {
+                                "startLine": related.line,
+                                "startColumn": related.column,
+                                "endLine": related.end_line,
+                                "endColumn": related.end_column,
+                            },
+                        },
+                        "message": { "text": related.message },
+                    })
+                })
+                .collect();
+            json!({
+                "ruleId": warning.rule.code(),
+                "ruleIndex": Rule::ALL.iter().position(|rule| *rule == warning.rule),
+                "level": level(warning.severity),
+                "message": { "text": warning.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": artifact_uri(&warning.filename) },
+                        "region": {
+                            "startLine": warning.line,
+                            "startColumn": warning.column,
+                            "endLine": warning.end_line,
+                            "endColumn": warning.end_column,
+                        },
+                    },
+                }],
+                "relatedLocations": related,
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            // Columns count characters, not the UTF-16 units SARIF
+            // assumes by default
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+// SARIF has no levels below `note`
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info | Severity::Hint => "note",
+    }
+}
+
+// Artifact locations are URI references, so Windows separators become `/`
+// and a leading `./` is dropped
+fn artifact_uri(filename: &str) -> String {
+    let uri = filename.replace('\\', "/");
+    uri.strip_prefix("./").unwrap_or(&uri).to_string()
+}
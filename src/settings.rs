@@ -0,0 +1,474 @@
+//! Settings read from a `pywrong.toml` file, or from the `[tool.pywrong]`
+//! table of a `pyproject.toml`, found in the directory of the analyzed paths
+//! or one of its parents. Keys are named after the command line options they
+//! stand in for, which take precedence over them:
+//!
+//! ```toml
+//! [tool.pywrong]
+//! python-version = "3.9..3.12"
+//! ignore = ["PW007"]
+//! exclude = ["migrations", "*_pb2.py"]
+//! frameworks = ["django"]
+//! safe-functions = ["log_and_ignore"]
+//! allow-swallowed = ["FileNotFoundError"]
+//! accept = ["AppError", "validate_*:ValueError"]
+//! required-env-vars = ["DATABASE_URL"]
+//! format = "github"
+//! format-template = "{file}:{line}:{col}: {code} {message}"
+//! group-by = "function"
+//! max-per-function = 5
+//! fail-on = "error"
+//!
+//! [tool.pywrong.severity]
+//! PW002 = "error"
+//! ```
+//!
+//! Only the subset of TOML these settings need is understood: strings,
+//! booleans, integers and arrays of them.
+
+use anyhow::{anyhow, bail, Context, Result};
+use pysleuth::{DocstringStyle, Framework, PythonVersion, Rule, Severity};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Settings from a configuration file. Unset options are left to the
+/// command line defaults.
+#[derive(Debug, Default)]
+pub struct Settings {
+    /// The file the settings were read from.
+    pub path: Option<PathBuf>,
+    /// The oldest and newest targeted version.
+    pub python_version: Option<(PythonVersion, PythonVersion)>,
+    pub select: Option<Vec<Rule>>,
+    pub ignore: Vec<Rule>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub respect_gitignore: bool,
+    pub format: Option<String>,
+    pub format_template: Option<String>,
+    pub group_by: Option<String>,
+    pub max_per_function: Option<usize>,
+    pub max_warnings: Option<usize>,
+    pub fail_on: Option<Severity>,
+    pub dedup: bool,
+    pub suggest_fixes: bool,
+    pub check_annotations: bool,
+    pub strict_asserts: bool,
+    pub skip_syntax_errors: bool,
+    pub public_only: bool,
+    pub docstring_style: Option<DocstringStyle>,
+    /// Set by `entry-points = true`, or to the functions listed.
+    pub entry_points: Option<Vec<String>>,
+    pub frameworks: Vec<Framework>,
+    /// Resolved relative to the configuration file.
+    pub stubs: Option<PathBuf>,
+    /// Resolved relative to the configuration file.
+    pub site_packages: Option<PathBuf>,
+    pub swallowing_decorators: Vec<String>,
+    pub higher_order: Vec<String>,
+    pub safe_functions: Vec<String>,
+    pub allow_swallowed: Vec<String>,
+    pub accept: Vec<String>,
+    pub required_env_vars: Vec<String>,
+    pub severities: HashMap<Rule, Severity>,
+}
+
+impl Settings {
+    /// The settings that apply to files under `dir`: those of the nearest
+    /// `pywrong.toml`, or of the nearest `pyproject.toml` with a
+    /// `[tool.pywrong]` table.
+    pub fn discover(dir: &Path) -> Result<Option<Self>> {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        for ancestor in dir.ancestors() {
+            let path = ancestor.join("pywrong.toml");
+            if path.is_file() {
+                return Settings::load(&path).map(Some);
+            }
+            let path = ancestor.join("pyproject.toml");
+            if path.is_file() {
+                let contents = read(&path)?;
+                if has_table(&contents, "tool.pywrong") {
+                    return Settings::parse(&path, &contents, "tool.pywrong").map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Load the settings of a `pywrong.toml` or `pyproject.toml` file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = read(path)?;
+        let table = if path
+            .file_name()
+            .is_some_and(|name| name == "pyproject.toml")
+        {
+            "tool.pywrong"
+        } else {
+            ""
+        };
+        Settings::parse(path, &contents, table)
+    }
+
+    fn parse(path: &Path, contents: &str, table: &str) -> Result<Self> {
+        let mut settings = Settings {
+            path: Some(path.to_path_buf()),
+            ..Settings::default()
+        };
+        let entries =
+            table_entries(contents, table).with_context(|| format!("in '{}'", path.display()))?;
+        for (line, key, value) in entries {
+            settings
+                .set(path, &key, value)
+                .with_context(|| format!("in '{}', line {}", path.display(), line))?;
+        }
+
+        // Severities are overridden in a subtable, keyed by rule selector
+        let severity_table = match table {
+            "" => "severity".to_string(),
+            table => format!("{}.severity", table),
+        };
+        let entries = table_entries(contents, &severity_table)
+            .with_context(|| format!("in '{}'", path.display()))?;
+        for (line, key, value) in entries {
+            let context = || format!("in '{}', line {}", path.display(), line);
+            let rules = Rule::select(&key)
+                .map_err(|e| anyhow!(e))
+                .with_context(context)?;
+            let severity: Severity = value
+                .string(&key)?
+                .parse()
+                .map_err(|e: String| anyhow!(e))
+                .with_context(context)?;
+            for rule in rules {
+                settings.severities.insert(rule, severity);
+            }
+        }
+        Ok(settings)
+    }
+
+    fn set(&mut self, path: &Path, key: &str, value: Value) -> Result<()> {
+        match key {
+            "python-version" => {
+                let version = value.string(key)?;
+                self.python_version =
+                    Some(PythonVersion::parse_range(&version).map_err(|e: String| anyhow!(e))?);
+            }
+            "select" => self.select = Some(rules(value, key)?),
+            "ignore" | "disable" => self.ignore = rules(value, key)?,
+            "include" => self.include = value.strings(key)?,
+            "exclude" => self.exclude = value.strings(key)?,
+            "respect-gitignore" => self.respect_gitignore = value.boolean(key)?,
+            "format" => self.format = Some(value.string(key)?),
+            "format-template" => self.format_template = Some(value.string(key)?),
+            "group-by" => self.group_by = Some(value.string(key)?),
+            "max-per-function" => self.max_per_function = Some(value.integer(key)?),
+            "max-warnings" => self.max_warnings = Some(value.integer(key)?),
+            "fail-on" => {
+                let severity = value.string(key)?;
+                self.fail_on = Some(severity.parse().map_err(|e: String| anyhow!(e))?);
+            }
+            "dedup" => self.dedup = value.boolean(key)?,
+            "suggest-fixes" => self.suggest_fixes = value.boolean(key)?,
+            "check-annotations" => self.check_annotations = value.boolean(key)?,
+            "strict-asserts" => self.strict_asserts = value.boolean(key)?,
+            "skip-syntax-errors" => self.skip_syntax_errors = value.boolean(key)?,
+            "public-only" => self.public_only = value.boolean(key)?,
+            "entry-points" => {
+                self.entry_points = match value {
+                    Value::Array(_) => Some(value.strings(key)?),
+                    value => value.boolean(key)?.then(Vec::new),
+                }
+            }
+            "frameworks" => {
+                self.frameworks = value
+                    .strings(key)?
+                    .iter()
+                    .map(|name| name.parse().map_err(|e: String| anyhow!(e)))
+                    .collect::<Result<_>>()?
+            }
+            "docstring-style" => {
+                let style = value.string(key)?;
+                self.docstring_style = Some(style.parse().map_err(|e: String| anyhow!(e))?);
+            }
+            "stubs" => {
+                let dir = path.parent().unwrap_or(Path::new("."));
+                self.stubs = Some(dir.join(value.string(key)?));
+            }
+            "site-packages" => {
+                let dir = path.parent().unwrap_or(Path::new("."));
+                self.site_packages = Some(dir.join(value.string(key)?));
+            }
+            "swallowing-decorators" => self.swallowing_decorators = value.strings(key)?,
+            "higher-order" => self.higher_order = value.strings(key)?,
+            "safe-functions" => self.safe_functions = value.strings(key)?,
+            "allow-swallowed" => self.allow_swallowed = value.strings(key)?,
+            "accept" => self.accept = value.strings(key)?,
+            "required-env-vars" => self.required_env_vars = value.strings(key)?,
+            _ => bail!("unknown setting '{}'", key),
+        }
+        Ok(())
+    }
+}
+
+fn rules(value: Value, key: &str) -> Result<Vec<Rule>> {
+    let mut rules = Vec::new();
+    for selector in value.strings(key)? {
+        rules.extend(Rule::select(&selector).map_err(|e| anyhow!(e))?);
+    }
+    Ok(rules)
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("failed to read '{}'", path.display()))
+}
+
+#[derive(Debug)]
+enum Value {
+    String(String),
+    Boolean(bool),
+    Integer(usize),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn string(self, key: &str) -> Result<String> {
+        match self {
+            Value::String(value) => Ok(value),
+            _ => bail!("'{}' must be a string", key),
+        }
+    }
+
+    fn boolean(self, key: &str) -> Result<bool> {
+        match self {
+            Value::Boolean(value) => Ok(value),
+            _ => bail!("'{}' must be true or false", key),
+        }
+    }
+
+    fn integer(self, key: &str) -> Result<usize> {
+        match self {
+            Value::Integer(value) => Ok(value),
+            _ => bail!("'{}' must be a non-negative integer", key),
+        }
+    }
+
+    fn strings(self, key: &str) -> Result<Vec<String>> {
+        match self {
+            Value::Array(values) => values.into_iter().map(|value| value.string(key)).collect(),
+            _ => bail!("'{}' must be an array of strings", key),
+        }
+    }
+}
+
+// The name of the table a `[header]` line opens, without spaces or quotes
+fn table_header(line: &str) -> Option<String> {
+    let line = strip_comment(line).trim();
+    let header = line.strip_prefix('[')?.strip_suffix(']')?;
+    // Arrays of tables, `[[name]]`, never hold these settings
+    let header = header.strip_prefix('[').map_or(header, |_| "\0");
+    Some(
+        header
+            .split('.')
+            .map(|part| part.trim().trim_matches('"'))
+            .collect::<Vec<_>>()
+            .join("."),
+    )
+}
+
+fn has_table(contents: &str, table: &str) -> bool {
+    contents
+        .lines()
+        .any(|line| table_header(line).as_deref() == Some(table))
+}
+
+// The `key = value` entries of a table, with their line numbers. The empty
+// table name stands for the keys before the first header.
+fn table_entries(contents: &str, table: &str) -> Result<Vec<(usize, String, Value)>> {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    let mut lines = contents.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        if let Some(header) = table_header(line) {
+            current = header;
+            continue;
+        }
+        if current != table || strip_comment(line).trim().is_empty() {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected 'key = value'", index + 1))?;
+        let key = key.trim().trim_matches('"').to_string();
+
+        // Arrays may span several lines
+        let mut value = value.to_string();
+        while !is_balanced(&value) {
+            let (_, next) = lines
+                .next()
+                .ok_or_else(|| anyhow!("line {}: unterminated array", index + 1))?;
+            value.push('\n');
+            value.push_str(next);
+        }
+
+        let mut parser = ValueParser {
+            text: value.as_bytes(),
+            position: 0,
+        };
+        let parsed = parser
+            .value()
+            .and_then(|parsed| parser.end().map(|_| parsed))
+            .map_err(|e| anyhow!("line {}: {}", index + 1, e))?;
+        entries.push((index + 1, key, parsed));
+    }
+    Ok(entries)
+}
+
+// The line without a trailing `# comment`, leaving `#` inside strings alone
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Whether every `[` outside strings and comments has been closed
+fn is_balanced(value: &str) -> bool {
+    let mut depth = 0i32;
+    for line in value.lines() {
+        let mut quote = None;
+        for c in strip_comment(line).chars() {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(q), _) if c == q => quote = None,
+                (None, '[') => depth += 1,
+                (None, ']') => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    depth <= 0
+}
+
+struct ValueParser<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl ValueParser<'_> {
+    // Skip whitespace, newlines and comments
+    fn skip(&mut self) {
+        while let Some(&c) = self.text.get(self.position) {
+            if c == b'#' {
+                while self.text.get(self.position).is_some_and(|&c| c != b'\n') {
+                    self.position += 1;
+                }
+            } else if c.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn end(&mut self) -> Result<(), String> {
+        self.skip();
+        match self.position == self.text.len() {
+            true => Ok(()),
+            false => Err("unexpected text after the value".to_string()),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip();
+        match self.text.get(self.position) {
+            Some(b'"') => self.basic_string().map(Value::String),
+            Some(b'\'') => self.literal_string().map(Value::String),
+            Some(b'[') => self.array(),
+            Some(_) => self.bare_value(),
+            None => Err("missing value".to_string()),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let mut value = Vec::new();
+        loop {
+            match self.text.get(self.position) {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    self.position += 1;
+                    match self.text.get(self.position) {
+                        Some(b'n') => value.push(b'\n'),
+                        Some(b't') => value.push(b'\t'),
+                        Some(&c @ (b'"' | b'\\')) => value.push(c),
+                        _ => return Err("unsupported escape in string".to_string()),
+                    }
+                }
+                Some(b'\n') | None => return Err("unterminated string".to_string()),
+                Some(&c) => value.push(c),
+            }
+            self.position += 1;
+        }
+        self.position += 1;
+        String::from_utf8(value).map_err(|_| "invalid UTF-8 in string".to_string())
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        let start = self.position + 1;
+        let length = self.text[start..]
+            .iter()
+            .position(|&c| c == b'\'' || c == b'\n')
+            .filter(|&length| self.text[start + length] == b'\'')
+            .ok_or("unterminated string")?;
+        self.position = start + length + 1;
+        Ok(String::from_utf8_lossy(&self.text[start..start + length]).into_owned())
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.position += 1;
+        let mut values = Vec::new();
+        loop {
+            self.skip();
+            if self.text.get(self.position) == Some(&b']') {
+                self.position += 1;
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip();
+            match self.text.get(self.position) {
+                Some(b',') => self.position += 1,
+                Some(b']') => {}
+                _ => return Err("expected ',' or ']' in array".to_string()),
+            }
+        }
+    }
+
+    fn bare_value(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        while self
+            .text
+            .get(self.position)
+            .is_some_and(|&c| c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'+' | b'.'))
+        {
+            self.position += 1;
+        }
+        let word = String::from_utf8_lossy(&self.text[start..self.position]);
+        match word.as_ref() {
+            "true" => Ok(Value::Boolean(true)),
+            "false" => Ok(Value::Boolean(false)),
+            _ if word.bytes().all(|c| c.is_ascii_digit()) => word
+                .parse()
+                .map(Value::Integer)
+                .map_err(|_| format!("integer '{}' is too large", word)),
+            _ => Err(format!("unsupported value '{}'", word)),
+        }
+    }
+}
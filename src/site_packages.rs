@@ -0,0 +1,300 @@
+//! Exceptions raised by third-party packages, learned from their source in
+//! a `site-packages` directory, so that calls such as `yaml.safe_load(s)`
+//! propagate what the package raises. A package is analyzed as a project
+//! the first time one of its functions is called, and what was learned is
+//! kept for the rest of the run.
+
+use crate::{function_exceptions, Config, ProjectFile, PythonVersion};
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tree_sitter::{Node, Parser};
+
+// Packages with more files than this, such as Django or NumPy, take too long
+// to analyze and are left out
+const MAX_PACKAGE_FILES: usize = 300;
+
+// How many re-exports are followed from the name a call uses
+const MAX_REEXPORTS: usize = 8;
+
+/// The packages installed in a `site-packages` directory.
+#[derive(Debug)]
+pub struct SitePackages {
+    dir: PathBuf,
+    // What was learned of each top-level package analyzed so far
+    packages: Mutex<HashMap<String, Arc<Package>>>,
+}
+
+#[derive(Debug, Default)]
+struct Package {
+    // The exceptions of each function, keyed by qualified name such as
+    // `requests.api.get`
+    functions: HashMap<String, HashSet<String>>,
+    // Names package modules import from elsewhere, such as `requests.get`
+    // for `from .api import get` in `requests/__init__.py`
+    reexports: HashMap<String, String>,
+    // The modules each module imports everything from with `import *`
+    star_imports: HashMap<String, Vec<String>>,
+}
+
+impl SitePackages {
+    /// The packages installed in `dir`.
+    pub fn new(dir: &Path) -> Result<Self> {
+        if !dir.is_dir() {
+            bail!("site-packages directory '{}' doesn't exist", dir.display());
+        }
+        Ok(SitePackages {
+            dir: dir.to_path_buf(),
+            packages: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The `site-packages` directory of the active virtual environment, as
+    /// named by `VIRTUAL_ENV`, if any.
+    pub fn detect() -> Option<PathBuf> {
+        let venv = PathBuf::from(env::var_os("VIRTUAL_ENV").filter(|venv| !venv.is_empty())?);
+        let windows = venv.join("Lib").join("site-packages");
+        if windows.is_dir() {
+            return Some(windows);
+        }
+        // `lib/python3.12/site-packages`, whatever the version
+        let mut candidates: Vec<PathBuf> = fs::read_dir(venv.join("lib"))
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path().join("site-packages"))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        candidates.sort();
+        candidates.pop()
+    }
+
+    /// The directory the packages are installed in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // A hash of the directory and of what is installed in it, for telling
+    // apart results obtained before and after installing or upgrading a
+    // package
+    pub(crate) fn fingerprint(&self) -> u64 {
+        let mut entries: Vec<String> = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| {
+                let modified = entry.metadata().and_then(|metadata| metadata.modified());
+                format!("{:?} {:?}", entry.file_name(), modified.ok())
+            })
+            .collect();
+        entries.sort();
+        entries.insert(0, self.dir.display().to_string());
+        let parts: Vec<&str> = entries.iter().map(String::as_str).collect();
+        crate::baseline::stable_hash(&parts)
+    }
+
+    /// Exceptions a call to the function or class with this qualified name,
+    /// such as `requests.get`, may raise.
+    pub(crate) fn call_exceptions(&self, name: &str, config: &Config) -> HashSet<String> {
+        let top = name.split('.').next().unwrap();
+        let package = self.package(top, config.python_version);
+        let mut name = name.to_string();
+        for _ in 0..MAX_REEXPORTS {
+            // Calling a class runs its constructor
+            let found = [
+                name.clone(),
+                format!("{}.__init__", name),
+                format!("{}.__new__", name),
+            ]
+            .into_iter()
+            .find_map(|candidate| package.functions.get(&candidate));
+            if let Some(raises) = found {
+                return raises.clone();
+            }
+            match package.reexported(&name) {
+                Some(target) => name = target,
+                None => break,
+            }
+        }
+        HashSet::new()
+    }
+
+    fn package(&self, top: &str, version: PythonVersion) -> Arc<Package> {
+        if let Some(package) = self.packages.lock().unwrap().get(top) {
+            return package.clone();
+        }
+        // Analyzed outside the lock, so other packages can be looked up
+        // meanwhile; a package may then be analyzed twice, to the same result
+        let package = Arc::new(self.analyze(top, version));
+        self.packages
+            .lock()
+            .unwrap()
+            .insert(top.to_string(), package.clone());
+        package
+    }
+
+    fn analyze(&self, top: &str, version: PythonVersion) -> Package {
+        let mut paths = Vec::new();
+        let module = self.dir.join(format!("{}.py", top));
+        if module.is_file() {
+            paths.push(module);
+        } else {
+            collect_sources(&self.dir.join(top), &mut paths);
+        }
+        if paths.is_empty() || paths.len() > MAX_PACKAGE_FILES {
+            return Package::default();
+        }
+        paths.sort();
+
+        let sources: Vec<(String, String, String)> = paths
+            .iter()
+            .filter_map(|path| {
+                let (source, _) = crate::encoding::read(path).ok()?;
+                let module = crate::module_name(&self.dir, path);
+                Some((path.display().to_string(), module, source))
+            })
+            .collect();
+        let files: Vec<ProjectFile> = sources
+            .iter()
+            .map(|(filename, module, source)| ProjectFile {
+                filename,
+                module,
+                source,
+            })
+            .collect();
+
+        // Only what the package itself raises and the standard library
+        // is counted, not the other packages it calls
+        let config = Config {
+            python_version: version,
+            ..Config::default()
+        };
+        let mut package = Package {
+            functions: function_exceptions(&files, &config),
+            ..Package::default()
+        };
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_python::LANGUAGE.into())
+            .expect("Error loading Python grammar");
+        for file in &files {
+            let tree = parser.parse(file.source, None).unwrap();
+            package.add_imports(file, tree.root_node());
+        }
+        package
+    }
+}
+
+impl Package {
+    // Record the `from ... import ...` statements at the top level of a
+    // module
+    fn add_imports(&mut self, file: &ProjectFile, root: Node) {
+        let text = |node: Node| node.utf8_text(file.source.as_bytes()).unwrap();
+        let mut cursor = root.walk();
+        for import in root
+            .named_children(&mut cursor)
+            .filter(|statement| statement.kind() == "import_from_statement")
+        {
+            let Some(from) = import.child_by_field_name("module_name") else {
+                continue;
+            };
+            let from = absolute_module(file, text(from));
+            let mut cursor = import.walk();
+            let is_star = import
+                .named_children(&mut cursor)
+                .any(|child| child.kind() == "wildcard_import");
+            if is_star {
+                self.star_imports
+                    .entry(file.module.to_string())
+                    .or_default()
+                    .push(from.clone());
+            }
+            let mut cursor = import.walk();
+            for name in import.children_by_field_name("name", &mut cursor) {
+                let (imported, bound) = match name.kind() {
+                    "aliased_import" => (
+                        name.child_by_field_name("name").unwrap(),
+                        name.child_by_field_name("alias").unwrap(),
+                    ),
+                    _ => (name, name),
+                };
+                self.reexports.insert(
+                    format!("{}.{}", file.module, text(bound)),
+                    format!("{}.{}", from, text(imported)),
+                );
+            }
+        }
+    }
+
+    // The name a re-exported name, or a name in a re-exported module, is
+    // defined under, such as `requests.api.get` for `requests.get`
+    fn reexported(&self, name: &str) -> Option<String> {
+        let parts: Vec<&str> = name.split('.').collect();
+        for end in (1..parts.len()).rev() {
+            let (prefix, rest) = (parts[..=end].join("."), &parts[end + 1..]);
+            if let Some(target) = self.reexports.get(&prefix) {
+                return Some(
+                    std::iter::once(target.as_str())
+                        .chain(rest.iter().copied())
+                        .collect::<Vec<_>>()
+                        .join("."),
+                );
+            }
+        }
+        // Names a module gets through `import *` are looked up in the
+        // modules it imports from
+        for end in (1..parts.len()).rev() {
+            let module = parts[..end].join(".");
+            let rest = parts[end..].join(".");
+            for from in self.star_imports.get(&module).into_iter().flatten() {
+                let candidate = format!("{}.{}", from, rest);
+                if self.functions.contains_key(&candidate)
+                    || self.reexports.contains_key(&candidate)
+                {
+                    return Some(candidate);
+                }
+            }
+        }
+        None
+    }
+}
+
+// The module a `from X import ...` of a file imports from, with relative
+// imports such as `.api` resolved against the file's package
+fn absolute_module(file: &ProjectFile, from: &str) -> String {
+    let level = from.len() - from.trim_start_matches('.').len();
+    if level == 0 {
+        return from.to_string();
+    }
+    let mut package: Vec<&str> = file.module.split('.').collect();
+    if !file.filename.ends_with("__init__.py") {
+        package.pop();
+    }
+    for _ in 1..level {
+        package.pop();
+    }
+    let rest = from.trim_start_matches('.');
+    if !rest.is_empty() {
+        package.push(rest);
+    }
+    package.join(".")
+}
+
+// The Python files of a package, leaving out its tests
+fn collect_sources(dir: &Path, paths: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let name = path.file_name().unwrap_or_default();
+        if path.is_dir() {
+            if name != "tests" && name != "test" {
+                collect_sources(&path, paths);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "py") {
+            paths.push(path);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Totals for a run, for tracking how many warnings a codebase carries over
+//! time: files analyzed, files that failed to parse, and warnings by rule,
+//! by exception and by file.
+
+use colored::*;
+use pysleuth::{count_by_rule, Rule, Warning};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Serialize)]
+pub struct Statistics {
+    pub files: usize,
+    /// Files that couldn't be read or contain syntax errors.
+    pub parse_failures: usize,
+    pub warnings: usize,
+    pub by_rule: BTreeMap<Rule, usize>,
+    pub by_exception: BTreeMap<String, usize>,
+    /// The files with the most warnings, most first.
+    pub top_files: Vec<FileCount>,
+}
+
+#[derive(Serialize)]
+pub struct FileCount {
+    pub filename: String,
+    pub warnings: usize,
+}
+
+impl Statistics {
+    /// Totals for the reported `warnings`, keeping the `top` files with the
+    /// most.
+    pub fn new(files: usize, parse_failures: usize, warnings: &[Warning], top: usize) -> Self {
+        let mut by_exception = BTreeMap::new();
+        let mut by_file: HashMap<&str, usize> = HashMap::new();
+        for warning in warnings {
+            for exception in &warning.exceptions {
+                *by_exception.entry(exception.clone()).or_insert(0) += 1;
+            }
+            *by_file.entry(&warning.filename).or_insert(0) += 1;
+        }
+
+        // Ties are broken by name, so the list is the same from run to run
+        let mut top_files: Vec<FileCount> = by_file
+            .into_iter()
+            .map(|(filename, warnings)| FileCount {
+                filename: filename.to_string(),
+                warnings,
+            })
+            .collect();
+        top_files.sort_by(|a, b| {
+            b.warnings
+                .cmp(&a.warnings)
+                .then_with(|| a.filename.cmp(&b.filename))
+        });
+        top_files.truncate(top);
+
+        Statistics {
+            files,
+            parse_failures,
+            warnings: warnings.len(),
+            by_rule: count_by_rule(warnings),
+            by_exception,
+            top_files,
+        }
+    }
+
+    pub fn print(&self) {
+        println!("{}", "Statistics:".bold());
+        println!("  Files analyzed: {}", self.files);
+        println!("  Parse failures: {}", self.parse_failures);
+        println!("  Warnings: {}", self.warnings);
+        if !self.by_rule.is_empty() {
+            println!("  By rule:");
+            for (rule, count) in &self.by_rule {
+                println!("    {} {}: {}", rule, rule.name().dimmed(), count);
+            }
+        }
+        if !self.by_exception.is_empty() {
+            println!("  By exception:");
+            for (exception, count) in &self.by_exception {
+                println!("    {}: {}", exception, count);
+            }
+        }
+        if !self.top_files.is_empty() {
+            println!("  Top files:");
+            for file in &self.top_files {
+                println!("    {}: {}", file.filename, file.warnings);
+            }
+        }
+    }
+}
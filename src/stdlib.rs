@@ -0,0 +1,44 @@
+//! Exceptions raised by common builtin and standard library callables, for
+//! calls to functions that aren't defined in the analyzed module. The table
+//! is embedded from `stdlib.json`; methods of builtin types are listed as
+//! `type.method`, such as `dict.pop`.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+#[derive(Deserialize)]
+struct Entry {
+    name: String,
+    raises: Vec<String>,
+    /// The call can't raise once it has this many arguments, as with a
+    /// default for `dict.pop(key, default)`.
+    safe_with_arguments: Option<usize>,
+}
+
+fn table() -> &'static HashMap<String, Entry> {
+    static TABLE: OnceLock<HashMap<String, Entry>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let entries: Vec<Entry> =
+            serde_json::from_str(include_str!("stdlib.json")).expect("invalid stdlib.json");
+        entries
+            .into_iter()
+            .map(|entry| (entry.name.clone(), entry))
+            .collect()
+    })
+}
+
+/// Exceptions a call to the callable with this qualified name may raise,
+/// given its number of arguments.
+pub(crate) fn call_exceptions(name: &str, arguments: usize) -> &'static [String] {
+    match table().get(name) {
+        Some(entry)
+            if entry
+                .safe_with_arguments
+                .is_none_or(|safe| arguments < safe) =>
+        {
+            &entry.raises
+        }
+        _ => &[],
+    }
+}
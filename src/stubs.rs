@@ -0,0 +1,183 @@
+//! Signatures read from `.pyi` stub files, such as a checkout of typeshed's
+//! `stdlib` directory. Stubs tell which names are classes and what functions
+//! and methods return, so that the receiver of a call such as
+//! `Path(p).resolve().read_text()` can be traced to `pathlib.Path`.
+
+use crate::{encoding, module_name};
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+/// Classes and return types declared by a set of stubs, keyed by qualified
+/// name such as `pathlib.Path` or `pathlib.Path.resolve`.
+#[derive(Debug, Default)]
+pub struct Stubs {
+    classes: HashSet<String>,
+    returns: HashMap<String, String>,
+}
+
+impl Stubs {
+    /// Load every `.pyi` file under `dir`, naming modules by their path
+    /// relative to it.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut stubs = Stubs::default();
+        stubs.load_dir(dir, dir)?;
+        Ok(stubs)
+    }
+
+    fn load_dir(&mut self, root: &Path, dir: &Path) -> Result<()> {
+        let entries = fs::read_dir(dir)
+            .with_context(|| format!("failed to read stub directory '{}'", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.load_dir(root, &path)?;
+            } else if path.extension().is_some_and(|ext| ext == "pyi") {
+                let (source, _) = encoding::read(&path)?;
+                self.add_module(&module_name(root, &path), &source);
+            }
+        }
+        Ok(())
+    }
+
+    // A hash of every declaration, for telling apart results obtained with
+    // different stubs
+    pub(crate) fn fingerprint(&self) -> u64 {
+        let mut declarations: Vec<String> = self.classes.iter().cloned().collect();
+        declarations.extend(
+            self.returns
+                .iter()
+                .map(|(function, returns)| format!("{} -> {}", function, returns)),
+        );
+        declarations.sort();
+        let parts: Vec<&str> = declarations.iter().map(String::as_str).collect();
+        crate::baseline::stable_hash(&parts)
+    }
+
+    /// Record the classes and return types declared by one stub module.
+    pub fn add_module(&mut self, module: &str, source_code: &str) {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_python::LANGUAGE.into())
+            .expect("Error loading Python grammar");
+        let tree = parser.parse(source_code, None).unwrap();
+        let root = tree.root_node();
+
+        let imports = stub_imports(root, source_code);
+        let mut cursor = root.walk();
+        let mut classes = Vec::new();
+        let mut functions = Vec::new();
+        for statement in root.named_children(&mut cursor) {
+            let definition = definition_of(statement);
+            match definition.kind() {
+                "class_definition" => classes.push(definition),
+                "function_definition" => functions.push((definition, None)),
+                _ => {}
+            }
+        }
+
+        let class_names: HashSet<&str> = classes
+            .iter()
+            .filter_map(|class| class.child_by_field_name("name"))
+            .map(|name| name.utf8_text(source_code.as_bytes()).unwrap())
+            .collect();
+        for class in &classes {
+            let name = class.child_by_field_name("name").unwrap();
+            let name = name.utf8_text(source_code.as_bytes()).unwrap();
+            self.classes.insert(format!("{}.{}", module, name));
+            let body = class.child_by_field_name("body").unwrap();
+            let mut cursor = body.walk();
+            for statement in body.named_children(&mut cursor) {
+                let definition = definition_of(statement);
+                if definition.kind() == "function_definition" {
+                    functions.push((definition, Some(name)));
+                }
+            }
+        }
+
+        for (function, class) in functions {
+            let Some(return_type) = function.child_by_field_name("return_type") else {
+                continue;
+            };
+            let name = function.child_by_field_name("name").unwrap();
+            let name = name.utf8_text(source_code.as_bytes()).unwrap();
+            let qualified = match class {
+                Some(class) => format!("{}.{}.{}", module, class, name),
+                None => format!("{}.{}", module, name),
+            };
+
+            // Only the base of a generic such as `list[str]` matters
+            let text = return_type.utf8_text(source_code.as_bytes()).unwrap();
+            let base = text.split('[').next().unwrap().trim();
+            let resolved = match base {
+                "Self" => class.map(|class| format!("{}.{}", module, class)),
+                "None" | "Any" | "NoReturn" | "Never" => None,
+                _ if text.contains('|') || base.ends_with("Optional") => None,
+                _ if class_names.contains(base) => Some(format!("{}.{}", module, base)),
+                _ => Some(
+                    imports
+                        .get(base)
+                        .cloned()
+                        .unwrap_or_else(|| base.to_string()),
+                ),
+            };
+            if let Some(resolved) = resolved {
+                // Overloads share a name; the first declaration wins
+                self.returns.entry(qualified).or_insert(resolved);
+            }
+        }
+    }
+
+    /// Whether the qualified name is a class declared by the stubs.
+    pub(crate) fn is_class(&self, name: &str) -> bool {
+        self.classes.contains(name)
+    }
+
+    /// The qualified return type of a function or method, if the stubs
+    /// declare one that isn't None or a union.
+    pub(crate) fn return_type(&self, name: &str) -> Option<&str> {
+        self.returns.get(name).map(|name| name.as_str())
+    }
+}
+
+// Decorated definitions such as `@overload` wrap the definition itself
+fn definition_of(statement: Node) -> Node {
+    if statement.kind() == "decorated_definition" {
+        statement
+            .child_by_field_name("definition")
+            .unwrap_or(statement)
+    } else {
+        statement
+    }
+}
+
+// Names bound by `from module import name` in a stub, mapped to their
+// qualified names
+fn stub_imports(root: Node, source_code: &str) -> HashMap<String, String> {
+    let mut imports = HashMap::new();
+    let mut cursor = root.walk();
+    for statement in root.named_children(&mut cursor) {
+        if statement.kind() != "import_from_statement" {
+            continue;
+        }
+        let module = statement.child_by_field_name("module_name").unwrap();
+        let module = module.utf8_text(source_code.as_bytes()).unwrap();
+        let mut cursor = statement.walk();
+        for name in statement.children_by_field_name("name", &mut cursor) {
+            let (imported, bound) = if name.kind() == "aliased_import" {
+                (
+                    name.child_by_field_name("name").unwrap(),
+                    name.child_by_field_name("alias").unwrap(),
+                )
+            } else {
+                (name, name)
+            };
+            let imported = imported.utf8_text(source_code.as_bytes()).unwrap();
+            let bound = bound.utf8_text(source_code.as_bytes()).unwrap();
+            imports.insert(bound.to_string(), format!("{}.{}", module, imported));
+        }
+    }
+    imports
+}
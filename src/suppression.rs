@@ -0,0 +1,141 @@
+//! Inline suppression comments. `# pywrong: ignore` silences every warning
+//! on its line, `# pywrong: ignore[KeyError, PW005]` only those for the
+//! listed exceptions or rules, and `# pywrong: skip-file` anywhere in a file
+//! silences the whole file. Suppressions that silence nothing are reported,
+//! so they don't outlive the code they were written for.
+
+use crate::{make_warning, Config, Rule, Warning};
+use tree_sitter::Node;
+
+struct Suppression<'a> {
+    comment: Node<'a>,
+    line: usize,
+    // `None` for a bare `ignore`, which silences everything
+    codes: Option<Vec<String>>,
+}
+
+enum Directive {
+    Ignore(Option<Vec<String>>),
+    SkipFile,
+}
+
+/// The file's warnings without the suppressed ones, plus warnings for
+/// unused suppressions.
+pub(crate) fn apply(
+    warnings: Vec<Warning>,
+    root: Node,
+    source_code: &str,
+    filename: &str,
+    config: &Config,
+) -> Vec<Warning> {
+    let mut comments = Vec::new();
+    collect_comments(root, &mut comments);
+
+    let mut suppressions = Vec::new();
+    for comment in comments {
+        let text = comment.utf8_text(source_code.as_bytes()).unwrap();
+        match parse_directive(text) {
+            Some(Directive::SkipFile) => return Vec::new(),
+            Some(Directive::Ignore(codes)) => suppressions.push(Suppression {
+                comment,
+                line: comment.start_position().row + 1,
+                codes,
+            }),
+            None => {}
+        }
+    }
+    if suppressions.is_empty() {
+        return warnings;
+    }
+
+    // The codes of each suppression that silenced a warning
+    let mut used: Vec<Vec<&str>> = vec![Vec::new(); suppressions.len()];
+    let mut kept = Vec::new();
+    for warning in warnings {
+        let mut suppressed = false;
+        for (suppression, used) in suppressions.iter().zip(&mut used) {
+            if suppression.line != warning.line {
+                continue;
+            }
+            match &suppression.codes {
+                None => {
+                    suppressed = true;
+                    used.push("");
+                }
+                Some(codes) => {
+                    for code in codes {
+                        let matches = code.eq_ignore_ascii_case(warning.rule.code())
+                            || warning.exceptions.iter().any(|exception| exception == code);
+                        if matches {
+                            suppressed = true;
+                            used.push(code);
+                        }
+                    }
+                }
+            }
+        }
+        if !suppressed {
+            kept.push(warning);
+        }
+    }
+
+    if config.is_enabled(Rule::UnusedSuppression) {
+        for (suppression, used) in suppressions.iter().zip(&used) {
+            let message = match &suppression.codes {
+                None if used.is_empty() => "Unused suppression comment".to_string(),
+                None => continue,
+                Some(codes) => {
+                    let unused: Vec<&str> = codes
+                        .iter()
+                        .map(|code| code.as_str())
+                        .filter(|code| !used.contains(code))
+                        .collect();
+                    if unused.is_empty() {
+                        continue;
+                    }
+                    format!("Unused suppression for {}", unused.join(", "))
+                }
+            };
+            kept.push(make_warning(
+                suppression.comment,
+                filename,
+                Rule::UnusedSuppression,
+                ("<module>", Vec::new()),
+                message,
+                None,
+            ));
+        }
+    }
+    kept
+}
+
+fn collect_comments<'a>(node: Node<'a>, comments: &mut Vec<Node<'a>>) {
+    if node.kind() == "comment" {
+        comments.push(node);
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_comments(child, comments);
+    }
+}
+
+// The directive of a comment, which may follow other text, as in
+// `# noqa  # pywrong: ignore[KeyError]`
+fn parse_directive(comment: &str) -> Option<Directive> {
+    let (_, rest) = comment.split_once("pywrong:")?;
+    let rest = rest.trim();
+    if rest.starts_with("skip-file") {
+        return Some(Directive::SkipFile);
+    }
+    let rest = rest.strip_prefix("ignore")?;
+    let Some(list) = rest.strip_prefix('[') else {
+        return Some(Directive::Ignore(None));
+    };
+    let (list, _) = list.split_once(']')?;
+    let codes = list
+        .split(',')
+        .map(|code| code.trim().to_string())
+        .filter(|code| !code.is_empty())
+        .collect();
+    Some(Directive::Ignore(Some(codes)))
+}
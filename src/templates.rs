@@ -0,0 +1,76 @@
+//! Placeholders of string templates, which `str.format_map(m)`,
+//! `str.format(**m)` and `string.Template.substitute(m)` look up in a
+//! mapping, raising KeyError for those it lacks.
+
+/// The named replacement fields of a `str.format` template, such as `name`
+/// for `"{name!r:>10}"` or `user` for `"{user.name}"`, including those
+/// nested in format specs. Positional fields like `{}` or `{0}` aren't
+/// looked up in the mapping and are left out.
+pub(crate) fn format_fields(template: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut chars = template.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            // `{{` and `}}` are literal braces
+            '{' if chars.peek().is_some_and(|&(_, next)| next == '{') => {
+                chars.next();
+            }
+            '{' => {
+                // A field ends at the brace closing it, past nested ones
+                let mut depth = 1;
+                let mut end = template.len();
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        end = i;
+                        break;
+                    }
+                }
+                let field = &template[start + 1..end];
+                let name_end = field.find(['!', ':', '.', '[']).unwrap_or(field.len());
+                let name = &field[..name_end];
+                if !name.is_empty() && !name.bytes().all(|c| c.is_ascii_digit()) {
+                    fields.push(name.to_string());
+                }
+                if let Some(spec) = field.find(':').map(|colon| &field[colon + 1..]) {
+                    fields.extend(format_fields(spec));
+                }
+            }
+            _ => {}
+        }
+    }
+    fields
+}
+
+/// The placeholders of a `string.Template`, such as `name` for `"$name"`
+/// or `"${name}"`; `$$` is a literal dollar sign.
+pub(crate) fn template_placeholders(template: &str) -> Vec<String> {
+    let identifier = |text: &str| -> usize {
+        text.char_indices()
+            .find(|&(i, c)| !(c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit())))
+            .map_or(text.len(), |(i, _)| i)
+    };
+    let mut placeholders = Vec::new();
+    let mut rest = template;
+    while let Some(dollar) = rest.find('$') {
+        rest = &rest[dollar + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            rest = after;
+        } else if let Some(braced) = rest.strip_prefix('{') {
+            let length = identifier(braced);
+            if length > 0 && braced[length..].starts_with('}') {
+                placeholders.push(braced[..length].to_string());
+            }
+        } else {
+            let length = identifier(rest);
+            if length > 0 {
+                placeholders.push(rest[..length].to_string());
+            }
+        }
+    }
+    placeholders
+}
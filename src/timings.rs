@@ -0,0 +1,106 @@
+//! Where the time of a run goes, for finding out why it is slow on a
+//! codebase: the time spent in each phase of the analysis, and the files
+//! that took longest.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A phase of the analysis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading files, and the code cells of notebooks.
+    Read,
+    /// Parsing sources into syntax trees.
+    Parse,
+    /// Collecting the functions of each file and what they raise directly.
+    Collect,
+    /// Propagating exceptions along calls until nothing changes.
+    Propagate,
+    /// Finding the warnings to report.
+    Report,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::Read,
+        Phase::Parse,
+        Phase::Collect,
+        Phase::Propagate,
+        Phase::Report,
+    ];
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Read => "file IO",
+            Phase::Parse => "parsing",
+            Phase::Collect => "function collection",
+            Phase::Propagate => "propagation",
+            Phase::Report => "reporting",
+        })
+    }
+}
+
+/// The time spent in each phase, summed over the threads analyzing files,
+/// and the time each file took. Shared between the threads.
+#[derive(Debug, Default)]
+pub struct Timings {
+    // Indexed by phase
+    phases: Mutex<[Duration; Phase::ALL.len()]>,
+    files: Mutex<HashMap<String, Duration>>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Timings::default()
+    }
+
+    /// Run `f`, counting its time in `phase`.
+    pub fn time<T>(&self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.add(phase, started.elapsed());
+        result
+    }
+
+    /// Count `duration` in `phase`.
+    pub fn add(&self, phase: Phase, duration: Duration) {
+        self.phases.lock().unwrap()[phase as usize] += duration;
+    }
+
+    /// Count `duration` as time taken by `filename`.
+    pub fn add_file(&self, filename: &str, duration: Duration) {
+        *self
+            .files
+            .lock()
+            .unwrap()
+            .entry(filename.to_string())
+            .or_default() += duration;
+    }
+
+    /// The time spent in each phase, in the order they run.
+    pub fn phases(&self) -> Vec<(Phase, Duration)> {
+        let phases = self.phases.lock().unwrap();
+        Phase::ALL
+            .into_iter()
+            .map(|phase| (phase, phases[phase as usize]))
+            .collect()
+    }
+
+    /// The `count` files that took longest, slowest first.
+    pub fn slowest_files(&self, count: usize) -> Vec<(String, Duration)> {
+        let mut files: Vec<(String, Duration)> = self
+            .files
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(filename, duration)| (filename.clone(), *duration))
+            .collect();
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        files.truncate(count);
+        files
+    }
+}
@@ -0,0 +1,574 @@
+//! An interactive terminal UI for triaging warnings, as when first adopting
+//! the linter on a large codebase: browse them by file, read the code they
+//! point at, follow the calls the exception comes through, and silence the
+//! acceptable ones with an inline comment or a baseline entry.
+//!
+//! The terminal is put in raw mode with `stty`. Keys are read from stdin
+//! whether or not it is a terminal, so a session can also be scripted.
+
+use crate::{severity_label, span_text};
+use anyhow::{bail, Context, Result};
+use clap::ArgMatches;
+use colored::*;
+use pysleuth::baseline::Baseline;
+use pysleuth::discovery::Discovery;
+use pysleuth::notebook;
+use pysleuth::{module_name, Analyzer, SourceFile, Warning};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const LIST_HELP: &str =
+    "j/k move  enter details  i ignore inline  b add to baseline  / filter  q quit";
+const DETAIL_HELP: &str =
+    "n/p step along the calls  i ignore inline  b add to baseline  esc back  q quit";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+    Backspace,
+    Interrupt,
+    Char(char),
+}
+
+// A step from the call to where the exception is raised: its file and
+// cell, the start and end of its span, and its label
+type Step<'a> = (
+    &'a str,
+    Option<usize>,
+    (usize, usize),
+    (usize, usize),
+    &'a str,
+);
+
+enum View {
+    List,
+    // The step along the warning's chain shown, 0 being the warning itself
+    Detail(usize),
+}
+
+struct State {
+    files: HashMap<String, SourceFile>,
+    // The warnings not suppressed yet, ordered by file and position
+    warnings: Vec<Warning>,
+    baseline: Option<PathBuf>,
+    filter: String,
+    // The filter being typed, applied on enter
+    editing_filter: Option<String>,
+    // The index of the selected warning among those the filter shows
+    selected: usize,
+    view: View,
+    status: String,
+}
+
+/// Analyze the files of the `tui` subcommand and triage their warnings
+/// until the user quits or the input ends.
+pub fn run(matches: &ArgMatches, discovery: &Discovery, analyzer: &Analyzer) -> Result<()> {
+    let project_root = matches.get_one::<PathBuf>("project");
+    let paths = match project_root {
+        Some(root) => discovery.files(std::slice::from_ref(root))?,
+        None => {
+            let files: Vec<PathBuf> = matches
+                .get_many::<String>("files")
+                .into_iter()
+                .flatten()
+                .map(PathBuf::from)
+                .collect();
+            discovery.files(&files)?
+        }
+    };
+    if paths.iter().any(|path| path.as_os_str() == "-") {
+        bail!("the TUI reads keys from stdin, so it can't also read source from it");
+    }
+
+    let sources = paths
+        .iter()
+        .map(|path| analyzer.read(path))
+        .collect::<Result<Vec<_>>>()?;
+    let mut warnings: Vec<Warning> = match project_root {
+        Some(root) => {
+            let modules: Vec<String> = paths.iter().map(|path| module_name(root, path)).collect();
+            analyzer.analyze_project(&sources, &modules).concat()
+        }
+        None => analyzer.analyze_files(&sources).concat(),
+    };
+    let files: HashMap<String, SourceFile> = sources
+        .into_iter()
+        .map(|file| (file.filename.clone(), file))
+        .collect();
+
+    // Warnings already in the baseline were triaged before
+    let baseline_path = matches.get_one::<PathBuf>("baseline").cloned();
+    let baseline = match &baseline_path {
+        Some(path) if path.exists() => Baseline::load(path)?,
+        _ => Baseline::default(),
+    };
+    warnings
+        .retain(|warning| !baseline.contains(warning, files[&warning.filename].source_of(warning)));
+    warnings.sort_by(|a, b| {
+        (&a.filename, a.cell, a.line, a.column).cmp(&(&b.filename, b.cell, b.line, b.column))
+    });
+
+    let mut state = State {
+        files,
+        warnings,
+        baseline: baseline_path,
+        filter: String::new(),
+        editing_filter: None,
+        selected: 0,
+        view: View::List,
+        status: String::new(),
+    };
+    let terminal = Terminal::enter()?;
+    let mut stdin = io::stdin().lock();
+    let mut buffer = [0; 64];
+    loop {
+        let (height, width) = terminal.size();
+        let lines: Vec<String> = state.render(height, width);
+        print!("\x1b[H\x1b[2J{}", lines.join("\r\n"));
+        io::stdout().flush()?;
+        let count = stdin.read(&mut buffer)?;
+        if count == 0 {
+            return Ok(());
+        }
+        for key in keys(&buffer[..count]) {
+            if !state.handle(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl State {
+    // Whether to go on after the key
+    fn handle(&mut self, key: Key) -> bool {
+        if let Some(filter) = &mut self.editing_filter {
+            match key {
+                Key::Enter => {
+                    self.filter = self.editing_filter.take().unwrap();
+                    self.selected = 0;
+                }
+                Key::Escape => self.editing_filter = None,
+                Key::Backspace => {
+                    filter.pop();
+                }
+                Key::Interrupt => return false,
+                Key::Char(c) if !c.is_control() => filter.push(c),
+                _ => {}
+            }
+            return true;
+        }
+        self.status.clear();
+        let count = self.visible().len();
+        match (key, &self.view) {
+            (Key::Char('q') | Key::Interrupt, _) => return false,
+            (Key::Char('j') | Key::Down, View::List) => {
+                self.selected = (self.selected + 1).min(count.saturating_sub(1))
+            }
+            (Key::Char('k') | Key::Up, View::List) => {
+                self.selected = self.selected.saturating_sub(1)
+            }
+            (Key::Enter | Key::Right, View::List) if count > 0 => self.view = View::Detail(0),
+            (Key::Char('/'), View::List) => self.editing_filter = Some(self.filter.clone()),
+            (Key::Char('n') | Key::Right | Key::Down, View::Detail(step)) => {
+                let steps = self.current().map_or(0, |warning| warning.related.len());
+                self.view = View::Detail((step + 1).min(steps));
+            }
+            (Key::Char('p') | Key::Left | Key::Up, View::Detail(step)) => {
+                self.view = View::Detail(step.saturating_sub(1))
+            }
+            (Key::Escape | Key::Backspace, View::Detail(_)) => self.view = View::List,
+            (Key::Char('i'), _) => self.ignore_inline(),
+            (Key::Char('b'), _) => self.add_to_baseline(),
+            _ => {}
+        }
+        true
+    }
+
+    // The indices of the warnings the filter shows, which matches the code
+    // or name of their rule or one of their exceptions
+    fn visible(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        (0..self.warnings.len())
+            .filter(|&index| {
+                let warning = &self.warnings[index];
+                filter.is_empty()
+                    || [warning.rule.code(), warning.rule.name()]
+                        .into_iter()
+                        .chain(warning.exceptions.iter().map(String::as_str))
+                        .any(|name| name.to_lowercase().contains(&filter))
+            })
+            .collect()
+    }
+
+    fn current_index(&self) -> Option<usize> {
+        self.visible().get(self.selected).copied()
+    }
+
+    fn current(&self) -> Option<&Warning> {
+        self.current_index().map(|index| &self.warnings[index])
+    }
+
+    // Write a `# pywrong: ignore[CODE]` comment on the selected warning's
+    // line
+    fn ignore_inline(&mut self) {
+        let Some(index) = self.current_index() else {
+            return;
+        };
+        let warning = &self.warnings[index];
+        let file = self.files.get_mut(&warning.filename).unwrap();
+        if file.notebook.is_some() {
+            self.status = "Comments can't be written into notebook cells; press b to add the warning to the baseline".to_string();
+            return;
+        }
+        let code = warning.rule.code();
+        let Some(source_code) = with_ignore(&file.source_code, warning.line, code) else {
+            self.status = format!(
+                "Line {} continues on the next one, so it can't end in a comment; press b to add the warning to the baseline",
+                warning.line
+            );
+            return;
+        };
+        let written = file
+            .encoding
+            .encode(&source_code)
+            .and_then(|bytes| Ok(fs::write(&warning.filename, bytes)?));
+        if let Err(e) = written {
+            self.status = format!("Failed to write '{}': {:#}", warning.filename, e);
+            return;
+        }
+        file.source_code = source_code;
+        self.status = format!(
+            "Ignored {} on line {} of '{}'",
+            code, warning.line, warning.filename
+        );
+        self.remove(index);
+    }
+
+    fn add_to_baseline(&mut self) {
+        let Some(index) = self.current_index() else {
+            return;
+        };
+        let Some(path) = &self.baseline else {
+            self.status = "Give --baseline FILE to record warnings in a baseline".to_string();
+            return;
+        };
+        let warning = &self.warnings[index];
+        let file = &self.files[&warning.filename];
+        let source_code = file.source_of(warning);
+        match Baseline::append(path, &[(warning.clone(), source_code)]) {
+            Ok(()) => {
+                self.status = format!("Added the warning to baseline '{}'", path.display());
+                self.remove(index);
+            }
+            Err(e) => self.status = format!("Error: {:#}", e),
+        }
+    }
+
+    // Drop a suppressed warning, selecting the one after it
+    fn remove(&mut self, index: usize) {
+        self.warnings.remove(index);
+        self.selected = self.selected.min(self.visible().len().saturating_sub(1));
+        self.view = View::List;
+    }
+
+    fn render(&self, height: usize, width: usize) -> Vec<String> {
+        let mut lines = match self.view {
+            View::List => self.render_list(height.saturating_sub(1), width),
+            View::Detail(step) => self.render_detail(step, height.saturating_sub(1), width),
+        };
+        let footer = match (&self.editing_filter, &self.view) {
+            (Some(filter), _) => format!("Filter by rule or exception: {}_", filter),
+            _ if !self.status.is_empty() => self.status.clone(),
+            (None, View::List) => LIST_HELP.to_string(),
+            (None, View::Detail(_)) => DETAIL_HELP.to_string(),
+        };
+        lines.resize(height.saturating_sub(1), String::new());
+        lines.push(fit(&footer, width).reversed().to_string());
+        lines
+    }
+
+    // The warnings under a header per file, scrolled to the selected one
+    fn render_list(&self, height: usize, width: usize) -> Vec<String> {
+        let visible = self.visible();
+        let mut title = format!("{} warning(s)", visible.len());
+        if !self.filter.is_empty() {
+            title.push_str(&format!(" matching '{}'", self.filter));
+        }
+        let mut lines = vec![fit(&title, width).bold().to_string()];
+        if visible.is_empty() {
+            lines.push("Nothing left to triage".to_string());
+            return lines;
+        }
+
+        let mut rows = Vec::new();
+        let mut selected_row = 0;
+        for (position, &index) in visible.iter().enumerate() {
+            let warning = &self.warnings[index];
+            let is_first =
+                position == 0 || self.warnings[visible[position - 1]].filename != warning.filename;
+            if is_first {
+                let count = visible
+                    .iter()
+                    .filter(|&&other| self.warnings[other].filename == warning.filename)
+                    .count();
+                let header = format!("{} ({})", warning.filename, count);
+                rows.push(fit(&header, width).bold().to_string());
+            }
+            let marker = if position == self.selected { '>' } else { ' ' };
+            let row = format!(
+                "{} {} {} {} [{}]",
+                marker,
+                notebook::position(warning.cell, warning.line, warning.column),
+                warning.severity,
+                warning.message,
+                warning.rule
+            );
+            let row = fit(&row, width);
+            if position == self.selected {
+                selected_row = rows.len();
+                rows.push(row.reversed().to_string());
+            } else {
+                rows.push(row);
+            }
+        }
+        let room = height.saturating_sub(lines.len()).max(1);
+        let offset = (selected_row + 1).saturating_sub(room);
+        lines.extend(rows.into_iter().skip(offset).take(room));
+        lines
+    }
+
+    // The selected warning, the code around one step of the chain from the
+    // call to where the exception is raised, and the steps
+    fn render_detail(&self, step: usize, height: usize, width: usize) -> Vec<String> {
+        let Some(warning) = self.current() else {
+            return Vec::new();
+        };
+        let header = format!(
+            "{}:{}: {}",
+            warning.filename,
+            notebook::position(warning.cell, warning.line, warning.column),
+            warning.message
+        );
+        let mut lines = vec![format!(
+            "{} {}",
+            severity_label(warning.severity),
+            fit(
+                &header,
+                width.saturating_sub(warning.severity.to_string().len() + 2)
+            )
+        )];
+
+        let steps: Vec<Step> = std::iter::once((
+            warning.filename.as_str(),
+            warning.cell,
+            (warning.line, warning.column),
+            (warning.end_line, warning.end_column),
+            "",
+        ))
+        .chain(warning.related.iter().map(|related| {
+            (
+                related.filename.as_str(),
+                related.cell,
+                (related.line, related.column),
+                (related.end_line, related.end_column),
+                related.message.as_str(),
+            )
+        }))
+        .collect();
+        let (filename, cell, start, end, label) = steps[step];
+        let title = format!(
+            "Step {} of {}: {}:{} {}",
+            step + 1,
+            steps.len(),
+            filename,
+            notebook::position(cell, start.0, start.1),
+            label
+        );
+        lines.push(fit(title.trim_end(), width).bold().to_string());
+
+        // The code around the step, with the line of the step marked
+        let source_lines = self.lines(filename, cell);
+        let room = height.saturating_sub(lines.len() + steps.len() + 3).max(3);
+        let first = start.0.saturating_sub(room / 2).max(1);
+        let last = (first + room - 2).min(source_lines.len());
+        let gutter = last.to_string().len();
+        for number in first..=last {
+            let code = source_lines.get(number - 1).unwrap_or(&"");
+            let line = fit(&format!("{:>gutter$} | {}", number, code), width);
+            if number == start.0 {
+                let length = match end.0 == start.0 {
+                    true => end.1.saturating_sub(start.1).max(1),
+                    false => 1,
+                };
+                let underline = format!(
+                    "{:gutter$} | {}{}",
+                    "",
+                    " ".repeat(start.1 - 1),
+                    "^".repeat(length)
+                );
+                lines.push(line.bold().to_string());
+                lines.push(fit(&underline, width).bright_red().to_string());
+            } else {
+                lines.push(line.dimmed().to_string());
+            }
+        }
+
+        lines.push(String::new());
+        for (index, (filename, cell, start, end, _)) in steps.iter().enumerate() {
+            let text = span_text(&self.lines(filename, *cell), *start, *end);
+            let marker = if index == step { '>' } else { ' ' };
+            let row = format!(
+                "{} {} at {}:{}",
+                marker,
+                text,
+                filename,
+                notebook::position(*cell, start.0, start.1)
+            );
+            let row = fit(&row, width);
+            lines.push(match index == step {
+                true => row.reversed().to_string(),
+                false => row,
+            });
+        }
+        lines
+    }
+
+    fn lines(&self, filename: &str, cell: Option<usize>) -> Vec<&str> {
+        self.files.get(filename).map_or(Vec::new(), |file| {
+            let source_code = match (&file.notebook, cell) {
+                (Some(notebook), Some(cell)) => notebook.cell_source(cell),
+                _ => &file.source_code,
+            };
+            source_code.lines().collect()
+        })
+    }
+}
+
+// The source with a comment ignoring `code` at the end of a 1-based line,
+// or with `code` added to the `# pywrong: ignore[...]` comment already
+// there. A line continued with a backslash can't take a comment.
+fn with_ignore(source_code: &str, line: usize, code: &str) -> Option<String> {
+    let lines: Vec<&str> = source_code.split_inclusive('\n').collect();
+    let text = lines.get(line - 1)?;
+    let content = text.trim_end_matches(['\r', '\n']);
+    let edited = match content.find("pywrong: ignore[") {
+        Some(start) => {
+            let end = start + content[start..].find(']')?;
+            format!("{}, {}{}", &content[..end], code, &content[end..])
+        }
+        None if content.trim_end().ends_with('\\') => return None,
+        None => format!("{}  # pywrong: ignore[{}]", content.trim_end(), code),
+    };
+    Some(format!(
+        "{}{}{}{}",
+        lines[..line - 1].concat(),
+        edited,
+        &text[content.len()..],
+        lines[line..].concat()
+    ))
+}
+
+// Text cut to the width of the terminal, so it doesn't wrap
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+// The keys of the bytes read at once, where the escape sequence of an
+// arrow key comes whole
+fn keys(bytes: &[u8]) -> Vec<Key> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    let mut keys = Vec::new();
+    while let Some(c) = chars.next() {
+        keys.push(match c {
+            '\x1b' if chars.peek() == Some(&'[') => {
+                chars.next();
+                match chars.next() {
+                    Some('A') => Key::Up,
+                    Some('B') => Key::Down,
+                    Some('C') => Key::Right,
+                    Some('D') => Key::Left,
+                    _ => continue,
+                }
+            }
+            '\x1b' => Key::Escape,
+            '\r' | '\n' => Key::Enter,
+            '\x7f' | '\x08' => Key::Backspace,
+            '\x03' => Key::Interrupt,
+            c => Key::Char(c),
+        });
+    }
+    keys
+}
+
+// The terminal in raw mode, showing the TUI in place of the screen until
+// dropped. Nothing is changed when stdin isn't a terminal.
+struct Terminal {
+    saved: Option<String>,
+}
+
+impl Terminal {
+    fn enter() -> Result<Self> {
+        if !io::stdin().is_terminal() {
+            return Ok(Terminal { saved: None });
+        }
+        let saved = stty(&["-g"])?.trim().to_string();
+        // A read returns a whole escape sequence, or an escape key on its
+        // own after a tenth of a second
+        stty(&["raw", "-echo", "min", "1", "time", "1"])?;
+        print!("\x1b[?1049h\x1b[?25l");
+        Ok(Terminal { saved: Some(saved) })
+    }
+
+    // The rows and columns of the terminal
+    fn size(&self) -> (usize, usize) {
+        let from_stty = self.saved.as_ref().and_then(|_| {
+            let size = stty(&["size"]).ok()?;
+            let (rows, columns) = size.trim().split_once(' ')?;
+            Some((rows.parse().ok()?, columns.parse().ok()?))
+        });
+        from_stty.unwrap_or_else(|| {
+            let variable = |name: &str, default: usize| {
+                env::var(name)
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(default)
+            };
+            (variable("LINES", 24), variable("COLUMNS", 80))
+        })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if let Some(saved) = &self.saved {
+            let _ = stty(&[saved]);
+            print!("\x1b[?25h\x1b[?1049l");
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+fn stty(args: &[&str]) -> Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .context("failed to run stty")?;
+    if !output.status.success() {
+        bail!(
+            "stty failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
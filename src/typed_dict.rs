@@ -0,0 +1,124 @@
+//! TypedDict classes defined in the analyzed module, whose declared fields
+//! tell which keys a subscript on an annotated value can rely on.
+
+use std::collections::HashMap;
+use tree_sitter::Node;
+
+/// What a TypedDict declares about a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Field {
+    Required,
+    NotRequired,
+    Undeclared,
+}
+
+/// The fields of each TypedDict class in a module, keyed by class name,
+/// with whether each one is required. Fields are inherited from TypedDict
+/// bases defined earlier in the module.
+pub(crate) fn module_typed_dicts(
+    root: Node,
+    source_code: &str,
+) -> HashMap<String, HashMap<String, bool>> {
+    let text = |node: Node| node.utf8_text(source_code.as_bytes()).unwrap();
+    let mut typed_dicts: HashMap<String, HashMap<String, bool>> = HashMap::new();
+    let mut classes = Vec::new();
+    collect_class_definitions(root, &mut classes);
+    for class in classes {
+        let (Some(name), Some(superclasses), Some(body)) = (
+            class.child_by_field_name("name"),
+            class.child_by_field_name("superclasses"),
+            class.child_by_field_name("body"),
+        ) else {
+            continue;
+        };
+
+        // `class Movie(TypedDict, total=False)`
+        let mut fields = HashMap::new();
+        let mut is_typed_dict = false;
+        let mut total = true;
+        let mut cursor = superclasses.walk();
+        for base in superclasses.named_children(&mut cursor) {
+            match base.kind() {
+                "identifier" | "attribute" => {
+                    let base = text(base);
+                    if matches!(base.rsplit('.').next(), Some("TypedDict")) {
+                        is_typed_dict = true;
+                    } else if let Some(inherited) = typed_dicts.get(base) {
+                        is_typed_dict = true;
+                        fields.extend(inherited.clone());
+                    }
+                }
+                "keyword_argument" => {
+                    let (Some(keyword), Some(value)) = (
+                        base.child_by_field_name("name"),
+                        base.child_by_field_name("value"),
+                    ) else {
+                        continue;
+                    };
+                    if text(keyword) == "total" && text(value) == "False" {
+                        total = false;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !is_typed_dict {
+            continue;
+        }
+
+        // Fields are annotations without a value: `title: str`
+        let mut cursor = body.walk();
+        for statement in body.named_children(&mut cursor) {
+            let Some(assignment) = statement
+                .named_child(0)
+                .filter(|child| child.kind() == "assignment")
+            else {
+                continue;
+            };
+            let (Some(field), Some(annotation)) = (
+                assignment.child_by_field_name("left"),
+                assignment.child_by_field_name("type"),
+            ) else {
+                continue;
+            };
+            if field.kind() != "identifier" || assignment.child_by_field_name("right").is_some() {
+                continue;
+            }
+            // `Required[T]` and `NotRequired[T]` override the class's totality
+            let wrapper = text(annotation).split('[').next().unwrap_or_default();
+            let required = match wrapper.rsplit('.').next() {
+                Some("Required") => true,
+                Some("NotRequired") => false,
+                _ => total,
+            };
+            fields.insert(text(field).to_string(), required);
+        }
+        typed_dicts.insert(text(name).to_string(), fields);
+    }
+    typed_dicts
+}
+
+/// What the TypedDict named `class` declares about `key`, or `None` if it
+/// isn't a TypedDict of the module.
+pub(crate) fn field(
+    typed_dicts: &HashMap<String, HashMap<String, bool>>,
+    class: &str,
+    key: &str,
+) -> Option<Field> {
+    let fields = typed_dicts.get(class)?;
+    Some(match fields.get(key) {
+        Some(true) => Field::Required,
+        Some(false) => Field::NotRequired,
+        None => Field::Undeclared,
+    })
+}
+
+fn collect_class_definitions<'a>(node: Node<'a>, classes: &mut Vec<Node<'a>>) {
+    if node.kind() == "class_definition" {
+        classes.push(node);
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_class_definitions(child, classes);
+    }
+}
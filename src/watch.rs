@@ -0,0 +1,119 @@
+//! Watch mode: analyze a directory, then poll it for saved files and
+//! re-analyze just those, printing the warnings of the files that changed
+//! and a summary for the whole directory.
+//!
+//! The directory is polled rather than watched with a filesystem notifier.
+//! It is walked again `MIN_POLL_INTERVAL` after a change and less and less
+//! often while nothing changes, up to every `MAX_POLL_INTERVAL`, so an idle
+//! watch costs little CPU even on large trees, at the price of the first
+//! change after a quiet spell taking a little longer to show up.
+
+use crate::{print_warning, SourceLines};
+use anyhow::Result;
+use pysleuth::baseline::Baseline;
+use pysleuth::discovery::Discovery;
+use pysleuth::{Analyzer, SourceFile, Warning};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+struct WatchedFile {
+    modified: SystemTime,
+    file: SourceFile,
+    warnings: Vec<Warning>,
+}
+
+/// Watch `dir` until interrupted.
+pub fn run(
+    dir: &Path,
+    discovery: &Discovery,
+    analyzer: &Analyzer,
+    baseline: &Baseline,
+) -> Result<()> {
+    let mut files: BTreeMap<PathBuf, WatchedFile> = BTreeMap::new();
+    let mut poll_interval = MIN_POLL_INTERVAL;
+    loop {
+        let paths: HashSet<PathBuf> = discovery
+            .files(std::slice::from_ref(&dir.to_path_buf()))?
+            .into_iter()
+            .collect();
+        let removed: Vec<PathBuf> = files
+            .keys()
+            .filter(|path| !paths.contains(*path))
+            .cloned()
+            .collect();
+        for path in &removed {
+            files.remove(path);
+        }
+        let mut changed = Vec::new();
+
+        for path in paths {
+            // A file may disappear or be half-written while it is saved, in
+            // which case it is picked up again on the next poll
+            let Ok(modified) = fs::metadata(&path).and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+            if files
+                .get(&path)
+                .is_some_and(|file| file.modified == modified)
+            {
+                continue;
+            }
+            let Ok(file) = analyzer.read(&path) else {
+                continue;
+            };
+            let mut warnings = analyzer.analyze_file(&file);
+            warnings.retain(|warning| !baseline.contains(warning, file.source_of(warning)));
+            changed.push(path.clone());
+            files.insert(
+                path,
+                WatchedFile {
+                    modified,
+                    file,
+                    warnings,
+                },
+            );
+        }
+
+        // Saves tend to come in bursts, so polling speeds up after one
+        if !removed.is_empty() || !changed.is_empty() {
+            changed.sort();
+            print_changes(dir, &files, &changed, &removed);
+            poll_interval = MIN_POLL_INTERVAL;
+        } else {
+            poll_interval = (poll_interval * 2).min(MAX_POLL_INTERVAL);
+        }
+        thread::sleep(poll_interval);
+    }
+}
+
+fn print_changes(
+    dir: &Path,
+    files: &BTreeMap<PathBuf, WatchedFile>,
+    changed: &[PathBuf],
+    removed: &[PathBuf],
+) {
+    for path in removed {
+        println!("'{}' was removed", path.display());
+    }
+    for path in changed {
+        let WatchedFile { file, warnings, .. } = &files[path];
+        let mut source_lines = SourceLines::default();
+        source_lines.add(&file.filename, &file.source_code, file.notebook.as_ref());
+        for warning in warnings {
+            print_warning(warning, &source_lines, false);
+        }
+    }
+    let count: usize = files.values().map(|file| file.warnings.len()).sum();
+    println!(
+        "Found {} warning(s) in {} file(s), watching '{}' for changes",
+        count,
+        files.len(),
+        dir.display()
+    );
+}
@@ -0,0 +1,134 @@
+//! Checkstyle and JUnit XML reports, the formats CI systems such as Jenkins
+//! and GitLab read lint violations and test results from.
+
+use pysleuth::{Severity, Warning};
+use std::fmt::Write;
+
+/// A Checkstyle report with a `<file>` for each file with warnings.
+pub fn checkstyle(warnings: &[Warning]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<checkstyle version=\"4.3\">\n");
+    for (filename, warnings) in by_file(warnings) {
+        writeln!(xml, "  <file name=\"{}\">", escape(filename)).unwrap();
+        for warning in warnings {
+            writeln!(
+                xml,
+                "    <error line=\"{}\" column=\"{}\" severity=\"{}\" message=\"{}\" source=\"{}.{}\"/>",
+                warning.line,
+                warning.column,
+                checkstyle_severity(warning.severity),
+                escape(&warning.message),
+                env!("CARGO_PKG_NAME"),
+                warning.rule,
+            )
+            .unwrap();
+        }
+        xml.push_str("  </file>\n");
+    }
+    xml.push_str("</checkstyle>");
+    xml
+}
+
+/// A JUnit report with a test suite for each of `filenames`, holding a
+/// failed test case for each warning, or a single passing one for files
+/// without warnings.
+pub fn junit(warnings: &[Warning], filenames: &[&str]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let tests = filenames
+        .iter()
+        .map(|filename| file_warnings(warnings, filename).count().max(1))
+        .sum::<usize>();
+    writeln!(
+        xml,
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\">",
+        env!("CARGO_PKG_NAME"),
+        tests,
+        warnings.len()
+    )
+    .unwrap();
+    for filename in filenames {
+        let failures = file_warnings(warnings, filename).count();
+        writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">",
+            escape(filename),
+            failures.max(1),
+            failures
+        )
+        .unwrap();
+        if failures == 0 {
+            writeln!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{0}\"/>",
+                escape(filename)
+            )
+            .unwrap();
+        }
+        for warning in file_warnings(warnings, filename) {
+            writeln!(
+                xml,
+                "    <testcase name=\"{} {}:{}\" classname=\"{}\">",
+                warning.rule,
+                warning.line,
+                warning.column,
+                escape(filename)
+            )
+            .unwrap();
+            writeln!(
+                xml,
+                "      <failure message=\"{}\" type=\"{}\">{}:{}:{}: {}</failure>",
+                escape(&warning.message),
+                warning.rule,
+                escape(filename),
+                warning.line,
+                warning.column,
+                escape(&warning.message)
+            )
+            .unwrap();
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>");
+    xml
+}
+
+// Warnings grouped by file, keeping the order files first appear in
+fn by_file(warnings: &[Warning]) -> Vec<(&str, Vec<&Warning>)> {
+    let mut files: Vec<(&str, Vec<&Warning>)> = Vec::new();
+    for warning in warnings {
+        match files
+            .iter_mut()
+            .find(|(filename, _)| *filename == warning.filename)
+        {
+            Some((_, warnings)) => warnings.push(warning),
+            None => files.push((&warning.filename, vec![warning])),
+        }
+    }
+    files
+}
+
+fn file_warnings<'a>(
+    warnings: &'a [Warning],
+    filename: &'a str,
+) -> impl Iterator<Item = &'a Warning> {
+    warnings
+        .iter()
+        .filter(move |warning| warning.filename == filename)
+}
+
+// Checkstyle has no level below `info`
+fn checkstyle_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info | Severity::Hint => "info",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
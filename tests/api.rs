@@ -0,0 +1,2849 @@
+use pysleuth::cache::Cache;
+use pysleuth::checks::{Check, CheckContext, Finding};
+use pysleuth::discovery::Discovery;
+use pysleuth::encoding::{self, Encoding};
+use pysleuth::site_packages::SitePackages;
+use pysleuth::stubs::Stubs;
+use pysleuth::{
+    analyze_source, Analyzer, Config, DocstringStyle, Document, Framework, PythonVersion, Rule,
+    Severity, SourceFile, Warning,
+};
+use std::time::{Duration, SystemTime};
+
+fn analyze(source: &str) -> Vec<Warning> {
+    analyze_source(source, "example.py", &Config::default())
+}
+
+#[test]
+fn reports_unguarded_dict_access() {
+    let warnings = analyze("def get(d):\n    return d['key']\n");
+
+    assert_eq!(
+        warnings,
+        vec![Warning {
+            filename: "example.py".to_string(),
+            cell: None,
+            line: 2,
+            column: 12,
+            end_line: 2,
+            end_column: 20,
+            rule: Rule::UnguardedDictAccess,
+            function: "get".to_string(),
+            exceptions: vec!["KeyError".to_string()],
+            message: "Possible KeyError in function 'get'".to_string(),
+            severity: Severity::Warning,
+            suggestion: None,
+            related: Vec::new(),
+        }]
+    );
+}
+
+#[test]
+fn reports_unhandled_calls_in_source_order() {
+    let source = "\
+def get(d):
+    return d['key']
+
+def caller(d):
+    return get(d)
+
+def unhandled(d):
+    return caller(d)
+
+def handled(d):
+    try:
+        return caller(d)
+    except KeyError:
+        return None
+";
+    let messages: Vec<(usize, String)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.line, w.message))
+        .collect();
+
+    // `get` already reports its own access, so its call site stays quiet
+    assert_eq!(
+        messages,
+        vec![
+            (2, "Possible KeyError in function 'get'".to_string()),
+            (
+                8,
+                "Possible KeyError not handled when calling 'caller' in function 'unhandled'"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn module_level_accesses_are_not_reported() {
+    assert!(analyze("config = {}\nvalue = config['key']\n").is_empty());
+}
+
+#[test]
+fn suggestions_are_only_attached_when_requested() {
+    let source = "def get(d):\n    return d['key']\n";
+    assert!(analyze(source)[0].suggestion.is_none());
+
+    let config = Config {
+        suggest_fixes: true,
+        ..Config::default()
+    };
+    let suggestion = analyze_source(source, "example.py", &config)[0]
+        .suggestion
+        .clone()
+        .unwrap();
+    assert_eq!(suggestion.original, "    return d['key']");
+    assert_eq!(suggestion.replacement, "    return d.get('key')");
+}
+
+#[test]
+fn parses_python_versions() {
+    assert_eq!("3.8".parse(), Ok(PythonVersion::new(3, 8)));
+    assert!("3".parse::<PythonVersion>().is_err());
+    assert!("3.x".parse::<PythonVersion>().is_err());
+}
+
+#[test]
+fn reports_stop_iteration_raised_in_generators() {
+    let source = "\
+def numbers(limit):
+    for i in range(limit):
+        yield i
+    raise StopIteration
+
+def not_a_generator():
+    raise StopIteration()
+
+def outer():
+    def inner():
+        yield 1
+    raise StopIteration
+";
+    let warnings = analyze(source);
+
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, 4);
+    assert_eq!(
+        warnings[0].message,
+        "StopIteration raised inside generator; it is raised as RuntimeError"
+    );
+
+    // Code that also runs before Python 3.7 only gets the warning
+    let config = Config {
+        python_version: PythonVersion::new(3, 6),
+        newest_python_version: Some(PythonVersion::new(3, 8)),
+        ..Config::default()
+    };
+    let warnings = analyze_source(source, "example.py", &config);
+    assert_eq!(
+        warnings[0].message,
+        "StopIteration raised inside generator; will become RuntimeError in Python 3.7+"
+    );
+}
+
+#[test]
+fn walrus_get_checks_guard_the_branch() {
+    let source = "\
+def f(d):
+    if (value := d.get('key')) is not None:
+        print(value, d['key'])
+    if (value := d.get('key')) is None:
+        print(d['key'])
+    if (value := d['other']):
+        print(value)
+";
+    let lines: Vec<usize> = analyze(source).iter().map(|w| w.line).collect();
+    assert_eq!(lines, vec![5, 6]);
+
+    // Assignment expressions only exist from Python 3.8 on
+    let config = Config {
+        python_version: PythonVersion::new(3, 7),
+        ..Config::default()
+    };
+    let lines: Vec<usize> = analyze_source(source, "example.py", &config)
+        .iter()
+        .filter(|w| w.rule != Rule::UnsupportedSyntax)
+        .map(|w| w.line)
+        .collect();
+    assert_eq!(lines, vec![3, 5, 6]);
+}
+
+#[test]
+fn reports_syntax_newer_than_the_oldest_targeted_version() {
+    let source = "\
+def f(command, items):
+    match command:
+        case 'go':
+            return 1
+    if (n := len(items)) > 3:
+        return n
+";
+    let (oldest, newest) = PythonVersion::parse_range("3.8..3.12").unwrap();
+    assert_eq!(oldest, PythonVersion::new(3, 8));
+    assert_eq!(newest, PythonVersion::new(3, 12));
+    assert!(PythonVersion::parse_range("3.12..3.8").is_err());
+
+    let config = Config {
+        python_version: oldest,
+        newest_python_version: Some(newest),
+        ..Config::default()
+    };
+    let warnings: Vec<(usize, String)> = analyze_source(source, "example.py", &config)
+        .into_iter()
+        .filter(|w| w.rule == Rule::UnsupportedSyntax)
+        .map(|w| (w.line, w.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![(
+            2,
+            "The match statement requires Python 3.10, but Python 3.8 is targeted".to_string()
+        )]
+    );
+}
+
+#[test]
+fn attribute_calls_resolve_to_their_last_component() {
+    let source = "\
+import utils as u
+
+def risky(d):
+    return d['key']
+
+def wrapper(d):
+    return helper(d)
+
+def helper(d):
+    return risky(d)
+
+def caller(d):
+    return u.wrapper(d)
+";
+    let warnings = analyze(source);
+    assert!(warnings.iter().any(|w| w.line == 13
+        && w.message
+            == "Possible KeyError not handled when calling 'u.wrapper' in function 'caller'"));
+}
+
+#[test]
+fn methods_are_not_matched_by_name_alone() {
+    let source = "\
+import yaml
+
+class Store:
+    def load(self, key):
+        raise ValueError(key)
+
+def read(f):
+    return yaml.load(f)
+";
+    let warnings = analyze(source);
+    assert!(warnings.iter().all(|w| w.rule != Rule::UnhandledCall));
+}
+
+#[test]
+fn reports_next_without_default() {
+    let source = "\
+def first(items):
+    return next(iter(items))
+
+def first_or_none(items):
+    return next(iter(items), None)
+
+def guarded_first(items):
+    try:
+        return next(iter(items))
+    except StopIteration:
+        return None
+
+def wrapper(items):
+    return first(items)
+
+def caller(items):
+    return wrapper(items)
+";
+    let messages: Vec<(usize, String)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.line, w.message))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                2,
+                "Possible StopIteration from next() without a default in function 'first'"
+                    .to_string()
+            ),
+            (
+                17,
+                "Possible StopIteration not handled when calling 'wrapper' in function 'caller'"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn disabled_rules_are_neither_reported_nor_propagated() {
+    let source = "\
+def get(d):
+    return d['key']
+
+def first(items):
+    return next(iter(items))
+
+def caller(d, items):
+    return get(d), first(items)
+";
+    let mut config = Config::default();
+    config.enabled_rules.remove(&Rule::UnguardedDictAccess);
+
+    let warnings = analyze_source(source, "example.py", &config);
+    let rules: Vec<Rule> = warnings.iter().map(|w| w.rule).collect();
+    assert_eq!(rules, vec![Rule::NextWithoutDefault]);
+}
+
+#[test]
+fn rules_parse_from_their_codes() {
+    assert_eq!("PW002".parse(), Ok(Rule::UnhandledCall));
+    assert_eq!("pw001".parse(), Ok(Rule::UnguardedDictAccess));
+    assert!("PW999".parse::<Rule>().is_err());
+}
+
+#[test]
+fn except_clauses_match_their_type_with_or_without_alias() {
+    let source = "\
+def aliased(d):
+    try:
+        return d['key']
+    except KeyError as e:
+        pass
+
+def plain(d):
+    try:
+        return d['key']
+    except KeyError:
+        pass
+
+def other_type(d):
+    try:
+        return d['key']
+    except ValueError as e:
+        pass
+
+def bare(d):
+    try:
+        return d['key']
+    except:
+        pass
+";
+    let lines: Vec<usize> = analyze(source)
+        .iter()
+        .filter(|w| !matches!(w.rule, Rule::BareExcept | Rule::UselessHandler))
+        .map(|w| w.line)
+        .collect();
+    assert_eq!(lines, vec![15]);
+}
+
+#[test]
+fn finally_blocks_are_not_guarded_by_their_handlers() {
+    let source = "\
+def close(d, resources):
+    try:
+        return d['key']
+    except KeyError:
+        return None
+    finally:
+        resources['handle'].close()
+
+def nested(d, resources):
+    try:
+        try:
+            pass
+        finally:
+            resources['handle'].close()
+    except KeyError:
+        pass
+";
+    let lines: Vec<usize> = analyze(source).iter().map(|w| w.line).collect();
+    assert_eq!(lines, vec![7]);
+}
+
+#[test]
+fn else_blocks_are_not_guarded_by_their_handlers() {
+    let source = "\
+def load(d, config):
+    try:
+        value = d['key']
+    except KeyError:
+        return None
+    else:
+        return config['path'] + value
+
+def nested(d):
+    try:
+        try:
+            pass
+        except ValueError:
+            pass
+        else:
+            return d['key']
+    except KeyError:
+        pass
+";
+    let lines: Vec<usize> = analyze(source)
+        .iter()
+        .filter(|w| w.rule != Rule::UselessHandler)
+        .map(|w| w.line)
+        .collect();
+    assert_eq!(lines, vec![7]);
+}
+
+#[test]
+fn handler_bodies_are_not_guarded_by_their_own_try() {
+    let source = "\
+def fallback(primary, secondary):
+    try:
+        return primary['key']
+    except KeyError:
+        return secondary['key']
+
+def convert(d):
+    try:
+        return d['key']
+    except KeyError:
+        raise ValueError('missing key')
+
+def caller(d):
+    return convert(d)
+
+def outer(primary, secondary):
+    try:
+        try:
+            return primary['key']
+        except KeyError:
+            return secondary['key']
+    except KeyError:
+        return None
+";
+    let warnings = analyze(source);
+    let lines: Vec<usize> = warnings.iter().map(|w| w.line).collect();
+    assert_eq!(lines, vec![5, 14]);
+    assert_eq!(warnings[1].exceptions, vec!["ValueError".to_string()]);
+}
+
+#[test]
+fn exceptions_raised_in_finally_replace_the_one_in_flight() {
+    let source = "\
+def load(d):
+    try:
+        return d['key']
+    finally:
+        raise RuntimeError('load failed')
+
+def first(items):
+    for item in items:
+        try:
+            return item['key']
+        finally:
+            continue
+
+def main(d, items):
+    return load(d), first(items)
+";
+    let messages: Vec<String> = analyze(source).into_iter().map(|w| w.message).collect();
+    assert_eq!(
+        messages,
+        ["Possible RuntimeError not handled when calling 'load' in function 'main'"]
+    );
+}
+
+#[test]
+fn annotations_classify_sequence_subscripts() {
+    let source = "\
+def items() -> List[int]:
+    return []
+
+def f(d: Dict[str, int], names: list[str], text: str = '', pair: Optional[typing.Tuple[int, int]] = None):
+    counts: Mapping[str, int] = {}
+    values: List[int] = []
+    return d['a'], names[0], text[0], pair[1], counts['b'], values[2], items()[3]
+";
+    let config = Config {
+        check_annotations: true,
+        ..Config::default()
+    };
+    let messages: Vec<(usize, Rule, String)> = analyze_source(source, "example.py", &config)
+        .into_iter()
+        .map(|w| (w.column, w.rule, w.message))
+        .collect();
+    let key_error = "Possible KeyError in function 'f'".to_string();
+    let index_error = "Possible IndexError in function 'f'".to_string();
+    assert_eq!(
+        messages,
+        vec![
+            (12, Rule::UnguardedDictAccess, key_error.clone()),
+            (20, Rule::UnguardedIndexAccess, index_error.clone()),
+            (30, Rule::UnguardedIndexAccess, index_error.clone()),
+            (39, Rule::UnguardedIndexAccess, index_error.clone()),
+            (48, Rule::UnguardedDictAccess, key_error),
+            (61, Rule::UnguardedIndexAccess, index_error.clone()),
+            (72, Rule::UnguardedIndexAccess, index_error),
+        ]
+    );
+}
+
+#[test]
+fn annotations_see_through_unions_and_skip_default_mappings() {
+    let source = "\
+def f(cfg: dict[str, int] | None, items: Union[list[str], None], counts: Counter[str]):
+    hits: defaultdict[str, int] = load()
+    return cfg['a'], items[0], counts['b'], hits['c']
+";
+    let config = Config {
+        check_annotations: true,
+        ..Config::default()
+    };
+    let rules: Vec<(usize, Rule)> = analyze_source(source, "example.py", &config)
+        .into_iter()
+        .map(|w| (w.column, w.rule))
+        .collect();
+    assert_eq!(
+        rules,
+        vec![
+            (12, Rule::UnguardedDictAccess),
+            (22, Rule::UnguardedIndexAccess)
+        ]
+    );
+}
+
+#[test]
+fn mapping_annotations_override_integer_index_heuristic() {
+    let source = "\
+def f(ids: Dict[int, str]):
+    return ids[0]
+";
+    let config = Config {
+        check_annotations: true,
+        ..Config::default()
+    };
+    let warnings = analyze_source(source, "example.py", &config);
+    assert_eq!(warnings[0].rule, Rule::UnguardedDictAccess);
+    assert_eq!(analyze(source)[0].rule, Rule::UnguardedIndexAccess);
+}
+
+#[test]
+fn sequence_subscripts_are_guarded_by_index_error_handlers() {
+    let source = "\
+def f(names: list[str], i):
+    try:
+        return names[i]
+    except IndexError:
+        return None
+";
+    let config = Config {
+        check_annotations: true,
+        ..Config::default()
+    };
+    assert!(analyze_source(source, "example.py", &config).is_empty());
+
+    let source = source.replace("except IndexError", "except KeyError");
+    let warnings = analyze_source(&source, "example.py", &config);
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[1].rule, Rule::UselessHandler);
+}
+
+#[test]
+fn public_only_limits_reports_to_functions_reachable_from_all() {
+    let source = "\
+__all__ = ['load', 'Store']
+
+def load(d):
+    return _parse(d)
+
+def _parse(d):
+    return d['key']
+
+def _unused(d):
+    return d['key']
+
+class Store:
+    def get(self, d):
+        return d['key']
+";
+    let config = Config {
+        public_only: true,
+        ..Config::default()
+    };
+    let lines: Vec<usize> = analyze_source(source, "example.py", &config)
+        .iter()
+        .map(|w| w.line)
+        .collect();
+    assert_eq!(lines, vec![7, 14]);
+
+    // Without `__all__` nothing is filtered
+    let source = source.replace("__all__ = ['load', 'Store']", "");
+    let warnings = analyze_source(&source, "example.py", &config);
+    assert_eq!(warnings.len(), 3);
+}
+
+#[test]
+fn reports_environment_variable_subscripts() {
+    let source = "\
+import os
+from os import environ as env
+
+def settings(name):
+    return os.environ['DATABASE_URL'], env[name], os.environ.get('DEBUG')
+";
+    let messages: Vec<(Rule, String)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.rule, w.message))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                Rule::UnguardedEnvironAccess,
+                "Possible KeyError: environment variable 'DATABASE_URL' may not be set; \
+                 use os.environ.get('DATABASE_URL', default), or validate it at startup \
+                 and list it as required"
+                    .to_string()
+            ),
+            (
+                Rule::UnguardedEnvironAccess,
+                "Possible KeyError: environment variable name may not be set; \
+                 use os.environ.get(name, default), or validate it at startup \
+                 and list it as required"
+                    .to_string()
+            ),
+        ]
+    );
+
+    let config = Config {
+        required_env_vars: ["DATABASE_URL".to_string()].into_iter().collect(),
+        ..Config::default()
+    };
+    let warnings = analyze_source(source, "example.py", &config);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].column, 40);
+}
+
+#[test]
+fn reports_sequence_indexing_as_index_error() {
+    let source = "\
+def f(items, text, d):
+    first = items[0]
+    last = text[-1]
+    middle = text[1:-1]
+    letter = 'abc'[d]
+    value = d['key']
+    try:
+        return items[1]
+    except LookupError:
+        return None
+";
+    let messages: Vec<(usize, Rule, String)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.line, w.rule, w.message))
+        .collect();
+    let index_error = "Possible IndexError in function 'f'".to_string();
+    assert_eq!(
+        messages,
+        vec![
+            (2, Rule::UnguardedIndexAccess, index_error.clone()),
+            (3, Rule::UnguardedIndexAccess, index_error.clone()),
+            (5, Rule::UnguardedIndexAccess, index_error),
+            (
+                6,
+                Rule::UnguardedDictAccess,
+                "Possible KeyError in function 'f'".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn classifies_subscripts_by_assigned_container() {
+    let source = "\
+def f(k, i):
+    counts = {}
+    names = list()
+    unknown = load()
+    return counts[k], names[i], unknown[k]
+";
+    let messages: Vec<String> = analyze(source).into_iter().map(|w| w.message).collect();
+    assert_eq!(messages.len(), 3);
+    assert!(messages[0].starts_with("Possible KeyError"));
+    assert!(messages[1].starts_with("Possible IndexError"));
+    assert!(messages[2].starts_with("Possible LookupError"));
+}
+
+#[test]
+fn ambiguous_lookups_are_guarded_by_either_handler() {
+    let source = "\
+def f(d, k):
+    try:
+        return d[k]
+    except KeyError:
+        return None
+";
+    assert!(analyze(source).is_empty());
+}
+
+#[test]
+fn explicit_raises_propagate_to_callers() {
+    let source = "\
+def parse(text):
+    if not text:
+        raise ValueError('empty')
+    try:
+        raise KeyError(text)
+    except KeyError:
+        pass
+    return text
+
+def load(text):
+    return parse(text)
+";
+    let warnings = analyze(source);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].message,
+        "Possible ValueError not handled when calling 'parse' in function 'load'"
+    );
+}
+
+#[test]
+fn handlers_do_not_cover_functions_defined_in_their_try_body() {
+    let source = "\
+def register(d, callbacks):
+    try:
+        def on_load():
+            return d['key']
+        fallback = lambda: d['other']
+        callbacks.extend([on_load, fallback])
+        return sorted(d, key=lambda k: d[k])
+    except LookupError:
+        return None
+";
+    let warnings = analyze(source);
+    let lines: Vec<usize> = warnings
+        .iter()
+        .filter(|w| w.rule == Rule::UnguardedDictAccess)
+        .map(|w| w.line)
+        .collect();
+
+    // The lambda passed to `sorted` runs within the try body; the others
+    // run whenever they are called
+    assert_eq!(lines, vec![4, 5]);
+}
+
+#[test]
+fn handlers_catch_subclasses_of_their_exception() {
+    let source = "\
+class NotFound(KeyError):
+    pass
+
+def find(d, k):
+    if k not in d:
+        raise NotFound(k)
+    return k
+
+def base(d):
+    try:
+        return d['key']
+    except BaseException:
+        return None
+
+def lookup(d):
+    try:
+        return find(d, 'key')
+    except LookupError:
+        return None
+
+def io(path):
+    try:
+        return find(path, 'key')
+    except IOError:
+        return None
+";
+    let warnings: Vec<Warning> = analyze(source)
+        .into_iter()
+        .filter(|w| {
+            !matches!(
+                w.rule,
+                Rule::BroadExcept | Rule::UselessHandler | Rule::SwallowedExit
+            )
+        })
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].message,
+        "Possible NotFound not handled when calling 'find' in function 'io'"
+    );
+}
+
+#[test]
+fn membership_tests_guard_subscripts() {
+    let source = "\
+def guarded(d, k, other):
+    if k in d:
+        a = d[k]
+    elif other and k in other:
+        a = other[k]
+    b = d[k] if k in d else None
+    c = k in d and d[k]
+    if k not in d:
+        pass
+    else:
+        a = d[k]
+    return a, b, c
+
+def early_exit(d, k):
+    if k not in d:
+        return None
+    return d[k]
+
+def filled_in(d, k):
+    if k not in d:
+        d[k] = []
+    return d[k]
+
+def unguarded(d, k):
+    if k in d:
+        pass
+    return d[k]
+";
+    let lines: Vec<usize> = analyze(source)
+        .into_iter()
+        .map(|w| w.line)
+        // `d[k] = []` is itself a subscript and checked on its own
+        .filter(|&line| line != 21)
+        .collect();
+    assert_eq!(lines, [27]);
+}
+
+#[test]
+fn guards_around_a_definition_do_not_cover_its_body() {
+    let source = "\
+def outer(d, k):
+    if k not in d:
+        return None
+
+    def inner():
+        return d[k]
+    return inner
+";
+    let lines: Vec<usize> = analyze(source).into_iter().map(|w| w.line).collect();
+    assert_eq!(lines, [6]);
+}
+
+#[test]
+fn defaultdict_and_counter_subscripts_are_safe() {
+    let source = "\
+import collections
+from collections import Counter, defaultdict
+
+def group(items, k):
+    groups = defaultdict(list)
+    counts = collections.Counter()
+    plain = {}
+    groups[k].append(k)
+    counts[k] += 1
+    return Counter(items)[k], d.get(k), d.setdefault(k, []), plain[k]
+";
+    let warnings = analyze(source);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].column, 62);
+}
+
+#[test]
+fn contextlib_suppress_guards_its_body() {
+    let source = "\
+import contextlib
+import contextlib as cl
+from contextlib import suppress as ignore
+
+def f(d):
+    with contextlib.suppress(KeyError):
+        d['a']
+    with cl.suppress(ValueError, LookupError), open('x') as g:
+        d['b']
+    with ignore(KeyError):
+        d['c']
+    with ignore(ValueError):
+        d['d']
+";
+    let warnings = analyze(source);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, 13);
+}
+
+#[test]
+fn tuple_handlers_catch_each_listed_exception() {
+    let source = "\
+def f(d):
+    try:
+        a = d['a']
+    except (ValueError, KeyError) as e:
+        a = None
+    try:
+        b = d['b']
+    except (KeyError):
+        b = None
+    try:
+        c = d['c']
+    except (ValueError, TypeError):
+        c = None
+    return a, b, c
+";
+    let warnings: Vec<Warning> = analyze(source)
+        .into_iter()
+        .filter(|w| w.rule != Rule::UselessHandler)
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, 11);
+}
+
+#[test]
+fn reports_divisors_that_may_be_zero() {
+    let source = "\
+def mean(values, n):
+    total = sum(values) / 2 + n % 1e3 + n // -1
+    label = 'total: %s' % total
+    if n:
+        total /= n
+    if n == 0:
+        return 0
+    scaled = total / n if n != 0 else 0
+    return total / n + scaled
+
+def risky(a, b):
+    try:
+        a / b
+    except ArithmeticError:
+        pass
+    return a % b
+
+def caller():
+    return risky(1, 0)
+
+def outer():
+    return caller()
+";
+    let warnings = analyze(source);
+    let messages: Vec<(usize, &str)> = warnings
+        .iter()
+        .map(|w| (w.line, w.message.as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (16, "Possible ZeroDivisionError in function 'risky'"),
+            (
+                22,
+                "Possible ZeroDivisionError not handled when calling 'caller' in function 'outer'"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn reports_attribute_access_on_values_that_may_be_none() {
+    let source = "\
+from typing import Optional
+
+def find(items, name):
+    for item in items:
+        if item.name == name:
+            return item
+    return None
+
+def use(items, user: Optional[str], other: int | None):
+    match = find(items, 'a')
+    print(match.name)
+    if other is not None:
+        other.bit_length()
+    user.upper()
+    cache = None
+    if cache is None:
+        cache = {}
+    cache.clear()
+    found = find(items, 'b')
+    if not found:
+        return
+    return found.name
+";
+    let warnings = analyze(source);
+    let messages: Vec<(usize, &str)> = warnings
+        .iter()
+        .map(|w| (w.line, w.message.as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                11,
+                "Possible AttributeError: 'match' may be None in function 'use'"
+            ),
+            (
+                14,
+                "Possible AttributeError: 'user' may be None in function 'use'"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn reports_conversions_that_may_raise_value_error() {
+    let source = "\
+import uuid
+from datetime import datetime
+
+def parse(fields):
+    count = int(fields[0])
+    ratio = float('0.5') + int(3.7) + float('inf')
+    when = datetime.strptime(fields[1], '%Y')
+    try:
+        ident = uuid.UUID(fields[2])
+    except ValueError:
+        ident = None
+    return count, ratio, when, ident
+";
+    let warnings: Vec<String> = analyze(source)
+        .into_iter()
+        .filter(|w| w.rule == Rule::UncheckedConversion)
+        .map(|w| w.message)
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            "Possible ValueError from int() in function 'parse'",
+            "Possible ValueError from datetime.strptime() in function 'parse'",
+        ]
+    );
+}
+
+#[test]
+fn stdlib_calls_propagate_their_exceptions() {
+    let source = "\
+import json
+from json import JSONDecodeError
+
+def load(path):
+    with open(path) as f:
+        return json.loads(f.read())
+
+def take(key):
+    registry = {}
+    registry.pop(key, None)
+    return registry.pop(key)
+
+def main():
+    try:
+        load('config.json')
+    except JSONDecodeError:
+        pass
+    take('a')
+";
+    let warnings: Vec<String> = analyze(source).into_iter().map(|w| w.message).collect();
+    assert_eq!(
+        warnings,
+        [
+            "Possible KeyError from pop() in function 'take'",
+            "Possible FileNotFoundError, OSError not handled when calling 'load' in function 'main'",
+        ]
+    );
+}
+
+#[test]
+fn docstring_raises_sections_seed_callers() {
+    let source = r#"
+def google(key):
+    """Look up a key.
+
+    Raises:
+        KeyError: If the key is missing.
+            Continued description.
+        ValueError: If the key is malformed.
+    """
+
+def numpy(key):
+    """Look up a key.
+
+    Raises
+    ------
+    LookupError
+        If the key is missing.
+
+    Returns
+    -------
+    str
+    """
+
+def sphinx(key):
+    """Look up a key.
+
+    :raises TimeoutError: if the lookup takes too long
+    """
+
+def caller():
+    try:
+        google('a')
+    except ValueError:
+        pass
+    numpy('b')
+    sphinx('c')
+"#;
+    let warnings: Vec<String> = analyze(source).into_iter().map(|w| w.message).collect();
+    assert_eq!(
+        warnings,
+        [
+            "Possible KeyError not handled when calling 'google' in function 'caller'",
+            "Possible LookupError not handled when calling 'numpy' in function 'caller'",
+            "Possible TimeoutError not handled when calling 'sphinx' in function 'caller'",
+        ]
+    );
+}
+
+#[test]
+fn methods_resolve_by_their_class() {
+    let source = "\
+class Foo:
+    def load(self):
+        raise KeyError('foo')
+
+    def reload(self):
+        return self.load()
+
+
+class Bar:
+    def load(self):
+        return None
+
+    def reload(self):
+        return self.load()
+
+
+def use():
+    foo = Foo()
+    bar = Bar()
+    return foo.load(), bar.load()
+";
+    let warnings: Vec<String> = analyze(source).into_iter().map(|w| w.message).collect();
+    assert_eq!(
+        warnings,
+        [
+            "Possible KeyError not handled when calling 'self.load' in function 'Foo.reload'",
+            "Possible KeyError not handled when calling 'foo.load' in function 'use'",
+        ]
+    );
+}
+
+#[test]
+fn stubs_resolve_method_calls_on_stdlib_classes() {
+    let source = "\
+from pathlib import Path
+
+def read(name):
+    path = Path(name).resolve()
+    return path.read_text()
+
+def main():
+    return read('config.toml')
+";
+    assert!(analyze(source).is_empty());
+
+    let mut stubs = Stubs::default();
+    stubs.add_module(
+        "pathlib",
+        "\
+from typing_extensions import Self
+
+class Path:
+    def resolve(self, strict: bool = False) -> Self: ...
+    def read_text(self, encoding: str | None = None) -> str: ...
+",
+    );
+    let config = Config {
+        stubs: Some(stubs),
+        ..Config::default()
+    };
+    let warnings: Vec<String> = analyze_source(source, "example.py", &config)
+        .into_iter()
+        .map(|w| w.message)
+        .collect();
+    assert_eq!(
+        warnings,
+        ["Possible FileNotFoundError, OSError not handled when calling 'read' in function 'main'"]
+    );
+}
+
+#[test]
+fn async_functions_and_await_sites_are_analyzed() {
+    let source = "\
+import contextlib
+
+async def fetch(d, stream):
+    async for chunk in stream:
+        try:
+            await process(d['a'])
+        except KeyError:
+            pass
+    with contextlib.suppress(StopAsyncIteration):
+        await anext(stream)
+    await anext(stream, None)
+    return await anext(stream)
+
+async def process(value):
+    if not value:
+        raise ValueError(value)
+
+async def main(d, stream):
+    async with lock:
+        await fetch(d, stream)
+        await process(d.get('b'))
+";
+    let warnings: Vec<String> = analyze(source).into_iter().map(|w| w.message).collect();
+    assert_eq!(
+        warnings,
+        [
+            "Possible ValueError not handled when calling 'process' in function 'fetch'",
+            "Possible StopAsyncIteration from anext() without a default in function 'fetch'",
+            "Possible ValueError not handled when calling 'process' in function 'main'",
+        ]
+    );
+}
+
+#[test]
+fn lambdas_are_functions_of_their_own() {
+    let source = "\
+def find(k):
+    raise ValueError(k)
+
+def f(d, keys):
+    get = lambda k: d[k]
+    lookup = lambda k: find(k)
+    present = [d[k] for k in keys if k in d]
+    copied = {k: d[k] for k in keys}
+    ordered = sorted(keys, key=lambda k: d[k])
+    return get('a'), lookup('b')
+";
+    let warnings: Vec<(usize, usize, String)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.line, w.column, w.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (5, 21, "Possible LookupError in function 'get'".to_string()),
+            (
+                6,
+                24,
+                "Possible ValueError not handled when calling 'find' in function 'lookup'"
+                    .to_string()
+            ),
+            (8, 18, "Possible LookupError in function 'f'".to_string()),
+            (
+                9,
+                42,
+                "Possible LookupError in function '<lambda:9:32>'".to_string()
+            ),
+            (
+                10,
+                22,
+                "Possible ValueError not handled when calling 'lookup' in function 'f'".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn swallowing_decorators_hide_the_exceptions_they_catch() {
+    let source = "\
+import functools
+import tenacity
+
+@functools.lru_cache(maxsize=None)
+def cached(key):
+    raise KeyError(key)
+
+@tenacity.retry(stop=tenacity.stop_after_attempt(3))
+def flaky(d):
+    return d['key']
+
+def main(d):
+    return cached('a'), flaky(d)
+";
+    let messages = |config: &Config| -> Vec<String> {
+        analyze_source(source, "example.py", config)
+            .into_iter()
+            .map(|w| w.message)
+            .collect()
+    };
+    assert_eq!(
+        messages(&Config::default()),
+        [
+            "Possible KeyError in function 'flaky'",
+            "Possible KeyError not handled when calling 'cached' in function 'main'",
+        ]
+    );
+
+    let config = Config {
+        swallowing_decorators: ["retry".to_string()].into_iter().collect(),
+        ..Config::default()
+    };
+    assert_eq!(
+        messages(&config),
+        ["Possible KeyError not handled when calling 'cached' in function 'main'"]
+    );
+}
+
+#[test]
+fn handlers_that_reraise_let_exceptions_escape() {
+    let source = "\
+def lookup(d):
+    try:
+        return d['key']
+    except KeyError:
+        print('missing key')
+        raise
+
+def convert(d):
+    try:
+        return d['key']
+    except KeyError as e:
+        raise RuntimeError('missing key') from e
+
+def recover(d):
+    try:
+        return d['key']
+    except KeyError as e:
+        try:
+            return d['other']
+        except KeyError:
+            return None
+
+def main(d):
+    return lookup(d), convert(d), recover(d)
+";
+    let messages: Vec<String> = analyze_source(source, "example.py", &Config::default())
+        .into_iter()
+        .map(|w| w.message)
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "Possible KeyError in function 'lookup'",
+            "Possible RuntimeError not handled when calling 'convert' in function 'main'",
+        ]
+    );
+}
+
+#[test]
+fn calls_through_aliases_inherit_the_target_exceptions() {
+    let source = "\
+def parse_config(path):
+    raise ValueError(path)
+
+handler = parse_config
+
+def main(path):
+    local = handler
+    return local(path)
+
+def run(path):
+    return handler(path)
+";
+    let messages: Vec<String> = analyze_source(source, "example.py", &Config::default())
+        .into_iter()
+        .map(|w| w.message)
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "Possible ValueError not handled when calling 'local' in function 'main'",
+            "Possible ValueError not handled when calling 'handler' in function 'run'",
+        ]
+    );
+}
+
+#[test]
+fn callbacks_raise_where_they_are_passed() {
+    let source = "\
+def load_item(name):
+    raise KeyError(name)
+
+def by_priority(item):
+    raise TypeError(item)
+
+def main(items):
+    loaded = list(map(load_item, items))
+    try:
+        return sorted(loaded, key=by_priority)
+    except TypeError:
+        return loaded
+";
+    let messages: Vec<String> = analyze_source(source, "example.py", &Config::default())
+        .into_iter()
+        .map(|w| w.message)
+        .collect();
+    assert_eq!(
+        messages,
+        ["Possible KeyError not handled when calling 'load_item' in function 'main'"]
+    );
+}
+
+#[test]
+fn generator_exceptions_surface_where_they_are_iterated() {
+    let source = "\
+def records(lines):
+    for line in lines:
+        yield parse(line)
+
+def parse(line):
+    raise ValueError(line)
+
+def main(lines):
+    pending = records(lines)
+    print('loading')
+    for record in pending:
+        print(record)
+    return list(records(lines))
+";
+    let warnings: Vec<(usize, String)> = analyze_source(source, "example.py", &Config::default())
+        .into_iter()
+        .filter(|w| w.message.contains("calling 'records'"))
+        .map(|w| (w.line, w.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (
+                11,
+                "Possible ValueError not handled when calling 'records' in function 'main'"
+                    .to_string()
+            ),
+            (
+                13,
+                "Possible ValueError not handled when calling 'records' in function 'main'"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn suppression_comments_silence_warnings_on_their_line() {
+    let source = "\
+def f(d, items):
+    a = d['key']  # pywrong: ignore
+    b = d['key'], items[0]  # pywrong: ignore[KeyError]
+    c = d['key']  # noqa # pywrong: ignore[PW001, IndexError]
+    return a, b, c  # pywrong: ignore
+";
+    let warnings: Vec<(usize, Rule, String)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.line, w.rule, w.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (
+                3,
+                Rule::UnguardedIndexAccess,
+                "Possible IndexError in function 'f'".to_string()
+            ),
+            (
+                4,
+                Rule::UnusedSuppression,
+                "Unused suppression for IndexError".to_string()
+            ),
+            (
+                5,
+                Rule::UnusedSuppression,
+                "Unused suppression comment".to_string()
+            ),
+        ]
+    );
+
+    let skipped = format!("# pywrong: skip-file\n{}", source);
+    assert!(analyze(&skipped).is_empty());
+}
+
+#[test]
+fn edited_documents_are_analyzed_like_their_new_source() {
+    let source = "def get(d):\n    return d['key']\n\ndef other(d):\n    return d\n";
+    let mut document = Document::new("example.py", source.to_string());
+    assert_eq!(document.analyze(&Config::default()), analyze(source));
+
+    // Move the subscript into `other`, and guard the one left in `get`
+    let start = source.find("d\n").unwrap();
+    document.edit(start, start + 1, "d['other']");
+    let start = source.find("return d[").unwrap();
+    document.edit(
+        start,
+        start + "return d['key']".len(),
+        "return d.get('key')",
+    );
+
+    let edited = "def get(d):\n    return d.get('key')\n\ndef other(d):\n    return d['other']\n";
+    assert_eq!(document.source(), edited);
+    let warnings = document.analyze(&Config::default());
+    assert_eq!(warnings, analyze(edited));
+    assert_eq!(warnings[0].function, "other");
+}
+
+#[test]
+fn analyzer_finds_and_analyzes_files_under_a_directory() {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tree");
+    let analyzer = Analyzer::new(Config::default()).with_jobs(2);
+    let warnings = analyzer
+        .analyze_paths(&[root], &Discovery::new(&[], &["tests".to_string()], true))
+        .unwrap();
+
+    let files: Vec<&str> = warnings
+        .iter()
+        .map(|warning| warning.filename.rsplit('/').next().unwrap())
+        .collect();
+    assert!(!files.is_empty());
+    assert!(files.iter().all(|file| *file == "config.py"));
+}
+
+#[test]
+fn source_files_point_notebook_warnings_at_cells() {
+    let path =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/notebook.ipynb");
+    let analyzer = Analyzer::new(Config::default());
+    let file = analyzer.read(&path).unwrap();
+    assert!(file.notebook.is_some());
+    assert!(file.source_code.contains("def score(row):"));
+
+    let warnings = analyzer.analyze_file(&file);
+    let key_error = warnings
+        .iter()
+        .find(|warning| warning.function == "score")
+        .unwrap();
+    assert_eq!((key_error.cell, key_error.line), (Some(2), 5));
+    assert_eq!(
+        file.source_of(key_error).lines().nth(4),
+        Some("    return row[\"score\"]")
+    );
+    assert_eq!(
+        analyzer.analyze_files(std::slice::from_ref(&file)),
+        [warnings]
+    );
+
+    let bytes = std::fs::read(&path).unwrap();
+    assert!(SourceFile::decode("notes.ipynb".to_string(), b"{").is_err());
+    assert_eq!(
+        SourceFile::decode("notes.ipynb".to_string(), &bytes)
+            .unwrap()
+            .source_code,
+        file.source_code
+    );
+}
+
+// Reports `parse(...)` calls as raising `ParseError`
+struct ParseCheck;
+
+impl Check for ParseCheck {
+    fn rule(&self) -> Rule {
+        Rule::Custom("ACME001")
+    }
+
+    fn name(&self) -> &str {
+        "UnhandledParseError"
+    }
+
+    fn node_kinds(&self) -> &[&str] {
+        &["call"]
+    }
+
+    fn check(&self, node: tree_sitter::Node, context: &CheckContext) -> Option<Finding> {
+        let function = node.child_by_field_name("function")?;
+        (context.text(function) == "parse").then(|| Finding {
+            exception: "ParseError".to_string(),
+            message: format!("Possible ParseError in function '{}'", context.function),
+        })
+    }
+}
+
+#[test]
+fn custom_checks_report_and_propagate_their_exceptions() {
+    let source = "\
+def load(text):
+    return parse(text)
+
+def run(text):
+    return load(text)
+
+def main(text):
+    return run(text)
+
+def guarded(text):
+    try:
+        return parse(text)
+    except ParseError:
+        return None
+";
+    let mut config = Config::default();
+    config.add_check(Box::new(ParseCheck));
+    assert_eq!(config.rule("acme001"), Ok(Rule::Custom("ACME001")));
+    assert!(config.rule("ACME002").is_err());
+    assert_eq!(config.rules().last(), Some(&Rule::Custom("ACME001")));
+    let warnings = analyze_source(source, "example.py", &config);
+
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].rule, Rule::Custom("ACME001"));
+    assert_eq!(warnings[0].rule.code(), "ACME001");
+    assert_eq!(
+        warnings[0].message,
+        "Possible ParseError in function 'load'"
+    );
+    assert_eq!(warnings[1].rule, Rule::UnhandledCall);
+    assert_eq!(warnings[1].function, "main");
+    assert_eq!(warnings[1].exceptions, vec!["ParseError".to_string()]);
+
+    // Ignoring the rule of a check stops it from running at all
+    config.enabled_rules.remove(&Rule::Custom("ACME001"));
+    assert!(analyze_source(source, "example.py", &config).is_empty());
+}
+
+#[test]
+fn handlers_covered_by_earlier_ones_are_unreachable() {
+    let source = "\
+class ConfigError(ValueError):
+    pass
+
+def load(d):
+    try:
+        return d['key']
+    except Exception:
+        return None
+    except KeyError:
+        return 1
+
+def parse(text):
+    try:
+        return int(text)
+    except ValueError:
+        pass
+    except (ConfigError, ValueError):
+        pass
+    except KeyboardInterrupt:
+        pass
+    except (TypeError, ValueError):
+        pass
+";
+    let warnings: Vec<Warning> = analyze(source)
+        .into_iter()
+        .filter(|warning| warning.rule == Rule::UnreachableHandler)
+        .collect();
+    let lines: Vec<usize> = warnings.iter().map(|w| w.line).collect();
+    assert_eq!(lines, vec![9, 17]);
+    assert_eq!(
+        warnings[1].message,
+        "Handler for ConfigError, ValueError never runs: 'except ValueError' on line 15 already catches it"
+    );
+}
+
+#[test]
+fn broad_handlers_suggest_the_exceptions_the_body_raises() {
+    let source = "\
+import json
+
+def load(d, text):
+    try:
+        return d['key'], json.loads(text)
+    except Exception:
+        return None
+
+def log_and_raise(d):
+    try:
+        return d['key']
+    except Exception:
+        print('failed')
+        raise
+
+def opaque(d):
+    try:
+        return helper(d['key'])
+    except Exception:
+        return None
+
+def parse(d):
+    try:
+        return d['key']
+    except KeyError:
+        return 0
+    except:
+        return 1
+";
+    let warnings: Vec<Warning> = analyze(source)
+        .into_iter()
+        .filter(|warning| matches!(warning.rule, Rule::BroadExcept | Rule::BareExcept))
+        .collect();
+    let lines: Vec<(Rule, usize)> = warnings.iter().map(|w| (w.rule, w.line)).collect();
+    assert_eq!(lines, vec![(Rule::BroadExcept, 6), (Rule::BareExcept, 27)]);
+    assert_eq!(
+        warnings[0].message,
+        "'except Exception' is broader than needed: the try body can only raise KeyError, json.JSONDecodeError"
+    );
+    assert_eq!(
+        warnings[1].message,
+        "Bare 'except:' also catches KeyboardInterrupt and SystemExit; catch a specific exception, or 'except Exception:' instead"
+    );
+}
+
+#[test]
+fn handlers_that_do_nothing_swallow_the_exceptions_reaching_them() {
+    let source = "\
+import json
+
+def load(d, text):
+    try:
+        return d['key'], json.loads(text)
+    except (KeyError, ValueError):
+        pass
+
+def read(d):
+    for k in d:
+        try:
+            print(d['key'])
+        except LookupError:
+            # Missing keys are fine
+            continue
+
+def logged(d):
+    try:
+        return d['key']
+    except KeyError:
+        print('missing')
+
+def nothing():
+    try:
+        print('x')
+    except OSError:
+        pass
+";
+    let enabled_rules: std::collections::HashSet<Rule> = Rule::ALL.into_iter().collect();
+    let swallowed = |config: Config| -> Vec<(usize, String)> {
+        analyze_source(source, "example.py", &config)
+            .into_iter()
+            .filter(|warning| warning.rule == Rule::SwallowedException)
+            .map(|warning| (warning.line, warning.message))
+            .collect()
+    };
+    assert!(swallowed(Config::default()).is_empty());
+    let config = Config {
+        enabled_rules: enabled_rules.clone(),
+        ..Config::default()
+    };
+    assert_eq!(
+        swallowed(config),
+        vec![
+            (
+                6,
+                "KeyError, json.JSONDecodeError silently swallowed; handle or log them".to_string()
+            ),
+            (
+                13,
+                "KeyError silently swallowed; handle or log it".to_string()
+            ),
+        ]
+    );
+
+    let config = Config {
+        enabled_rules,
+        allowed_swallowed: ["LookupError".to_string()].into_iter().collect(),
+        ..Config::default()
+    };
+    assert_eq!(swallowed(config).len(), 1);
+}
+
+#[test]
+fn raise_from_in_a_handler_translates_the_exception() {
+    let source = "\
+class ConfigError(Exception):
+    pass
+
+def lookup(d):
+    return d['key']
+
+def load(d):
+    try:
+        return lookup(d)
+    except KeyError as e:
+        raise ConfigError('missing key') from e
+
+def main():
+    load({})
+";
+    let warnings: Vec<Warning> = analyze(source)
+        .into_iter()
+        .filter(|warning| warning.rule == Rule::UnhandledCall)
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].exceptions, vec!["ConfigError"]);
+    let chain: Vec<(usize, &str)> = warnings[0]
+        .related
+        .iter()
+        .map(|related| (related.line, related.message.as_str()))
+        .collect();
+    assert_eq!(
+        chain,
+        vec![
+            (11, "ConfigError may be raised here in 'load'"),
+            (
+                9,
+                "KeyError may be raised here in 'load', and is raised again as ConfigError"
+            ),
+            (5, "KeyError may be raised here in 'lookup'"),
+        ]
+    );
+}
+
+#[test]
+fn nested_functions_raise_only_where_they_are_called() {
+    let source = "\
+def helper(d):
+    return d.get('key')
+
+def outer(d):
+    def helper(x):
+        if not x:
+            raise ValueError('empty')
+        return x
+
+    def unused(y):
+        return y['other']
+
+    return helper(d)
+
+def caller():
+    return helper({})
+";
+    let warnings: Vec<(usize, String)> = analyze(source)
+        .into_iter()
+        .map(|warning| (warning.line, warning.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (
+                11,
+                "Possible KeyError in function 'outer.<locals>.unused'".to_string()
+            ),
+            (
+                13,
+                "Possible ValueError not handled when calling 'helper' in function 'outer'"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn functions_defined_twice_are_told_apart() {
+    let source = "\
+def load(d):
+    return d['key']
+
+def setup():
+    raise RuntimeError('not ready')
+
+setup()
+
+def load(d):
+    return d.get('key')
+
+def setup():
+    pass
+
+def main(d):
+    setup()
+    return load(d)
+";
+    let warnings: Vec<(usize, String)> = analyze(source)
+        .into_iter()
+        .map(|warning| (warning.line, warning.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (2, "Possible KeyError in function 'load'".to_string()),
+            (
+                7,
+                "Possible RuntimeError not handled when calling 'setup' in function '<module>'"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn keys_known_from_literals_and_stores_are_present() {
+    let source = "\
+def known():
+    d = {'a': 1, \"b\": 2}
+    e = dict(x=1)
+    print(d['a'], d['b'], e['x'])
+    d['c'] = 3
+    print(d['c'])
+
+def unknown(flag, other):
+    d = {'a': 1}
+    print(d['z'])
+    if flag:
+        d = other
+    print(d['a'])
+
+def removed():
+    d = {'a': 1}
+    d.pop('a')
+    print(d['a'])
+";
+    let lines: Vec<usize> = analyze(source)
+        .iter()
+        .filter(|w| w.rule == Rule::UnguardedDictAccess)
+        .map(|w| w.line)
+        .collect();
+    assert_eq!(lines, vec![10, 13, 17, 18]);
+}
+
+#[test]
+fn storing_a_key_is_not_reading_it() {
+    let source = "\
+def update(d, items, names):
+    d['a'] = 1
+    first, d['b'] = items
+    for d['c'] in items:
+        pass
+    d['count'] += 1
+    del d['old']
+    names = []
+    names[0] = 'x'
+";
+    let warnings: Vec<(usize, Vec<String>)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.line, w.exceptions))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (3, vec!["ValueError".to_string()]),
+            (6, vec!["KeyError".to_string()]),
+            (7, vec!["KeyError".to_string()]),
+            (9, vec!["IndexError".to_string()]),
+        ]
+    );
+}
+
+#[test]
+fn typed_dict_fields_decide_which_keys_are_present() {
+    let source = "\
+from typing import TypedDict, NotRequired
+
+class Movie(TypedDict):
+    title: str
+    year: NotRequired[int]
+
+class Film(Movie, total=False):
+    director: str
+
+def show(movie: Movie, film: Film):
+    print(movie['title'], movie['year'], movie['rating'])
+    print(film['title'], film['director'])
+";
+    let warnings: Vec<(usize, usize, String)> = analyze(source)
+        .into_iter()
+        .map(|warning| (warning.line, warning.column, warning.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (11, 27, "Possible KeyError in function 'show'".to_string()),
+            (
+                11,
+                42,
+                "Key 'rating' is not declared in TypedDict 'Movie' in function 'show'".to_string()
+            ),
+            (12, 26, "Possible KeyError in function 'show'".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn pops_without_a_default_raise() {
+    let source = "\
+def take(d, k):
+    if k in d:
+        d.pop(k)
+    d.pop(k, None)
+    return d.pop(k)
+
+def last(items):
+    stack = [1, 2]
+    stack.pop(0)
+
+def caller(d):
+    try:
+        return take(d, 'x')
+    except KeyError:
+        return None
+";
+    let warnings: Vec<(usize, Rule, String)> = analyze(source)
+        .into_iter()
+        .map(|warning| (warning.line, warning.rule, warning.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (
+                5,
+                Rule::UnguardedDictAccess,
+                "Possible KeyError from pop() in function 'take'".to_string()
+            ),
+            (
+                9,
+                Rule::UnguardedIndexAccess,
+                "Possible IndexError from pop() in function 'last'".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn next_inside_generators_raises_runtime_error() {
+    let source = "\
+def pairs(it):
+    while True:
+        yield next(it), next(it, None)
+
+def firsts(it):
+    try:
+        yield next(it)
+    except StopIteration:
+        return
+";
+    let warnings = analyze(source);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, 3);
+    assert_eq!(
+        warnings[0].message,
+        "Possible StopIteration from next() without a default in generator 'pairs'; \
+         it is raised as RuntimeError"
+    );
+}
+
+#[test]
+fn constructors_raise_at_instantiation() {
+    let source = "\
+class Config:
+    def __init__(self, path):
+        if not path:
+            raise ValueError('no path')
+        self.path = path
+
+def load(path):
+    cfg = Config(path)
+    return cfg
+
+def safe_load(path):
+    try:
+        return Config(path)
+    except ValueError:
+        return None
+";
+    let warnings: Vec<(usize, String)> = analyze(source)
+        .into_iter()
+        .map(|warning| (warning.line, warning.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![(
+            8,
+            "Possible ValueError not handled when calling 'Config' in function 'load'".to_string()
+        )]
+    );
+}
+
+#[test]
+fn property_getters_raise_where_they_are_read() {
+    let source = "\
+class Store:
+    def __init__(self, data):
+        self._data = data
+
+    @property
+    def value(self):
+        if 'value' not in self._data:
+            raise KeyError('value')
+        return self._data['value']
+
+def main(data):
+    store = Store(data)
+    store.value = 1
+    print(store.value)
+    try:
+        print(store.value)
+    except KeyError:
+        pass
+";
+    let warnings: Vec<(usize, Rule, String)> = analyze(source)
+        .into_iter()
+        .map(|warning| (warning.line, warning.rule, warning.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![(
+            14,
+            Rule::UnhandledPropertyAccess,
+            "Possible KeyError not handled when reading property 'Store.value' in function 'main'"
+                .to_string()
+        )]
+    );
+}
+
+#[test]
+fn context_managers_raise_from_enter_and_exit() {
+    let source = "\
+class Lock:
+    def __enter__(self):
+        raise TimeoutError('busy')
+
+    def __exit__(self, *args):
+        return False
+
+class Session:
+    async def __aenter__(self):
+        return self
+
+    async def __aexit__(self, *args):
+        raise ConnectionError()
+
+def update(d):
+    lock = Lock()
+    with lock:
+        pass
+    try:
+        with Lock():
+            pass
+    except TimeoutError:
+        pass
+
+async def fetch():
+    async with Session() as session:
+        pass
+";
+    let warnings: Vec<(usize, String)> = analyze(source)
+        .into_iter()
+        .filter(|warning| warning.rule == Rule::UnhandledCall)
+        .map(|warning| (warning.line, warning.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (
+                17,
+                "Possible TimeoutError not handled when calling 'Lock.__enter__' in function 'update'"
+                    .to_string()
+            ),
+            (
+                26,
+                "Possible ConnectionError not handled when calling 'Session.__aexit__' in function 'fetch'"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn unpacking_values_of_unknown_length_raises() {
+    let source = "\
+def parse(line, parts):
+    key, value = line.split('=')
+    first, *rest = parts
+    x, y = 1, 2
+    head, sep, tail = line.partition('=')
+    if len(parts) == 2:
+        a, b = parts
+    try:
+        c, d = parts
+    except ValueError:
+        pass
+    return key, value
+";
+    let warnings: Vec<(usize, Rule)> = analyze(source)
+        .into_iter()
+        .map(|warning| (warning.line, warning.rule))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![(2, Rule::UncheckedUnpacking), (3, Rule::UncheckedUnpacking)]
+    );
+}
+
+#[test]
+fn strict_asserts_raise_assertion_error() {
+    let source = "\
+def validate(value):
+    assert value > 0, 'value must be positive'
+    return value
+
+def main():
+    validate(-1)
+";
+    assert!(analyze(source).is_empty());
+
+    let config = Config {
+        strict_asserts: true,
+        ..Config::default()
+    };
+    let warnings = analyze_source(source, "example.py", &config);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].message,
+        "Possible AssertionError not handled when calling 'validate' in function 'main'"
+    );
+}
+
+#[test]
+fn exceptions_propagate_through_recursive_calls() {
+    let source = "\
+def main(tree):
+    walk(tree)
+
+def walk(node):
+    for child in node.children:
+        visit(child)
+
+def visit(node):
+    if node.leaf:
+        check(node)
+    else:
+        walk(node)
+
+def check(node):
+    if node.value < 0:
+        raise ValueError(node.value)
+";
+    let warnings: Vec<String> = analyze(source)
+        .into_iter()
+        .map(|warning| warning.message)
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            "Possible ValueError not handled when calling 'walk' in function 'main'",
+            "Possible ValueError not handled when calling 'visit' in function 'walk'",
+            "Possible ValueError not handled when calling 'check' in function 'visit'",
+            "Possible ValueError not handled when calling 'walk' in function 'visit'",
+        ]
+    );
+}
+
+#[test]
+fn syntax_errors_are_reported() {
+    let source = "\
+def load(config):
+    return config['path'
+
+def main(d):
+    return d['key']
+";
+    let warnings: Vec<(usize, Rule, String)> = analyze(source)
+        .into_iter()
+        .map(|warning| (warning.line, warning.rule, warning.message))
+        .collect();
+    assert_eq!(
+        warnings.first(),
+        Some(&(2, Rule::SyntaxError, "Syntax error at 2:5".to_string()))
+    );
+    assert!(warnings.len() > 1);
+
+    let config = Config {
+        skip_syntax_errors: true,
+        ..Config::default()
+    };
+    let warnings = analyze_source(source, "example.py", &config);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].rule, Rule::SyntaxError);
+    assert_eq!(warnings[0].severity, Severity::Error);
+}
+
+#[test]
+fn calls_into_installed_packages_raise_what_the_package_raises() {
+    let source = "\
+import yamlish
+from yamlish import safe_load
+
+def load(text):
+    return yamlish.safe_load(text)
+
+def load_imported(text):
+    return safe_load(text)
+
+def load_or_none(text):
+    try:
+        return yamlish.safe_load(text)
+    except yamlish.YAMLError:
+        return None
+
+def main(text):
+    load(text)
+    load_imported(text)
+    load_or_none(text)
+    yamlish.check(text)
+";
+    let site_packages =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/site-packages");
+    let config = Config {
+        site_packages: Some(SitePackages::new(&site_packages).unwrap()),
+        ..Config::default()
+    };
+    let warnings: Vec<(usize, Vec<String>)> = analyze_source(source, "example.py", &config)
+        .into_iter()
+        .map(|w| (w.line, w.exceptions))
+        .collect();
+    let raises = vec!["YAMLError".to_string()];
+    assert_eq!(warnings, vec![(17, raises.clone()), (18, raises)]);
+
+    // Without the packages, their calls raise nothing known
+    assert!(analyze(source).is_empty());
+}
+
+#[test]
+fn templates_filled_from_mappings_may_lack_placeholders() {
+    let source = "\
+from string import Template
+
+def render(d, user):
+    print('{name}'.format_map(d))
+    print('{name} {age:{width}}'.format(**d, width=3))
+    print('{0} {name}'.format(1, name=user))
+    known = {'name': user}
+    print('{name}'.format_map(known))
+    print(Template('$who pays $$5 for ${what}').substitute(d))
+    template = Template('$who')
+    print(template.substitute(who=user))
+    print(template.safe_substitute(d))
+";
+    let messages: Vec<(usize, String)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.line, w.message))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                4,
+                "Possible KeyError from format_map() in function 'render': 'name' may be missing from the mapping".to_string()
+            ),
+            (
+                5,
+                "Possible KeyError from format() in function 'render': 'name', 'age' may be missing from the mapping".to_string()
+            ),
+            (
+                9,
+                "Possible KeyError from substitute() in function 'render': 'who', 'what' may be missing from the mapping".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn docstring_raises_sections_are_checked_against_what_functions_raise() {
+    let source = "\
+def load(d):
+    \"\"\"Load a record.
+
+    Raises:
+        ValueError: if the record is empty.
+    \"\"\"
+    return d['key']
+
+def check(n):
+    \"\"\"Check a number.
+
+    :raises LookupError: if it is negative
+    \"\"\"
+    if n < 0:
+        raise KeyError(n)
+";
+    let mut config = Config {
+        enabled_rules: Rule::ALL.into_iter().collect(),
+        ..Config::default()
+    };
+    let messages = |config: &Config| -> Vec<(usize, String)> {
+        analyze_source(source, "example.py", config)
+            .into_iter()
+            .filter(|w| w.rule == Rule::DocstringRaises)
+            .map(|w| (w.line, w.message))
+            .collect()
+    };
+    assert_eq!(
+        messages(&config),
+        vec![
+            (
+                1,
+                "Function 'load' may raise KeyError, which its docstring doesn't document"
+                    .to_string()
+            ),
+            (
+                2,
+                "Docstring documents ValueError, which function 'load' doesn't raise".to_string()
+            ),
+        ]
+    );
+
+    // Sphinx fields aren't read as Google style
+    config.docstring_style = Some(DocstringStyle::Google);
+    assert_eq!(
+        messages(&config)[2],
+        (
+            9,
+            "Function 'check' may raise KeyError, which its docstring doesn't document".to_string()
+        )
+    );
+}
+
+#[test]
+fn handlers_for_exceptions_the_try_body_cannot_raise_are_reported() {
+    let source = "\
+def helper():
+    raise ValueError('bad')
+
+def run(d, path):
+    try:
+        helper()
+    except (ValueError, FileNotFoundError):
+        pass
+    try:
+        value = d['key']
+    except KeyError:
+        value = None
+    try:
+        open(path).read()
+    except KeyError:
+        pass
+    return value
+";
+    let warnings: Vec<Warning> = analyze(source)
+        .into_iter()
+        .filter(|w| w.rule == Rule::UselessHandler)
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].line, 7);
+    assert_eq!(
+        warnings[0].message,
+        "Handler for FileNotFoundError never runs: the try body can't raise it"
+    );
+}
+
+#[test]
+fn exception_groups_raise_what_they_contain() {
+    let source = "\
+def validate(d):
+    raise ExceptionGroup('invalid', [KeyError('a'), ValueError('b')])
+
+def partial(d):
+    try:
+        validate(d)
+    except* KeyError:
+        pass
+
+def whole(d):
+    try:
+        validate(d)
+    except ExceptionGroup:
+        pass
+
+def each(d):
+    try:
+        validate(d)
+    except* KeyError:
+        pass
+    except* ValueError:
+        pass
+
+def lookup(d, k):
+    try:
+        return d[k]
+    except ExceptionGroup:
+        return None
+";
+    let messages: Vec<String> = analyze(source).into_iter().map(|w| w.message).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "Possible ValueError not handled when calling 'validate' in function 'partial'",
+            // A lookup error raised on its own isn't in a group
+            "Possible LookupError in function 'lookup'",
+            "Handler for ExceptionGroup never runs: the try body can't raise it",
+        ]
+    );
+}
+
+#[test]
+fn handlers_swallowing_system_exit_are_reported() {
+    let source = "\
+import sys
+
+def fail(code):
+    sys.exit(code)
+
+def run(d):
+    try:
+        fail(1)
+    except Exception:
+        pass
+    try:
+        fail(2)
+    except BaseException as e:
+        print(e)
+    try:
+        fail(3)
+    except SystemExit:
+        pass
+    try:
+        d.clear()
+    except BaseException:
+        raise
+
+def main():
+    fail(0)
+";
+    let messages: Vec<(usize, String)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.line, w.message))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                13,
+                "'except BaseException:' swallows SystemExit and KeyboardInterrupt, so the program can't be stopped; re-raise them".to_string()
+            ),
+            (
+                17,
+                "'except SystemExit:' swallows SystemExit, so the program can't be stopped; re-raise it".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn columns_count_characters_rather_than_bytes() {
+    let warnings = analyze("def f(d):\n    label = 'café'; return d['k']\n");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!((warnings[0].line, warnings[0].column), (2, 28));
+    assert_eq!((warnings[0].end_line, warnings[0].end_column), (2, 34));
+}
+
+#[test]
+fn entry_point_mode_reports_only_escaping_exceptions() {
+    let source = "\
+def load(d):
+    return d['key']
+
+def safe(d):
+    try:
+        return load(d)
+    except KeyError:
+        return None
+
+def main(d):
+    safe(d)
+    return load(d)
+
+def serve(d):
+    return int(d)
+
+if __name__ == '__main__':
+    main({})
+";
+    let config = Config {
+        entry_points: Some(["serve".to_string()].into_iter().collect()),
+        ..Config::default()
+    };
+    let messages: Vec<(usize, Rule, String)> = analyze_source(source, "example.py", &config)
+        .into_iter()
+        .map(|w| (w.line, w.rule, w.message))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                10,
+                Rule::EntryPointEscape,
+                "Possible KeyError escapes entry point 'main', crashing the program".to_string()
+            ),
+            (
+                14,
+                Rule::EntryPointEscape,
+                "Possible ValueError escapes entry point 'serve', crashing the program".to_string()
+            ),
+            (
+                18,
+                Rule::EntryPointEscape,
+                "Possible KeyError escapes the __main__ block, crashing the program".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn methods_named_main_are_not_entry_points() {
+    let source = "\
+class App:
+    def main(self):
+        raise ValueError('bad')
+
+def run():
+    def main():
+        raise KeyError('key')
+    return main
+";
+    let config = Config {
+        entry_points: Some(Default::default()),
+        ..Config::default()
+    };
+    let warnings = analyze_source(source, "example.py", &config);
+    assert!(warnings
+        .iter()
+        .all(|warning| warning.rule != Rule::EntryPointEscape));
+
+    // Unless they are given as entry points
+    let config = Config {
+        entry_points: Some(["App.main".to_string()].into_iter().collect()),
+        ..Config::default()
+    };
+    let warnings = analyze_source(source, "example.py", &config);
+    let escaping: Vec<&str> = warnings
+        .iter()
+        .filter(|warning| warning.rule == Rule::EntryPointEscape)
+        .map(|warning| warning.message.as_str())
+        .collect();
+    assert_eq!(
+        escaping,
+        ["Possible ValueError escapes entry point 'App.main', crashing the program"]
+    );
+}
+
+#[test]
+fn acceptable_exceptions_are_not_warned_about() {
+    let source = "\
+class AppError(Exception):
+    pass
+
+class NotFound(AppError):
+    pass
+
+def find(d, k):
+    if k not in d:
+        raise NotFound(k)
+    return d[k]
+
+def validate_age(text):
+    return int(text)
+
+def run(d):
+    find(d, 'x')
+    int(d['count'])
+    return validate_age(d['age'])
+";
+    let config = Config {
+        acceptable_exceptions: ["AppError".to_string()].into_iter().collect(),
+        acceptable_by_function: [("validate_*".to_string(), "ValueError".to_string())]
+            .into_iter()
+            .collect(),
+        ..Config::default()
+    };
+    let messages: Vec<String> = analyze_source(source, "example.py", &config)
+        .into_iter()
+        .map(|w| w.message)
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            "Possible ValueError from int() in function 'run'",
+            "Possible KeyError in function 'run'",
+            "Possible KeyError in function 'run'",
+        ]
+    );
+}
+
+#[test]
+fn django_request_parameters_raise_multi_value_dict_key_error() {
+    let source = "\
+def search(request):
+    query = request.GET['q']
+    if 'page' in request.GET:
+        query += request.GET['page']
+    try:
+        query += request.POST['sort']
+    except KeyError:
+        pass
+    return query
+";
+    let config = Config {
+        frameworks: vec![Framework::Django],
+        ..Config::default()
+    };
+    let messages: Vec<(usize, String)> = analyze_source(source, "views.py", &config)
+        .into_iter()
+        .map(|w| (w.line, w.message))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![(
+            2,
+            "Possible MultiValueDictKeyError from request.GET in function 'search'".to_string()
+        )]
+    );
+}
+
+#[test]
+fn flask_views_are_entry_points() {
+    let source = "\
+from flask import Flask, abort, request
+
+app = Flask(__name__)
+
+@app.post('/items')
+def create():
+    count = request.args['count']
+    return request.json['name'] * int(count)
+
+def helper(data):
+    return data['name']
+";
+    let config = Config {
+        frameworks: vec![Framework::Flask],
+        entry_points: Some(Default::default()),
+        ..Config::default()
+    };
+    let messages: Vec<(usize, String)> = analyze_source(source, "app.py", &config)
+        .into_iter()
+        .map(|w| (w.line, w.message))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![(
+            6,
+            "Possible KeyError, ValueError escapes view 'create', becoming a 500 response"
+                .to_string()
+        )]
+    );
+}
+
+#[test]
+fn inherited_methods_are_resolved_through_base_classes() {
+    let source = "\
+class Base:
+    def load(self, text):
+        if not text:
+            raise ValueError('empty')
+        return text
+
+class Middle(Base):
+    pass
+
+class Child(Middle):
+    def run(self, text):
+        return self.load(text)
+
+class Quiet(Base):
+    def load(self, text):
+        return text
+
+    def run(self, text):
+        return self.load(text)
+";
+    let messages: Vec<(usize, String)> = analyze(source)
+        .into_iter()
+        .map(|w| (w.line, w.message))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![(
+            12,
+            "Possible ValueError not handled when calling 'self.load' in function 'Child.run'"
+                .to_string()
+        )]
+    );
+}
+
+#[test]
+fn sources_are_decoded_by_their_declared_encoding_or_byte_order_mark() {
+    let latin1 = b"# -*- coding: latin-1 -*-\ndef f(d):\n    return d['\xe9t\xe9']\n";
+    let (source, encoding) = encoding::decode(latin1).unwrap();
+    assert_eq!(encoding, Encoding::Latin1);
+    assert!(source.ends_with("d['été']\n"));
+    assert_eq!(encoding.encode(&source).unwrap(), latin1.to_vec());
+    // Offsets in the decoded source map back to the bytes of the file
+    let offset = source.find("']").unwrap();
+    assert_eq!(encoding.file_offset(&source, offset), latin1.len() - 3);
+
+    // The cookie may be on the second line, after a shebang
+    let cp1252 = b"#!/usr/bin/env python\n# vim: set fileencoding=cp1252 :\nx = '\x93'\n";
+    let (source, encoding) = encoding::decode(cp1252).unwrap();
+    assert_eq!(encoding, Encoding::Windows1252);
+    assert!(source.ends_with("x = '\u{201c}'\n"));
+
+    let (source, encoding) = encoding::decode(b"\xef\xbb\xbfx = 1\n").unwrap();
+    assert_eq!((source.as_str(), encoding), ("x = 1\n", Encoding::Utf8Bom));
+
+    let error = encoding::decode(b"x = 1\ny = '\xe9'\n").unwrap_err();
+    assert!(error.to_string().starts_with("invalid UTF-8 on line 2"));
+    assert!(encoding::decode(b"# coding: euc-jp\n").is_err());
+
+    // Positions are counted in the decoded source
+    let (source, _) = encoding::decode(latin1).unwrap();
+    let warnings = analyze(&source);
+    assert_eq!((warnings[0].line, warnings[0].column), (3, 12));
+}
+
+#[test]
+fn warnings_of_custom_checks_survive_the_cache() {
+    let dir = std::env::temp_dir().join(format!("pysleuth-custom-{}", std::process::id()));
+    let mut config = Config::default();
+    config.add_check(Box::new(ParseCheck));
+    let analyzer = Analyzer::new(config).with_cache(&dir);
+    let source = "def load(text):\n    return parse(text)\n";
+
+    let first = analyzer.analyze_source(source, "example.py");
+    let cached = Cache::new(&dir, analyzer.config()).get("example.py", source);
+    let second = analyzer.analyze_source(source, "example.py");
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].rule, Rule::Custom("ACME001"));
+    assert_eq!(cached.as_ref(), Some(&first));
+    assert_eq!(second, first);
+}
+
+#[test]
+fn cache_eviction_only_removes_files_the_cache_wrote() {
+    let dir = std::env::temp_dir().join(format!("pysleuth-evict-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let old = SystemTime::now() - Duration::from_secs(60 * 24 * 60 * 60);
+    let names = [
+        "0123456789abcdef.json",
+        "0123456789abcdef.4242.tmp",
+        "notes.txt",
+        "0123456789abcdef.txt",
+    ];
+    for name in names {
+        let file = std::fs::File::create(dir.join(name)).unwrap();
+        file.set_modified(old).unwrap();
+    }
+
+    Cache::new(&dir, &Config::default()).evict();
+    let exists = names.map(|name| dir.join(name).exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(exists, [false, false, true, true]);
+}
@@ -0,0 +1,939 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+fn run(fixture: &str) -> String {
+    run_with(fixture, &[])
+}
+
+fn run_with(fixture: &str, args: &[&str]) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(fixture);
+    let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .args(args)
+        .arg(&path)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("failed to run pysleuth");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn match_statement_case_bodies_are_analyzed() {
+    let output = run("match_statement.py");
+
+    // Subscripts in a case body and in a case guard are reported
+    assert!(output.contains("match_statement.py:4:20: Warning: Possible LookupError"));
+    assert!(output.contains("match_statement.py:5:32: Warning: Possible KeyError"));
+
+    // Dict patterns are not subscripts, and guarded case bodies stay quiet
+    assert!(!output.contains("match_statement.py:3:"));
+    assert!(!output.contains("match_statement.py:9:"));
+}
+
+#[test]
+fn except_star_handlers_depend_on_python_version() {
+    let output = run("except_star.py");
+    assert!(!output.contains("Possible KeyError"));
+
+    let output = run_with("except_star.py", &["--python-version", "3.10"]);
+    assert!(output.contains("except_star.py:3:16: Warning: Possible KeyError"));
+}
+
+#[test]
+fn suggest_fixes_proposes_get_or_try_except() {
+    let output = run("suggest_fixes.py");
+    assert!(!output.contains("Suggested fix:"));
+
+    let output = run_with("suggest_fixes.py", &["--suggest-fixes"]);
+
+    // Reads become `.get()` calls
+    assert!(output.contains("-     return config[\"name\"]\n+     return config.get(\"name\")"));
+
+    // Writes are wrapped in a try/except block instead
+    assert!(output.contains("+     try:\n+         counts[\"total\"] += 1\n+     except KeyError:"));
+    assert!(output.contains("+             del cache[\"stale\"]"));
+}
+
+#[test]
+fn fix_rewrites_files_and_dry_run_prints_a_diff() {
+    let source = std::fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/fixes.py"),
+    )
+    .unwrap();
+    let path = std::env::temp_dir().join(format!("pysleuth-fix-{}.py", std::process::id()));
+    std::fs::write(&path, &source).unwrap();
+    let fix = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+            .args(args)
+            .arg(&path)
+            .output()
+            .unwrap()
+    };
+
+    let output = fix(&["--fix-dry-run"]);
+    let diff = String::from_utf8(output.stdout).unwrap();
+    assert!(diff.contains("@@ -2,7 +2,7 @@\n     return config[\"name\"]\n \n def total(counts):\n-    return counts[\"n\"] + 1\n+    return counts.get(\"n\", 0) + 1\n"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), source);
+
+    // Only the default inferred from `+ 1` is safe to apply; `.get()` with
+    // no default and try/except blocks are left as suggestions
+    let output = fix(&["--fix"]);
+    let fixed = std::fs::read_to_string(&path).unwrap();
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Fixed 1 warning(s)"));
+    assert!(fixed.contains("    return counts.get(\"n\", 0) + 1\n"));
+    assert!(fixed.contains("    return config[\"name\"]\n"));
+
+    // The warnings left are reported where they are in the rewritten file
+    std::fs::write(&path, &source).unwrap();
+    let output = fix(&["--fix", "--unsafe-fixes"]);
+    let fixed = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Fixed 2 warning(s)"));
+    assert!(fixed.contains(
+        "    try:\n        counts[\"total\"] += len(\"\"\"\ntwo\n\"\"\")\n    except KeyError:\n        # TODO: handle the missing key\n        raise\n"
+    ));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(":2:12: Warning: Possible KeyError in function 'read'"));
+    assert!(stdout.contains(":17:12: Warning: Possible KeyError in function 'last'"));
+}
+
+#[test]
+fn getitem_exceptions_propagate_to_subscripts() {
+    let output = run("getitem.py");
+
+    // Instances built from the class and `self` both resolve to `__getitem__`
+    assert!(output.contains(
+        "getitem.py:23:12: Warning: Possible LookupError not handled when calling 'Registry.__getitem__'"
+    ));
+    assert!(output.contains(
+        "getitem.py:9:16: Warning: Possible LookupError not handled when calling 'Registry.__getitem__' in function 'Registry.first'"
+    ));
+
+    // A `__getitem__` that cannot raise, or a guarded subscript, stays quiet
+    assert!(!output.contains("getitem.py:28:"));
+    assert!(!output.contains("getitem.py:33:"));
+}
+
+#[test]
+fn baseline_suppresses_recorded_warnings() {
+    let baseline =
+        std::env::temp_dir().join(format!("pysleuth-baseline-{}.json", std::process::id()));
+    let baseline_arg = baseline.to_str().unwrap();
+
+    let output = run_with("suggest_fixes.py", &["--update-baseline", baseline_arg]);
+    assert!(output.is_empty());
+
+    let recorded = std::fs::read_to_string(&baseline).unwrap();
+    assert!(recorded.contains("Possible KeyError in function 'read'"));
+
+    let output = run_with("suggest_fixes.py", &["--baseline", baseline_arg]);
+    assert!(output.is_empty());
+
+    // Recorded warnings stay suppressed when the code around them moves
+    let moved = std::env::temp_dir().join(format!("pysleuth-moved-{}.py", std::process::id()));
+    let source = std::fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/suggest_fixes.py"),
+    )
+    .unwrap();
+    std::fs::write(&moved, format!("import os\n\n{}", source)).unwrap();
+    let moved_arg = moved.to_str().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .args(["--write-baseline", baseline_arg, moved_arg])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    std::fs::write(&moved, format!("import os\nimport sys\n\n\n{}", source)).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .args(["--baseline", baseline_arg, moved_arg])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&baseline).unwrap();
+    std::fs::remove_file(&moved).unwrap();
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn summary_counts_warnings_per_rule() {
+    let output = run("suggest_fixes.py");
+    assert!(output.ends_with("Found 3 warning(s) (PW001: 3)\n"));
+
+    let output = run_with("suggest_fixes.py", &["--disable", "PW001"]);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn json_format_prints_structured_warnings() {
+    let output = run_with("suggest_fixes.py", &["--format", "json"]);
+    let warnings: serde_json::Value = serde_json::from_str(&output).unwrap();
+    let warnings = warnings.as_array().unwrap();
+    assert_eq!(warnings.len(), 3);
+    assert_eq!(warnings[0]["line"], 2);
+    assert_eq!(warnings[0]["column"], 12);
+    assert_eq!(warnings[0]["rule"], "PW001");
+    assert_eq!(warnings[0]["function"], "read");
+    assert_eq!(warnings[0]["exceptions"], serde_json::json!(["KeyError"]));
+    assert_eq!(
+        warnings[0]["message"],
+        "Possible KeyError in function 'read'"
+    );
+    assert!(warnings[0]["filename"]
+        .as_str()
+        .unwrap()
+        .ends_with("suggest_fixes.py"));
+}
+
+#[test]
+fn sarif_format_describes_rules_and_locations() {
+    let output = run_with("suggest_fixes.py", &["--format", "sarif"]);
+    let log: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(log["version"], "2.1.0");
+
+    let run = &log["runs"][0];
+    let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+    assert_eq!(rules[0]["id"], "PW001");
+    assert_eq!(rules[4]["id"], "PW005");
+    assert_eq!(rules[4]["name"], "UnguardedIndexAccess");
+
+    let result = &run["results"][0];
+    assert_eq!(result["ruleId"], "PW001");
+    assert_eq!(result["ruleIndex"], 0);
+    assert_eq!(result["level"], "warning");
+    let region = &result["locations"][0]["physicalLocation"]["region"];
+    assert_eq!(region["startLine"], 2);
+    assert_eq!(region["startColumn"], 12);
+    assert_eq!(region["endColumn"], 26);
+}
+
+#[test]
+fn github_format_prints_workflow_commands() {
+    let output = run_with("suggest_fixes.py", &["--format", "github"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("::warning file="));
+    assert!(lines[0].ends_with(
+        "suggest_fixes.py,line=2,col=12,endLine=2,endColumn=26,title=PW001::Possible KeyError in function 'read'"
+    ));
+}
+
+#[test]
+fn settings_are_read_from_pyproject_toml() {
+    let output = run("configured");
+    assert!(output.contains("app.py,line=5,col=22,endLine=5,endColumn=28,title=PW009::"));
+    assert!(!output.contains("calling 'fetch'"));
+    assert!(!output.contains("vendor"));
+
+    // Options on the command line take precedence
+    let output = run_with("configured", &["--format", "text", "--exclude", "app.py"]);
+    assert!(output.contains("vendor/lib.py:2:12: Warning: Possible KeyError"));
+    assert!(!output.contains("app.py"));
+
+    let output = run_with("configured/app.py", &["--config", "/dev/null"]);
+    assert!(output.contains("calling 'fetch'"));
+}
+
+#[test]
+fn exit_status_reflects_warnings_and_failures() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let status = |args: &[&str], fixture: &str| {
+        Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+            .args(args)
+            .arg(fixtures.join(fixture))
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+    assert_eq!(status(&[], "suggest_fixes.py"), Some(1));
+    assert_eq!(status(&["--exit-zero"], "suggest_fixes.py"), Some(0));
+    assert_eq!(status(&[], "project/app/main.py"), Some(0));
+    assert_eq!(status(&[], "missing.py"), Some(2));
+    assert_eq!(status(&[], "syntax_error.py"), Some(2));
+    assert_eq!(status(&["--stubs", "missing"], "suggest_fixes.py"), Some(2));
+
+    // A warning budget fails only when it is exceeded
+    assert_eq!(
+        status(&["--max-warnings", "3"], "suggest_fixes.py"),
+        Some(0)
+    );
+    assert_eq!(
+        status(&["--max-warnings", "2"], "suggest_fixes.py"),
+        Some(1)
+    );
+    let output = run_with("suggest_fixes.py", &["--max-warnings", "2"]);
+    assert!(output.ends_with("Budget: 3 of 2 warning(s) allowed (1 over)\n"));
+}
+
+#[test]
+fn rules_are_selected_and_ignored_by_code_or_name() {
+    let output = run("getitem.py");
+    assert!(output.contains("in function 'Registry.first' [PW002]\n"));
+    assert!(output.contains(" [PW001]\n"));
+
+    let output = run_with("getitem.py", &["--select", "unguarded-dict-access"]);
+    assert!(!output.contains("[PW002]"));
+    assert!(output.contains("[PW001]"));
+
+    let output = run_with("getitem.py", &["--select", "PW00", "--ignore", "PW002"]);
+    assert!(!output.contains("[PW002]"));
+    assert!(output.contains("[PW001]"));
+}
+
+#[test]
+fn severities_are_overridden_per_rule() {
+    let output = run_with("getitem.py", &["--severity", "PW002=error"]);
+    assert!(output.contains("getitem.py:23:12: Error: Possible LookupError not handled"));
+    assert!(output.contains("Warning: Possible LookupError in function"));
+
+    let output = run_with(
+        "getitem.py",
+        &["--severity", "PW002=error", "--format", "github"],
+    );
+    assert!(output.contains("::error file="));
+    assert!(output.contains("::warning file="));
+
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/getitem.py");
+    let status = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+            .args(args)
+            .arg(&fixture)
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+    assert_eq!(status(&["--fail-on", "error"]), Some(0));
+    assert_eq!(
+        status(&["--fail-on", "error", "--severity", "PW002=error"]),
+        Some(1)
+    );
+    assert_eq!(status(&["--error"]), Some(0));
+    assert_eq!(status(&["--error", "--severity", "PW002=error"]), Some(1));
+
+    // The severity to fail on can be set, and overridden on the command line
+    let settings =
+        std::env::temp_dir().join(format!("pysleuth-fail-on-{}.toml", std::process::id()));
+    std::fs::write(&settings, "fail-on = \"error\"\n").unwrap();
+    let config = settings.to_str().unwrap();
+    assert_eq!(status(&["--config", config]), Some(0));
+    assert_eq!(
+        status(&["--config", config, "--fail-on", "warning"]),
+        Some(1)
+    );
+    std::fs::remove_file(&settings).unwrap();
+}
+
+fn run_stdin(source: &str, args: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .args(args)
+        .arg("-")
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run pysleuth");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn stdin_is_reported_under_the_given_filename() {
+    let source = "def get(d):\n    return d['key']\n";
+
+    let output = run_stdin(source, &[]);
+    assert!(output.starts_with("<stdin>:2:12: Warning: Possible KeyError"));
+
+    let output = run_stdin(source, &["--stdin-filename", "src/app.py"]);
+    assert!(output.starts_with("src/app.py:2:12: Warning: Possible KeyError"));
+
+    // Settings are found from the given filename, as for the file itself
+    let output = run_stdin(
+        source,
+        &["--stdin-filename", "tests/fixtures/configured/app.py"],
+    );
+    assert!(output.starts_with("::warning file=tests/fixtures/configured/app.py,line=2,"));
+}
+
+fn lsp_frame(message: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", message.len(), message)
+}
+
+#[test]
+fn lsp_publishes_diagnostics_for_open_documents() {
+    let open = r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///tmp/app.py","languageId":"python","version":1,"text":"def get(d):\n    return d['key']\n"}}}"#;
+    // Only the changed range is sent, here turning `d['key']` into `d.get('key')`
+    let change = r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///tmp/app.py","version":2},"contentChanges":[{"range":{"start":{"line":1,"character":12},"end":{"line":1,"character":19}},"text":".get('key')"}]}}"#;
+    let input = [
+        lsp_frame(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{}}}"#),
+        lsp_frame(r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#),
+        lsp_frame(open),
+        lsp_frame(change),
+        lsp_frame(r#"{"jsonrpc":"2.0","id":2,"method":"shutdown"}"#),
+        lsp_frame(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+    ]
+    .concat();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run pysleuth");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let output = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.contains(r#""textDocumentSync":2"#));
+    assert!(output.contains(r#""method":"textDocument/publishDiagnostics""#));
+    assert!(output.contains(r#""code":"PW001""#));
+    assert!(output.contains(
+        r#""range":{"end":{"character":19,"line":1},"start":{"character":11,"line":1}}"#
+    ));
+    assert!(output.contains(r#""message":"Possible KeyError in function 'get'""#));
+    assert!(output.contains(r#""diagnostics":[]"#));
+}
+
+#[test]
+fn lsp_skips_malformed_messages() {
+    let open = r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///tmp/app.py","languageId":"python","version":1,"text":"def get(d):\n    return d['key']\n"}}}"#;
+    let input = [
+        lsp_frame("{not json"),
+        lsp_frame(r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{}}"#),
+        lsp_frame(open),
+        lsp_frame(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+    ]
+    .concat();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run pysleuth");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(r#""error":{"code":-32700,"#));
+    assert!(stdout.contains(r#""message":"Possible KeyError in function 'get'""#));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Ignoring malformed textDocument/didOpen notification"));
+}
+
+#[test]
+fn project_mode_follows_imports_between_files() {
+    let output = run_with("project", &["--project"]);
+    assert!(output.contains(
+        "main.py:6:12: Warning: Possible KeyError not handled when calling 'load' in function 'run'"
+    ));
+    assert!(output.contains(
+        "main.py:10:12: Warning: Possible LookupError not handled when calling 'store.Store.get' in function 'fetch'"
+    ));
+    assert!(!output.contains("main.py:15:"));
+    assert!(output.contains(
+        "star.py:5:12: Warning: Possible KeyError not handled when calling 'load' in function 'open_store'"
+    ));
+    assert!(output.contains("star.py:1:20: Info: Calls to names from 'from .store import *'"));
+
+    // Without --project each file is analyzed on its own
+    let output = run("project/app/main.py");
+    assert!(output.is_empty());
+}
+
+#[test]
+fn directories_are_searched_for_python_files() {
+    let output = run("tree");
+    for file in [
+        "generated/out.py",
+        "lib/config.py",
+        "lib/messages_pb2.py",
+        "tests/test_config.py",
+    ] {
+        assert!(output.contains(&format!("{}:2:12:", file)), "{}", file);
+    }
+    assert!(!output.contains("notes.txt"));
+
+    let output = run_with("tree", &["--respect-gitignore", "--exclude", "tests"]);
+    assert!(output.contains("lib/config.py:2:12:"));
+    assert_eq!(output.matches("Possible KeyError").count(), 1);
+
+    let output = run_with("tree", &["--include", "tests/*.py"]);
+    assert!(output.contains("tests/test_config.py:2:12:"));
+    assert_eq!(output.matches("Possible KeyError").count(), 1);
+}
+
+#[test]
+fn diff_only_reports_warnings_on_changed_lines() {
+    let patch = "\
+--- a/tests/fixtures/suggest_fixes.py
++++ b/tests/fixtures/suggest_fixes.py
+@@ -4,2 +4,2 @@
+ def bump(counts):
+-    counts[\"total\"] -= 1
++    counts[\"total\"] += 1
+";
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .args(["--diff", "-", "tests/fixtures/suggest_fixes.py"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run pysleuth");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(patch.as_bytes())
+        .unwrap();
+    let output = String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap();
+
+    assert!(output.contains("suggest_fixes.py:5:5: Warning: Possible KeyError in function 'bump'"));
+    assert!(!output.contains("suggest_fixes.py:2:"));
+    assert!(!output.contains("suggest_fixes.py:9:"));
+}
+
+#[test]
+fn watch_reanalyzes_saved_files() {
+    let dir = std::env::temp_dir().join(format!("pysleuth-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("app.py");
+    std::fs::write(&file, "def read(d):\n    return d['key']\n").unwrap();
+    std::fs::write(dir.join("other.py"), "def other(d):\n    return d['key']\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .arg("--watch")
+        .arg(&dir)
+        .env("NO_COLOR", "1")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run pysleuth");
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+    std::fs::write(&file, "def read(d):\n    return d.get('key')\n").unwrap();
+    // Polling slows down while nothing changes, up to every two seconds
+    std::thread::sleep(std::time::Duration::from_millis(2500));
+    child.kill().unwrap();
+    let output = String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(output.contains("app.py:2:12: Warning: Possible KeyError in function 'read'"));
+    assert!(output.contains("Found 2 warning(s) in 2 file(s), watching"));
+
+    // Only the saved file is printed again
+    let (_, after_save) = output.split_once("Found 2 warning(s)").unwrap();
+    assert!(!after_save.contains("other.py:2:12"));
+    assert!(after_save.contains("Found 1 warning(s) in 2 file(s), watching"));
+}
+
+#[test]
+fn parallel_analysis_keeps_the_output_order() {
+    let output = |jobs: &str| {
+        Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+            .args(["--jobs", jobs, "--format", "json"])
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures"))
+            .output()
+            .unwrap()
+            .stdout
+    };
+    let sequential = output("1");
+    assert!(!sequential.is_empty());
+    assert_eq!(output("8"), sequential);
+}
+
+#[test]
+fn results_are_cached_by_file_contents() {
+    let dir = std::env::temp_dir().join(format!("pysleuth-cache-{}", std::process::id()));
+    let dir_arg = dir.to_str().unwrap();
+    let entries = || std::fs::read_dir(&dir).map_or(0, |entries| entries.count());
+
+    let uncached = run_with("suggest_fixes.py", &["--no-cache"]);
+    assert_eq!(entries(), 0);
+
+    let first = run_with("suggest_fixes.py", &["--cache-dir", dir_arg]);
+    assert_eq!(entries(), 1);
+    let second = run_with("suggest_fixes.py", &["--cache-dir", dir_arg]);
+    assert_eq!(entries(), 1);
+
+    // Options affecting the analysis are part of the key
+    run_with(
+        "suggest_fixes.py",
+        &["--cache-dir", dir_arg, "--suggest-fixes"],
+    );
+    assert_eq!(entries(), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(first, uncached);
+    assert_eq!(second, uncached);
+}
+
+#[test]
+fn text_output_points_at_where_called_functions_raise() {
+    let output = run("snippets.py");
+
+    // The call is underlined, and the call inside `read` it raises through
+    // is labelled below it
+    assert!(output.contains(
+        "8 |     return read(config)\n  |            ^^^^^^^^^^^^\n  |\n5 |     return load(config)\n  |            ------------ KeyError may be raised here in 'read'\n"
+    ));
+
+    // The short format keeps one line per warning
+    let output = run_with("snippets.py", &["--format", "short"]);
+    assert_eq!(output.lines().count(), 3);
+    assert!(output.contains(
+        "snippets.py:8:12: Warning: Possible KeyError not handled when calling 'read' in function 'main' [PW002]\n"
+    ));
+    assert!(!output.contains(" |"));
+}
+
+#[test]
+fn color_follows_the_color_option() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/snippets.py");
+    let colored = |args: &[&str], no_color: Option<&str>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_pysleuth"));
+        command.args(args).arg(&path).env_remove("NO_COLOR");
+        if let Some(value) = no_color {
+            command.env("NO_COLOR", value);
+        }
+        let output = command.output().expect("failed to run pysleuth");
+        String::from_utf8(output.stdout).unwrap().contains('\x1b')
+    };
+
+    // Output piped to the test isn't a terminal
+    assert!(!colored(&[], None));
+    assert!(!colored(&["--color", "never"], None));
+
+    // An explicit choice wins over NO_COLOR
+    assert!(colored(&["--color", "always"], Some("1")));
+    assert!(colored(&["--color", "always", "--format", "short"], None));
+    assert!(!colored(&["--color", "always", "--format", "json"], None));
+}
+
+#[test]
+fn statistics_total_warnings_by_rule_exception_and_file() {
+    let output = run_with("snippets.py", &["--statistics=1"]);
+    assert!(output.contains("  Files analyzed: 1\n  Parse failures: 0\n  Warnings: 2\n"));
+    assert!(output.contains("    PW002 UnhandledCall: 1\n"));
+    assert!(output.contains("  By exception:\n    KeyError: 2\n"));
+
+    let output = run_with("snippets.py", &["--statistics", "--format", "json"]);
+    let log: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(log["warnings"].as_array().unwrap().len(), 2);
+    assert_eq!(log["statistics"]["by_rule"]["PW001"], 1);
+    assert_eq!(log["statistics"]["top_files"][0]["warnings"], 2);
+}
+
+#[test]
+fn html_report_shows_snippets_and_the_propagation_chain() {
+    let report = std::env::temp_dir().join(format!("pysleuth-report-{}.html", std::process::id()));
+    let output = run_with(
+        "snippets.py",
+        &["--format", "html", "--output", report.to_str().unwrap()],
+    );
+    assert!(output.is_empty());
+
+    let html = std::fs::read_to_string(&report).unwrap();
+    std::fs::remove_file(&report).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<option>PW002</option>"));
+
+    // The call and each step it raises through are marked in their snippets
+    assert!(html.contains("<span class=\"k\">return</span> <span class=\"m\">read(config)</span>"));
+    assert!(html.contains("snippets.py:5:12: KeyError may be raised here in 'read'"));
+    assert!(html.contains("snippets.py:2:12: KeyError may be raised here in 'load'"));
+}
+
+#[test]
+fn checkstyle_and_junit_reports_list_each_warning() {
+    let output = run_with("snippets.py", &["--format", "checkstyle"]);
+    assert!(output.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<checkstyle"));
+    assert!(output.contains(
+        "<error line=\"8\" column=\"12\" severity=\"warning\" message=\"Possible KeyError not handled when calling 'read' in function 'main'\" source=\"pysleuth.PW002\"/>"
+    ));
+
+    let output = run_with("snippets.py", &["--format", "junit"]);
+    assert!(output.contains("<testsuites name=\"pysleuth\" tests=\"2\" failures=\"2\">"));
+    assert!(output.contains("<testcase name=\"PW001 2:12\""));
+    assert!(output
+        .contains("<failure message=\"Possible KeyError in function 'load'\" type=\"PW001\">"));
+}
+
+#[test]
+fn quiet_prints_only_warnings_and_verbose_logs_to_stderr() {
+    let output = run_with("snippets.py", &["--quiet"]);
+    assert_eq!(output.lines().count(), 2);
+    assert!(output
+        .contains("snippets.py:2:12: Warning: Possible KeyError in function 'load' [PW001]\n"));
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/snippets.py");
+    let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .args(["--verbose", "--no-cache", "--select", "PW001"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Not caching results\n"));
+    assert!(stderr.contains("Enabled rules: PW001\n"));
+    assert!(stderr.contains("Found 1 file(s) to analyze"));
+    assert!(stderr.contains("Analyzed 1 file(s) in "));
+
+    // The warnings stay on stdout
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with("Found 1 warning(s) (PW001: 1)\n"));
+}
+
+#[test]
+fn explain_prints_the_chain_behind_a_call_warning() {
+    let output = run_with("snippets.py", &["--explain", "--format", "short"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines[2].starts_with("  = because: read(config) at "));
+    assert!(lines[2].contains("snippets.py:8:12 → load(config) at "));
+    assert!(lines[2].ends_with("snippets.py:2:12"));
+    assert!(lines[2].contains(" → config[\"key\"] at "));
+
+    // The subscript itself has nothing to follow
+    assert!(lines[0].ends_with("[PW001]"));
+    assert!(lines[1].ends_with("[PW002]"));
+}
+
+#[test]
+fn warnings_are_grouped_and_folded_per_function() {
+    let source = "\
+def load(d):
+    if not d:
+        raise ValueError()
+    return d
+
+def main(d, e):
+    load(d)
+    load(d)
+    print(e['a'], e['b'], e['c'])
+";
+    let output = run_stdin(
+        source,
+        &["--format", "short", "--group-by", "function", "--dedup"],
+    );
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "<stdin>: function 'main' (5 warning(s))");
+    assert!(lines[1].starts_with("<stdin>:7:5: Warning: Possible ValueError"));
+    assert!(lines[2].starts_with("<stdin>:9:11: Warning: Possible KeyError"));
+    assert_eq!(lines[5], "… and 1 more in function 'main'");
+    assert_eq!(lines[6], "Found 5 warning(s) (PW001: 3, PW002: 2)");
+
+    let output = run_stdin(source, &["--format", "short", "--max-per-function", "1"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[1], "… and 4 more in function 'main'");
+}
+
+#[test]
+fn graph_prints_exception_flow_between_project_functions() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("project");
+    let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .arg("graph")
+        .arg("--project")
+        .arg(&root)
+        .output()
+        .expect("failed to run pysleuth");
+    assert!(output.status.success());
+    let dot = String::from_utf8(output.stdout).unwrap();
+    assert!(dot.starts_with("digraph exceptions {"));
+    assert!(dot.contains("\"app.main.run\" -> \"app.store.load\" [label=\"KeyError\", color=red];"));
+    assert!(dot.contains("\"app.main.safe\" -> \"app.store.load\" [style=dashed];"));
+    assert!(dot.contains("\"app.store.load\" [label=\"app.store.load\\nraises KeyError\""));
+}
+
+#[test]
+fn report_raises_lists_public_functions() {
+    let source = "\
+def load(d):
+    return d['key']
+
+def _check(n):
+    if n < 0:
+        raise ValueError(n)
+
+def main(d):
+    _check(len(d))
+    return load(d)
+";
+    let dir = std::env::temp_dir().join(format!("pysleuth-report-raises-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("records.py");
+    std::fs::write(&path, source).unwrap();
+    let report = |format: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+            .args(["report-raises", "--format", format])
+            .arg(&path)
+            .output()
+            .expect("failed to run pysleuth");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let text = report("text");
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", text);
+    assert!(lines[0].ends_with(":1: records.load raises KeyError"));
+    assert!(lines[1].ends_with(":8: records.main raises KeyError, ValueError"));
+    assert_eq!(lines[2], "2 public function(s)");
+
+    let markdown = report("markdown");
+    assert!(markdown.contains("| `records.main` | `KeyError`, `ValueError` |"));
+    let json: serde_json::Value = serde_json::from_str(&report("json")).unwrap();
+    assert_eq!(
+        json[1]["raises"],
+        serde_json::json!(["KeyError", "ValueError"])
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn progress_is_only_drawn_on_a_terminal() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("snippets.py");
+    let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .args(["--progress", "--no-cache"])
+        .arg(&path)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("failed to run pysleuth");
+    assert!(String::from_utf8(output.stderr).unwrap().is_empty());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("Found "));
+}
+
+#[test]
+fn notebooks_are_reported_by_cell() {
+    let output = run("notebook.ipynb");
+    assert!(output.contains(
+        "notebook.ipynb:cell 2:line 5:12: Warning: Possible KeyError in function 'score'"
+    ));
+    assert!(output.contains("5 |     return row[\"score\"]"));
+
+    // Cells run at the top level, so module-level code is reported too
+    assert!(output.contains(
+        "notebook.ipynb:cell 3:line 3:14: Warning: Possible IndexError in function '<module>'"
+    ));
+    assert!(output.contains("Found 2 warning(s)"));
+}
+
+#[test]
+fn tui_suppresses_warnings_inline_and_in_a_baseline() {
+    let dir = std::env::temp_dir().join(format!("pysleuth-tui-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("app.py");
+    let baseline = dir.join("baseline.json");
+    std::fs::write(
+        &path,
+        "def get(config):\n    return config[\"name\"]\n\n\ndef port(config):\n    return config[\"port\"]\n",
+    )
+    .unwrap();
+
+    // Ignore the second warning inline, then add the first to the baseline
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .arg("tui")
+        .arg(&path)
+        .arg("--baseline")
+        .arg(&baseline)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("failed to run pysleuth");
+    child.stdin.take().unwrap().write_all(b"jibq").unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let source = std::fs::read_to_string(&path).unwrap();
+    assert!(source.contains("    return config[\"name\"]\n"));
+    assert!(source.contains("    return config[\"port\"]  # pywrong: ignore[PW001]\n"));
+    let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .arg(&path)
+        .arg("--baseline")
+        .arg(&baseline)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn timings_are_printed_on_stderr() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/getitem.py");
+    let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .args(["--timings", "--no-cache", "--format", "short"])
+        .arg(&path)
+        .output()
+        .expect("failed to run pysleuth");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    for phase in [
+        "file IO",
+        "parsing",
+        "function collection",
+        "propagation",
+        "reporting",
+    ] {
+        assert!(
+            stderr.contains(&format!("  {} ", phase)),
+            "missing {}",
+            phase
+        );
+    }
+    assert!(stderr.contains("Slowest files:\n"));
+    assert!(stderr.contains("getitem.py\n"));
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with(path.to_str().unwrap()));
+}
+
+#[test]
+fn format_template_lays_out_each_warning() {
+    let output = run_with(
+        "getitem.py",
+        &[
+            "--format-template",
+            "{line}:{col}:{end_col} {severity} {code} [{exceptions}] {{{function}}} {message}",
+        ],
+    );
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines[1],
+        "18:12:23 Warning PW001 [LookupError] {fetch} Possible LookupError in function 'fetch'"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pysleuth"))
+        .args(["--format-template", "{file}:{lnum}", "-"])
+        .output()
+        .expect("failed to run pysleuth");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("unknown field '{lnum}' in format template"));
+}
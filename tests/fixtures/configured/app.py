@@ -0,0 +1,5 @@
+def fetch(d):
+    raise KeyError(d)
+
+def main(d):
+    return fetch(d), int(d)
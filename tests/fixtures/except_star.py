@@ -0,0 +1,5 @@
+def lookup(d):
+    try:
+        return d["key"]
+    except* KeyError:
+        return None
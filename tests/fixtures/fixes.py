@@ -0,0 +1,13 @@
+def read(config):
+    return config["name"]
+
+def total(counts):
+    return counts["n"] + 1
+
+def bump(counts):
+    counts["total"] += len("""
+two
+""")
+
+def last(config):
+    return config["last"]
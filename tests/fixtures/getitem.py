@@ -0,0 +1,35 @@
+class Registry:
+    def __init__(self):
+        self.items = {}
+
+    def __getitem__(self, name):
+        return fetch(self.items, name)
+
+    def first(self):
+        return self["first"]
+
+
+class SafeRegistry:
+    def __getitem__(self, name):
+        return None
+
+
+def fetch(items, name):
+    return items[name]
+
+
+def lookup(name):
+    registry = Registry()
+    return registry[name]
+
+
+def safe_lookup(name):
+    registry = SafeRegistry()
+    return registry[name]
+
+
+def guarded_lookup(name):
+    try:
+        return Registry()[name]
+    except KeyError:
+        return None
@@ -0,0 +1,13 @@
+def handle(command, result, key):
+    match command:
+        case {"key": value}:
+            return result[key]
+        case [first, *rest] if result["flag"]:
+            return first
+        case Point(x=0):
+            try:
+                return result[value]
+            except KeyError:
+                return None
+        case _:
+            return None
@@ -0,0 +1,17 @@
+from . import store
+from .store import load
+
+
+def run(config):
+    return load(config)
+
+
+def fetch(key):
+    return store.Store.get(None, key)
+
+
+def safe(config):
+    try:
+        return load(config)
+    except KeyError:
+        return None
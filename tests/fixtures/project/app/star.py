@@ -0,0 +1,5 @@
+from .store import *
+
+
+def open_store(config):
+    return load(config)
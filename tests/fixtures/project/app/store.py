@@ -0,0 +1,9 @@
+def load(config):
+    if not config:
+        raise KeyError("config")
+    return config
+
+
+class Store:
+    def get(self, key):
+        raise LookupError(key)
@@ -0,0 +1,2 @@
+from .loader import safe_load
+from .errors import *
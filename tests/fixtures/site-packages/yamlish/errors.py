@@ -0,0 +1,5 @@
+class YAMLError(Exception):
+    pass
+
+def check(x):
+    raise YAMLError()
@@ -0,0 +1,6 @@
+from .errors import YAMLError
+
+def safe_load(text):
+    if not text:
+        raise YAMLError("empty")
+    return text
@@ -0,0 +1,8 @@
+def load(config):
+    return config["key"]
+
+def read(config):
+    return load(config)
+
+def main(config):
+    return read(config)
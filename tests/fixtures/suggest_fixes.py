@@ -0,0 +1,9 @@
+def read(config):
+    return config["name"]
+
+def bump(counts):
+    counts["total"] += 1
+
+def cleanup(cache):
+    if cache:
+        del cache["stale"]
@@ -0,0 +1,5 @@
+def load(config):
+    return config["path"
+
+def main():
+    load({})